@@ -135,6 +135,39 @@ impl ArtifactoryClient {
         }
     }
 
+    pub async fn delete(&self,
+                        ident: &PackageIdent,
+                        target: PackageTarget)
+                        -> ArtifactoryResult<Response> {
+        debug!("ArtifactoryClient delete request for {} ({})",
+               ident, target);
+
+        let url = self.url_path_for(ident, target);
+        debug!("ArtifactoryClient delete url = {}", url);
+
+        let resp = match self.inner
+                             .delete(&url)
+                             .send()
+                             .await
+                             .map_err(ArtifactoryError::HttpClient)
+        {
+            Ok(resp) => resp,
+            Err(err) => {
+                error!("ArtifactoryClient delete failed, err={}", err);
+                return Err(err);
+            }
+        };
+
+        debug!("Artifactory response status: {:?}", resp.status());
+
+        if resp.status().is_success() {
+            Ok(resp)
+        } else {
+            error!("Artifactory delete non-success status: {:?}", resp.status());
+            Err(ArtifactoryError::ApiError(resp.status(), HashMap::new()))
+        }
+    }
+
     fn url_path_for(&self, ident: &PackageIdent, target: PackageTarget) -> String {
         let hart_name = ident.archive_name_with_target(target)
                              .expect("ident is fully qualified");
@@ -28,6 +28,7 @@ use std::{env,
 use artifactory_client::config::ArtifactoryCfg;
 use github_api_client::config::GitHubCfg;
 use oauth_client::config::OAuth2Cfg;
+use url::Url;
 
 use crate::{db::config::DataStoreCfg,
             hab_core::{self,
@@ -60,6 +61,11 @@ pub struct Config {
     pub memcache:    MemcacheCfg,
     pub jobsrv:      JobsrvCfg,
     pub datastore:   DataStoreCfg,
+    pub retention:   RetentionCfg,
+    pub mirror:      MirrorCfg,
+    pub scheduler:   SchedulerCfg,
+    pub uploads:     UploadsCfg,
+    pub bundles:     BundlesCfg,
 }
 
 impl Default for Config {
@@ -73,7 +79,12 @@ impl Default for Config {
                  ui:          UiCfg::default(),
                  memcache:    MemcacheCfg::default(),
                  jobsrv:      JobsrvCfg::default(),
-                 datastore:   DataStoreCfg::default(), }
+                 datastore:   DataStoreCfg::default(),
+                 retention:   RetentionCfg::default(),
+                 mirror:      MirrorCfg::default(),
+                 scheduler:   SchedulerCfg::default(),
+                 uploads:     UploadsCfg::default(),
+                 bundles:     BundlesCfg::default(), }
     }
 }
 
@@ -99,54 +110,64 @@ impl From<hab_core::Error> for ConfigError {
 pub enum S3Backend {
     Aws,
     Minio,
+    Filesystem,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
 pub struct S3Cfg {
     // These are for using S3 as the artifact storage
-    pub key_id:      String,
-    pub secret_key:  String,
-    pub bucket_name: String,
-    pub backend:     S3Backend,
-    pub endpoint:    String,
+    pub key_id:              String,
+    pub secret_key:          String,
+    pub bucket_name:         String,
+    pub backend:             S3Backend,
+    pub endpoint:            String,
+    /// Allow clients to ask for a redirect to a pre-signed URL instead of proxying downloads
+    pub presigned_downloads: bool,
+    /// Seconds a pre-signed download URL stays valid
+    pub presigned_url_ttl:   u64,
 }
 
 impl Default for S3Cfg {
     fn default() -> Self {
-        S3Cfg { key_id:      String::from("depot"),
-                secret_key:  String::from("password"),
-                bucket_name: String::from("habitat-builder-artifact-store.default"),
-                backend:     S3Backend::Minio,
-                endpoint:    String::from("http://localhost:9000"), }
+        S3Cfg { key_id:              String::from("depot"),
+                secret_key:          String::from("password"),
+                bucket_name:         String::from("habitat-builder-artifact-store.default"),
+                backend:             S3Backend::Minio,
+                endpoint:            String::from("http://localhost:9000"),
+                presigned_downloads: false,
+                presigned_url_ttl:   300, }
     }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
 pub struct ApiCfg {
-    pub data_path:        PathBuf,
-    pub log_path:         PathBuf,
-    pub key_path:         PathBuf,
-    pub targets:          Vec<PackageTarget>,
-    pub build_targets:    Vec<PackageTarget>,
-    pub features_enabled: String,
-    pub build_on_upload:  bool,
-    pub private_max_age:  usize,
+    pub data_path:          PathBuf,
+    pub log_path:           PathBuf,
+    pub key_path:           PathBuf,
+    pub targets:            Vec<PackageTarget>,
+    pub build_targets:      Vec<PackageTarget>,
+    pub features_enabled:   String,
+    pub build_on_upload:    bool,
+    pub private_max_age:    usize,
+    /// Seconds a resumable upload session may go without receiving a chunk before it expires
+    pub upload_session_ttl: u64,
 }
 
 impl Default for ApiCfg {
     fn default() -> Self {
-        ApiCfg { data_path:        PathBuf::from("/hab/svc/builder-api/data"),
-                 log_path:         env::temp_dir(),
-                 key_path:         PathBuf::from("/hab/svc/builder-api/files"),
-                 targets:          vec![target::X86_64_LINUX,
-                                        target::X86_64_LINUX_KERNEL2,
-                                        target::X86_64_WINDOWS,],
-                 build_targets:    vec![target::X86_64_LINUX, target::X86_64_WINDOWS],
-                 features_enabled: String::from("jobsrv"),
-                 build_on_upload:  true,
-                 private_max_age:  300, }
+        ApiCfg { data_path:          PathBuf::from("/hab/svc/builder-api/data"),
+                 log_path:           env::temp_dir(),
+                 key_path:           PathBuf::from("/hab/svc/builder-api/files"),
+                 targets:            vec![target::X86_64_LINUX,
+                                          target::X86_64_LINUX_KERNEL2,
+                                          target::X86_64_WINDOWS,],
+                 build_targets:      vec![target::X86_64_LINUX, target::X86_64_WINDOWS],
+                 features_enabled:   String::from("jobsrv"),
+                 build_on_upload:    true,
+                 private_max_age:    300,
+                 upload_session_ttl: 86400, }
     }
 }
 
@@ -329,6 +350,118 @@ impl fmt::Display for JobsrvCfg {
     }
 }
 
+/// Background sweeper which applies the per-origin package retention policies
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RetentionCfg {
+    pub enabled:      bool,
+    pub interval_sec: u64,
+    /// Only log what would be removed instead of deleting it
+    pub dry_run:      bool,
+}
+
+impl Default for RetentionCfg {
+    fn default() -> Self {
+        RetentionCfg { enabled:      false,
+                       interval_sec: 86400,
+                       dry_run:      false, }
+    }
+}
+
+/// Background sync of the channels mirrored from upstream Builder instances
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MirrorCfg {
+    pub enabled:        bool,
+    pub interval_sec:   u64,
+    /// How long a sync may run before it is failed as interrupted
+    pub lease_sec:      u64,
+    /// URL this Builder is reachable at, which mirrored packages are uploaded to
+    pub local_url:      String,
+    /// Token of an account allowed to upload packages and keys to the mirrored origins
+    pub local_token:    String,
+    /// Hosts channels may be mirrored from. No mirror can be set while this is empty.
+    pub upstream_hosts: Vec<String>,
+}
+
+impl MirrorCfg {
+    /// Whether the given URL is an http(s) URL of one of the allowed upstream hosts
+    pub fn allows_upstream(&self, upstream_url: &str) -> bool {
+        match Url::parse(upstream_url) {
+            Ok(ref url) if url.scheme() == "http" || url.scheme() == "https" => {
+                url.host_str().map_or(false, |host| {
+                                  self.upstream_hosts
+                                      .iter()
+                                      .any(|h| h.eq_ignore_ascii_case(host))
+                              })
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Default for MirrorCfg {
+    fn default() -> Self {
+        MirrorCfg { enabled:        false,
+                    interval_sec:   3600,
+                    lease_sec:      10800,
+                    local_url:      String::from("http://localhost:9636"),
+                    local_token:    String::new(),
+                    upstream_hosts: Vec::new(), }
+    }
+}
+
+/// Background runner of the scheduled channel promotions
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SchedulerCfg {
+    pub enabled:      bool,
+    /// How often to look for promotions which are due
+    pub interval_sec: u64,
+    /// How long a promotion may run before it is failed as interrupted
+    pub lease_sec:    u64,
+}
+
+impl Default for SchedulerCfg {
+    fn default() -> Self {
+        SchedulerCfg { enabled:      false,
+                       interval_sec: 60,
+                       lease_sec:    3600, }
+    }
+}
+
+/// Background expiry of abandoned resumable upload sessions
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UploadsCfg {
+    pub enabled:      bool,
+    pub interval_sec: u64,
+}
+
+impl Default for UploadsCfg {
+    fn default() -> Self {
+        UploadsCfg { enabled:      false,
+                     interval_sec: 3600, }
+    }
+}
+
+/// Limits on the bundles accepted for import
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BundlesCfg {
+    /// Largest bundle accepted, as uploaded
+    pub max_upload_bytes:   u64,
+    /// Largest total size of the files a bundle may unpack to
+    pub max_unpacked_bytes: u64,
+}
+
+impl Default for BundlesCfg {
+    fn default() -> Self {
+        BundlesCfg { max_upload_bytes:   2 * 1024 * 1024 * 1024,
+                     max_unpacked_bytes: 4 * 1024 * 1024 * 1024, }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -346,6 +479,7 @@ mod tests {
         features_enabled = "foo, bar"
         build_on_upload = false
         private_max_age = 400
+        upload_session_ttl = 3600
 
         [http]
         listen = "0:0:0:0:0:0:0:1"
@@ -372,6 +506,8 @@ mod tests {
         secret_key = "aW5S3c437Key7hIn817s7o7a11yN457y70Wr173L1k37h15"
         endpoint = "http://localhost:9000"
         bucket_name = "hibbity-bibbity-poopity-scoopity"
+        presigned_downloads = true
+        presigned_url_ttl = 60
 
         [artifactory]
         api_url = "http://abcde"
@@ -385,6 +521,32 @@ mod tests {
         host = "1.2.3.4"
         port = 1234
 
+        [retention]
+        enabled = true
+        interval_sec = 3600
+        dry_run = true
+
+        [mirror]
+        enabled = true
+        interval_sec = 600
+        lease_sec = 1800
+        local_url = "http://builder.example.com"
+        local_token = "abc123"
+        upstream_hosts = ["bldr.habitat.sh"]
+
+        [scheduler]
+        enabled = true
+        interval_sec = 30
+        lease_sec = 600
+
+        [uploads]
+        enabled = true
+        interval_sec = 600
+
+        [bundles]
+        max_upload_bytes = 1024
+        max_unpacked_bytes = 4096
+
         [datastore]
         host = "1.1.1.1"
         port = 9000
@@ -419,6 +581,7 @@ mod tests {
         assert_eq!(&config.api.features_enabled, "foo, bar");
         assert_eq!(config.api.build_on_upload, false);
         assert_eq!(config.api.private_max_age, 400);
+        assert_eq!(config.api.upload_session_ttl, 3600);
 
         assert_eq!(&format!("{}", config.http.listen), "::1");
 
@@ -428,6 +591,28 @@ mod tests {
 
         assert_eq!(&format!("{}", config.jobsrv), "http://1.2.3.4:1234");
 
+        assert_eq!(config.retention.enabled, true);
+        assert_eq!(config.retention.interval_sec, 3600);
+        assert_eq!(config.retention.dry_run, true);
+
+        assert_eq!(config.mirror.enabled, true);
+        assert_eq!(config.mirror.interval_sec, 600);
+        assert_eq!(config.mirror.lease_sec, 1800);
+        assert_eq!(&config.mirror.local_url, "http://builder.example.com");
+        assert_eq!(&config.mirror.local_token, "abc123");
+        assert_eq!(config.mirror.upstream_hosts,
+                   vec!["bldr.habitat.sh".to_string()]);
+
+        assert_eq!(config.scheduler.enabled, true);
+        assert_eq!(config.scheduler.interval_sec, 30);
+        assert_eq!(config.scheduler.lease_sec, 600);
+
+        assert_eq!(config.uploads.enabled, true);
+        assert_eq!(config.uploads.interval_sec, 600);
+
+        assert_eq!(config.bundles.max_upload_bytes, 1024);
+        assert_eq!(config.bundles.max_unpacked_bytes, 4096);
+
         assert_eq!(config.http.port, 9636);
         assert_eq!(config.http.handler_count, 128);
         assert_eq!(config.http.keep_alive, 30);
@@ -446,6 +631,8 @@ mod tests {
                    "aW5S3c437Key7hIn817s7o7a11yN457y70Wr173L1k37h15");
         assert_eq!(config.s3.endpoint, "http://localhost:9000");
         assert_eq!(config.s3.bucket_name, "hibbity-bibbity-poopity-scoopity");
+        assert_eq!(config.s3.presigned_downloads, true);
+        assert_eq!(config.s3.presigned_url_ttl, 60);
 
         assert_eq!(config.artifactory.api_url, "http://abcde");
         assert_eq!(config.artifactory.api_key, "secret");
@@ -474,5 +661,32 @@ mod tests {
 
         let config = Config::from_raw(&content).unwrap();
         assert_eq!(config.http.port, 9000);
+        assert_eq!(config.retention.enabled, false);
+        assert_eq!(config.scheduler.enabled, false);
+    }
+
+    #[test]
+    fn mirror_upstream_must_be_an_allowed_host() {
+        let mut cfg = MirrorCfg::default();
+        assert!(!cfg.allows_upstream("https://bldr.habitat.sh"));
+
+        cfg.upstream_hosts = vec!["bldr.habitat.sh".to_string()];
+        assert!(cfg.allows_upstream("https://bldr.habitat.sh"));
+        assert!(cfg.allows_upstream("http://BLDR.habitat.sh:8080/v1"));
+        assert!(!cfg.allows_upstream("https://169.254.169.254"));
+        assert!(!cfg.allows_upstream("https://bldr.habitat.sh.evil.com"));
+        assert!(!cfg.allows_upstream("file://bldr.habitat.sh/etc/passwd"));
+        assert!(!cfg.allows_upstream("bldr.habitat.sh"));
+    }
+
+    #[test]
+    fn config_filesystem_backend() {
+        let content = r#"
+        [s3]
+        backend = "filesystem"
+        "#;
+
+        let config = Config::from_raw(&content).unwrap();
+        assert_eq!(config.s3.backend, S3Backend::Filesystem);
     }
 }
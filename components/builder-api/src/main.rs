@@ -21,7 +21,8 @@ extern crate clap;
 extern crate log;
 
 use std::{fmt,
-          path::PathBuf,
+          path::{Path,
+                 PathBuf},
           process,
           str::FromStr};
 
@@ -29,8 +30,12 @@ use habitat_builder_api as bldr_api;
 use habitat_core as hab_core;
 
 use crate::{bldr_api::{config::Config,
-                       server},
-            hab_core::config::ConfigFile};
+                       server::{self,
+                                services::bundle::{self,
+                                                   BundleExport}}},
+            hab_core::{config::ConfigFile,
+                       package::PackageTarget,
+                       ChannelIdent}};
 
 const VERSION: &str = include_str!(concat!(env!("OUT_DIR"), "/VERSION"));
 const CFG_DEFAULT_PATH: &str = "/hab/svc/builder-api/config/config.toml";
@@ -40,12 +45,40 @@ async fn main() {
     env_logger::init();
     let matches = app().get_matches();
     debug!("CLI matches: {:?}", matches);
-    match server::run(config_from_args(&matches)).await {
+    let config = config_from_args(&matches);
+    let result = match matches.subcommand() {
+        ("export", Some(args)) => export(&config, args).await,
+        _ => server::run(config).await,
+    };
+    match result {
         Ok(_) => std::process::exit(0),
         Err(e) => exit_with(e, 1),
     }
 }
 
+async fn export(config: &Config, args: &clap::ArgMatches<'_>) -> server::error::Result<()> {
+    // The package storage backend depends on the enabled features
+    server::enable_features(config);
+
+    let channel = ChannelIdent::from(args.value_of("CHANNEL").unwrap());
+    let target = match args.value_of("target") {
+        Some(target) => PackageTarget::from_str(target)?,
+        None => PackageTarget::active_target(),
+    };
+    let req = BundleExport { origin: args.value_of("ORIGIN").unwrap(),
+                             channel: &channel,
+                             target,
+                             closure: args.is_present("closure"),
+                             output: Path::new(args.value_of("OUTPUT").unwrap()) };
+
+    let index = bundle::export(config, &req).await?;
+    println!("Exported {} packages and {} keys to {}",
+             index.packages.len(),
+             index.keys.len(),
+             req.output.display());
+    Ok(())
+}
+
 fn app<'a, 'b>() -> clap::App<'a, 'b> {
     clap_app!(BuilderApi =>
         (version: VERSION)
@@ -60,6 +93,20 @@ fn app<'a, 'b>() -> clap::App<'a, 'b> {
                 "Filepath to store packages, keys, and other artifacts.")
             (@arg port: --port +takes_value "Listen port. [default: 9636]")
         )
+        (@subcommand export =>
+            (about: "Export a channel to a bundle for installing without access to Builder")
+            (@arg ORIGIN: +required +takes_value "The origin of the channel")
+            (@arg CHANNEL: +required +takes_value "The channel to export")
+            (@arg OUTPUT: +required +takes_value "Filepath to write the bundle (.tar.gz) to")
+            (@arg target: -t --target +takes_value
+                "Package target to export. [default: the target of this system]")
+            (@arg closure: --closure
+                "Include the runtime dependencies of the channel's packages, from any origin")
+            (@arg config: -c --config +takes_value
+                "Filepath to configuration file. [default: /hab/svc/builder-api/config/config.toml]")
+            (@arg path: -p --path +takes_value
+                "Filepath to store packages, keys, and other artifacts.")
+        )
     )
 }
 
@@ -14,13 +14,16 @@
 
 use std::str::FromStr;
 
-use actix_web::HttpRequest;
+use actix_web::{http::Method,
+                HttpRequest};
+use chrono::Utc;
 
 use crate::{bldr_core::{access_token::BUILDER_ACCOUNT_ID,
                         metrics::CounterMetric,
                         privilege::*},
             db::models::origin::*,
-            protocol::originsrv};
+            protocol::originsrv::{self,
+                                  TokenScope}};
 
 use crate::server::{error::{Error,
                             Result},
@@ -50,6 +53,10 @@ pub fn authorize_session(req: &HttpRequest,
         }
     };
 
+    if session.has_access_token() {
+        authorize_token(req, session.get_access_token(), origin_opt)?;
+    }
+
     if let Some(origin) = origin_opt {
         let minimum_req_role = match min_role {
             Some(r) => r,
@@ -92,6 +99,104 @@ pub fn authorize_session(req: &HttpRequest,
     Ok(session)
 }
 
+// Checks the request against the expiry, scopes, origins and channels of the access token
+// the session was created from
+fn authorize_token(req: &HttpRequest,
+                   token: &originsrv::AccessToken,
+                   origin_opt: Option<&str>)
+                   -> Result<()> {
+    if token.get_expires() < Utc::now().timestamp() {
+        debug!("authorize_session: access token of account {} has expired",
+               token.get_account_id());
+        return Err(Error::Authentication);
+    }
+
+    if !token.is_restricted() {
+        return Ok(());
+    }
+
+    // Every scope allows reading
+    let scope = required_scope(req);
+    let scoped = token.get_scopes().is_empty()
+                 || scope == Some(TokenScope::Read)
+                 || scope.map_or(false, |s| token.get_scopes().contains(&s));
+    if !scoped {
+        debug!("authorize_session: access token of account {} lacks the {:?} scope for {} {}",
+               token.get_account_id(),
+               scope,
+               req.method(),
+               req.path());
+        return Err(Error::Authorization);
+    }
+
+    let origin = origin_opt.or_else(|| req.match_info().get("origin"));
+    if !token.get_origins().is_empty() {
+        let allowed = match origin {
+            Some(origin) => token.get_origins().iter().any(|o| o == origin),
+            // Reads which span origins are left to the visibility checks
+            None => scope == Some(TokenScope::Read),
+        };
+        if !allowed {
+            debug!("authorize_session: access token of account {} is not allowed in origin {:?}",
+                   token.get_account_id(),
+                   origin);
+            return Err(Error::Authorization);
+        }
+    }
+
+    if scope == Some(TokenScope::Promote) && !token.get_channels().is_empty() {
+        let channel = req.match_info().get("channel");
+        if !channel.map_or(false, |c| token.get_channels().iter().any(|t| t == c)) {
+            debug!("authorize_session: access token of account {} may not promote with channel \
+                    {:?}",
+                   token.get_account_id(),
+                   channel);
+            return Err(Error::Authorization);
+        }
+    }
+
+    Ok(())
+}
+
+// The scope a restricted access token needs to make the request. Requests which no scope
+// covers, such as managing origins or tokens or reading keys and credentials, need an
+// unrestricted token.
+fn required_scope(req: &HttpRequest) -> Option<TokenScope> {
+    let method = req.method();
+    let path = req.path().trim_start_matches("/v1");
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    if method == Method::GET || method == Method::HEAD {
+        return match segments.as_slice() {
+            ["depot", "origins", _, "integrations", _, name] if *name != "names" => None,
+            ["depot", "origins", _, "secret_keys", ..]
+            | ["depot", "origins", _, "encryption_key"]
+            | ["depot", "origins", _, "secret", ..]
+            | ["depot", "origins", _, "service-accounts", _, "tokens"]
+            | ["projects", _, _, "integrations", ..]
+            | ["profile", "access-tokens"] => None,
+            _ => Some(TokenScope::Read),
+        };
+    }
+
+    match segments.as_slice() {
+        ["depot", "bundles", "import"] => Some(TokenScope::Upload),
+        ["depot", "pkgs", "schedule", ..] => Some(TokenScope::Jobs),
+        ["depot", "pkgs", _, _, _, _] if method == Method::POST => Some(TokenScope::Upload),
+        ["depot", "pkgs", _, _, _, _, "uploads", ..]
+        | ["depot", "pkgs", _, _, _, _, "provenance"] => Some(TokenScope::Upload),
+        ["depot", "channels", _, _, "pkgs", .., "promote"]
+        | ["depot", "channels", _, _, "pkgs", .., "demote"]
+        | ["depot", "channels", _, _, "scheduled-promotions"]
+        | ["depot", "origins", _, "scheduled-promotions", _]
+        | ["jobs", "group", _, "promote", _]
+        | ["jobs", "group", _, "demote", _]
+        | ["jobs", "group", _, "revert", _] => Some(TokenScope::Promote),
+        ["jobs", "group", _, "cancel"] => Some(TokenScope::Jobs),
+        _ => None,
+    }
+}
+
 pub fn check_origin_owner(req: &HttpRequest, account_id: u64, origin: &str) -> Result<bool> {
     let conn = req_state(req).db.get_conn().map_err(Error::DbError)?;
 
@@ -128,10 +233,10 @@ pub fn check_origin_member(req: &HttpRequest, origin: &str, account_id: u64) ->
     }
 }
 
-fn check_origin_member_role(req: &HttpRequest,
-                            origin: &str,
-                            account_id: u64)
-                            -> Option<OriginMemberRole> {
+pub fn check_origin_member_role(req: &HttpRequest,
+                                origin: &str,
+                                account_id: u64)
+                                -> Option<OriginMemberRole> {
     if account_id == BUILDER_ACCOUNT_ID {
         Some(OriginMemberRole::Owner)
     } else {
@@ -178,3 +283,67 @@ fn check_origin_member_role(req: &HttpRequest,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn scope_of(method: Method, path: &str) -> Option<TokenScope> {
+        required_scope(&TestRequest::with_uri(path).method(method).to_http_request())
+    }
+
+    #[test]
+    fn reads_need_the_read_scope() {
+        assert_eq!(scope_of(Method::GET, "/v1/depot/origins/core/keys"),
+                   Some(TokenScope::Read));
+        assert_eq!(scope_of(Method::HEAD, "/v1/depot/pkgs/core/redis"),
+                   Some(TokenScope::Read));
+        assert_eq!(scope_of(Method::GET,
+                            "/v1/depot/origins/core/integrations/docker/names"),
+                   Some(TokenScope::Read));
+
+        // Keys and credentials are not handed out to restricted tokens
+        assert_eq!(scope_of(Method::GET, "/v1/depot/origins/core/secret_keys/latest"),
+                   None);
+        assert_eq!(scope_of(Method::GET, "/v1/depot/origins/core/encryption_key"),
+                   None);
+        assert_eq!(scope_of(Method::GET,
+                            "/v1/depot/origins/core/integrations/docker/hub"),
+                   None);
+        assert_eq!(scope_of(Method::GET, "/v1/depot/origins/core/secret"), None);
+        assert_eq!(scope_of(Method::GET,
+                            "/v1/depot/origins/core/service-accounts/ci/tokens"),
+                   None);
+        assert_eq!(scope_of(Method::GET,
+                            "/v1/projects/core/redis/integrations/docker/default"),
+                   None);
+        assert_eq!(scope_of(Method::GET, "/v1/profile/access-tokens"), None);
+    }
+
+    #[test]
+    fn writes_map_to_their_scope() {
+        assert_eq!(scope_of(Method::POST,
+                            "/v1/depot/pkgs/core/redis/3.2.1/20170215222111"),
+                   Some(TokenScope::Upload));
+        assert_eq!(scope_of(Method::PUT,
+                            "/v1/depot/channels/core/stable/pkgs/redis/3.2.1/20170215222111/\
+                             promote"),
+                   Some(TokenScope::Promote));
+        assert_eq!(scope_of(Method::PUT, "/v1/depot/channels/core/stable/pkgs/demote"),
+                   Some(TokenScope::Promote));
+        assert_eq!(scope_of(Method::POST, "/v1/jobs/group/1234/promote/stable"),
+                   Some(TokenScope::Promote));
+        assert_eq!(scope_of(Method::POST, "/v1/depot/pkgs/schedule/core/redis"),
+                   Some(TokenScope::Jobs));
+    }
+
+    #[test]
+    fn other_writes_need_an_unrestricted_token() {
+        assert_eq!(scope_of(Method::DELETE,
+                            "/v1/depot/pkgs/core/redis/3.2.1/20170215222111"),
+                   None);
+        assert_eq!(scope_of(Method::POST, "/v1/profile/access-tokens"), None);
+        assert_eq!(scope_of(Method::PUT, "/v1/depot/origins/core"), None);
+    }
+}
@@ -30,7 +30,9 @@ use std::{fmt,
 
 use crate::{bldr_core,
             db,
-            hab_core};
+            hab_core,
+            server::services::{closure::UnpromotableDep,
+                               policy::PolicyViolation}};
 
 #[derive(Debug)]
 pub enum Error {
@@ -39,6 +41,8 @@ pub enum Error {
     Authorization,
     BadRequest,
     BuilderCore(bldr_core::Error),
+    Bundle(String),
+    ChannelProtected(String),
     Conflict,
     CreateBucketError(RusotoError<rusoto_s3::CreateBucketError>),
     DbError(db::error::Error),
@@ -54,15 +58,19 @@ pub enum Error {
     MultipartUploadReq(RusotoError<rusoto_s3::CreateMultipartUploadError>),
     NotFound,
     OAuth(OAuthError),
+    PackageDelete(RusotoError<rusoto_s3::DeleteObjectError>),
     PackageDownload(RusotoError<rusoto_s3::GetObjectError>),
     PackageUpload(RusotoError<rusoto_s3::PutObjectError>),
     PartialUpload(RusotoError<rusoto_s3::UploadPartError>),
     PayloadError(actix_web::error::PayloadError),
+    PolicyViolation(Vec<PolicyViolation>),
     Protobuf(protobuf::ProtobufError),
     SerdeJson(serde_json::Error),
     System,
     TLSError(openssl::error::ErrorStack),
+    Unavailable(String),
     Unprocessable,
+    UnpromotableDeps(Vec<UnpromotableDep>),
     Utf8(string::FromUtf8Error),
 }
 
@@ -76,6 +84,8 @@ impl fmt::Display for Error {
             Error::Authorization => "User is not authorized to perform operation".to_string(),
             Error::BadRequest => "Bad request".to_string(),
             Error::BuilderCore(ref e) => format!("{}", e),
+            Error::Bundle(ref e) => format!("Bundle error: {}", e),
+            Error::ChannelProtected(ref e) => e.clone(),
             Error::Conflict => "Entity conflict".to_string(),
             Error::CreateBucketError(ref e) => format!("{}", e),
             Error::DbError(ref e) => format!("{}", e),
@@ -91,15 +101,23 @@ impl fmt::Display for Error {
             Error::MultipartUploadReq(ref e) => format!("{}", e),
             Error::NotFound => "Entity not found".to_string(),
             Error::OAuth(ref e) => format!("{}", e),
+            Error::PackageDelete(ref e) => format!("{}", e),
             Error::PackageDownload(ref e) => format!("{}", e),
             Error::PackageUpload(ref e) => format!("{}", e),
             Error::PartialUpload(ref e) => format!("{}", e),
             Error::PayloadError(ref e) => format!("{}", e),
+            Error::PolicyViolation(ref v) => {
+                format!("Channel policy violated by {} check(s)", v.len())
+            }
             Error::Protobuf(ref e) => format!("{}", e),
             Error::SerdeJson(ref e) => format!("{}", e),
             Error::System => "Internal error".to_string(),
             Error::TLSError(ref e) => format!("{}", e),
+            Error::Unavailable(ref e) => e.clone(),
             Error::Unprocessable => "Unprocessable entity".to_string(),
+            Error::UnpromotableDeps(ref d) => {
+                format!("{} runtime dependencies can not be promoted", d.len())
+            }
             Error::Utf8(ref e) => format!("{}", e),
         };
         write!(f, "{}", msg)
@@ -113,13 +131,18 @@ impl ResponseError for Error {
             Error::Authentication => HttpResponse::new(StatusCode::UNAUTHORIZED),
             Error::Authorization => HttpResponse::new(StatusCode::FORBIDDEN),
             Error::BadRequest => HttpResponse::new(StatusCode::BAD_REQUEST),
+            Error::Bundle(ref e) => HttpResponse::UnprocessableEntity().body(e.clone()),
+            Error::ChannelProtected(ref e) => HttpResponse::Forbidden().body(e.clone()),
             Error::Conflict => HttpResponse::new(StatusCode::CONFLICT),
             Error::Github(_) => HttpResponse::new(StatusCode::FORBIDDEN),
             Error::NotFound => HttpResponse::new(StatusCode::NOT_FOUND),
             Error::OAuth(_) => HttpResponse::new(StatusCode::UNAUTHORIZED),
+            Error::PolicyViolation(ref v) => HttpResponse::Forbidden().json(v),
             Error::DieselError(ref e) => HttpResponse::new(diesel_err_to_http(&e)),
             Error::System => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            Error::Unavailable(ref e) => HttpResponse::ServiceUnavailable().body(e.clone()),
             Error::Unprocessable => HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY),
+            Error::UnpromotableDeps(ref d) => HttpResponse::UnprocessableEntity().json(d),
 
             // Default
             _ => HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY),
@@ -134,14 +157,19 @@ impl Into<HttpResponse> for Error {
             Error::Authentication => HttpResponse::new(StatusCode::UNAUTHORIZED),
             Error::Authorization => HttpResponse::new(StatusCode::FORBIDDEN),
             Error::BadRequest => HttpResponse::new(StatusCode::BAD_REQUEST),
+            Error::Bundle(ref e) => HttpResponse::UnprocessableEntity().body(e.clone()),
+            Error::ChannelProtected(ref e) => HttpResponse::Forbidden().body(e.clone()),
             Error::Conflict => HttpResponse::new(StatusCode::CONFLICT),
             Error::Github(_) => HttpResponse::new(StatusCode::FORBIDDEN),
             Error::NotFound => HttpResponse::new(StatusCode::NOT_FOUND),
             Error::OAuth(_) => HttpResponse::new(StatusCode::UNAUTHORIZED),
+            Error::PolicyViolation(ref v) => HttpResponse::Forbidden().json(v),
             Error::BuilderCore(ref e) => HttpResponse::new(bldr_core_err_to_http(e)),
             Error::DieselError(ref e) => HttpResponse::new(diesel_err_to_http(e)),
             Error::System => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            Error::Unavailable(ref e) => HttpResponse::ServiceUnavailable().body(e.clone()),
             Error::Unprocessable => HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY),
+            Error::UnpromotableDeps(ref d) => HttpResponse::UnprocessableEntity().json(d),
 
             // Default
             _ => HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY),
@@ -232,6 +260,20 @@ impl From<string::FromUtf8Error> for Error {
     fn from(err: string::FromUtf8Error) -> Error { Error::Utf8(err) }
 }
 
+impl From<actix_web::error::BlockingError<Error>> for Error {
+    fn from(err: actix_web::error::BlockingError<Error>) -> Error {
+        match err {
+            actix_web::error::BlockingError::Error(e) => e,
+            actix_web::error::BlockingError::Canceled => Error::System,
+        }
+    }
+}
+
 impl From<actix_web::error::BlockingError<std::io::Error>> for Error {
-    fn from(err: actix_web::error::BlockingError<std::io::Error>) -> Error { err.into() }
+    fn from(err: actix_web::error::BlockingError<std::io::Error>) -> Error {
+        match err {
+            actix_web::error::BlockingError::Error(e) => Error::IO(e),
+            actix_web::error::BlockingError::Canceled => Error::System,
+        }
+    }
 }
@@ -16,8 +16,11 @@ pub const NO_CACHE: &str = "private, no-cache, no-store";
 pub const CACHE: &str = "public, max-age=31536000"; // ONE_YEAR_IN_SECONDS
 
 pub const APPLICATION_JSON: &str = "application/json";
+pub const APPLICATION_NDJSON: &str = "application/x-ndjson";
+pub const TEXT_CSV: &str = "text/csv";
 
 pub const XFILENAME: &str = "x-filename"; // must be lowercase
+pub const XNEXTCURSOR: &str = "x-next-cursor"; // must be lowercase
 
 pub enum Cache {
     NoCache,
@@ -131,13 +131,14 @@ fn authenticate(token: &str, state: &AppState) -> error::Result<originsrv::Sessi
 
             match AccountToken::list(session.get_id(), &*conn).map_err(error::Error::DieselError) {
                 Ok(access_tokens) => {
-                    assert!(access_tokens.len() <= 1); // Can only have max of 1 for now
-                    match access_tokens.first() {
+                    let trimmed = token.trim_end_matches('=');
+                    match access_tokens.iter()
+                                       .find(|t| t.token.trim_end_matches('=') == trimmed)
+                    {
                         Some(access_token) => {
-                            let new_token = access_token.token.clone();
-                            if token.trim_end_matches('=') != new_token.trim_end_matches('=') {
-                                // Token is valid but revoked or otherwise expired
-                                return Err(error::Error::Authorization);
+                            if let Err(err) = AccountToken::touch(access_token.id, &*conn) {
+                                warn!("Unable to update the last use of token {}, err={}",
+                                      access_token.id, err);
                             }
 
                             let account = Account::get_by_id(session.get_id() as i64, &*conn)
@@ -145,11 +146,11 @@ fn authenticate(token: &str, state: &AppState) -> error::Result<originsrv::Sessi
                             session.set_name(account.name);
                             session.set_email(account.email);
 
-                            memcache.set_session(&new_token, &session, None);
+                            memcache.set_session(&access_token.token, &session, None);
                             Ok(session)
                         }
                         None => {
-                            // We have no tokens in the database for this user
+                            // Token is valid but revoked or otherwise replaced
                             Err(error::Error::Authorization)
                         }
                     }
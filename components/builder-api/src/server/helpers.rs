@@ -178,6 +178,85 @@ pub fn all_visibilities() -> Vec<PackageVisibility> {
          PackageVisibility::Hidden,]
 }
 
+// Matches a value against a shell style glob, where `*` matches any run of characters
+// and `?` matches exactly one character
+pub fn glob_match(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut p, mut v) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if let Some((star_p, star_v)) = backtrack {
+            backtrack = Some((star_p, star_v + 1));
+            p = star_p + 1;
+            v = star_v + 1;
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ByteRange {
+    Full,
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+// Resolves the value of a Range header against a body of `len` bytes. Only a single
+// byte range is supported, anything else is answered with the full body. The returned
+// bounds are inclusive.
+pub fn byte_range(header: Option<&str>, len: u64) -> ByteRange {
+    let spec = match header.map(str::trim) {
+        Some(h) if h.starts_with("bytes=") && !h.contains(',') => h["bytes=".len()..].trim(),
+        _ => return ByteRange::Full,
+    };
+
+    let mut parts = spec.splitn(2, '-');
+    let (first, last) = match (parts.next(), parts.next()) {
+        (Some(first), Some(last)) => (first.trim(), last.trim()),
+        _ => return ByteRange::Full,
+    };
+
+    let (start, end) = if first.is_empty() {
+        // Suffix range, the last N bytes of the body
+        match last.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(n) => (len.saturating_sub(n), len.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        }
+    } else {
+        let start = match first.parse::<u64>() {
+            Ok(start) => start,
+            Err(_) => return ByteRange::Full,
+        };
+        let end = if last.is_empty() {
+            len.saturating_sub(1)
+        } else {
+            match last.parse::<u64>() {
+                Ok(end) if end >= start => end.min(len.saturating_sub(1)),
+                _ => return ByteRange::Full,
+            }
+        };
+        (start, end)
+    };
+
+    if len == 0 || start >= len {
+        return ByteRange::Unsatisfiable;
+    }
+
+    ByteRange::Partial(start, end)
+}
+
 pub fn trigger_from_request(req: &HttpRequest) -> jobsrv::JobGroupTrigger {
     // TODO: the search strings should be configurable.
     if let Some(ref agent) = req.headers().get(header::USER_AGENT) {
@@ -229,3 +308,41 @@ pub fn req_state(req: &HttpRequest) -> &AppState {
     req.app_data::<actix_web::web::Data<AppState>>()
        .expect("request state")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_match_patterns() {
+        assert!(glob_match("1.2.3", "1.2.3"));
+        assert!(glob_match("1.*", "1.2.3"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("1.?.3", "1.2.3"));
+        assert!(glob_match("*.3", "1.2.3"));
+        assert!(!glob_match("1.?", "1.10"));
+        assert!(!glob_match("2.*", "1.2.3"));
+        assert!(!glob_match("1.2", "1.2.3"));
+    }
+
+    #[test]
+    fn byte_range_specs() {
+        assert_eq!(byte_range(None, 100), ByteRange::Full);
+        assert_eq!(byte_range(Some("bytes=0-49"), 100),
+                   ByteRange::Partial(0, 49));
+        assert_eq!(byte_range(Some("bytes=50-"), 100),
+                   ByteRange::Partial(50, 99));
+        assert_eq!(byte_range(Some("bytes=-10"), 100),
+                   ByteRange::Partial(90, 99));
+        assert_eq!(byte_range(Some("bytes=90-200"), 100),
+                   ByteRange::Partial(90, 99));
+        assert_eq!(byte_range(Some("bytes=-200"), 100),
+                   ByteRange::Partial(0, 99));
+        assert_eq!(byte_range(Some("bytes=100-"), 100),
+                   ByteRange::Unsatisfiable);
+        assert_eq!(byte_range(Some("bytes=-0"), 100), ByteRange::Unsatisfiable);
+        assert_eq!(byte_range(Some("bytes=0-1,5-6"), 100), ByteRange::Full);
+        assert_eq!(byte_range(Some("bytes=5-1"), 100), ByteRange::Full);
+        assert_eq!(byte_range(Some("items=0-1"), 100), ByteRange::Full);
+    }
+}
@@ -37,29 +37,36 @@ use crate::{bldr_core::rpc::RpcClient,
                  DbPool}};
 use github_api_client::GitHubClient;
 
-use artifactory_client::client::ArtifactoryClient;
 use oauth_client::client::OAuth2Client;
 
 use self::framework::middleware::authentication_middleware;
 
 use self::services::{memcache::MemcacheClient,
-                     s3::S3Handler};
+                     storage::{self,
+                               PackageStorage}};
 
 use openssl::ssl::{SslAcceptor,
                    SslFiletype,
                    SslMethod,
                    SslVerifyMode};
 
-use self::resources::{authenticate::Authenticate,
+use self::resources::{audit::Audit,
+                      authenticate::Authenticate,
+                      bundles::Bundles,
                       channels::Channels,
                       ext::Ext,
                       jobs::Jobs,
+                      mirrors::Mirrors,
                       notify::Notify,
                       origins::Origins,
                       pkgs::Packages,
                       profile::Profile,
                       projects::Projects,
+                      retention::Retention,
+                      scheduled_promotions::ScheduledPromotions,
+                      service_accounts::ServiceAccounts,
                       settings::Settings,
+                      teams::Teams,
                       user::User};
 
 use rand::{self,
@@ -94,30 +101,28 @@ features! {
 
 // Application state
 pub struct AppState {
-    config:      Config,
-    packages:    S3Handler,
-    github:      GitHubClient,
-    jobsrv:      RpcClient,
-    oauth:       OAuth2Client,
-    memcache:    RefCell<MemcacheClient>,
-    artifactory: ArtifactoryClient,
-    db:          DbPool,
+    config:   Config,
+    packages: Box<dyn PackageStorage>,
+    github:   GitHubClient,
+    jobsrv:   RpcClient,
+    oauth:    OAuth2Client,
+    memcache: RefCell<MemcacheClient>,
+    db:       DbPool,
 }
 
 impl AppState {
     pub fn new(config: &Config, db: DbPool) -> error::Result<AppState> {
         Ok(AppState { config: config.clone(),
-                      packages: S3Handler::new(config.s3.clone()),
+                      packages: storage::new(config)?,
                       github: GitHubClient::new(config.github.clone())?,
                       jobsrv: RpcClient::new(&format!("{}", config.jobsrv)),
                       oauth: OAuth2Client::new(config.oauth.clone())?,
                       memcache: RefCell::new(MemcacheClient::new(&config.memcache.clone())),
-                      artifactory: ArtifactoryClient::new(config.artifactory.clone())?,
                       db })
     }
 }
 
-fn enable_features(config: &Config) {
+pub fn enable_features(config: &Config) {
     let features: HashMap<_, _> = HashMap::from_iter(vec![("LIST", feat::List),
                                                           ("JOBSRV", feat::Jobsrv),
                                                           ("LEGACYPROJECT", feat::LegacyProject),
@@ -155,6 +160,22 @@ pub async fn run(config: Config) -> error::Result<()> {
 
     migrations::migrate_to_encrypted(&db_pool.get_conn().unwrap(), &config.api.key_path).unwrap();
 
+    if config.retention.enabled {
+        actix_rt::spawn(services::retention::start(config.clone(), db_pool.clone()));
+    }
+
+    if config.mirror.enabled {
+        actix_rt::spawn(services::mirror::start(config.clone(), db_pool.clone()));
+    }
+
+    if config.scheduler.enabled {
+        actix_rt::spawn(services::scheduler::start(config.clone(), db_pool.clone()));
+    }
+
+    if config.uploads.enabled {
+        actix_rt::spawn(services::uploads::start(config.clone(), db_pool.clone()));
+    }
+
     let mut srv = HttpServer::new(move || {
                       let app_state = match AppState::new(&config, db_pool.clone()) {
                           Ok(state) => state,
@@ -170,16 +191,23 @@ pub async fn run(config: Config) -> error::Result<()> {
             .wrap(Logger::default().exclude("/v1/status"))
             .service(
                 web::scope("/v1")
+                    .configure(Audit::register)
                     .configure(Authenticate::register)
+                    .configure(Bundles::register)
                     .configure(Channels::register)
                     .configure(Ext::register)
                     .configure(Jobs::register)
+                    .configure(Mirrors::register)
                     .configure(Notify::register)
                     .configure(Origins::register)
                     .configure(Packages::register)
                     .configure(Profile::register)
                     .configure(Projects::register)
+                    .configure(Retention::register)
+                    .configure(ScheduledPromotions::register)
+                    .configure(ServiceAccounts::register)
                     .configure(Settings::register)
+                    .configure(Teams::register)
                     .configure(User::register)
                     .service(
                         web::resource("/status")
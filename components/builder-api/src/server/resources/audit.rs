@@ -0,0 +1,268 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::{http,
+                web::{self,
+                      Data,
+                      Path,
+                      Query,
+                      ServiceConfig},
+                HttpRequest,
+                HttpResponse};
+use chrono::{DateTime,
+             Utc};
+
+use crate::{db::models::{audit::*,
+                         origin::*},
+            protocol::jobsrv,
+            server::{authorize::authorize_session,
+                     error::{Error,
+                             Result},
+                     feat,
+                     framework::{headers,
+                                 middleware::route_message},
+                     AppState}};
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 1000;
+
+const CSV_HEADER: &str =
+    "created_at,source,id,operation,trigger,requester_id,requester_name,target_object,channel";
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    #[serde(default)]
+    pub operation: Option<String>,
+    #[serde(default)]
+    pub requester: Option<String>,
+    #[serde(default)]
+    pub target:    Option<String>,
+    #[serde(default)]
+    pub from:      Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub to:        Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub cursor:    Option<String>,
+    #[serde(default)]
+    pub limit:     Option<i64>,
+    #[serde(default)]
+    pub format:    Option<String>,
+}
+
+#[derive(Serialize)]
+struct AuditPage {
+    events:      Vec<AuditEvent>,
+    next_cursor: Option<String>,
+}
+
+enum AuditFormat {
+    Json,
+    JsonLines,
+    Csv,
+}
+
+pub struct Audit;
+
+impl Audit {
+    // Route registration
+    //
+    pub fn register(cfg: &mut ServiceConfig) {
+        cfg.route("/depot/origins/{origin}/audit",
+                  web::get().to(get_origin_audit));
+    }
+}
+
+// Route handlers - these functions can return any Responder trait
+//
+#[allow(clippy::needless_pass_by_value)]
+async fn get_origin_audit(req: HttpRequest,
+                          path: Path<String>,
+                          query: Query<AuditQuery>,
+                          state: Data<AppState>)
+                          -> HttpResponse {
+    let origin = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Owner)) {
+        return err.into();
+    }
+
+    let format = match query.format.as_deref() {
+        None | Some("json") => AuditFormat::Json,
+        Some("jsonl") => AuditFormat::JsonLines,
+        Some("csv") => AuditFormat::Csv,
+        Some(_) => return Error::Unprocessable.into(),
+    };
+
+    let page = match do_get_origin_audit(&req, &origin, &query, &state).await {
+        Ok(page) => page,
+        Err(err) => {
+            debug!("{}", err);
+            return err.into();
+        }
+    };
+
+    // The exports carry the cursor of the next page in a header, so that their bodies
+    // are nothing but events
+    let mut response = HttpResponse::Ok();
+    response.header(http::header::CACHE_CONTROL, headers::NO_CACHE);
+    if let Some(ref cursor) = page.next_cursor {
+        response.header(http::header::HeaderName::from_static(headers::XNEXTCURSOR),
+                        cursor.as_str());
+    }
+
+    match format {
+        AuditFormat::Json => response.json(page),
+        AuditFormat::JsonLines => {
+            let mut body = String::new();
+            for event in page.events.iter() {
+                match serde_json::to_string(event) {
+                    Ok(line) => {
+                        body.push_str(&line);
+                        body.push('\n');
+                    }
+                    Err(err) => return Error::SerdeJson(err).into(),
+                }
+            }
+            response.header(http::header::CONTENT_TYPE, headers::APPLICATION_NDJSON)
+                    .body(body)
+        }
+        AuditFormat::Csv => {
+            let mut body = format!("{}\n", CSV_HEADER);
+            for event in page.events.iter() {
+                body.push_str(&csv_row(event));
+                body.push('\n');
+            }
+            response.header(http::header::CONTENT_TYPE, headers::TEXT_CSV)
+                    .body(body)
+        }
+    }
+}
+
+// Internal - these functions should return Result<..>
+//
+async fn do_get_origin_audit(req: &HttpRequest,
+                             origin: &str,
+                             query: &AuditQuery,
+                             state: &AppState)
+                             -> Result<AuditPage> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if limit < 1 || limit > MAX_LIMIT {
+        return Err(Error::Unprocessable);
+    }
+    let cursor = match query.cursor {
+        Some(ref cursor) => {
+            Some(cursor.parse::<AuditCursor>()
+                       .map_err(|_| Error::BadRequest)?)
+        }
+        None => None,
+    };
+
+    let filter = AuditFilter { origin,
+                               operation: query.operation.as_deref(),
+                               requester_name: query.requester.as_deref(),
+                               target_object: query.target.as_deref(),
+                               from: query.from,
+                               to: query.to,
+                               cursor,
+                               limit };
+
+    let mut events = {
+        let conn = state.db.get_conn().map_err(Error::DbError)?;
+        AuditEvent::list(&filter, &*conn)?
+    };
+    if feat::is_enabled(feat::Jobsrv) {
+        events.append(&mut job_group_events(req, &filter).await?);
+    }
+
+    let events = AuditEvent::timeline(events, limit);
+    let next_cursor = if events.len() as i64 == limit {
+        events.last().map(|event| event.cursor().to_string())
+    } else {
+        None
+    };
+
+    Ok(AuditPage { events,
+                   next_cursor })
+}
+
+// Job group creation and cancelation is recorded by the job server
+async fn job_group_events(req: &HttpRequest, filter: &AuditFilter<'_>) -> Result<Vec<AuditEvent>> {
+    let mut request = jobsrv::JobGroupAuditGet::new();
+    request.set_origin(filter.origin.to_string());
+    if let Some(name) = filter.operation {
+        match AuditEvent::job_group_operation(name) {
+            Some(operation) => request.set_operation(operation),
+            None => return Ok(Vec::new()),
+        }
+    }
+    if let Some(requester_name) = filter.requester_name {
+        request.set_requester_name(requester_name.to_string());
+    }
+    if let Some(target_object) = filter.target_object {
+        request.set_project_name(target_object.to_string());
+    }
+    if let Some(from) = filter.from {
+        request.set_from(from.to_rfc3339());
+    }
+    if let Some(to) = filter.to {
+        request.set_to(to.to_rfc3339());
+    }
+    if let Some(cursor) = filter.cursor {
+        match cursor.bound(AuditSource::JobGroup) {
+            AuditBound::Before(at) => request.set_before(at.to_rfc3339()),
+            AuditBound::AtOrBefore(at) => {
+                request.set_before(at.to_rfc3339());
+                request.set_before_inclusive(true);
+            }
+            AuditBound::BeforeId(at, group_id) => {
+                request.set_before(at.to_rfc3339());
+                request.set_before_group_id(group_id as u64);
+            }
+        }
+    }
+    request.set_limit(filter.limit as u32);
+
+    let response =
+        route_message::<jobsrv::JobGroupAuditGet, jobsrv::JobGroupAuditResponse>(req, &request).await?;
+
+    Ok(response.get_audits()
+               .iter()
+               .filter_map(AuditEvent::from_job_group_audit)
+               .collect())
+}
+
+fn csv_row(event: &AuditEvent) -> String {
+    let fields = [event.created_at.to_rfc3339(),
+                  event.source.to_string(),
+                  event.id.to_string(),
+                  event.operation.clone(),
+                  event.trigger.clone().unwrap_or_default(),
+                  event.requester_id.to_string(),
+                  event.requester_name.clone(),
+                  event.target_object.clone(),
+                  event.channel.clone().unwrap_or_default()];
+
+    fields.iter()
+          .map(|field| csv_field(field))
+          .collect::<Vec<String>>()
+          .join(",")
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
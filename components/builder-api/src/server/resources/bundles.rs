@@ -0,0 +1,379 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::{BTreeSet,
+                        HashMap,
+                        HashSet},
+          fs::{self,
+               File},
+          io::{BufWriter,
+               Write},
+          iter,
+          path::Path as FsPath,
+          str::FromStr};
+
+use actix_web::{web::{self,
+                      Data,
+                      Query,
+                      ServiceConfig},
+                HttpRequest,
+                HttpResponse};
+use diesel::result::Error::NotFound;
+use futures::StreamExt;
+use tempfile::tempdir_in;
+
+use crate::{bldr_core::access_token::BUILDER_ACCOUNT_ID,
+            db::models::{channel::{Channel,
+                                   PackageChannelTrigger},
+                         keys::*,
+                         origin::*,
+                         package::{BuilderPackageIdent,
+                                   BuilderPackageTarget,
+                                   GetPackage,
+                                   Package,
+                                   PackageVisibility}},
+            hab_core::{crypto::keys::{parse_key_str,
+                                      parse_name_with_rev,
+                                      PairType},
+                       package::PackageTarget,
+                       ChannelIdent},
+            server::{authorize::{authorize_session,
+                                 check_origin_member_role},
+                     error::{Error,
+                             Result},
+                     helpers,
+                     resources::pkgs::do_upload_package_file,
+                     services::{bundle,
+                                closure::{self,
+                                          ClosureRequester},
+                                policy::{self,
+                                         Candidate},
+                                protection::{self,
+                                             ChannelWrite}},
+                     AppState}};
+
+#[derive(Deserialize)]
+pub struct ImportOptions {
+    // Create the origins and origin public keys of the bundle which do not exist here yet.
+    // Origins are only ever created for the builder itself.
+    #[serde(default)]
+    pub create_missing: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct BundleImportReport {
+    pub origin:          String,
+    pub channel:         String,
+    pub target:          String,
+    pub created_origins: Vec<String>,
+    pub created_keys:    Vec<String>,
+    pub uploaded:        Vec<String>,
+    // Already uploaded with the same checksum, by an earlier import or otherwise
+    pub existing:        Vec<String>,
+    pub promoted:        Vec<String>,
+}
+
+pub struct Bundles;
+
+impl Bundles {
+    // Route registration
+    //
+    pub fn register(cfg: &mut ServiceConfig) {
+        cfg.route("/depot/bundles/import", web::post().to(import_bundle));
+    }
+}
+
+// Route handlers - these functions can return any Responder trait
+//
+#[allow(clippy::needless_pass_by_value)]
+async fn import_bundle(req: HttpRequest,
+                       options: Query<ImportOptions>,
+                       stream: web::Payload,
+                       state: Data<AppState>)
+                       -> HttpResponse {
+    if let Err(err) = authorize_session(&req, None, None) {
+        return err.into();
+    }
+
+    let dir = match tempdir_in(&state.config.api.data_path) {
+        Ok(dir) => dir,
+        Err(err) => return Error::IO(err).into(),
+    };
+    let bundle_path = dir.path().join("bundle.tar.gz");
+
+    if let Err(err) =
+        receive_bundle(stream, &bundle_path, state.config.bundles.max_upload_bytes).await
+    {
+        debug!("Failed to receive bundle, err={}", err);
+        return err.into();
+    }
+
+    match do_import_bundle(&req,
+                           &bundle_path,
+                           &dir.path().join("contents"),
+                           options.create_missing,
+                           &state).await
+    {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(err) => {
+            debug!("Failed to import bundle, err={}", err);
+            err.into()
+        }
+    }
+}
+
+// Internal - these functions should return Result<..>
+//
+async fn receive_bundle(mut stream: web::Payload, path: &FsPath, max_bytes: u64) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut received: u64 = 0;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        received += chunk.len() as u64;
+        if received > max_bytes {
+            return Err(Error::Bundle(format!("bundle is larger than {} bytes", max_bytes)));
+        }
+        writer = web::block(move || writer.write_all(&chunk).map(|_| writer)).await?;
+    }
+
+    writer.into_inner()?.sync_all()?;
+    Ok(())
+}
+
+// Everything in the bundle is verified, and every permission checked, before anything is
+// changed. Packages and channel members which are already here are skipped, so an import
+// can be run again.
+async fn do_import_bundle(req: &HttpRequest,
+                          bundle_path: &FsPath,
+                          dir: &FsPath,
+                          create_missing: bool,
+                          state: &AppState)
+                          -> Result<BundleImportReport> {
+    let session = authorize_session(req, None, None)?;
+    let account_id = session.get_id() as i64;
+
+    // Unpacking and signature checks take a while, so they are kept off the server threads
+    let (index, target, checked) = {
+        let bundle_path = bundle_path.to_path_buf();
+        let dir = dir.to_path_buf();
+        let max_bytes = state.config.bundles.max_unpacked_bytes;
+        let targets = state.config.api.targets.clone();
+        web::block(move || -> Result<_> {
+            let index = bundle::unpack(&bundle_path, &dir, max_bytes)?;
+            let target = PackageTarget::from_str(&index.target)?;
+            if !targets.contains(&target) {
+                return Err(Error::Bundle(format!("{} is not a supported target", target)));
+            }
+            let checked = index.packages
+                               .iter()
+                               .map(|package| bundle::verify_package(&dir, target, package))
+                               .collect::<Result<Vec<_>>>()?;
+            Ok((index, target, checked))
+        }).await?
+    };
+
+    let mut verified = Vec::new();
+    for ((ident, tdeps, path), package) in checked.into_iter().zip(index.packages.iter()) {
+        if package.in_channel && ident.origin != index.origin {
+            return Err(Error::Bundle(format!("{} can not be a member of the {} \
+                                              channel of the {} origin",
+                                             ident, index.channel, index.origin)));
+        }
+        verified.push((ident, tdeps, path, package));
+    }
+
+    let channel = ChannelIdent::from(index.channel.as_str());
+    let mut report = BundleImportReport { origin: index.origin.clone(),
+                                          channel: index.channel.clone(),
+                                          target: index.target.clone(),
+                                          ..Default::default() };
+    let mut uploads = Vec::new();
+    let mut candidates = Vec::new();
+    {
+        let conn = state.db.get_conn().map_err(Error::DbError)?;
+
+        // The packages were verified with the bundled keys, which have to be the ones known
+        // here for the same revisions
+        let mut missing_keys = Vec::new();
+        for key in index.keys.iter() {
+            let (origin, revision) = parse_name_with_rev(&key.name)?;
+            let body = fs::read(bundle::bundle_path(dir, &key.file)?)?;
+            match OriginPublicSigningKey::get(&origin, &revision, &*conn) {
+                Ok(existing) => {
+                    if String::from_utf8_lossy(&existing.body).trim()
+                       != String::from_utf8_lossy(&body).trim()
+                    {
+                        return Err(Error::Bundle(format!("{} does not match the key already \
+                                                          here",
+                                                         key.name)));
+                    }
+                }
+                Err(NotFound) => missing_keys.push((key, origin, revision, body)),
+                Err(err) => return Err(Error::DieselError(err)),
+            }
+        }
+
+        let origins: BTreeSet<&str> = verified.iter()
+                                              .map(|(ident, ..)| ident.origin.as_str())
+                                              .chain(iter::once(index.origin.as_str()))
+                                              .collect();
+        let mut missing_origins = Vec::new();
+        for origin in origins {
+            match Origin::get(origin, &*conn) {
+                Ok(_) => (),
+                Err(NotFound) if create_missing => {
+                    if session.get_id() != BUILDER_ACCOUNT_ID {
+                        return Err(Error::Authorization);
+                    }
+                    missing_origins.push(origin);
+                }
+                Err(NotFound) => {
+                    return Err(Error::Bundle(format!("origin {} does not exist", origin)))
+                }
+                Err(err) => return Err(Error::DieselError(err)),
+            }
+        }
+
+        // Left to the upload checks, which reject or flag unknown signers per origin
+        if !create_missing {
+            missing_keys.clear();
+        }
+        for (key, origin, _, body) in missing_keys.iter() {
+            // The importer owns the origins it creates
+            if !missing_origins.contains(&origin.as_str()) {
+                authorize_session(req, Some(origin), Some(OriginMemberRole::Administrator))?;
+            }
+
+            match parse_key_str(&String::from_utf8(body.clone())?) {
+                Ok((PairType::Public, ..)) => (),
+                _ => return Err(Error::Bundle(format!("{} is not a public key", key.file))),
+            }
+        }
+
+        let mut ids = HashMap::new();
+        for (ident, _, path, package) in verified.iter() {
+            let lookup = GetPackage { ident:      BuilderPackageIdent(ident.clone()),
+                                      visibility: helpers::all_visibilities(),
+                                      target:     BuilderPackageTarget(target), };
+            match Package::get(lookup, &*conn) {
+                Ok(existing) if existing.checksum == package.checksum => {
+                    ids.insert(ident.to_string(), existing.id);
+                    report.existing.push(ident.to_string())
+                }
+                Ok(_) => {
+                    return Err(Error::Bundle(format!("{} already exists with a \
+                                                      different checksum",
+                                                     ident)))
+                }
+                Err(NotFound) => uploads.push((ident, path, package)),
+                Err(err) => return Err(Error::DieselError(err)),
+            }
+        }
+
+        // The channel members which are missing here. Those not uploaded yet have an id of 0.
+        let members: Vec<String> = verified.iter()
+                                           .filter(|(.., package)| package.in_channel)
+                                           .map(|(ident, ..)| ident.to_string())
+                                           .collect();
+        let present: HashSet<String> = Channel::filter_members(&channel,
+                                                               &members,
+                                                               &index.target,
+                                                               &*conn)?.iter()
+                                                                       .map(ToString::to_string)
+                                                                       .collect();
+        for (ident, tdeps, ..) in verified.iter().filter(|(ident, .., package)| {
+                                                     package.in_channel
+                                                     && !present.contains(&ident.to_string())
+                                                 })
+        {
+            candidates.push(Candidate { id:     ids.get(&ident.to_string()).copied().unwrap_or(0),
+                                        ident:  BuilderPackageIdent(ident.clone()),
+                                        target: BuilderPackageTarget(target),
+                                        tdeps:  tdeps.iter()
+                                                     .cloned()
+                                                     .map(BuilderPackageIdent)
+                                                     .collect(), });
+        }
+
+        // A channel of an origin created by this import has no protection or policy yet
+        if !candidates.is_empty() && !missing_origins.contains(&index.origin.as_str()) {
+            authorize_session(req, Some(&index.origin), Some(OriginMemberRole::Maintainer))?;
+            let role = check_origin_member_role(req, &index.origin, session.get_id());
+            protection::enforce(&index.origin,
+                                &channel,
+                                ChannelWrite::Promote,
+                                session.get_id(),
+                                role,
+                                &*conn)?;
+            policy::enforce_candidates(&index.origin, &channel, role, &candidates, &*conn)?;
+        }
+
+        // Nothing has been changed up to here
+        for origin in missing_origins {
+            let new_origin = NewOrigin { name: origin,
+                                         owner_id: account_id,
+                                         default_package_visibility: &PackageVisibility::Public, };
+            Origin::create(&new_origin, &*conn)?;
+            origin_audit(origin,
+                         OriginOperation::OriginCreate,
+                         origin,
+                         account_id,
+                         session.get_name(),
+                         &*conn);
+            report.created_origins.push(origin.to_string());
+        }
+
+        for (key, origin, revision, body) in missing_keys.iter() {
+            let new_key = NewOriginPublicSigningKey { owner_id: account_id,
+                                                      origin,
+                                                      full_name: &key.name,
+                                                      name: origin,
+                                                      revision,
+                                                      body };
+            OriginPublicSigningKey::create(&new_key, &*conn)?;
+            report.created_keys.push(key.name.clone());
+        }
+    }
+
+    for (ident, path, package) in uploads {
+        do_upload_package_file(req, ident, target, &package.checksum, path).await?;
+        report.uploaded.push(ident.to_string());
+    }
+
+    if !candidates.is_empty() {
+        let conn = state.db.get_conn().map_err(Error::DbError)?;
+
+        let mut packages = Vec::new();
+        for candidate in candidates {
+            let lookup = GetPackage { ident:      candidate.ident,
+                                      visibility: helpers::all_visibilities(),
+                                      target:     candidate.target, };
+            packages.push(Package::get(lookup, &*conn)?);
+        }
+
+        let requester = ClosureRequester { trigger: PackageChannelTrigger::Import,
+                                           id:      account_id,
+                                           name:    session.get_name(),
+                                           group:   None, };
+        closure::promote(&channel, &packages, &requester, &*conn)?;
+
+        state.memcache
+             .borrow_mut()
+             .clear_cache_for_channel(&index.origin, &channel);
+        report.promoted = packages.iter().map(|p| p.ident.to_string()).collect();
+    }
+
+    Ok(report)
+}
@@ -18,6 +18,7 @@ use actix_web::{http::{self,
                        StatusCode},
                 web::{self,
                       Data,
+                      Json,
                       Path,
                       Query,
                       ServiceConfig},
@@ -26,7 +27,9 @@ use actix_web::{http::{self,
 use diesel::{pg::PgConnection,
              result::{DatabaseErrorKind,
                       Error::{DatabaseError,
-                              NotFound}}};
+                              NotFound}},
+             Connection};
+use serde::ser::Serialize;
 
 use crate::{bldr_core::metrics::CounterMetric,
             hab_core::{package::{PackageIdent,
@@ -34,12 +37,19 @@ use crate::{bldr_core::metrics::CounterMetric,
                        ChannelIdent}};
 
 use crate::db::models::{channel::*,
+                        channel_history::*,
+                        channel_policy::*,
+                        channel_snapshot::*,
+                        mirror::OriginChannelMirror,
                         origin::*,
                         package::{BuilderPackageIdent,
+                                  BuilderPackageTarget,
+                                  GetPackage,
                                   GetPackageGroup,
                                   Package}};
 
-use crate::server::{authorize::authorize_session,
+use crate::server::{authorize::{authorize_session,
+                                check_origin_member_role},
                     error::{Error,
                             Result},
                     framework::headers,
@@ -49,7 +59,13 @@ use crate::server::{authorize::authorize_session,
                               Pagination,
                               Target,
                               ToChannel},
-                    services::metrics::Counter,
+                    services::{closure::{self,
+                                         ClosureRequester},
+                               diff::ChannelDiffEntry,
+                               metrics::Counter,
+                               policy,
+                               protection::{self,
+                                            ChannelWrite}},
                     AppState};
 
 // Query param containers
@@ -59,6 +75,44 @@ struct SandboxBool {
     sandbox: bool,
 }
 
+#[derive(Debug, Default, Clone, Deserialize)]
+struct ClosureMode {
+    #[serde(default)]
+    closure: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChannelPolicyReq {
+    #[serde(default)]
+    pub require_channel:          Option<String>,
+    #[serde(default)]
+    pub require_tdeps_in_channel: bool,
+    #[serde(default)]
+    pub require_worker_build:     bool,
+    #[serde(default)]
+    pub min_member_role:          Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChannelProtectionReq {
+    #[serde(default)]
+    pub promote_min_role: Option<String>,
+    #[serde(default)]
+    pub demote_min_role:  Option<String>,
+    #[serde(default)]
+    pub no_delete:        bool,
+    // Account ids, which are strings like everywhere else in the API
+    #[serde(default)]
+    pub allowed_accounts: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ChannelSnapshotDetail {
+    #[serde(flatten)]
+    snapshot: ChannelSnapshot,
+    packages: Vec<SnapshotPackage>,
+}
+
 pub struct Channels;
 
 impl Channels {
@@ -70,6 +124,32 @@ impl Channels {
                   web::post().to(create_channel))
            .route("/depot/channels/{origin}/{channel}",
                   web::delete().to(delete_channel))
+           .route("/depot/channels/{origin}/{channel}/policy",
+                  web::get().to(get_channel_policy))
+           .route("/depot/channels/{origin}/{channel}/policy",
+                  web::put().to(set_channel_policy))
+           .route("/depot/channels/{origin}/{channel}/policy",
+                  web::delete().to(delete_channel_policy))
+           .route("/depot/channels/{origin}/{channel}/protection",
+                  web::get().to(get_channel_protection))
+           .route("/depot/channels/{origin}/{channel}/protection",
+                  web::put().to(set_channel_protection))
+           .route("/depot/channels/{origin}/{channel}/snapshots",
+                  web::get().to(list_channel_snapshots))
+           .route("/depot/channels/{origin}/{channel}/snapshots/{name}",
+                  web::post().to(create_channel_snapshot))
+           .route("/depot/channels/{origin}/{channel}/snapshots/{name}",
+                  web::get().to(get_channel_snapshot))
+           .route("/depot/channels/{origin}/{channel}/snapshots/{name}/diff",
+                  web::get().to(diff_channel_snapshot))
+           .route("/depot/channels/{origin}/{channel}/snapshots/{name}/restore",
+                  web::post().to(restore_channel_snapshot))
+           .route("/depot/channels/{origin}/{channel}/diff/{other}",
+                  web::get().to(diff_channels))
+           .route("/depot/channels/{origin}/{channel}/history",
+                  web::get().to(get_channel_history))
+           .route("/depot/channels/{origin}/{channel}/history/{id}/revert",
+                  web::post().to(revert_channel_history_entry))
            .route("/depot/channels/{origin}/{channel}/pkgs",
                   web::get().to(get_packages_for_origin_channel))
            .route("/depot/channels/{origin}/{channel}/pkgs/_latest",
@@ -174,27 +254,467 @@ fn delete_channel(req: HttpRequest,
     let (origin, channel) = path.into_inner();
     let channel = ChannelIdent::from(channel);
 
-    if let Err(_err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Maintainer)) {
-        return HttpResponse::new(StatusCode::UNAUTHORIZED);
-    }
+    let account_id =
+        match authorize_session(&req, Some(&origin), Some(OriginMemberRole::Maintainer)) {
+            Ok(session) => session.get_id(),
+            Err(_) => return HttpResponse::new(StatusCode::UNAUTHORIZED),
+        };
 
     if channel == ChannelIdent::stable() || channel == ChannelIdent::unstable() {
         return HttpResponse::new(StatusCode::FORBIDDEN);
     }
 
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let role = check_origin_member_role(&req, &origin, account_id);
+    if let Err(err) = protection::enforce(&origin,
+                                          &channel,
+                                          ChannelWrite::Delete,
+                                          account_id,
+                                          role,
+                                          &*conn)
+    {
+        return err.into();
+    }
+
     state.memcache
          .borrow_mut()
          .clear_cache_for_channel(&origin, &channel);
 
+    let deleted = conn.transaction::<_, Error, _>(|| {
+                          Channel::delete(&origin, &channel, &*conn)?;
+                          // A channel created again later with the same name starts out
+                          // without a policy or snapshots
+                          OriginChannelPolicy::delete(&origin, channel.as_str(), &*conn)?;
+                          ChannelSnapshot::delete_all(&origin, channel.as_str(), &*conn)?;
+                          Ok(())
+                      });
+
+    match deleted {
+        Ok(_) => {
+            // Otherwise the next mirror sync would create the channel again
+            if let Err(err) = OriginChannelMirror::delete(&origin, channel.as_str(), &*conn) {
+                warn!("Failed to delete mirror of channel {}, err={}",
+                      channel, err);
+            }
+            HttpResponse::new(StatusCode::OK)
+        }
+        Err(err) => {
+            debug!("Failed to delete channel, err={}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn get_channel_policy(req: HttpRequest,
+                      path: Path<(String, String)>,
+                      state: Data<AppState>)
+                      -> HttpResponse {
+    let (origin, channel) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
     let conn = match state.db.get_conn().map_err(Error::DbError) {
         Ok(conn_ref) => conn_ref,
         Err(err) => return err.into(),
     };
 
-    match Channel::delete(&origin, &channel, &*conn).map_err(Error::DieselError) {
-        Ok(_) => HttpResponse::new(StatusCode::OK),
+    match OriginChannelPolicy::get(&origin, &channel, &*conn).map_err(Error::DieselError) {
+        Ok(policy) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(policy)
+        }
         Err(err) => {
-            debug!("Failed to delete channel, err={}", err);
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn set_channel_policy(req: HttpRequest,
+                      path: Path<(String, String)>,
+                      body: Json<ChannelPolicyReq>,
+                      state: Data<AppState>)
+                      -> HttpResponse {
+    let (origin, channel) = path.into_inner();
+    let channel = ChannelIdent::from(channel);
+
+    let account_id =
+        match authorize_session(&req, Some(&origin), Some(OriginMemberRole::Administrator)) {
+            Ok(session) => session.get_id(),
+            Err(err) => return err.into(),
+        };
+
+    // Nothing is ever promoted into unstable, uploads land there directly
+    if channel == ChannelIdent::unstable() {
+        return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    if body.require_channel.as_deref() == Some(channel.as_str()) {
+        return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let min_member_role = match body.min_member_role {
+        Some(ref role) => {
+            match OriginMemberRole::from_str(role) {
+                Ok(role) => Some(role),
+                Err(err) => {
+                    debug!("{}", err);
+                    return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+                }
+            }
+        }
+        None => None,
+    };
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    if let Err(err) = Channel::get(&origin, &channel, &*conn).map_err(Error::DieselError) {
+        debug!("{}", err);
+        return err.into();
+    }
+
+    let new_policy = NewOriginChannelPolicy { origin: &origin,
+                                              channel: channel.as_str(),
+                                              require_channel: body.require_channel.as_deref(),
+                                              require_tdeps_in_channel:
+                                                  body.require_tdeps_in_channel,
+                                              require_worker_build: body.require_worker_build,
+                                              min_member_role,
+                                              owner_id: account_id as i64 };
+
+    match OriginChannelPolicy::set(&new_policy, &*conn).map_err(Error::DieselError) {
+        Ok(policy) => HttpResponse::Ok().json(policy),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn delete_channel_policy(req: HttpRequest,
+                         path: Path<(String, String)>,
+                         state: Data<AppState>)
+                         -> HttpResponse {
+    let (origin, channel) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Administrator))
+    {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    match OriginChannelPolicy::delete(&origin, &channel, &*conn).map_err(Error::DieselError) {
+        Ok(0) => HttpResponse::NotFound().into(),
+        Ok(_) => HttpResponse::NoContent().into(),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn get_channel_protection(req: HttpRequest,
+                          path: Path<(String, String)>,
+                          state: Data<AppState>)
+                          -> HttpResponse {
+    let (origin, channel) = path.into_inner();
+    let channel = ChannelIdent::from(channel);
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    // The protection settings are columns of the channel itself
+    match Channel::get(&origin, &channel, &*conn).map_err(Error::DieselError) {
+        Ok(channel) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(channel)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn set_channel_protection(req: HttpRequest,
+                          path: Path<(String, String)>,
+                          body: Json<ChannelProtectionReq>,
+                          state: Data<AppState>)
+                          -> HttpResponse {
+    let (origin, channel) = path.into_inner();
+    let channel = ChannelIdent::from(channel);
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Administrator))
+    {
+        return err.into();
+    }
+
+    let promote_min_role = match parse_min_role(body.promote_min_role.as_deref()) {
+        Ok(role) => role,
+        Err(err) => return err.into(),
+    };
+    let demote_min_role = match parse_min_role(body.demote_min_role.as_deref()) {
+        Ok(role) => role,
+        Err(err) => return err.into(),
+    };
+
+    let mut allowed_accounts = Vec::new();
+    for account in body.allowed_accounts.iter() {
+        match account.parse::<i64>() {
+            Ok(id) => allowed_accounts.push(id),
+            Err(err) => {
+                debug!("Invalid account id {}, err={}", account, err);
+                return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+            }
+        }
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let protection = ChannelProtection { promote_min_role,
+                                         demote_min_role,
+                                         no_delete: body.no_delete,
+                                         allowed_accounts };
+
+    match Channel::set_protection(&origin, &channel, &protection, &*conn)
+        .map_err(Error::DieselError)
+    {
+        Ok(channel) => HttpResponse::Ok().json(channel),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn list_channel_snapshots(req: HttpRequest,
+                          path: Path<(String, String)>,
+                          state: Data<AppState>)
+                          -> HttpResponse {
+    let (origin, channel) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    match ChannelSnapshot::list(&origin, &channel, &*conn).map_err(Error::DieselError) {
+        Ok(snapshots) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(snapshots)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn create_channel_snapshot(req: HttpRequest,
+                           path: Path<(String, String, String)>,
+                           state: Data<AppState>)
+                           -> HttpResponse {
+    let (origin, channel, name) = path.into_inner();
+    let channel = ChannelIdent::from(channel);
+
+    let session_id =
+        match authorize_session(&req, Some(&origin), Some(OriginMemberRole::Maintainer)) {
+            Ok(session) => session.get_id(),
+            Err(err) => return err.into(),
+        };
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let channel = match Channel::get(&origin, &channel, &*conn) {
+        Ok(channel) => channel,
+        Err(err) => {
+            debug!("{}", err);
+            return Error::DieselError(err).into();
+        }
+    };
+
+    match ChannelSnapshot::create(&channel, &name, session_id as i64, &*conn) {
+        Ok(snapshot) => HttpResponse::Created().json(snapshot),
+        Err(DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            HttpResponse::Conflict().into()
+        }
+        Err(err) => {
+            debug!("Failed to create channel snapshot, err={}", err);
+            Error::DieselError(err).into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn get_channel_snapshot(req: HttpRequest,
+                        path: Path<(String, String, String)>,
+                        state: Data<AppState>)
+                        -> HttpResponse {
+    let (origin, channel, name) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let detail = ChannelSnapshot::get(&origin, &channel, &name, &*conn).and_then(|snapshot| {
+                     let packages = snapshot.packages(&*conn)?;
+                     Ok(ChannelSnapshotDetail { snapshot, packages })
+                 });
+
+    match detail.map_err(Error::DieselError) {
+        Ok(detail) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(detail)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn diff_channel_snapshot(req: HttpRequest,
+                         path: Path<(String, String, String)>,
+                         state: Data<AppState>)
+                         -> HttpResponse {
+    let (origin, channel, name) = path.into_inner();
+    let channel = ChannelIdent::from(channel);
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let live = match Channel::get(&origin, &channel, &*conn) {
+        Ok(live) => live,
+        Err(err) => return Error::DieselError(err).into(),
+    };
+
+    let diff = match ChannelSnapshot::get(&origin, channel.as_str(), &name, &*conn) {
+        Ok(snapshot) => snapshot.diff(live.id, &*conn),
+        Err(err) => Err(err),
+    };
+
+    match diff.map_err(Error::DieselError) {
+        Ok(diff) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(diff)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn restore_channel_snapshot(req: HttpRequest,
+                            path: Path<(String, String, String)>,
+                            state: Data<AppState>)
+                            -> HttpResponse {
+    let (origin, channel, name) = path.into_inner();
+    let channel = ChannelIdent::from(channel);
+
+    // Packages only ever leave unstable by being deleted
+    if channel == ChannelIdent::unstable() {
+        return HttpResponse::new(StatusCode::FORBIDDEN);
+    }
+
+    let session = match authorize_session(&req, Some(&origin), Some(OriginMemberRole::Maintainer)) {
+        Ok(session) => session,
+        Err(err) => return err.into(),
+    };
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let live = match Channel::get(&origin, &channel, &*conn) {
+        Ok(live) => live,
+        Err(err) => return Error::DieselError(err).into(),
+    };
+
+    // A restore may both promote into the channel and demote from it
+    let role = check_origin_member_role(&req, &origin, session.get_id());
+    for write in &[ChannelWrite::Promote, ChannelWrite::Demote] {
+        if let Some(reason) = protection::check(&live, *write, session.get_id() as i64, role) {
+            return Error::ChannelProtected(reason).into();
+        }
+    }
+
+    let snapshot = match ChannelSnapshot::get(&origin, channel.as_str(), &name, &*conn) {
+        Ok(snapshot) => snapshot,
+        Err(err) => return Error::DieselError(err).into(),
+    };
+
+    let trigger = helpers::trigger_from_request_model(&req);
+    let request = RestoreChannelSnapshot { channel_id: live.id,
+                                           trigger,
+                                           requester_id: session.get_id() as i64,
+                                           requester_name: session.get_name() };
+
+    let check = |promoted: &[SnapshotPackage]| {
+        do_check_snapshot_restore(&origin, &channel, role, promoted, &*conn)
+    };
+
+    match snapshot.restore(&request, check, &*conn) {
+        Ok(restore) => {
+            let mut memcache = state.memcache.borrow_mut();
+            memcache.clear_cache_for_channel(&origin, &channel);
+            for package in restore.promoted.iter().chain(restore.demoted.iter()) {
+                memcache.clear_cache_for_package(&package.ident);
+            }
+            HttpResponse::Ok().json(restore)
+        }
+        Err(err) => {
+            debug!("Failed to restore channel snapshot, err={}", err);
             err.into()
         }
     }
@@ -333,8 +853,28 @@ fn do_promote_or_demote_channel_packages(req: &HttpRequest,
         return Err(Error::BadRequest);
     }
 
+    let role = check_origin_member_role(req, origin, session_id as u64);
+    let write = if promote {
+        ChannelWrite::Promote
+    } else {
+        ChannelWrite::Demote
+    };
+    protection::enforce(origin, ch_target, write, session_id as u64, role, &*conn)?;
+
     let pkgs = do_get_all_channel_packages(&req, &origin, &ch_source)?;
 
+    #[rustfmt::skip]
+    let op = Package::get_group(
+        GetPackageGroup {
+            pkgs,
+            visibility: helpers::all_visibilities()
+        },
+    &*conn)?;
+
+    if promote {
+        policy::enforce(origin, ch_target, role, &op, &*conn)?;
+    }
+
     #[rustfmt::skip]
     let channel = match Channel::get(&origin, &ch_target, &*conn) {
         Ok(channel) => channel,
@@ -358,14 +898,6 @@ fn do_promote_or_demote_channel_packages(req: &HttpRequest,
         }
     };
 
-    #[rustfmt::skip]
-    let op = Package::get_group(
-        GetPackageGroup {
-            pkgs,
-            visibility: helpers::all_visibilities()
-        },
-    &*conn)?;
-
     let mut ids: Vec<i64> = op.iter().map(|x| x.id).collect();
 
     pkg_ids.append(&mut ids);
@@ -384,6 +916,7 @@ fn do_promote_or_demote_channel_packages(req: &HttpRequest,
 fn promote_package(req: HttpRequest,
                    path: Path<(String, String, String, String, String)>,
                    qtarget: Query<Target>,
+                   closure: Query<ClosureMode>,
                    state: Data<AppState>)
                    -> HttpResponse {
     let (origin, channel, pkg, version, release) = path.into_inner();
@@ -416,6 +949,53 @@ fn promote_package(req: HttpRequest,
         Err(err) => return err.into(),
     };
 
+    let package = match Package::get(GetPackage { ident:      BuilderPackageIdent(ident.clone()),
+                                                  visibility: helpers::all_visibilities(),
+                                                  target:     BuilderPackageTarget(target), },
+                                     &*conn).map_err(Error::DieselError)
+    {
+        Ok(package) => package,
+        Err(err) => return err.into(),
+    };
+
+    if closure.closure {
+        return match do_promote_package_closure(&req,
+                                                &channel,
+                                                package,
+                                                session.get_id(),
+                                                session.get_name(),
+                                                &*conn)
+        {
+            Ok(packages) => {
+                let mut memcache = state.memcache.borrow_mut();
+                for package in packages.iter() {
+                    memcache.clear_cache_for_package(&package.ident);
+                }
+                let idents: Vec<&BuilderPackageIdent> = packages.iter().map(|p| &p.ident).collect();
+                HttpResponse::Ok().json(idents)
+            }
+            Err(err) => {
+                debug!("Failed to promote package closure, err={}", err);
+                err.into()
+            }
+        };
+    }
+
+    let role = check_origin_member_role(&req, &origin, session.get_id());
+    if let Err(err) = protection::enforce(&origin,
+                                          &channel,
+                                          ChannelWrite::Promote,
+                                          session.get_id(),
+                                          role,
+                                          &*conn)
+    {
+        return err.into();
+    }
+
+    if let Err(err) = policy::enforce(&origin, &channel, role, &[package], &*conn) {
+        return err.into();
+    }
+
     match OriginChannelPackage::promote(
         OriginChannelPromote {
             ident: BuilderPackageIdent(ident.clone()),
@@ -437,6 +1017,7 @@ fn promote_package(req: HttpRequest,
                     requester_id: session.get_id() as i64,
                     requester_name: &session.get_name(),
                     origin: &origin,
+                    target: Some(BuilderPackageTarget(target)),
                 },
                 &*conn,
             ) {
@@ -496,6 +1077,17 @@ fn demote_package(req: HttpRequest,
         Err(err) => return err.into(),
     };
 
+    let role = check_origin_member_role(&req, &origin, session.get_id());
+    if let Err(err) = protection::enforce(&origin,
+                                          &channel,
+                                          ChannelWrite::Demote,
+                                          session.get_id(),
+                                          role,
+                                          &*conn)
+    {
+        return err.into();
+    }
+
     match OriginChannelPackage::demote(OriginChannelDemote { ident:
                                                                  BuilderPackageIdent(ident.clone()),
                                                              target,
@@ -518,6 +1110,7 @@ fn demote_package(req: HttpRequest,
                     requester_id: session.get_id() as i64,
                     requester_name: &session.get_name(),
                     origin: &origin,
+                    target: Some(BuilderPackageTarget(target)),
                 },
                 &*conn,
             ) {
@@ -704,8 +1297,255 @@ fn get_latest_packages_for_origin_channel(req: HttpRequest,
     }
 }
 
+#[allow(clippy::needless_pass_by_value)]
+fn diff_channels(req: HttpRequest,
+                 path: Path<(String, String, String)>,
+                 qtarget: Query<Target>,
+                 pagination: Query<Pagination>)
+                 -> HttpResponse {
+    let (origin, a, b) = path.into_inner();
+    let a = ChannelIdent::from(a);
+    let b = ChannelIdent::from(b);
+
+    let targets: Vec<PackageTarget> = match qtarget.target {
+        Some(ref t) => {
+            match PackageTarget::from_str(t) {
+                Ok(t) => vec![t],
+                Err(err) => {
+                    debug!("Invalid target requested: {}, err = {:?}", t, err);
+                    return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+                }
+            }
+        }
+        None => PackageTarget::targets().cloned().collect(),
+    };
+
+    match do_diff_channels(&req, &origin, &a, &b, &targets, &pagination) {
+        Ok((entries, count)) => {
+            postprocess_channel_package_list(&req, &entries, count, &pagination)
+        }
+        Err(err) => {
+            debug!("Failed to diff channels, err={}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn get_channel_history(req: HttpRequest,
+                       path: Path<(String, String)>,
+                       pagination: Query<Pagination>,
+                       state: Data<AppState>)
+                       -> HttpResponse {
+    let (origin, channel) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let (start, end) = helpers::extract_pagination(&pagination);
+
+    match ChannelHistory::list(&origin,
+                               &channel,
+                               start as i64,
+                               (end - start + 1) as i64,
+                               &*conn).map_err(Error::DieselError)
+    {
+        Ok((entries, count)) => {
+            postprocess_channel_package_list(&req, &entries, count, &pagination)
+        }
+        Err(err) => {
+            debug!("Failed to get channel history, err={}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn revert_channel_history_entry(req: HttpRequest,
+                                path: Path<(String, String, String)>,
+                                state: Data<AppState>)
+                                -> HttpResponse {
+    let (origin, channel, id_str) = path.into_inner();
+
+    let id = match id_str.parse::<i64>() {
+        Ok(id) => id,
+        Err(e) => {
+            debug!("Error finding id. e = {:?}", e);
+            return HttpResponse::new(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let entry = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn) => ChannelHistory::get(id, &*conn),
+        Err(err) => return err.into(),
+    };
+
+    let entry = match entry {
+        Ok(ref entry) if entry.origin != origin || entry.channel != channel => {
+            return HttpResponse::new(StatusCode::NOT_FOUND)
+        }
+        Ok(entry) => entry,
+        Err(err) => return Error::DieselError(err).into(),
+    };
+
+    match do_revert_channel_history(&req, &[entry]) {
+        Ok(reverted) => HttpResponse::Ok().json(reverted),
+        Err(err) => {
+            debug!("Failed to revert channel history entry, err={}", err);
+            err.into()
+        }
+    }
+}
+
 // Internal - these functions should return Result<..>
 //
+fn parse_min_role(role: Option<&str>) -> Result<Option<OriginMemberRole>> {
+    role.map(OriginMemberRole::from_str)
+        .transpose()
+        .map_err(|err| {
+            debug!("{}", err);
+            Error::Unprocessable
+        })
+}
+
+// The packages a restore puts back into the channel have to pass its policy, like any
+// other promotion
+fn do_check_snapshot_restore(origin: &str,
+                             channel: &ChannelIdent,
+                             role: Option<OriginMemberRole>,
+                             promoted: &[SnapshotPackage],
+                             conn: &PgConnection)
+                             -> Result<()> {
+    if promoted.is_empty() {
+        return Ok(());
+    }
+
+    let mut packages = Vec::new();
+    for package in promoted {
+        packages.push(Package::get(GetPackage { ident:      package.ident.clone(),
+                                                visibility: helpers::all_visibilities(),
+                                                target:     package.target.clone(), },
+                                   conn)?);
+    }
+
+    policy::enforce(origin, channel, role, &packages, conn)
+}
+
+// Promotes the package along with its runtime deps which are missing from the channel, in
+// one transaction. Returns everything promoted, the package first.
+fn do_promote_package_closure(req: &HttpRequest,
+                              channel: &ChannelIdent,
+                              package: Package,
+                              account_id: u64,
+                              account_name: &str,
+                              conn: &PgConnection)
+                              -> Result<Vec<Package>> {
+    let deps = closure::missing_deps(req, account_id, channel, &[package.clone()], conn)?;
+    let mut packages = vec![package];
+    packages.extend(deps);
+
+    protection::enforce_each(channel,
+                             ChannelWrite::Promote,
+                             &packages,
+                             account_id,
+                             |origin| check_origin_member_role(req, origin, account_id),
+                             conn)?;
+    policy::enforce_each(channel,
+                         &packages,
+                         |origin| check_origin_member_role(req, origin, account_id),
+                         conn)?;
+
+    closure::promote(channel,
+                     &packages,
+                     &ClosureRequester { trigger: helpers::trigger_from_request_model(req),
+                                         id:      account_id as i64,
+                                         name:    account_name,
+                                         group:   None, },
+                     conn)?;
+    Ok(packages)
+}
+
+// Undoes entries of the channel history. Reverting an entry takes the maintainer role in its
+// origin, or the administrator role when someone else made the change.
+pub fn do_revert_channel_history(req: &HttpRequest,
+                                 entries: &[ChannelHistoryEntry])
+                                 -> Result<Vec<ChannelHistoryPackage>> {
+    let conn = req_state(req).db.get_conn().map_err(Error::DbError)?;
+    let mut requester = None;
+
+    for entry in entries {
+        // Packages only ever leave unstable by being deleted
+        if entry.channel == ChannelIdent::unstable().as_str() {
+            return Err(Error::Authorization);
+        }
+
+        let session =
+            authorize_session(req, Some(&entry.origin), Some(OriginMemberRole::Maintainer))?;
+        if entry.requester_id != session.get_id() as i64 {
+            authorize_session(req,
+                              Some(&entry.origin),
+                              Some(OriginMemberRole::Administrator))?;
+        }
+
+        // Reverting a promotion demotes and the other way around
+        let write = match entry.operation {
+            PackageChannelOperation::Promote => ChannelWrite::Demote,
+            PackageChannelOperation::Demote => ChannelWrite::Promote,
+        };
+        let channel = ChannelIdent::from(entry.channel.as_str());
+        let role = check_origin_member_role(req, &entry.origin, session.get_id());
+        protection::enforce(&entry.origin,
+                            &channel,
+                            write,
+                            session.get_id(),
+                            role,
+                            &*conn)?;
+
+        // Reverting a demotion promotes, which has to pass the policy of the channel
+        if entry.operation == PackageChannelOperation::Demote {
+            let mut packages = Vec::new();
+            for package in ChannelHistory::existing_packages(entry, &*conn)? {
+                let target = match package.target {
+                    Some(target) => target,
+                    None => continue,
+                };
+                packages.push(Package::get(GetPackage { ident: package.ident,
+                                                        visibility: helpers::all_visibilities(),
+                                                        target },
+                                           &*conn)?);
+            }
+            policy::enforce(&entry.origin, &channel, role, &packages, &*conn)?;
+        }
+        requester = Some(session);
+    }
+
+    let session = match requester {
+        Some(session) => session,
+        None => return Err(Error::NotFound),
+    };
+
+    let request = RevertChannelHistory { trigger:        helpers::trigger_from_request_model(req),
+                                         requester_id:   session.get_id() as i64,
+                                         requester_name: session.get_name(), };
+    let reverted = ChannelHistory::revert(entries, &request, &*conn)?;
+
+    let mut memcache = req_state(req).memcache.borrow_mut();
+    for entry in entries {
+        memcache.clear_cache_for_channel(&entry.origin,
+                                         &ChannelIdent::from(entry.channel.as_str()));
+    }
+    for package in reverted.iter() {
+        memcache.clear_cache_for_package(&package.ident);
+    }
+
+    Ok(reverted)
+}
 
 fn do_get_latest_channel_packages(req: &HttpRequest,
                                   qtarget: &Query<Target>,
@@ -740,6 +1580,39 @@ fn do_get_latest_channel_packages(req: &HttpRequest,
     .map_err(Error::DieselError)
 }
 
+// The latest packages of two channels compared, for each of the targets
+fn do_diff_channels(req: &HttpRequest,
+                    origin: &str,
+                    a: &ChannelIdent,
+                    b: &ChannelIdent,
+                    targets: &[PackageTarget],
+                    pagination: &Query<Pagination>)
+                    -> Result<(Vec<ChannelDiffEntry>, i64)> {
+    let opt_session_id = match authorize_session(req, None, None) {
+        Ok(session) => Some(session.get_id()),
+        Err(_) => None,
+    };
+    let visibility = helpers::visibility_for_optional_session(req, opt_session_id, origin);
+    let (start, end) = helpers::extract_pagination(pagination);
+    let targets: Vec<String> = targets.iter().map(PackageTarget::to_string).collect();
+
+    let conn = req_state(req).db.get_conn().map_err(Error::DbError)?;
+
+    Channel::get(origin, a, &*conn)?;
+    Channel::get(origin, b, &*conn)?;
+
+    let (rows, count) = Channel::diff_latest(&DiffChannels { visibility: &visibility,
+                                                             origin,
+                                                             a,
+                                                             b,
+                                                             targets: &targets,
+                                                             start: start.max(0) as i64,
+                                                             limit: (end - start + 1) as i64 },
+                                             &*conn)?;
+
+    Ok((rows.into_iter().map(ChannelDiffEntry::from).collect(), count))
+}
+
 fn do_get_channel_packages(req: &HttpRequest,
                            pagination: &Query<Pagination>,
                            ident: &PackageIdent,
@@ -925,11 +1798,11 @@ pub fn channels_for_package_ident(req: &HttpRequest,
 
 // Helper
 
-fn postprocess_channel_package_list(_req: &HttpRequest,
-                                    packages: &[BuilderPackageIdent],
-                                    count: i64,
-                                    pagination: &Query<Pagination>)
-                                    -> HttpResponse {
+fn postprocess_channel_package_list<T: Serialize>(_req: &HttpRequest,
+                                                  packages: &[T],
+                                                  count: i64,
+                                                  pagination: &Query<Pagination>)
+                                                  -> HttpResponse {
     let (start, _) = helpers::extract_pagination(pagination);
     let pkg_count = packages.len() as isize;
     let stop = match pkg_count {
@@ -37,14 +37,19 @@ use crate::hab_core::{package::{Identifiable,
                       ChannelIdent};
 
 use crate::db::models::{channel::*,
+                        channel_history::{ChannelHistory,
+                                          ChannelHistoryPackage},
                         jobs::*,
                         origin::*,
                         package::*,
                         projects::*,
                         settings::*};
-use diesel::result::Error::NotFound;
+use diesel::{pg::PgConnection,
+             result::Error::NotFound,
+             Connection};
 
-use crate::server::{authorize::authorize_session,
+use crate::server::{authorize::{authorize_session,
+                                check_origin_member_role},
                     error::{Error,
                             Result},
                     feat,
@@ -53,13 +58,22 @@ use crate::server::{authorize::authorize_session,
                     helpers::{self,
                               req_state,
                               Target},
-                    resources::{channels::channels_for_package_ident,
-                                pkgs::platforms_for_package_ident}};
+                    resources::{channels::{channels_for_package_ident,
+                                           do_revert_channel_history},
+                                pkgs::platforms_for_package_ident},
+                    services::{closure::{self,
+                                         ClosureRequester},
+                               policy,
+                               protection::{self,
+                                            ChannelWrite}}};
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct GroupPromoteReq {
     #[serde(default)]
-    pub idents: Vec<String>,
+    pub idents:  Vec<String>,
+    // Also promote the runtime deps missing from the channel
+    #[serde(default)]
+    pub closure: bool,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -86,6 +100,8 @@ impl Jobs {
                   web::post().to(promote_job_group))
            .route("/jobs/group/{id}/demote/{channel}",
                   web::post().to(demote_job_group))
+           .route("/jobs/group/{id}/revert/{channel}",
+                  web::post().to(revert_job_group))
            .route("/jobs/group/{id}/cancel", web::post().to(cancel_job_group))
            .route("/rdeps/{origin}/{name}", web::get().to(get_rdeps))
            .route("/rdeps/{origin}/{name}/group",
@@ -320,7 +336,12 @@ async fn promote_job_group(req: HttpRequest,
     let (group_id, channel) = path.into_inner();
     let channel = ChannelIdent::from(channel);
 
-    match promote_or_demote_job_group(&req, &group_id, &body.idents, &channel, true).await {
+    match promote_or_demote_job_group(&req,
+                                      &group_id,
+                                      &body.idents,
+                                      &channel,
+                                      true,
+                                      body.closure).await {
         Ok(_) => HttpResponse::NoContent().finish(),
         Err(err) => {
             debug!("{}", err);
@@ -337,7 +358,7 @@ async fn demote_job_group(req: HttpRequest,
     let (group_id, channel) = path.into_inner();
     let channel = ChannelIdent::from(channel);
 
-    match promote_or_demote_job_group(&req, &group_id, &body.idents, &channel, false).await {
+    match promote_or_demote_job_group(&req, &group_id, &body.idents, &channel, false, false).await {
         Ok(_) => HttpResponse::NoContent().finish(),
         Err(err) => {
             debug!("{}", err);
@@ -346,6 +367,30 @@ async fn demote_job_group(req: HttpRequest,
     }
 }
 
+// Undoes every promotion or demotion the group made in the channel
+#[allow(clippy::needless_pass_by_value)]
+fn revert_job_group(req: HttpRequest, path: Path<(String, String)>) -> HttpResponse {
+    let (id_str, channel) = path.into_inner();
+
+    // Group id 0 is shared by every bulk change that did not come from a job group
+    let group_id = match id_str.parse::<u64>() {
+        Ok(id) if id > 0 => id,
+        Ok(_) => return HttpResponse::new(StatusCode::BAD_REQUEST),
+        Err(e) => {
+            debug!("Error finding id. e = {:?}", e);
+            return HttpResponse::new(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    match do_revert_job_group(&req, group_id, &channel) {
+        Ok(reverted) => HttpResponse::Ok().json(reverted),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
 #[allow(clippy::needless_pass_by_value)]
 async fn cancel_job_group(req: HttpRequest, path: Path<String>) -> HttpResponse {
     let id_str = path.into_inner();
@@ -374,20 +419,19 @@ fn do_group_promotion_or_demotion(req: &HttpRequest,
                                   projects: Vec<&jobsrv::JobGroupProject>,
                                   origin: &str,
                                   target: PackageTarget,
-                                  promote: bool)
+                                  promote: bool,
+                                  conn: &PgConnection)
                                   -> Result<Vec<i64>> {
     let session = authorize_session(req, Some(&origin), Some(OriginMemberRole::Maintainer))?;
 
-    let conn = req_state(req).db.get_conn().map_err(Error::DbError)?;
-
-    let channel = match Channel::get(origin, channel, &*conn) {
+    let channel = match Channel::get(origin, channel, conn) {
         Ok(channel) => channel,
         Err(NotFound) => {
             if (channel != &ChannelIdent::stable()) && (channel != &ChannelIdent::unstable()) {
                 Channel::create(&CreateChannel { name: channel.as_str(),
                                                  origin,
                                                  owner_id: session.get_id() as i64 },
-                                &*conn)?
+                                conn)?
             } else {
                 warn!("Unable to retrieve default channel: {}", channel);
                 return Err(Error::DieselError(NotFound));
@@ -417,26 +461,106 @@ fn do_group_promotion_or_demotion(req: &HttpRequest,
                 visibility: helpers::all_visibilities(),
                 target: BuilderPackageTarget(target),
             },
-            &*conn,
+            conn,
         )?;
 
         package_ids.push(op.id);
     }
 
     if promote {
-        Channel::promote_packages(channel.id, &package_ids, &*conn)?;
+        Channel::promote_packages(channel.id, &package_ids, conn)?;
     } else {
-        Channel::demote_packages(channel.id, &package_ids, &*conn)?;
+        Channel::demote_packages(channel.id, &package_ids, conn)?;
     }
 
     Ok(package_ids)
 }
 
+fn do_revert_job_group(req: &HttpRequest,
+                       group_id: u64,
+                       channel: &str)
+                       -> Result<Vec<ChannelHistoryPackage>> {
+    authorize_session(req, None, Some(OriginMemberRole::Maintainer))?;
+
+    let entries = {
+        let conn = req_state(req).db.get_conn().map_err(Error::DbError)?;
+        ChannelHistory::for_group(group_id as i64, channel, &*conn)?
+    };
+
+    do_revert_channel_history(req, &entries)
+}
+
+// Checks the projects of every origin against the policy of the channel they are being
+// promoted into, along with the runtime deps missing from the channel in closure mode.
+// This happens before anything is promoted, as the origins are committed one at a time.
+// Returns the missing deps.
+fn check_group_promotion(req: &HttpRequest,
+                         channel: &ChannelIdent,
+                         origin_map: &HashMap<String, Vec<&jobsrv::JobGroupProject>>,
+                         target: PackageTarget,
+                         closure: bool)
+                         -> Result<Vec<Package>> {
+    let session = authorize_session(req, None, Some(OriginMemberRole::Maintainer))?;
+    let conn = req_state(req).db.get_conn().map_err(Error::DbError)?;
+
+    let mut packages = Vec::new();
+    for projects in origin_map.values() {
+        for project in projects.iter() {
+            let ident = PackageIdent::from_str(project.get_ident())?;
+            packages.push(Package::get(GetPackage { ident:      BuilderPackageIdent(ident),
+                                                    visibility: helpers::all_visibilities(),
+                                                    target:     BuilderPackageTarget(target), },
+                                       &*conn)?);
+        }
+    }
+
+    let deps = if closure {
+        closure::missing_deps(req, session.get_id(), channel, &packages, &*conn)?
+    } else {
+        Vec::new()
+    };
+    packages.extend(deps.iter().cloned());
+
+    protection::enforce_each(channel,
+                             ChannelWrite::Promote,
+                             &packages,
+                             session.get_id(),
+                             |origin| check_origin_member_role(req, origin, session.get_id()),
+                             &*conn)?;
+    policy::enforce_each(channel,
+                         &packages,
+                         |origin| check_origin_member_role(req, origin, session.get_id()),
+                         &*conn)?;
+    Ok(deps)
+}
+
+// Checks the channel of every origin of the group is open to the demotion, before any of
+// them is committed
+fn check_group_demotion(req: &HttpRequest,
+                        channel: &ChannelIdent,
+                        origin_map: &HashMap<String, Vec<&jobsrv::JobGroupProject>>)
+                        -> Result<()> {
+    let session = authorize_session(req, None, Some(OriginMemberRole::Maintainer))?;
+    let conn = req_state(req).db.get_conn().map_err(Error::DbError)?;
+
+    for origin in origin_map.keys() {
+        let role = check_origin_member_role(req, origin, session.get_id());
+        protection::enforce(origin,
+                            channel,
+                            ChannelWrite::Demote,
+                            session.get_id(),
+                            role,
+                            &*conn)?;
+    }
+    Ok(())
+}
+
 async fn promote_or_demote_job_group(req: &HttpRequest,
                                      group_id_str: &str,
                                      idents: &[String],
                                      channel: &ChannelIdent,
-                                     promote: bool)
+                                     promote: bool,
+                                     closure: bool)
                                      -> Result<()> {
     authorize_session(&req, None, Some(OriginMemberRole::Maintainer))?;
 
@@ -467,12 +591,9 @@ async fn promote_or_demote_job_group(req: &HttpRequest,
     };
 
     // We can't assume that every project in the group belongs to the same origin. It's entirely
-    // possible that there are multiple origins present within the group. Because of this, there's
-    // no way to atomically commit the entire promotion/demotion at once. It's possible origin
-    // shards can be on different machines, so for now, the best we can do is partition the projects
-    // by origin, and commit each origin at once. Ultimately, it'd be nice to have a way to
-    // atomically commit the entire promotion/demotion at once, but that would require a cross-shard
-    // tool that we don't currently have.
+    // possible that there are multiple origins present within the group, so the projects are
+    // partitioned by origin. Every origin, along with any missing deps, is then committed in a
+    // single transaction.
     for project in group.get_projects().iter() {
         if project.get_state() == jobsrv::JobGroupProjectState::Success {
             let ident_str = project.get_ident();
@@ -487,53 +608,69 @@ async fn promote_or_demote_job_group(req: &HttpRequest,
         }
     }
 
+    let deps = if promote {
+        check_group_promotion(req, channel, &origin_map, target, closure)?
+    } else {
+        check_group_demotion(req, channel, &origin_map)?;
+        Vec::new()
+    };
+
     let jgt = helpers::trigger_from_request(req);
     let trigger = PackageChannelTrigger::from(jgt);
     let conn = req_state(req).db.get_conn().map_err(Error::DbError)?;
 
-    for (origin, projects) in origin_map.iter() {
-        match do_group_promotion_or_demotion(req,
-                                             channel,
-                                             projects.to_vec(),
-                                             &origin,
-                                             target,
-                                             promote)
-        {
-            Ok(package_ids) => {
-                let pco = if promote {
-                    PackageChannelOperation::Promote
-                } else {
-                    PackageChannelOperation::Demote
-                };
+    let session = authorize_session(req, None, Some(OriginMemberRole::Maintainer))?;
+    let pco = if promote {
+        PackageChannelOperation::Promote
+    } else {
+        PackageChannelOperation::Demote
+    };
 
-                // TODO: This feels like with the added weight of calls to authorize
-                // session, this might not be the best way to handle passing the session
-                // info in. We probably should consider a refactor here.
-
-                let session =
-                    authorize_session(req, None, Some(OriginMemberRole::Maintainer)).unwrap(); // Unwrap ok
-
-                PackageGroupChannelAudit::audit(PackageGroupChannelAudit { origin: &origin,
-                                                                           channel:
-                                                                               channel.as_str(),
-                                                                           package_ids,
-                                                                           operation: pco,
-                                                                           trigger:
-                                                                               trigger.clone(),
-                                                                           requester_id:
-                                                                               session.get_id()
-                                                                               as i64,
-                                                                           requester_name:
-                                                                               session.get_name(),
-                                                                           group_id: group_id
-                                                                                     as i64 },
-                                                &*conn)?;
+    conn.transaction::<_, Error, _>(|| {
+            // The deps go in ahead of the group, so no origin of it is ever missing them
+            if !deps.is_empty() {
+                closure::promote(channel,
+                                 &deps,
+                                 &ClosureRequester { trigger: trigger.clone(),
+                                                     id:      session.get_id() as i64,
+                                                     name:    session.get_name(),
+                                                     group:   Some(group_id as i64), },
+                                 &*conn)?;
             }
-            Err(e) => {
-                debug!("Failed to promote or demote group, err: {:?}", e);
-                return Err(e);
+
+            for (origin, projects) in origin_map.iter() {
+                let package_ids = match do_group_promotion_or_demotion(req,
+                                                                       channel,
+                                                                       projects.to_vec(),
+                                                                       origin,
+                                                                       target,
+                                                                       promote,
+                                                                       &*conn)
+                {
+                    Ok(package_ids) => package_ids,
+                    Err(e) => {
+                        debug!("Failed to promote or demote group, err: {:?}", e);
+                        return Err(e);
+                    }
+                };
+
+                let audit = PackageGroupChannelAudit { origin,
+                                                       channel: channel.as_str(),
+                                                       package_ids,
+                                                       operation: pco,
+                                                       trigger: trigger.clone(),
+                                                       requester_id: session.get_id() as i64,
+                                                       requester_name: session.get_name(),
+                                                       group_id: group_id as i64 };
+                PackageGroupChannelAudit::audit(audit, &*conn)?;
             }
-        }
+            Ok(())
+        })?;
+
+    for dep in deps.iter() {
+        req_state(req).memcache
+                      .borrow_mut()
+                      .clear_cache_for_package(&dep.ident);
     }
 
     Ok(())
@@ -0,0 +1,224 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::{http::{self,
+                       StatusCode},
+                web::{self,
+                      Data,
+                      Json,
+                      Path,
+                      ServiceConfig},
+                HttpRequest,
+                HttpResponse};
+
+use crate::{db::models::{mirror::*,
+                         origin::*},
+            server::{authorize::authorize_session,
+                     error::Error,
+                     framework::headers,
+                     AppState}};
+
+// Number of past sync runs returned for a mirror
+const MIRROR_RUNS_LIMIT: i64 = 50;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChannelMirrorReq {
+    pub upstream_url: String,
+    #[serde(default)]
+    pub include:      Vec<String>,
+    #[serde(default)]
+    pub exclude:      Vec<String>,
+}
+
+pub struct Mirrors;
+
+impl Mirrors {
+    // Route registration
+    //
+    pub fn register(cfg: &mut ServiceConfig) {
+        cfg.route("/depot/origins/{origin}/mirrors",
+                  web::get().to(list_channel_mirrors))
+           .route("/depot/channels/{origin}/{channel}/mirror",
+                  web::get().to(get_channel_mirror))
+           .route("/depot/channels/{origin}/{channel}/mirror",
+                  web::put().to(set_channel_mirror))
+           .route("/depot/channels/{origin}/{channel}/mirror",
+                  web::delete().to(delete_channel_mirror))
+           .route("/depot/channels/{origin}/{channel}/mirror/runs",
+                  web::get().to(list_channel_mirror_runs));
+    }
+}
+
+// Route handlers - these functions can return any Responder trait
+//
+#[allow(clippy::needless_pass_by_value)]
+fn list_channel_mirrors(req: HttpRequest,
+                        path: Path<String>,
+                        state: Data<AppState>)
+                        -> HttpResponse {
+    let origin = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    match OriginChannelMirror::list(&origin, &*conn).map_err(Error::DieselError) {
+        Ok(mirrors) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(mirrors)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn get_channel_mirror(req: HttpRequest,
+                      path: Path<(String, String)>,
+                      state: Data<AppState>)
+                      -> HttpResponse {
+    let (origin, channel) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    match OriginChannelMirror::get(&origin, &channel, &*conn).map_err(Error::DieselError) {
+        Ok(mirror) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(mirror)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn set_channel_mirror(req: HttpRequest,
+                      path: Path<(String, String)>,
+                      body: Json<ChannelMirrorReq>,
+                      state: Data<AppState>)
+                      -> HttpResponse {
+    let (origin, channel) = path.into_inner();
+
+    let account_id =
+        match authorize_session(&req, Some(&origin), Some(OriginMemberRole::Administrator)) {
+            Ok(session) => session.get_id(),
+            Err(err) => return err.into(),
+        };
+
+    if !state.config.mirror.allows_upstream(&body.upstream_url) {
+        return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    // The channel does not need to exist yet, the first sync creates it
+    let new_mirror = NewOriginChannelMirror { origin:       &origin,
+                                              channel:      &channel,
+                                              upstream_url: body.upstream_url.trim_end_matches('/'),
+                                              include:      body.include.clone(),
+                                              exclude:      body.exclude.clone(),
+                                              owner_id:     account_id as i64, };
+
+    match OriginChannelMirror::set(&new_mirror, &*conn).map_err(Error::DieselError) {
+        Ok(mirror) => HttpResponse::Ok().json(mirror),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn delete_channel_mirror(req: HttpRequest,
+                         path: Path<(String, String)>,
+                         state: Data<AppState>)
+                         -> HttpResponse {
+    let (origin, channel) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Administrator))
+    {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    match OriginChannelMirror::delete(&origin, &channel, &*conn).map_err(Error::DieselError) {
+        Ok(0) => HttpResponse::NotFound().into(),
+        Ok(_) => HttpResponse::NoContent().into(),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn list_channel_mirror_runs(req: HttpRequest,
+                            path: Path<(String, String)>,
+                            state: Data<AppState>)
+                            -> HttpResponse {
+    let (origin, channel) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let mirror = match OriginChannelMirror::get(&origin, &channel, &*conn) {
+        Ok(mirror) => mirror,
+        Err(err) => {
+            debug!("{}", err);
+            return Error::DieselError(err).into();
+        }
+    };
+
+    match OriginChannelMirrorRun::list(mirror.id, MIRROR_RUNS_LIMIT, &*conn)
+        .map_err(Error::DieselError)
+    {
+        Ok(runs) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(runs)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
@@ -1,11 +1,18 @@
+pub mod audit;
 pub mod authenticate;
+pub mod bundles;
 pub mod channels;
 pub mod ext;
 pub mod jobs;
+pub mod mirrors;
 pub mod notify;
 pub mod origins;
 pub mod pkgs;
 pub mod profile;
 pub mod projects;
+pub mod retention;
+pub mod scheduled_promotions;
+pub mod service_accounts;
 pub mod settings;
+pub mod teams;
 pub mod user;
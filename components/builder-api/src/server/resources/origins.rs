@@ -39,7 +39,8 @@ use actix_web::{body::Body,
 use builder_core::Error::OriginDeleteError;
 use bytes::Bytes;
 use diesel::{pg::PgConnection,
-             result::Error::NotFound};
+             result::Error::NotFound,
+             Connection};
 
 use crate::{bldr_core,
             hab_core::{crypto::{keys::{box_key_pair::WrappedSealedBox,
@@ -60,11 +61,13 @@ use crate::db::models::{account::*,
                         keys::*,
                         origin::*,
                         package::{BuilderPackageIdent,
+                                  BuilderPackageTarget,
                                   ListPackages,
                                   Package,
                                   PackageVisibility},
                         projects::Project,
-                        secrets::*};
+                        secrets::*,
+                        upload::PackageUpload};
 
 use crate::server::{authorize::{authorize_session,
                                 check_origin_member,
@@ -78,6 +81,7 @@ use crate::server::{authorize::{authorize_session,
                               Pagination,
                               Role},
                     resources::pkgs::postprocess_package_list,
+                    services::uploads,
                     AppState};
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -88,6 +92,35 @@ struct OriginSecretPayload {
     value: String,
 }
 
+#[derive(Serialize)]
+struct KeyRotation {
+    revision: String,
+    retired:  Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct KeySignersQuery {
+    #[serde(default)]
+    channels: String,
+}
+
+// The packages of the requested channels signed with a key revision. Revisions the
+// origin does not know about have no state, and packages uploaded before signers were
+// recorded are listed last, without a signer.
+#[derive(Serialize)]
+struct KeySigners {
+    signer:   Option<String>,
+    state:    Option<OriginKeyState>,
+    packages: Vec<SignedPackage>,
+}
+
+#[derive(Serialize)]
+struct SignedPackage {
+    channel: String,
+    ident:   BuilderPackageIdent,
+    target:  BuilderPackageTarget,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct CreateOriginHandlerReq {
     pub name: String,
@@ -96,7 +129,8 @@ pub struct CreateOriginHandlerReq {
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct UpdateOriginHandlerReq {
-    pub default_package_visibility: Option<PackageVisibility>,
+    pub default_package_visibility:   Option<PackageVisibility>,
+    pub reject_unknown_key_revisions: Option<bool>,
 }
 
 pub struct Origins {}
@@ -132,11 +166,19 @@ impl Origins {
                   web::delete().to(rescind_invitation))
            .route("/depot/origins/{origin}/invitations/{invitation_id}/ignore",
                   web::put().to(ignore_invitation))
+           .route("/depot/origins/{origin}/signer_flags",
+                  web::get().to(list_package_signer_flags))
            .route("/depot/origins/{origin}/keys/latest",
                   web::get().to(download_latest_origin_key))
            .route("/depot/origins/{origin}/keys", web::post().to(create_keys))
            .route("/depot/origins/{origin}/keys",
                   web::get().to(list_origin_keys))
+           .route("/depot/origins/{origin}/keys/rotate",
+                  web::post().to(rotate_keys))
+           .route("/depot/origins/{origin}/keys/signers",
+                  web::get().to(list_key_signers))
+           .route("/depot/origins/{origin}/keys/{revision}/revoke",
+                  web::post().to(revoke_key))
            .route("/depot/origins/{origin}/keys/{revision}",
                   web::post().to(upload_origin_key))
            .route("/depot/origins/{origin}/keys/{revision}",
@@ -258,8 +300,45 @@ fn update_origin(req: HttpRequest,
         None => PackageVisibility::Public,
     };
 
-    match Origin::update(&origin, dpv, &*conn).map_err(Error::DieselError) {
-        Ok(_) => HttpResponse::NoContent().into(),
+    if let Err(err) = Origin::update(&origin, dpv, &*conn).map_err(Error::DieselError) {
+        debug!("{}", err);
+        return err.into();
+    }
+
+    if let Some(reject) = body.0.reject_unknown_key_revisions {
+        if let Err(err) =
+            Origin::update_signer_policy(&origin, reject, &*conn).map_err(Error::DieselError)
+        {
+            debug!("{}", err);
+            return err.into();
+        }
+    }
+
+    HttpResponse::NoContent().into()
+}
+
+// Uploads that were accepted even though the signing key revision was unknown to the origin
+#[allow(clippy::needless_pass_by_value)]
+fn list_package_signer_flags(req: HttpRequest,
+                             path: Path<String>,
+                             state: Data<AppState>)
+                             -> HttpResponse {
+    let origin = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    match PackageSignerFlag::list(&origin, &*conn).map_err(Error::DieselError) {
+        Ok(flags) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(flags)
+        }
         Err(err) => {
             debug!("{}", err);
             err.into()
@@ -291,8 +370,21 @@ fn delete_origin(req: HttpRequest, path: Path<String>, state: Data<AppState>) ->
     // that the user has already cleaned up the most critical origin data.
     match origin_delete_preflight(&origin, &*conn) {
         Ok(_) => {
+            let service_tokens = match ServiceAccount::tokens(&origin, &*conn) {
+                Ok(tokens) => tokens,
+                Err(err) => return Error::DieselError(err).into(),
+            };
+            let upload_ids = match PackageUpload::list_ids_for_origin(&origin, &*conn) {
+                Ok(ids) => ids,
+                Err(err) => return Error::DieselError(err).into(),
+            };
+
             match Origin::delete(&origin, &*conn).map_err(Error::DieselError) {
                 Ok(_) => {
+                    purge_sessions(&state, &service_tokens);
+                    for id in upload_ids {
+                        uploads::remove_session_file(&state.config.api.data_path, id);
+                    }
                     origin_audit(&origin,
                                  OriginOperation::OriginDelete,
                                  &origin,
@@ -406,8 +498,6 @@ fn create_keys(req: HttpRequest, path: Path<String>, state: Data<AppState>) -> H
             Err(err) => return err.into(),
         };
 
-    let pair = SigKeyPair::generate_pair_for_origin(&origin);
-
     let conn = match state.db.get_conn().map_err(Error::DbError) {
         Ok(conn_ref) => conn_ref,
         Err(err) => {
@@ -416,63 +506,134 @@ fn create_keys(req: HttpRequest, path: Path<String>, state: Data<AppState>) -> H
         }
     };
 
-    let pk_body = match pair.to_public_string().map_err(Error::HabitatCore) {
-        Ok(pk) => pk.into_bytes(),
+    match generate_origin_signing_keys(&req, &origin, account_id, &*conn) {
+        Ok(_) => HttpResponse::Created().finish(),
         Err(err) => {
-            error!("create_keys: Failed to get pk body, err={}", err);
-            return err.into();
+            error!("create_keys: Failed to create keys, err={}", err);
+            err.into()
         }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn rotate_keys(req: HttpRequest, path: Path<String>, state: Data<AppState>) -> HttpResponse {
+    let origin = path.into_inner();
+
+    let session =
+        match authorize_session(&req, Some(&origin), Some(OriginMemberRole::Administrator)) {
+            Ok(session) => session,
+            Err(err) => return err.into(),
+        };
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
     };
 
-    let new_pk = NewOriginPublicSigningKey { owner_id:  account_id as i64,
-                                             origin:    &origin,
-                                             full_name: &format!("{}-{}", &origin, &pair.rev),
-                                             name:      &origin,
-                                             revision:  &pair.rev,
-                                             body:      &pk_body, };
+    let rotation =
+        conn.transaction::<_, Error, _>(|| {
+                let revision =
+                    generate_origin_signing_keys(&req, &origin, session.get_id(), &*conn)?;
+                let retired = OriginPublicSigningKey::retire_others(&origin, &revision, &*conn)?;
+                Ok(KeyRotation { revision, retired })
+            });
 
-    match OriginPublicSigningKey::create(&new_pk, &*conn).map_err(Error::DieselError) {
-        Ok(_) => (),
+    match rotation {
+        Ok(rotation) => {
+            origin_audit(&origin,
+                         OriginOperation::KeyRotate,
+                         &format!("{}-{}", origin, rotation.revision),
+                         session.get_id() as i64,
+                         session.get_name(),
+                         &*conn);
+            HttpResponse::Created().json(rotation)
+        }
         Err(err) => {
-            error!("create_keys: Failed to create public key, err={}", err);
-            return err.into();
+            debug!("{}", err);
+            err.into()
         }
     }
+}
 
-    let sk_body = match pair.to_secret_string().map_err(Error::HabitatCore) {
-        Ok(sk) => sk.into_bytes(),
-        Err(err) => {
-            error!("create_keys: Failed to get sk body, err={}", err);
-            return err.into();
-        }
-    };
+#[allow(clippy::needless_pass_by_value)]
+fn revoke_key(req: HttpRequest,
+              path: Path<(String, String)>,
+              state: Data<AppState>)
+              -> HttpResponse {
+    let (origin, revision) = path.into_inner();
 
-    let (sk_encrypted, bldr_key_rev) = match encrypt(&req, &Bytes::from(sk_body)) {
-        Ok((encrypted, rev)) => (encrypted, rev),
-        Err(err) => {
-            debug!("create_keys: Failed to encrypt sk_body, err={:?}", err);
-            return err.into();
-        }
+    let session = match authorize_session(&req, Some(&origin), Some(OriginMemberRole::Owner)) {
+        Ok(session) => session,
+        Err(err) => return err.into(),
     };
 
-    let new_sk = NewOriginPrivateSigningKey { owner_id:           account_id as i64,
-                                              origin:             &origin,
-                                              full_name:          &format!("{}-{}",
-                                                                           &origin, &pair.rev),
-                                              name:               &origin,
-                                              revision:           &pair.rev,
-                                              body:               &sk_encrypted.as_bytes(),
-                                              encryption_key_rev: &bldr_key_rev, };
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
 
-    match OriginPrivateSigningKey::create(&new_sk, &*conn).map_err(Error::DieselError) {
+    match OriginPublicSigningKey::get(&origin, &revision, &*conn) {
+        Ok(ref key) if key.state == OriginKeyState::Revoked => {
+            return HttpResponse::NoContent().into()
+        }
         Ok(_) => (),
+        Err(err) => return Error::DieselError(err).into(),
+    }
+
+    match OriginPublicSigningKey::revoke(&origin, &revision, &*conn).map_err(Error::DieselError) {
+        Ok(_) => {
+            origin_audit(&origin,
+                         OriginOperation::KeyRevoke,
+                         &format!("{}-{}", origin, revision),
+                         session.get_id() as i64,
+                         session.get_name(),
+                         &*conn);
+            HttpResponse::NoContent().into()
+        }
         Err(err) => {
-            error!("create_keys: Failed to create private key, err={}", err);
-            return err.into();
+            debug!("{}", err);
+            err.into()
         }
     }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn list_key_signers(req: HttpRequest,
+                    path: Path<String>,
+                    query: Query<KeySignersQuery>,
+                    state: Data<AppState>)
+                    -> HttpResponse {
+    let origin = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
 
-    HttpResponse::Created().finish()
+    let channels: Vec<String> = query.channels
+                                     .split(',')
+                                     .map(str::trim)
+                                     .filter(|channel| !channel.is_empty())
+                                     .map(str::to_string)
+                                     .collect();
+    if channels.is_empty() {
+        return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    match do_list_key_signers(&origin, &channels, &*conn) {
+        Ok(report) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(report)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
 }
 
 #[allow(clippy::needless_pass_by_value)]
@@ -494,6 +655,7 @@ fn list_origin_keys(path: Path<String>, state: Data<AppState>) -> HttpResponse {
                                                    &key.name, &key.revision));
                         ident.set_origin(key.name.to_string());
                         ident.set_revision(key.revision.to_string());
+                        ident.set_state(key.state.to_string());
                         ident
                     })
                     .collect();
@@ -1008,6 +1170,10 @@ fn invite_to_origin(req: HttpRequest,
 
     let (recipient_id, recipient_name) =
         match Account::get(&user, &*conn).map_err(Error::DieselError) {
+            // Service accounts belong to a single origin and are added to it directly
+            Ok(ref account) if account.service_origin.is_some() => {
+                return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY)
+            }
             Ok(account) => (account.id, account.name),
             Err(err) => {
                 debug!("{}", err);
@@ -1311,6 +1477,10 @@ fn transfer_origin_ownership(req: HttpRequest,
 
     let (recipient_id, _recipient_name) =
         match Account::get(&user, &*conn).map_err(Error::DieselError) {
+            // Service accounts can not own an origin
+            Ok(ref account) if account.service_origin.is_some() => {
+                return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY)
+            }
             Ok(account) => (account.id, account.name),
             Err(err) => {
                 debug!("{}", err);
@@ -1323,8 +1493,15 @@ fn transfer_origin_ownership(req: HttpRequest,
         return HttpResponse::new(StatusCode::FORBIDDEN);
     }
 
+    // The service accounts of the origin are deleted with the transfer
+    let service_tokens = match ServiceAccount::tokens(&origin, &*conn) {
+        Ok(tokens) => tokens,
+        Err(err) => return Error::DieselError(err).into(),
+    };
+
     match Origin::transfer(&origin, recipient_id, &*conn).map_err(Error::DieselError) {
         Ok(_) => {
+            purge_sessions(&state, &service_tokens);
             origin_audit(&origin,
                          OriginOperation::OwnerTransfer,
                          &recipient_id.to_string(),
@@ -1649,6 +1826,14 @@ fn get_origin_integration(req: HttpRequest,
 // Internal helpers
 //
 
+// Drops the cached sessions of deleted tokens so they stop working right away
+fn purge_sessions(state: &AppState, tokens: &[AccountToken]) {
+    let mut memcache = state.memcache.borrow_mut();
+    for token in tokens {
+        memcache.delete_session_key(&token.token)
+    }
+}
+
 fn download_content_as_file(content: &[u8], filename: String) -> HttpResponse {
     HttpResponse::Ok()
         .header(
@@ -1704,6 +1889,84 @@ fn generate_origin_encryption_keys(origin: &str,
     OriginPublicEncryptionKey::create(&new_pk, &*conn).map_err(Error::DieselError)
 }
 
+// Generates a signing key pair for the origin and returns its revision. The secret key
+// is stored encrypted.
+fn generate_origin_signing_keys(req: &HttpRequest,
+                                origin: &str,
+                                session_id: u64,
+                                conn: &PgConnection)
+                                -> Result<String> {
+    debug!("Generating signing keys for {}", origin);
+
+    let pair = SigKeyPair::generate_pair_for_origin(origin);
+
+    let pk_body = pair.to_public_string()
+                      .map_err(Error::HabitatCore)?
+                      .into_bytes();
+
+    let new_pk = NewOriginPublicSigningKey { owner_id:  session_id as i64,
+                                             origin:    &origin,
+                                             full_name: &format!("{}-{}", &origin, &pair.rev),
+                                             name:      &origin,
+                                             revision:  &pair.rev,
+                                             body:      &pk_body, };
+
+    OriginPublicSigningKey::create(&new_pk, &*conn)?;
+
+    let sk_body = pair.to_secret_string()
+                      .map_err(Error::HabitatCore)?
+                      .into_bytes();
+
+    let (sk_encrypted, bldr_key_rev) = encrypt(req, &Bytes::from(sk_body))?;
+
+    let new_sk = NewOriginPrivateSigningKey { owner_id:           session_id as i64,
+                                              origin:             &origin,
+                                              full_name:          &format!("{}-{}",
+                                                                           &origin, &pair.rev),
+                                              name:               &origin,
+                                              revision:           &pair.rev,
+                                              body:               &sk_encrypted.as_bytes(),
+                                              encryption_key_rev: &bldr_key_rev, };
+
+    OriginPrivateSigningKey::create(&new_sk, &*conn)?;
+    Ok(pair.rev)
+}
+
+fn do_list_key_signers(origin: &str,
+                       channels: &[String],
+                       conn: &PgConnection)
+                       -> Result<Vec<KeySigners>> {
+    let mut report: Vec<KeySigners> =
+        OriginPublicSigningKey::list(origin, conn)?.into_iter()
+                                                   .map(|key| {
+                                                       KeySigners { signer:   Some(key.full_name),
+                                                                    state:    Some(key.state),
+                                                                    packages: Vec::new(), }
+                                                   })
+                                                   .collect();
+
+    for package in PackageSigner::list_in_channels(origin, channels, conn)? {
+        let index = match report.iter()
+                                .position(|entry| entry.signer == package.signer)
+        {
+            Some(index) => index,
+            None => {
+                report.push(KeySigners { signer:   package.signer.clone(),
+                                         state:    None,
+                                         packages: Vec::new(), });
+                report.len() - 1
+            }
+        };
+        report[index].packages
+                     .push(SignedPackage { channel: package.channel,
+                                           ident:   package.ident,
+                                           target:  package.target, });
+    }
+
+    report.sort_by_key(|entry| entry.signer.is_none());
+    Ok(report)
+}
+
 fn encrypt(req: &HttpRequest, content: &Bytes) -> Result<(String, String)> {
     bldr_core::integrations::encrypt(&req_state(req).config.api.key_path, content)
         .map_err(Error::BuilderCore)
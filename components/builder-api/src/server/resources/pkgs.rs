@@ -12,25 +12,42 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::{bldr_core::{error::Error::RpcError,
-                        metrics::CounterMetric},
-            db::models::{channel::Channel,
-                         origin::*,
-                         package::{BuilderPackageIdent,
-                                   BuilderPackageTarget,
-                                   DeletePackage,
-                                   GetLatestPackage,
-                                   GetPackage,
-                                   ListPackages,
-                                   NewPackage,
-                                   Package,
-                                   PackageIdentWithChannelPlatform,
-                                   PackageVisibility,
-                                   SearchPackages},
-                         settings::{GetOriginPackageSettings,
-                                    NewOriginPackageSettings,
-                                    OriginPackageSettings}},
-            hab_core::{package::{FromArchive,
+use crate::{bldr_core::{access_token::BUILDER_ACCOUNT_ID,
+                        error::Error::RpcError,
+                        metrics::CounterMetric,
+                        rpc::RpcClient},
+            db::{models::{channel::Channel,
+                          keys::{NewPackageSignerFlag,
+                                 OriginKeyState,
+                                 OriginPublicSigningKey,
+                                 PackageSigner,
+                                 PackageSignerFlag},
+                          origin::*,
+                          package::{BuilderPackageIdent,
+                                    BuilderPackageTarget,
+                                    DeletePackage,
+                                    GetLatestPackage,
+                                    GetPackage,
+                                    GetPackageGroup,
+                                    ListPackages,
+                                    ListPackagesForDeletion,
+                                    NewPackage,
+                                    Package,
+                                    PackageIdentWithChannelPlatform,
+                                    PackageVisibility,
+                                    SearchPackages},
+                          provenance::{NewPackageProvenance,
+                                       PackageProvenance},
+                          settings::{GetOriginPackageSettings,
+                                     NewOriginPackageSettings,
+                                     OriginPackageSettings},
+                          upload::{NewPackageUpload,
+                                   PackageUpload}},
+                 DbPool},
+            hab_core::{crypto::{artifact,
+                                keys::parse_name_with_rev,
+                                SigKeyPair},
+                       package::{FromArchive,
                                  Identifiable,
                                  PackageArchive,
                                  PackageIdent,
@@ -47,12 +64,18 @@ use crate::{bldr_core::{error::Error::RpcError,
                                  middleware::route_message},
                      helpers::{self,
                                req_state,
+                               ByteRange,
                                Pagination,
                                Target},
                      resources::channels::channels_for_package_ident,
-                     services::metrics::Counter,
+                     services::{diff::{self,
+                                       PackageDiff},
+                                metrics::Counter,
+                                storage::RangedDownload,
+                                uploads},
                      AppState}};
 use actix_web::{body::Body,
+                dev::HttpResponseBuilder,
                 http::{self,
                        header::{ContentDisposition,
                                 ContentType,
@@ -61,24 +84,39 @@ use actix_web::{body::Body,
                        StatusCode},
                 web::{self,
                       Data,
+                      Json,
                       Path,
                       Query,
                       ServiceConfig},
                 HttpRequest,
                 HttpResponse};
 use bytes::Bytes;
-use diesel::result::Error::NotFound;
+use chrono::{DateTime,
+             SecondsFormat,
+             Utc};
+use diesel::{pg::PgConnection,
+             result::Error::NotFound,
+             Connection};
 use futures::{channel::mpsc,
+              Stream,
               StreamExt};
 use serde::ser::Serialize;
-use std::{fs::{self,
+use std::{collections::HashMap,
+          fmt,
+          fs::{self,
                remove_file,
-               File},
-          io::{BufReader,
+               File,
+               OpenOptions},
+          io::{self,
+               BufReader,
                BufWriter,
                Read,
+               Seek,
+               SeekFrom,
                Write},
-          path::PathBuf,
+          path::{Path as FsPath,
+                 PathBuf},
+          result,
           str::FromStr};
 use tempfile::tempdir_in;
 use uuid::Uuid;
@@ -110,6 +148,156 @@ pub struct Schedule {
 
 fn default_target() -> String { "x86_64-linux".to_string() }
 
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeleteBlocker {
+    StableChannel,
+    ProtectedChannel,
+    ReverseDependencies,
+    Visibility,
+}
+
+impl fmt::Display for DeleteBlocker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match *self {
+            DeleteBlocker::StableChannel => "package is in the stable channel",
+            DeleteBlocker::ProtectedChannel => {
+                "package is in a channel which is protected from deletion"
+            }
+            DeleteBlocker::ReverseDependencies => "package has reverse dependencies",
+            DeleteBlocker::Visibility => "package visibility requires the maintainer role",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkDeleteSelector {
+    #[serde(default)]
+    name:           Option<String>,
+    #[serde(default)]
+    version:        Option<String>,
+    #[serde(default)]
+    target:         Option<String>,
+    #[serde(default)]
+    channel:        Option<String>,
+    #[serde(default)]
+    created_after:  Option<DateTime<Utc>>,
+    #[serde(default)]
+    created_before: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProvenanceReq {
+    job_id:         u64,
+    group_id:       u64,
+    vcs_url:        String,
+    #[serde(default)]
+    vcs_sha:        Option<String>,
+    worker:         String,
+    plan_path:      String,
+    studio_version: String,
+    hab_version:    String,
+    #[serde(default)]
+    deps:           Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SbomOptions {
+    #[serde(default)]
+    build_deps: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchFilters {
+    #[serde(default)]
+    origin:         Option<String>,
+    #[serde(default)]
+    target:         Option<String>,
+    #[serde(default)]
+    channel:        Option<String>,
+    #[serde(default)]
+    visibility:     Option<String>,
+    #[serde(default)]
+    created_after:  Option<DateTime<Utc>>,
+    #[serde(default)]
+    created_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    depends_on:     Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadOptions {
+    #[serde(default)]
+    presigned: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkDeleteExecute {
+    #[serde(default)]
+    execute: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkDeleteEntry {
+    ident:  BuilderPackageIdent,
+    target: BuilderPackageTarget,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<DeleteBlocker>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkDeletePlan {
+    executed: bool,
+    delete:   Vec<BulkDeleteEntry>,
+    blocked:  Vec<BulkDeleteEntry>,
+}
+
+// Outcome of checking the signer of an uploaded archive against the origin's public keys
+enum SignerCheck {
+    Verified(String),
+    UnknownRevision(String),
+    Rejected(SignerRejection),
+}
+
+enum SignerRejection {
+    BadSignature(String),
+    UnreadableHeader(String),
+    ForeignOrigin(String),
+    Revoked(String),
+}
+
+impl SignerRejection {
+    // Each failure has its own upload error code, so clients can tell them apart
+    fn code(&self) -> &'static str {
+        match *self {
+            SignerRejection::BadSignature(_) => "ds:up:8",
+            SignerRejection::UnreadableHeader(_) => "ds:up:9",
+            SignerRejection::ForeignOrigin(_) => "ds:up:10",
+            SignerRejection::Revoked(_) => "ds:up:11",
+        }
+    }
+}
+
+impl fmt::Display for SignerRejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SignerRejection::BadSignature(ref e) => {
+                write!(f, "signature verification failed: {}", e)
+            }
+            SignerRejection::UnreadableHeader(ref e) => {
+                write!(f, "unreadable signature header: {}", e)
+            }
+            SignerRejection::ForeignOrigin(ref signer) => {
+                write!(f, "signer {} does not belong to the origin", signer)
+            }
+            SignerRejection::Revoked(ref signer) => {
+                write!(f, "signing key revision {} has been revoked", signer)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GetSchedule {
     #[serde(default)]
@@ -133,6 +321,8 @@ impl Packages {
            .route("/depot/pkgs/search/{query}", web::get().to(search_packages))
            .route("/depot/pkgs/schedule/{groupid}",
                   web::get().to(get_schedule))
+           .route("/depot/pkgs/{origin}/bulk_delete",
+                  web::post().to(bulk_delete_packages))
            .route("/depot/pkgs/{origin}/{pkg}",
                   web::get().to(get_packages_for_origin_package))
            .route("/depot/pkgs/schedule/{origin}/status",
@@ -153,10 +343,28 @@ impl Packages {
                   web::get().to(get_package))
            .route("/depot/pkgs/{origin}/{pkg}/{version}/{release}",
                   web::delete().to(delete_package))
+           .route("/depot/pkgs/{origin}/{pkg}/{version}/{release}/uploads",
+                  web::post().to(start_upload_session))
+           .route("/depot/pkgs/{origin}/{pkg}/{version}/{release}/uploads/{id}",
+                  web::get().to(get_upload_session))
+           .route("/depot/pkgs/{origin}/{pkg}/{version}/{release}/uploads/{id}",
+                  web::delete().to(abort_upload_session))
+           .route("/depot/pkgs/{origin}/{pkg}/{version}/{release}/uploads/{id}/finalize",
+                  web::post().to(finalize_upload_session))
+           .route("/depot/pkgs/{origin}/{pkg}/{version}/{release}/uploads/{id}/{chunk}",
+                  web::put().to(upload_session_chunk))
            .route("/depot/pkgs/{origin}/{pkg}/{version}/{release}/download",
                   web::get().to(download_package))
            .route("/depot/pkgs/{origin}/{pkg}/{version}/{release}/channels",
                   web::get().to(get_package_channels))
+           .route("/depot/pkgs/{origin}/{pkg}/{version}/{release}/provenance",
+                  web::get().to(get_package_provenance))
+           .route("/depot/pkgs/{origin}/{pkg}/{version}/{release}/provenance",
+                  web::put().to(put_package_provenance))
+           .route("/depot/pkgs/{origin}/{pkg}/{version}/{release}/sbom/{format}",
+                  web::get().to(get_package_sbom))
+           .route("/depot/pkgs/{origin}/{pkg}/{version}/{release}/diff/{to_version}/{to_release}",
+                  web::get().to(diff_package))
            .route("/depot/pkgs/{origin}/{pkg}/{version}/{release}/{visibility}",
                   web::patch().to(package_privacy_toggle));
     }
@@ -322,83 +530,69 @@ async fn delete_package(req: HttpRequest,
         None => helpers::target_from_headers(&req),
     };
 
+    match check_package_deletable(&state.jobsrv, &ident, target, &state.db).await {
+        Ok(None) => {}
+        Ok(Some(blocker)) => {
+            debug!("Deleting package not allowed: {}, reason = {}",
+                   ident, blocker);
+            return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        Err(err) => {
+            debug!("{}", err);
+            return err.into();
+        }
+    }
+
     let conn = match state.db.get_conn().map_err(Error::DbError) {
         Ok(conn_ref) => conn_ref,
         Err(err) => return err.into(),
     };
 
-    // Check whether package is in stable channel
-    match Package::list_package_channels(&BuilderPackageIdent(ident.clone()),
-                                         target,
-                                         helpers::all_visibilities(),
-                                         &*conn)
-    {
-        Ok(channels) => {
-            if channels.iter()
-                       .any(|c| c.name == ChannelIdent::stable().to_string())
-            {
-                debug!("Deleting package in stable channel not allowed: {}", ident);
-                return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
-            }
+    match do_delete_package(&ident, target, &*conn) {
+        Ok(_) => {
+            state.memcache.borrow_mut().clear_cache_for_package(&ident);
+            HttpResponse::NoContent().finish()
         }
         Err(err) => {
             debug!("{}", err);
-            return Error::DieselError(err).into();
-        }
-    }
-
-    // Check whether package project has any rdeps
-    if feat::is_enabled(feat::Jobsrv) {
-        let mut rdeps_get = jobsrv::JobGraphPackageReverseDependenciesGet::new();
-        rdeps_get.set_origin(ident.origin().to_string());
-        rdeps_get.set_name(ident.name().to_string());
-        rdeps_get.set_target(target.to_string());
-
-        match route_message::<jobsrv::JobGraphPackageReverseDependenciesGet,
-                            jobsrv::JobGraphPackageReverseDependencies>(&req, &rdeps_get).await
-        {
-            Ok(rdeps) => {
-                if !rdeps.get_rdeps().is_empty() {
-                    debug!("Deleting package with rdeps not allowed: {}", ident);
-                    return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
-                }
-            }
-            Err(err) => {
-                debug!("{}", err);
-                return err.into();
-            }
+            err.into()
         }
     }
+}
 
-    // TODO (SA): Wrap in transaction, or better yet, eliminate need to do
-    // channel package deletion
-    let pkg = match Package::get(GetPackage { ident:      BuilderPackageIdent(ident.clone()),
-                                              visibility: helpers::all_visibilities(),
-                                              target:     BuilderPackageTarget(target), },
-                                 &*conn).map_err(Error::DieselError)
-    {
-        Ok(pkg) => pkg,
-        Err(err) => return err.into(),
-    };
+// Returns the deletion plan for every release matching the selector. The plan is only
+// carried out when the `execute` query parameter is set.
+#[allow(clippy::needless_pass_by_value)]
+async fn bulk_delete_packages(req: HttpRequest,
+                              path: Path<String>,
+                              selector: Json<BulkDeleteSelector>,
+                              qexecute: Query<BulkDeleteExecute>,
+                              state: Data<AppState>)
+                              -> HttpResponse {
+    let origin = path.into_inner();
 
-    if let Err(err) = Channel::delete_channel_package(pkg.id, &*conn).map_err(Error::DieselError) {
-        debug!("{}", err);
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
         return err.into();
     }
 
-    match Package::delete(DeletePackage { ident:  BuilderPackageIdent(ident.clone()),
-                                          target: BuilderPackageTarget(target), },
-                          &*conn).map_err(Error::DieselError)
-    {
-        Ok(_) => {
-            state.memcache.borrow_mut().clear_cache_for_package(&ident);
-            HttpResponse::NoContent().finish()
-        }
+    let mut plan = match do_plan_bulk_delete(&req, &origin, &selector, &state).await {
+        Ok(plan) => plan,
         Err(err) => {
             debug!("{}", err);
-            err.into()
+            return err.into();
         }
+    };
+
+    if qexecute.execute {
+        if let Err(err) = do_execute_bulk_delete(&plan, &state) {
+            debug!("{}", err);
+            return err.into();
+        }
+        plan.executed = true;
     }
+
+    HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                      .json(plan)
 }
 
 // TODO : Convert to async
@@ -406,6 +600,7 @@ async fn delete_package(req: HttpRequest,
 async fn download_package(req: HttpRequest,
                           path: Path<(String, String, String, String)>,
                           qtarget: Query<Target>,
+                          qoptions: Query<DownloadOptions>,
                           state: Data<AppState>)
                           -> HttpResponse {
     let (origin, name, version, release) = path.into_inner();
@@ -450,41 +645,70 @@ async fn download_package(req: HttpRequest,
                        &*conn)
     {
         Ok(package) => {
-            let dir = tempdir_in(&state.config.api.data_path).expect("Unable to create a tempdir!");
-            let file_path = dir.path().join(archive_name(&package.ident, target));
-            let temp_ident = ident;
-            let is_private = package.visibility != PackageVisibility::Public;
-
-            // TODO: Aggregate Artifactory/S3 into a provider model
-            if feat::is_enabled(feat::Artifactory) {
-                match state.artifactory
-                           .download(&file_path, &temp_ident, target)
-                           .await
-                {
-                    Ok(archive) => {
-                        download_response_for_archive(&archive, &file_path, is_private, &state)
+            // Large downloads can bypass the API entirely when the client asks for it and
+            // the backend supports it
+            if qoptions.presigned {
+                match state.packages.presigned_download_url(&ident, target).await {
+                    Ok(Some(url)) => {
+                        return HttpResponse::Found().header(http::header::LOCATION, url)
+                                                    .header(http::header::CACHE_CONTROL,
+                                                            headers::NO_CACHE)
+                                                    .finish();
                     }
-                    Err(e) => {
-                        warn!("Failed to download package, ident={}, err={:?}",
-                              temp_ident, e);
-                        HttpResponse::new(StatusCode::NOT_FOUND)
+                    Ok(None) => {}
+                    Err(err) => {
+                        warn!("Unable to pre-sign download for {}, err={:?}", ident, err);
                     }
                 }
-            } else {
-                match state.packages
-                           .download(&file_path, &temp_ident, target)
-                           .await
-                {
-                    Ok(archive) => {
-                        download_response_for_archive(&archive, &file_path, is_private, &state)
+            }
+
+            let is_private = package.visibility != PackageVisibility::Public;
+            let range = req.headers()
+                           .get(http::header::RANGE)
+                           .and_then(|r| r.to_str().ok());
+
+            if let Some(range) = range {
+                match state.packages.download_range(&ident, target, range).await {
+                    Ok(Some(RangedDownload::Partial { start,
+                                                      end,
+                                                      len,
+                                                      body, })) => {
+                        let filename = archive_name(&package.ident, target);
+                        let filename = filename.to_string_lossy();
+                        let mut response = HttpResponse::PartialContent();
+                        response.header(http::header::CONTENT_RANGE,
+                                        format!("bytes {}-{}/{}", start, end, len));
+                        return stream_download_response(response, &filename, body, is_private,
+                                                        &state);
                     }
-                    Err(e) => {
-                        warn!("Failed to download package, ident={}, err={:?}",
-                              temp_ident, e);
-                        HttpResponse::new(StatusCode::NOT_FOUND)
+                    Ok(Some(RangedDownload::Unsatisfiable(len))) => {
+                        return range_not_satisfiable(len);
+                    }
+                    Ok(Some(RangedDownload::Full)) | Ok(None) => {}
+                    Err(err) => {
+                        warn!("Unable to fetch range of {}, err={:?}", ident, err);
+                        return HttpResponse::new(StatusCode::NOT_FOUND);
                     }
                 }
             }
+
+            let dir = tempdir_in(&state.config.api.data_path).expect("Unable to create a tempdir!");
+            let file_path = dir.path().join(archive_name(&package.ident, target));
+            let temp_ident = ident;
+
+            match state.packages
+                       .download(&file_path, &temp_ident, target)
+                       .await
+            {
+                Ok(archive) => {
+                    download_response_for_archive(&archive, &file_path, is_private, range, &state)
+                }
+                Err(e) => {
+                    warn!("Failed to download package, ident={}, err={:?}",
+                          temp_ident, e);
+                    HttpResponse::new(StatusCode::NOT_FOUND)
+                }
+            }
         }
         Err(err) => Error::DieselError(err).into(),
     }
@@ -524,6 +748,166 @@ async fn upload_package(req: HttpRequest,
     }
 }
 
+// Resumable uploads. A session is started with the same parameters as a regular upload,
+// chunks are then PUT in order starting from 0, and the session is finalized once the
+// whole archive has been received.
+#[allow(clippy::needless_pass_by_value)]
+fn start_upload_session(req: HttpRequest,
+                        path: Path<(String, String, String, String)>,
+                        qupload: Query<Upload>,
+                        state: Data<AppState>)
+                        -> HttpResponse {
+    let (origin, name, version, release) = path.into_inner();
+
+    let ident = PackageIdent::new(origin, name, Some(version), Some(release));
+
+    if !ident.valid() || !ident.fully_qualified() {
+        info!("Invalid or not fully qualified package identifier: {}",
+              ident);
+        return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    match do_start_upload_session(&req, &qupload, &ident, &state) {
+        Ok(upload) => HttpResponse::Created().json(upload),
+        Err(Error::Conflict) => {
+            debug!("Failed to start upload for {}, metadata already exists",
+                   &ident);
+            HttpResponse::new(StatusCode::CONFLICT)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn get_upload_session(req: HttpRequest,
+                      path: Path<(String, String, String, String, i64)>,
+                      state: Data<AppState>)
+                      -> HttpResponse {
+    let (origin, name, version, release, id) = path.into_inner();
+    let ident = PackageIdent::new(origin, name, Some(version), Some(release));
+
+    match do_get_upload_session(&req, &ident, id, &state) {
+        Ok(upload) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(upload)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn abort_upload_session(req: HttpRequest,
+                        path: Path<(String, String, String, String, i64)>,
+                        state: Data<AppState>)
+                        -> HttpResponse {
+    let (origin, name, version, release, id) = path.into_inner();
+    let ident = PackageIdent::new(origin, name, Some(version), Some(release));
+
+    match do_lock_upload_session(&req, &ident, id, &state).and_then(|(upload, _lock)| {
+                                                              uploads::discard(&upload,
+                                                                               &state.config
+                                                                                     .api
+                                                                                     .data_path,
+                                                                               &state.db)
+                                                          }) {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+async fn upload_session_chunk(req: HttpRequest,
+                              path: Path<(String, String, String, String, i64, i32)>,
+                              stream: web::Payload,
+                              state: Data<AppState>)
+                              -> Result<HttpResponse> {
+    let (origin, name, version, release, id, chunk) = path.into_inner();
+    let ident = PackageIdent::new(origin, name, Some(version), Some(release));
+
+    let (upload, _lock) = match do_lock_upload_session(&req, &ident, id, &state) {
+        Ok(locked) => locked,
+        Err(err) => {
+            debug!("{}", err);
+            return Ok(err.into());
+        }
+    };
+
+    // A chunk we already have is most likely a retry after a lost response
+    if chunk < upload.next_chunk {
+        return Ok(HttpResponse::Ok().json(upload));
+    }
+
+    if chunk > upload.next_chunk {
+        debug!("Out of order chunk {} for upload {}, expected {}",
+               chunk, id, upload.next_chunk);
+        return Ok(HttpResponse::Conflict().json(upload));
+    }
+
+    let received_bytes = do_write_upload_chunk(&upload, stream, &state).await?;
+
+    let conn = state.db.get_conn().map_err(Error::DbError)?;
+
+    match PackageUpload::record_chunk(id, chunk, received_bytes, &*conn) {
+        Ok(upload) => Ok(HttpResponse::Ok().json(upload)),
+        Err(NotFound) => {
+            debug!("Chunk {} for upload {} was recorded concurrently",
+                   chunk, id);
+            Ok(HttpResponse::new(StatusCode::CONFLICT))
+        }
+        Err(err) => Err(Error::DieselError(err)),
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+async fn finalize_upload_session(req: HttpRequest,
+                                 path: Path<(String, String, String, String, i64)>,
+                                 state: Data<AppState>)
+                                 -> HttpResponse {
+    let (origin, name, version, release, id) = path.into_inner();
+    let ident = PackageIdent::new(origin, name, Some(version), Some(release));
+
+    let (upload, _lock) = match do_lock_upload_session(&req, &ident, id, &state) {
+        Ok(locked) => locked,
+        Err(err) => {
+            debug!("{}", err);
+            return err.into();
+        }
+    };
+
+    let qupload = Upload { target:   Some(upload.target.to_string()),
+                           checksum: upload.checksum.clone(),
+                           builder:  upload.builder.clone(),
+                           forced:   upload.forced, };
+
+    // The package may have been uploaded by other means since the session was started
+    if let Err(err) = do_upload_package_check(&req, &qupload, &ident) {
+        debug!("Failed to finalize upload {} for {}, err={}",
+               id, &ident, err);
+        return err.into();
+    }
+
+    state.memcache.borrow_mut().clear_cache_for_package(&ident);
+
+    let temp_path = uploads::session_path(&state.config.api.data_path, upload.id);
+    let response = do_upload_package_finish(&req, &qupload, &ident, &temp_path).await;
+
+    // A session can only be finalized once, whatever the outcome
+    if let Err(err) = uploads::discard(&upload, &state.config.api.data_path, &state.db) {
+        warn!("Unable to remove upload session {}, err={}", id, err);
+    }
+
+    response
+}
+
 // TODO REVIEW: should this path be under jobs instead?
 #[allow(clippy::needless_pass_by_value)]
 async fn schedule_job_group(req: HttpRequest,
@@ -701,41 +1085,237 @@ fn get_package_channels(req: HttpRequest,
 }
 
 #[allow(clippy::needless_pass_by_value)]
-fn list_package_versions(req: HttpRequest,
-                         path: Path<(String, String)>,
-                         state: Data<AppState>)
-                         -> HttpResponse {
-    let (origin, name) = path.into_inner();
-
-    let opt_session_id = match authorize_session(&req, None, None) {
-        Ok(session) => Some(session.get_id()),
-        Err(_) => None,
-    };
+fn list_package_versions(req: HttpRequest,
+                         path: Path<(String, String)>,
+                         state: Data<AppState>)
+                         -> HttpResponse {
+    let (origin, name) = path.into_inner();
+
+    let opt_session_id = match authorize_session(&req, None, None) {
+        Ok(session) => Some(session.get_id()),
+        Err(_) => None,
+    };
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let ident = PackageIdent::new(origin.to_string(), name, None, None);
+
+    match Package::list_package_versions(&BuilderPackageIdent(ident.clone()),
+                                         helpers::visibility_for_optional_session(&req,
+                                                                                  opt_session_id,
+                                                                                  &origin),
+                                         &*conn)
+    {
+        Ok(packages) => {
+            trace!(target: "habitat_builder_api::server::resources::pkgs::versions", "list_package_versions for {} found {} package versions: {:?}", ident, packages.len(), packages);
+
+            let body = serde_json::to_string(&packages).unwrap();
+            HttpResponse::Ok().header(http::header::CONTENT_TYPE, headers::APPLICATION_JSON)
+                              .header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .body(body)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            Error::DieselError(err).into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn get_package_provenance(req: HttpRequest,
+                          path: Path<(String, String, String, String)>,
+                          qtarget: Query<Target>,
+                          state: Data<AppState>)
+                          -> HttpResponse {
+    let (origin, name, version, release) = path.into_inner();
+
+    let ident = PackageIdent::new(origin, name, Some(version), Some(release));
+
+    if !ident.valid() || !ident.fully_qualified() {
+        info!("Invalid or not fully qualified package identifier: {}",
+              ident);
+        return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let target = match qtarget.target {
+        Some(ref t) => {
+            trace!("Query requested target = {}", t);
+            match PackageTarget::from_str(t) {
+                Ok(t) => t,
+                Err(err) => {
+                    debug!("Invalid target requested: {}, err = {:?}", t, err);
+                    return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+                }
+            }
+        }
+        None => helpers::target_from_headers(&req),
+    };
+
+    match do_get_package_provenance(&req, &ident, target, &state) {
+        Ok(provenance) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(provenance)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+// Records the provenance of a package built by a worker. Only the builder itself may attest
+// packages, so this is rejected for any other session.
+#[allow(clippy::needless_pass_by_value)]
+fn put_package_provenance(req: HttpRequest,
+                          path: Path<(String, String, String, String)>,
+                          qtarget: Query<Target>,
+                          body: Json<ProvenanceReq>,
+                          state: Data<AppState>)
+                          -> HttpResponse {
+    let (origin, name, version, release) = path.into_inner();
+
+    match authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        Ok(session) if session.get_id() == BUILDER_ACCOUNT_ID => {}
+        Ok(_) => return Error::Authorization.into(),
+        Err(err) => return err.into(),
+    }
+
+    let ident = PackageIdent::new(origin, name, Some(version), Some(release));
+
+    if !ident.valid() || !ident.fully_qualified() {
+        info!("Invalid or not fully qualified package identifier: {}",
+              ident);
+        return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let target = match qtarget.target {
+        Some(ref t) => {
+            trace!("Query requested target = {}", t);
+            match PackageTarget::from_str(t) {
+                Ok(t) => t,
+                Err(err) => {
+                    debug!("Invalid target requested: {}, err = {:?}", t, err);
+                    return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+                }
+            }
+        }
+        None => helpers::target_from_headers(&req),
+    };
+
+    match do_put_package_provenance(&ident, target, &body, &state) {
+        Ok(provenance) => HttpResponse::Ok().json(provenance),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn get_package_sbom(req: HttpRequest,
+                    path: Path<(String, String, String, String, String)>,
+                    qtarget: Query<Target>,
+                    options: Query<SbomOptions>,
+                    state: Data<AppState>)
+                    -> HttpResponse {
+    let (origin, name, version, release, format) = path.into_inner();
+
+    let ident = PackageIdent::new(origin, name, Some(version), Some(release));
+
+    if !ident.valid() || !ident.fully_qualified() {
+        info!("Invalid or not fully qualified package identifier: {}",
+              ident);
+        return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    if format != "spdx" && format != "cyclonedx" {
+        debug!("Unknown SBOM format requested: {}", format);
+        return HttpResponse::new(StatusCode::NOT_FOUND);
+    }
+
+    let target = match qtarget.target {
+        Some(ref t) => {
+            trace!("Query requested target = {}", t);
+            match PackageTarget::from_str(t) {
+                Ok(t) => t,
+                Err(err) => {
+                    debug!("Invalid target requested: {}, err = {:?}", t, err);
+                    return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+                }
+            }
+        }
+        None => helpers::target_from_headers(&req),
+    };
+
+    let components = match do_get_sbom_components(&req, &ident, target, options.build_deps, &state)
+    {
+        Ok(components) => components,
+        Err(err) => {
+            debug!("{}", err);
+            return err.into();
+        }
+    };
+
+    let created = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let mut response = HttpResponse::Ok();
+    response.header(http::header::CACHE_CONTROL, headers::NO_CACHE);
+
+    if format == "spdx" {
+        let info = req.connection_info();
+        let namespace = format!("{}://{}/v1/depot/pkgs/{}/sbom/spdx/{}",
+                                info.scheme(),
+                                info.host(),
+                                ident,
+                                Uuid::new_v4());
+        response.json(sbom::spdx(&components, &namespace, &created))
+    } else {
+        response.json(sbom::cyclonedx(&components, &Uuid::new_v4().to_string(), &created))
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn diff_package(req: HttpRequest,
+                path: Path<(String, String, String, String, String, String)>,
+                qtarget: Query<Target>,
+                state: Data<AppState>)
+                -> HttpResponse {
+    let (origin, name, version, release, to_version, to_release) = path.into_inner();
+
+    let from = PackageIdent::new(origin.clone(), name.clone(), Some(version), Some(release));
+    let to = PackageIdent::new(origin, name, Some(to_version), Some(to_release));
+
+    if !from.valid() || !to.valid() {
+        info!("Invalid package identifiers for diff: {} {}", from, to);
+        return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+    }
 
-    let conn = match state.db.get_conn().map_err(Error::DbError) {
-        Ok(conn_ref) => conn_ref,
-        Err(err) => return err.into(),
+    // TODO: Deprecate target from headers
+    let target = match qtarget.target {
+        Some(ref t) => {
+            trace!("Query requested target = {}", t);
+            match PackageTarget::from_str(t) {
+                Ok(t) => t,
+                Err(err) => {
+                    debug!("Invalid target requested: {}, err = {:?}", t, err);
+                    return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+                }
+            }
+        }
+        None => helpers::target_from_headers(&req),
     };
 
-    let ident = PackageIdent::new(origin.to_string(), name, None, None);
-
-    match Package::list_package_versions(&BuilderPackageIdent(ident.clone()),
-                                         helpers::visibility_for_optional_session(&req,
-                                                                                  opt_session_id,
-                                                                                  &origin),
-                                         &*conn)
-    {
-        Ok(packages) => {
-            trace!(target: "habitat_builder_api::server::resources::pkgs::versions", "list_package_versions for {} found {} package versions: {:?}", ident, packages.len(), packages);
-
-            let body = serde_json::to_string(&packages).unwrap();
-            HttpResponse::Ok().header(http::header::CONTENT_TYPE, headers::APPLICATION_JSON)
-                              .header(http::header::CACHE_CONTROL, headers::NO_CACHE)
-                              .body(body)
+    match do_diff_package(&req, &from, &to, target, &state) {
+        Ok(diff) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL,
+                                      headers::Cache::default().to_string())
+                              .json(diff)
         }
         Err(err) => {
             debug!("{}", err);
-            Error::DieselError(err).into()
+            err.into()
         }
     }
 }
@@ -744,11 +1324,13 @@ fn list_package_versions(req: HttpRequest,
 fn search_packages(req: HttpRequest,
                    path: Path<String>,
                    pagination: Query<Pagination>,
+                   filters: Query<SearchFilters>,
                    state: Data<AppState>)
                    -> HttpResponse {
     Counter::SearchPackages.increment();
 
     let query = path.into_inner();
+    let filters = filters.into_inner();
 
     let opt_session_id = match authorize_session(&req, None, None) {
         Ok(session) => Some(session.get_id() as i64),
@@ -768,36 +1350,73 @@ fn search_packages(req: HttpRequest,
     // search across all origins, similar to how the "distinct" search works now, but returning all
     // the details instead of just names.
     let decoded_query = match percent_encoding::percent_decode(query.as_bytes()).decode_utf8() {
-        Ok(q) => q.to_string().trim_end_matches('/').replace("/", " & "),
+        Ok(q) => q.to_string().trim_end_matches('/').to_string(),
         Err(err) => {
             debug!("{}", err);
             return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
         }
     };
 
-    debug!("search_packages called with: {}", decoded_query);
+    // Results are ranked by how closely the package name matches the last segment of the query
+    let term = decoded_query.rsplit('/')
+                            .next()
+                            .unwrap_or_default()
+                            .to_string();
+    let decoded_query = decoded_query.replace("/", " & ");
 
-    if pagination.distinct {
-        return match Package::search_distinct(SearchPackages { query:      decoded_query,
-                                                               page:       page as i64,
-                                                               limit:      per_page as i64,
-                                                               account_id: opt_session_id, },
-                                              &*conn)
-        {
-            Ok((packages, count)) => postprocess_package_list(&req, &packages, count, &pagination),
-            Err(err) => {
-                debug!("{}", err);
-                Error::DieselError(err).into()
+    debug!("search_packages called with: {}, filters: {:?}",
+           decoded_query, filters);
+
+    let target = match filters.target {
+        Some(ref t) => {
+            match PackageTarget::from_str(t) {
+                Ok(t) => Some(BuilderPackageTarget(t)),
+                Err(_) => return Error::BadRequest.into(),
             }
-        };
-    }
+        }
+        None => None,
+    };
 
-    match Package::search(SearchPackages { query:      decoded_query,
-                                           page:       page as i64,
-                                           limit:      per_page as i64,
-                                           account_id: opt_session_id, },
-                          &*conn)
-    {
+    let visibility = match filters.visibility {
+        Some(ref v) => {
+            match PackageVisibility::from_str(v) {
+                Ok(v) => Some(v),
+                Err(_) => return Error::BadRequest.into(),
+            }
+        }
+        None => None,
+    };
+
+    let depends_on = match filters.depends_on {
+        Some(ref d) => {
+            match PackageIdent::from_str(d) {
+                Ok(ident) => Some(ident.to_string()),
+                Err(_) => return Error::BadRequest.into(),
+            }
+        }
+        None => None,
+    };
+
+    let sp = SearchPackages { query: decoded_query,
+                              term,
+                              page: page as i64,
+                              limit: per_page as i64,
+                              account_id: opt_session_id,
+                              origin: filters.origin,
+                              target,
+                              channel: filters.channel.map(ChannelIdent::from),
+                              visibility,
+                              created_after: filters.created_after,
+                              created_before: filters.created_before,
+                              depends_on };
+
+    let result = if pagination.distinct {
+        Package::search_distinct(sp, &*conn)
+    } else {
+        Package::search(sp, &*conn)
+    };
+
+    match result {
         Ok((packages, count)) => postprocess_package_list(&req, &packages, count, &pagination),
         Err(err) => {
             debug!("{}", err);
@@ -916,8 +1535,198 @@ pub fn postprocess_extended_package_list(_req: &HttpRequest,
             .body(body)
 }
 
+// Runs the checks that guard package deletion. Packages in the stable channel, and packages
+// whose project has reverse dependencies, are never deleted. No connection is held across the
+// reverse dependencies request.
+pub async fn check_package_deletable(jobsrv: &RpcClient,
+                                     ident: &PackageIdent,
+                                     target: PackageTarget,
+                                     db: &DbPool)
+                                     -> Result<Option<DeleteBlocker>> {
+    let channels = {
+        let conn = db.get_conn().map_err(Error::DbError)?;
+        Package::list_package_channels(&BuilderPackageIdent(ident.clone()),
+                                       target,
+                                       helpers::all_visibilities(),
+                                       &*conn).map_err(Error::DieselError)?
+    };
+    if channels.iter()
+               .any(|c| c.name == ChannelIdent::stable().to_string())
+    {
+        return Ok(Some(DeleteBlocker::StableChannel));
+    }
+
+    if channels.iter().any(|c| c.no_delete) {
+        return Ok(Some(DeleteBlocker::ProtectedChannel));
+    }
+
+    if feat::is_enabled(feat::Jobsrv) {
+        let mut rdeps_get = jobsrv::JobGraphPackageReverseDependenciesGet::new();
+        rdeps_get.set_origin(ident.origin().to_string());
+        rdeps_get.set_name(ident.name().to_string());
+        rdeps_get.set_target(target.to_string());
+
+        Counter::RouteMessage.increment();
+        let rdeps = jobsrv.rpc::<jobsrv::JobGraphPackageReverseDependenciesGet,
+                                 jobsrv::JobGraphPackageReverseDependencies>(&rdeps_get)
+                          .await
+                          .map_err(Error::BuilderCore)?;
+        if !rdeps.get_rdeps().is_empty() {
+            return Ok(Some(DeleteBlocker::ReverseDependencies));
+        }
+    }
+
+    Ok(None)
+}
+
+// Removes the package and its channel memberships. Callers are responsible for running
+// check_package_deletable first and for clearing any cached entries afterwards.
+pub fn do_delete_package(ident: &PackageIdent,
+                         target: PackageTarget,
+                         conn: &PgConnection)
+                         -> Result<()> {
+    // TODO (SA): Wrap in transaction, or better yet, eliminate need to do
+    // channel package deletion
+    let pkg = Package::get(GetPackage { ident:      BuilderPackageIdent(ident.clone()),
+                                        visibility: helpers::all_visibilities(),
+                                        target:     BuilderPackageTarget(target), },
+                           conn).map_err(Error::DieselError)?;
+
+    Channel::delete_channel_package(pkg.id, conn).map_err(Error::DieselError)?;
+
+    Package::delete(DeletePackage { ident:  BuilderPackageIdent(ident.clone()),
+                                    target: BuilderPackageTarget(target), },
+                    conn).map_err(Error::DieselError)?;
+    Ok(())
+}
+
 // Internal - these functions should return Result<..>
 //
+// Checks that the archive was signed by one of the origin's registered public signing keys
+fn check_package_signer(archive_path: &FsPath,
+                        origin: &str,
+                        data_path: &FsPath,
+                        conn: &PgConnection)
+                        -> Result<SignerCheck> {
+    let header = match artifact::get_artifact_header(archive_path) {
+        Ok(header) => header,
+        Err(e) => {
+            return Ok(SignerCheck::Rejected(SignerRejection::UnreadableHeader(e.to_string())))
+        }
+    };
+
+    let signer = header.key_name;
+    let (name, rev) = match parse_name_with_rev(&signer) {
+        Ok(val) => val,
+        Err(e) => {
+            let reason = format!("invalid signer {}: {}", signer, e);
+            return Ok(SignerCheck::Rejected(SignerRejection::UnreadableHeader(reason)));
+        }
+    };
+
+    if name != origin {
+        return Ok(SignerCheck::Rejected(SignerRejection::ForeignOrigin(signer)));
+    }
+
+    let key = match OriginPublicSigningKey::get(origin, &rev, conn) {
+        Ok(key) => key,
+        Err(NotFound) => return Ok(SignerCheck::UnknownRevision(signer)),
+        Err(err) => return Err(Error::DieselError(err)),
+    };
+
+    if key.state == OriginKeyState::Revoked {
+        return Ok(SignerCheck::Rejected(SignerRejection::Revoked(signer)));
+    }
+
+    // Verification reads keys from a cache directory, so give it one holding just this key
+    let cache = tempdir_in(data_path)?;
+    SigKeyPair::write_file_from_str(&String::from_utf8(key.body)?, cache.path())?;
+
+    match artifact::verify(archive_path, cache.path()) {
+        Ok(_) => Ok(SignerCheck::Verified(signer)),
+        Err(e) => Ok(SignerCheck::Rejected(SignerRejection::BadSignature(e.to_string()))),
+    }
+}
+
+async fn do_plan_bulk_delete(req: &HttpRequest,
+                             origin: &str,
+                             selector: &BulkDeleteSelector,
+                             state: &AppState)
+                             -> Result<BulkDeletePlan> {
+    let target = match selector.target {
+        Some(ref t) => Some(PackageTarget::from_str(t).map_err(|_| Error::BadRequest)?),
+        None => None,
+    };
+    let channel = selector.channel
+                          .as_ref()
+                          .map(|c| ChannelIdent::from(c.as_str()));
+
+    let list_req = ListPackagesForDeletion { origin,
+                                             name: selector.name.as_deref(),
+                                             target: target.map(BuilderPackageTarget),
+                                             channel: channel.as_ref(),
+                                             created_after: selector.created_after,
+                                             created_before: selector.created_before };
+    let packages = {
+        let conn = state.db.get_conn().map_err(Error::DbError)?;
+        Package::list_for_deletion(&list_req, &*conn).map_err(Error::DieselError)?
+    };
+
+    // Members may only remove public releases, private and hidden ones need a maintainer
+    let can_delete_private =
+        authorize_session(req, Some(origin), Some(OriginMemberRole::Maintainer)).is_ok();
+
+    let mut plan = BulkDeletePlan { executed: false,
+                                    delete:   Vec::new(),
+                                    blocked:  Vec::new(), };
+
+    for package in packages {
+        if let Some(ref pattern) = selector.version {
+            if !helpers::glob_match(pattern, package.ident.version().unwrap_or_default()) {
+                continue;
+            }
+        }
+
+        let reason = if !can_delete_private && package.visibility != PackageVisibility::Public {
+            Some(DeleteBlocker::Visibility)
+        } else {
+            check_package_deletable(&state.jobsrv, &package.ident, *package.target, &state.db).await?
+        };
+
+        let entry = BulkDeleteEntry { ident: package.ident,
+                                      target: package.target,
+                                      reason };
+        if entry.reason.is_some() {
+            plan.blocked.push(entry);
+        } else {
+            plan.delete.push(entry);
+        }
+    }
+
+    Ok(plan)
+}
+
+// Removes every unblocked release of the plan in a single transaction, so either all of
+// them are deleted or none are.
+fn do_execute_bulk_delete(plan: &BulkDeletePlan, state: &AppState) -> Result<()> {
+    let conn = state.db.get_conn().map_err(Error::DbError)?;
+
+    conn.transaction::<_, Error, _>(|| {
+            for entry in plan.delete.iter() {
+                do_delete_package(&entry.ident, *entry.target, &*conn)?;
+            }
+            Ok(())
+        })?;
+
+    for entry in plan.delete.iter() {
+        state.memcache
+             .borrow_mut()
+             .clear_cache_for_package(&entry.ident);
+    }
+
+    Ok(())
+}
+
 fn do_get_packages(req: &HttpRequest,
                    ident: &PackageIdent,
                    pagination: &Query<Pagination>)
@@ -964,10 +1773,12 @@ fn do_get_packages(req: &HttpRequest,
 
 //  Async helpers
 //
-fn do_upload_package_start(req: &HttpRequest,
-                           qupload: &Query<Upload>,
+// Checks that the caller may upload the package, and that it does not already exist unless
+// the upload is forced
+fn do_upload_package_check(req: &HttpRequest,
+                           qupload: &Upload,
                            ident: &PackageIdent)
-                           -> Result<(PathBuf, BufWriter<File>)> {
+                           -> Result<()> {
     authorize_session(req, Some(&ident.origin), Some(OriginMemberRole::Member))?;
 
     let conn = req_state(req).db.get_conn().map_err(Error::DbError)?;
@@ -998,6 +1809,15 @@ fn do_upload_package_start(req: &HttpRequest,
         }
     }
 
+    Ok(())
+}
+
+fn do_upload_package_start(req: &HttpRequest,
+                           qupload: &Query<Upload>,
+                           ident: &PackageIdent)
+                           -> Result<(PathBuf, BufWriter<File>)> {
+    do_upload_package_check(req, qupload, ident)?;
+
     debug!("UPLOADING {}, params={:?}", ident, qupload);
 
     // Create a temp file at the data path
@@ -1010,10 +1830,40 @@ fn do_upload_package_start(req: &HttpRequest,
     Ok((temp_path, writer))
 }
 
+// Uploads an archive which is already on disk, such as one unpacked from a bundle, with the
+// same checks as a regular upload. The archive is moved out of `temp_path`.
+pub async fn do_upload_package_file(req: &HttpRequest,
+                                    ident: &PackageIdent,
+                                    target: PackageTarget,
+                                    checksum: &str,
+                                    temp_path: &PathBuf)
+                                    -> Result<()> {
+    let qupload = Upload { target:   Some(target.to_string()),
+                           checksum: checksum.to_string(),
+                           builder:  None,
+                           forced:   false, };
+
+    do_upload_package_check(req, &qupload, ident)?;
+
+    req_state(req).memcache
+                  .borrow_mut()
+                  .clear_cache_for_package(ident);
+
+    let response = do_upload_package_finish(req, &qupload, ident, temp_path).await;
+    if response.status() == StatusCode::CREATED {
+        Ok(())
+    } else {
+        Err(Error::Bundle(format!("upload of {} ({}) failed with {}",
+                                  ident,
+                                  target,
+                                  response.status())))
+    }
+}
+
 // TODO: Break this up further, convert S3 upload to async
 #[allow(clippy::cognitive_complexity)]
 async fn do_upload_package_finish(req: &HttpRequest,
-                                  qupload: &Query<Upload>,
+                                  qupload: &Upload,
                                   ident: &PackageIdent,
                                   temp_path: &PathBuf)
                                   -> HttpResponse {
@@ -1068,6 +1918,41 @@ async fn do_upload_package_finish(req: &HttpRequest,
         Err(err) => return err.into(),
     };
 
+    // Signer revisions the origin does not know about are either rejected or flagged,
+    // depending on the origin setting
+    let mut unknown_signer = None;
+    let signer = match check_package_signer(&temp_path,
+                                            &ident.origin,
+                                            &req_state(req).config.api.data_path,
+                                            &*conn)
+    {
+        Ok(SignerCheck::Verified(signer)) => signer,
+        Ok(SignerCheck::UnknownRevision(signer)) => {
+            match Origin::get(&ident.origin, &*conn) {
+                Ok(origin) if origin.reject_unknown_key_revisions => {
+                    debug!("Unknown signing key revision {} for {}", signer, ident);
+                    let message = format!("ds:up:7, unknown signing key revision {}", signer);
+                    return HttpResponse::with_body(StatusCode::UNPROCESSABLE_ENTITY,
+                                                   Body::from_message(message));
+                }
+                Ok(_) => {
+                    warn!("Accepting {} signed with unknown key revision {}",
+                          ident, signer);
+                    unknown_signer = Some(signer.clone());
+                    signer
+                }
+                Err(err) => return Error::DieselError(err).into(),
+            }
+        }
+        Ok(SignerCheck::Rejected(reason)) => {
+            debug!("Signature check failed for {}: {}", ident, reason);
+            let message = format!("{}, {}", reason.code(), reason);
+            return HttpResponse::with_body(StatusCode::UNPROCESSABLE_ENTITY,
+                                           Body::from_message(message));
+        }
+        Err(err) => return err.into(),
+    };
+
     // If upload was forced, and a previously uploaded package exists in DB
     // make sure the checksums match the original (idempotency)
     if qupload.forced {
@@ -1114,22 +1999,11 @@ async fn do_upload_package_finish(req: &HttpRequest,
         }
     }
 
-    // TODO: Make upload async
-    // TODO: Aggregate Artifactory/S3 into a provider model
-    if feat::is_enabled(feat::Artifactory) {
-        if let Err(err) = req_state(req).artifactory
-                                        .upload(&filename, &temp_ident, target_from_artifact)
-                                        .await
-                                        .map_err(Error::Artifactory)
-        {
-            warn!("Unable to upload archive to artifactory!");
-            return err.into();
-        }
-    } else if let Err(err) = req_state(req).packages
-                                           .upload(&filename, &temp_ident, target_from_artifact)
-                                           .await
+    if let Err(err) = req_state(req).packages
+                                    .upload(&filename, &temp_ident, target_from_artifact)
+                                    .await
     {
-        warn!("Unable to upload archive to s3!");
+        warn!("Unable to upload archive to package storage!");
         return err.into();
     }
 
@@ -1192,6 +2066,32 @@ async fn do_upload_package_finish(req: &HttpRequest,
     // Re-create origin package as needed (eg, checksum update)
     match Package::create(&package, &*conn) {
         Ok(pkg) => {
+            if let Err(err) = PackageSigner::record(pkg.id, &pkg.origin, &signer, &*conn) {
+                warn!("Failed to record signer of {}, err={:?}", ident, err);
+            }
+
+            if let Some(ref signer) = unknown_signer {
+                let flag = NewPackageSignerFlag { origin: &pkg.origin,
+                                                  package_id: pkg.id,
+                                                  ident: pkg.ident.clone(),
+                                                  target: pkg.target.clone(),
+                                                  signer,
+                                                  owner_id: pkg.owner_id };
+                if let Err(err) = PackageSignerFlag::create(&flag, &*conn) {
+                    warn!("Failed to flag unknown signer for {}, err={:?}", ident, err);
+                }
+            }
+
+            // Uploads made with the builder's own token are attested by the worker
+            // once its job completes
+            if session.get_id() != BUILDER_ACCOUNT_ID {
+                let provenance = NewPackageProvenance { package_id: pkg.id,
+                                                        ..Default::default() };
+                if let Err(err) = PackageProvenance::create(&provenance, &*conn) {
+                    warn!("Failed to record provenance for {}, err={:?}", ident, err);
+                }
+            }
+
             if feat::is_enabled(feat::Jobsrv) {
                 let mut job_graph_package = jobsrv::JobGraphPackageCreate::new();
                 job_graph_package.set_package(pkg.into());
@@ -1294,6 +2194,263 @@ async fn do_upload_package_async(req: HttpRequest,
     }
 }
 
+fn do_start_upload_session(req: &HttpRequest,
+                           qupload: &Upload,
+                           ident: &PackageIdent,
+                           state: &AppState)
+                           -> Result<PackageUpload> {
+    do_upload_package_check(req, qupload, ident)?;
+
+    let session = authorize_session(req, None, None)?;
+    let target = match qupload.target {
+        Some(ref t) => PackageTarget::from_str(t)?,
+        None => helpers::target_from_headers(req),
+    };
+
+    let conn = state.db.get_conn().map_err(Error::DbError)?;
+
+    let upload = PackageUpload::create(&NewPackageUpload { origin:   &ident.origin,
+                                                           ident:
+                                                               BuilderPackageIdent(ident.clone()),
+                                                           target:   BuilderPackageTarget(target),
+                                                           checksum: &qupload.checksum,
+                                                           forced:   qupload.forced,
+                                                           builder:  qupload.builder.as_deref(),
+                                                           owner_id: session.get_id() as i64, },
+                                       &*conn)?;
+
+    File::create(uploads::session_path(&state.config.api.data_path, upload.id))?;
+
+    debug!("Started upload session {} for {}, params={:?}",
+           upload.id, ident, qupload);
+
+    Ok(upload)
+}
+
+// Loads an upload session, making sure it is for the requested package and was started by
+// the caller. Expired sessions are discarded and treated as missing.
+fn do_get_upload_session(req: &HttpRequest,
+                         ident: &PackageIdent,
+                         id: i64,
+                         state: &AppState)
+                         -> Result<PackageUpload> {
+    let session = authorize_session(req, Some(&ident.origin), Some(OriginMemberRole::Member))?;
+
+    let conn = state.db.get_conn().map_err(Error::DbError)?;
+    let upload = PackageUpload::get(id, &*conn)?;
+
+    if *upload.ident != *ident || upload.owner_id != session.get_id() as i64 {
+        return Err(Error::NotFound);
+    }
+
+    if uploads::is_expired(&upload, state.config.api.upload_session_ttl) {
+        uploads::expire(&upload,
+                        state.config.api.upload_session_ttl,
+                        &state.config.api.data_path,
+                        &state.db)?;
+        return Err(Error::NotFound);
+    }
+
+    Ok(upload)
+}
+
+// Loads an upload session like do_get_upload_session and locks it for writing. The session
+// is read again once the lock is held, so it reflects every chunk written before. Returns
+// Conflict if another request holds the lock.
+fn do_lock_upload_session(req: &HttpRequest,
+                          ident: &PackageIdent,
+                          id: i64,
+                          state: &AppState)
+                          -> Result<(PackageUpload, uploads::SessionLock)> {
+    do_get_upload_session(req, ident, id, state)?;
+
+    let lock = match uploads::SessionLock::try_acquire(id,
+                                                       state.config.api.upload_session_ttl,
+                                                       &state.db)?
+    {
+        Some(lock) => lock,
+        None => {
+            debug!("Upload {} is already being written to", id);
+            return Err(Error::Conflict);
+        }
+    };
+
+    let conn = state.db.get_conn().map_err(Error::DbError)?;
+    let upload = PackageUpload::get(id, &*conn)?;
+    Ok((upload, lock))
+}
+
+// Appends a chunk to the session file, returning the number of bytes received so far
+async fn do_write_upload_chunk(upload: &PackageUpload,
+                               mut stream: web::Payload,
+                               state: &AppState)
+                               -> Result<i64> {
+    let path = uploads::session_path(&state.config.api.data_path, upload.id);
+    let received_bytes = upload.received_bytes as u64;
+
+    // Drop anything left behind by an interrupted attempt at this chunk
+    let file = web::block(move || {
+                   let mut file = OpenOptions::new().write(true).open(&path)?;
+                   file.set_len(received_bytes)?;
+                   file.seek(SeekFrom::End(0))?;
+                   Ok::<_, io::Error>(file)
+               }).await?;
+
+    let mut writer = BufWriter::new(file);
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        debug!("Writing upload {} chunk, size: {}", upload.id, chunk.len());
+        writer = web::block(move || writer.write_all(&chunk).map(|_| writer)).await?;
+    }
+
+    let file = writer.into_inner().map_err(Error::InnerError)?;
+    let len = web::block(move || {
+                  file.sync_all()?;
+                  Ok::<_, io::Error>(file.metadata()?.len())
+              }).await?;
+    Ok(len as i64)
+}
+
+fn do_get_package_provenance(req: &HttpRequest,
+                             ident: &PackageIdent,
+                             target: PackageTarget,
+                             state: &AppState)
+                             -> Result<PackageProvenance> {
+    let opt_session_id = match authorize_session(req, None, None) {
+        Ok(session) => Some(session.get_id()),
+        Err(_) => None,
+    };
+
+    let conn = state.db.get_conn().map_err(Error::DbError)?;
+
+    let pkg = Package::get(GetPackage { ident:      BuilderPackageIdent(ident.clone()),
+                                        visibility:
+                                            helpers::visibility_for_optional_session(req,
+                                                                                     opt_session_id,
+                                                                                     &ident.origin),
+                                        target:     BuilderPackageTarget(target), },
+                           &*conn)?;
+
+    // Packages uploaded before provenance was recorded have no row
+    match PackageProvenance::get(pkg.id, &*conn) {
+        Ok(provenance) => Ok(provenance),
+        Err(NotFound) => Ok(PackageProvenance::unattested(pkg.id)),
+        Err(err) => Err(Error::DieselError(err)),
+    }
+}
+
+fn do_put_package_provenance(ident: &PackageIdent,
+                             target: PackageTarget,
+                             body: &ProvenanceReq,
+                             state: &AppState)
+                             -> Result<PackageProvenance> {
+    let mut deps = Vec::new();
+    for dep in body.deps.iter() {
+        match PackageIdent::from_str(dep) {
+            Ok(dep) if dep.fully_qualified() => deps.push(BuilderPackageIdent(dep)),
+            _ => {
+                debug!("Invalid provenance dependency: {}", dep);
+                return Err(Error::BadRequest);
+            }
+        }
+    }
+
+    let conn = state.db.get_conn().map_err(Error::DbError)?;
+
+    let pkg = Package::get(GetPackage { ident:      BuilderPackageIdent(ident.clone()),
+                                        visibility: helpers::all_visibilities(),
+                                        target:     BuilderPackageTarget(target), },
+                           &*conn)?;
+
+    let provenance = NewPackageProvenance { package_id: pkg.id,
+                                            attested: true,
+                                            job_id: Some(body.job_id as i64),
+                                            group_id: Some(body.group_id as i64),
+                                            vcs_url: Some(&body.vcs_url),
+                                            vcs_sha: body.vcs_sha.as_deref(),
+                                            worker: Some(&body.worker),
+                                            plan_path: Some(&body.plan_path),
+                                            studio_version: Some(&body.studio_version),
+                                            hab_version: Some(&body.hab_version),
+                                            deps };
+
+    debug!("Recording provenance for {} ({}) from job {}",
+           ident, target, body.job_id);
+
+    PackageProvenance::create(&provenance, &*conn).map_err(Error::DieselError)
+}
+
+fn do_get_sbom_components(req: &HttpRequest,
+                          ident: &PackageIdent,
+                          target: PackageTarget,
+                          include_build: bool,
+                          state: &AppState)
+                          -> Result<Vec<Component>> {
+    let opt_session_id = match authorize_session(req, None, None) {
+        Ok(session) => Some(session.get_id()),
+        Err(_) => None,
+    };
+    let visibility = helpers::visibility_for_optional_session(req, opt_session_id, &ident.origin);
+
+    let conn = state.db.get_conn().map_err(Error::DbError)?;
+
+    let root = Package::get(GetPackage { ident:      BuilderPackageIdent(ident.clone()),
+                                         visibility: visibility.clone(),
+                                         target:     BuilderPackageTarget(target), },
+                            &*conn)?;
+
+    let mut pkgs = root.tdeps.clone();
+    if include_build {
+        pkgs.extend(root.build_tdeps.iter().cloned());
+    }
+
+    // The group lookup is scoped to neither a target nor an origin, so the dependencies of
+    // origins the caller can not see into are left with their ident alone
+    let mut visible: HashMap<String, Vec<PackageVisibility>> = HashMap::new();
+    visible.insert(ident.origin.clone(), visibility);
+    let group = GetPackageGroup { pkgs,
+                                  visibility: helpers::all_visibilities() };
+    let mut packages = Package::get_group(group, &*conn)?;
+    packages.retain(|p| {
+                p.target == root.target
+                && visible.entry(p.origin.clone())
+                          .or_insert_with(|| {
+                              helpers::visibility_for_optional_session(req,
+                                                                       opt_session_id,
+                                                                       &p.origin)
+                          })
+                          .contains(&p.visibility)
+            });
+
+    Ok(sbom::components(&root, &packages, include_build))
+}
+
+fn do_diff_package(req: &HttpRequest,
+                   from: &PackageIdent,
+                   to: &PackageIdent,
+                   target: PackageTarget,
+                   state: &AppState)
+                   -> Result<PackageDiff> {
+    let opt_session_id = match authorize_session(req, None, None) {
+        Ok(session) => Some(session.get_id()),
+        Err(_) => None,
+    };
+    let visibility = helpers::visibility_for_optional_session(req, opt_session_id, &from.origin);
+
+    let conn = state.db.get_conn().map_err(Error::DbError)?;
+
+    let get = |ident: &PackageIdent| {
+        Package::get(GetPackage { ident:      BuilderPackageIdent(ident.clone()),
+                                  visibility: visibility.clone(),
+                                  target:     BuilderPackageTarget(target), },
+                     &*conn)
+    };
+    let from = get(from)?;
+    let to = get(to)?;
+
+    Ok(diff::diff_packages(&from, &to))
+}
+
 fn do_get_package(req: &HttpRequest,
                   qtarget: &Query<Target>,
                   ident: &PackageIdent)
@@ -1445,36 +2602,95 @@ fn archive_name(ident: &PackageIdent, target: PackageTarget) -> PathBuf {
 fn download_response_for_archive(archive: &PackageArchive,
                                  file_path: &PathBuf,
                                  is_private: bool,
+                                 range: Option<&str>,
                                  state: &Data<AppState>)
                                  -> HttpResponse {
     let filename = archive.file_name();
-    let file = match File::open(&file_path) {
+    let mut file = match File::open(&file_path) {
         Ok(f) => f,
         Err(err) => {
             warn!("Unable to open file: {:?}", file_path);
             return Error::IO(err).into();
         }
     };
-    let reader = BufReader::new(file);
-    let bytes: Vec<u8> = reader.bytes().map(|r| r.unwrap()).collect();
+    let len = match file.metadata() {
+        Ok(metadata) => metadata.len(),
+        Err(err) => return Error::IO(err).into(),
+    };
+
+    let (response, start, end) = match helpers::byte_range(range, len) {
+        ByteRange::Full => (HttpResponse::Ok(), 0, len),
+        ByteRange::Partial(start, end) => {
+            let mut response = HttpResponse::PartialContent();
+            response.header(http::header::CONTENT_RANGE,
+                            format!("bytes {}-{}/{}", start, end, len));
+            (response, start, end + 1)
+        }
+        ByteRange::Unsatisfiable => return range_not_satisfiable(len),
+    };
+
+    if let Err(err) = file.seek(SeekFrom::Start(start)) {
+        return Error::IO(err).into();
+    }
+    let mut bytes = Vec::new();
+    if let Err(err) = BufReader::new(file).take(end - start)
+                                          .read_to_end(&mut bytes)
+    {
+        warn!("Unable to read file: {:?}", file_path);
+        return Error::IO(err).into();
+    }
+
+    download_response(response, &filename, bytes, is_private, state)
+}
 
+fn download_response(response: HttpResponseBuilder,
+                     filename: &str,
+                     bytes: Vec<u8>,
+                     is_private: bool,
+                     state: &Data<AppState>)
+                     -> HttpResponse {
     let (tx, rx_body) = mpsc::unbounded();
     let _ = tx.unbounded_send(Bytes::from(bytes));
+
+    #[allow(clippy::redundant_closure)] //  Ok::<_, ()>
+    stream_download_response(response,
+                             filename,
+                             rx_body.map(|s| Ok::<_, ()>(s)),
+                             is_private,
+                             state)
+}
+
+fn stream_download_response<S, E>(mut response: HttpResponseBuilder,
+                                  filename: &str,
+                                  body: S,
+                                  is_private: bool,
+                                  state: &Data<AppState>)
+                                  -> HttpResponse
+    where S: Stream<Item = result::Result<Bytes, E>> + Unpin + 'static,
+          E: Into<actix_web::Error> + 'static
+{
     let cache_hdr = if is_private {
         headers::Cache::MaxAge(state.config.api.private_max_age).to_string()
     } else {
         headers::Cache::default().to_string()
     };
 
-    #[allow(clippy::redundant_closure)] //  Ok::<_, ()>
-    HttpResponse::Ok().header(http::header::CONTENT_DISPOSITION,
+    response.header(http::header::CONTENT_DISPOSITION,
             ContentDisposition { disposition: DispositionType::Attachment,
-                                 parameters:  vec![DispositionParam::Filename(filename)], })
+                                 parameters:
+                                     vec![DispositionParam::Filename(filename.to_string())], })
     .header(http::header::HeaderName::from_static(headers::XFILENAME),
-            archive.file_name())
+            filename)
     .set(ContentType::octet_stream())
+    .header(http::header::ACCEPT_RANGES, "bytes")
     .header(http::header::CACHE_CONTROL, cache_hdr)
-    .streaming(rx_body.map(|s| Ok::<_, ()>(s)))
+    .streaming(body)
+}
+
+fn range_not_satisfiable(len: u64) -> HttpResponse {
+    HttpResponse::RangeNotSatisfiable().header(http::header::CONTENT_RANGE,
+                                               format!("bytes */{}", len))
+                                       .finish()
 }
 
 async fn has_circular_deps(req: &HttpRequest,
@@ -12,17 +12,25 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 //
+use std::str::FromStr;
+
 use actix_web::{http::{self,
                        StatusCode},
                 web::{self,
+                      Bytes,
                       Data,
                       Json,
                       Path,
                       ServiceConfig},
                 HttpRequest,
                 HttpResponse};
+use chrono::{DateTime,
+             Utc};
+use diesel::result::Error::NotFound;
+use protobuf::RepeatedField;
 
 use crate::{bldr_core,
+            hab_core::package::ident,
             protocol::originsrv};
 
 use crate::db::models::account::*;
@@ -34,6 +42,34 @@ use crate::server::{authorize::authorize_session,
                     helpers::req_state,
                     AppState};
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AccessTokenReq {
+    #[serde(default = "default_token_name")]
+    pub name:       String,
+    // Any of read, upload, promote and jobs. No scopes allow everything the account can do.
+    #[serde(default)]
+    pub scopes:     Vec<String>,
+    #[serde(default)]
+    pub origins:    Vec<String>,
+    // The channels the promote scope is limited to
+    #[serde(default)]
+    pub channels:   Vec<String>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Default for AccessTokenReq {
+    fn default() -> Self {
+        AccessTokenReq { name:       default_token_name(),
+                         scopes:     Vec::new(),
+                         origins:    Vec::new(),
+                         channels:   Vec::new(),
+                         expires_at: None, }
+    }
+}
+
+fn default_token_name() -> String { "default".to_string() }
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UserUpdateReq {
     #[serde(default)]
@@ -110,27 +146,12 @@ fn get_access_tokens(req: HttpRequest) -> HttpResponse {
 }
 
 #[allow(clippy::needless_pass_by_value)]
-fn generate_access_token(req: HttpRequest, state: Data<AppState>) -> HttpResponse {
+fn generate_access_token(req: HttpRequest, body: Bytes, state: Data<AppState>) -> HttpResponse {
     let account_id = match authorize_session(&req, None, None) {
         Ok(session) => session.get_id(),
         Err(err) => return err.into(),
     };
 
-    let conn = match state.db.get_conn().map_err(Error::DbError) {
-        Ok(conn_ref) => conn_ref,
-        Err(err) => return err.into(),
-    };
-
-    // Memcache supports multiple tokens but to preserve legacy behavior
-    // we must purge any existing tokens AFTER generating new ones
-    let access_tokens = match AccountToken::list(account_id, &*conn).map_err(Error::DieselError) {
-        Ok(access_tokens) => access_tokens,
-        Err(err) => {
-            debug!("{}", err);
-            return err.into();
-        }
-    };
-
     // TODO: Provide an API for this
     let flags = {
         let extension = req.extensions();
@@ -138,21 +159,8 @@ fn generate_access_token(req: HttpRequest, state: Data<AppState>) -> HttpRespons
         session.get_flags()
     };
 
-    let token = bldr_core::access_token::generate_user_token(&state.config.api.key_path,
-                                                             account_id,
-                                                             flags).unwrap();
-
-    let new_token = NewAccountToken { account_id: account_id as i64,
-                                      token:      &token, };
-
-    match AccountToken::create(&new_token, &*conn).map_err(Error::DieselError) {
-        Ok(account_token) => {
-            let mut memcache = state.memcache.borrow_mut();
-            for token in access_tokens {
-                memcache.delete_session_key(&token.token)
-            }
-            HttpResponse::Ok().json(account_token)
-        }
+    match do_generate_access_token(&state, account_id, flags, &body) {
+        Ok(account_token) => HttpResponse::Ok().json(account_token),
         Err(err) => {
             debug!("{}", err);
             err.into()
@@ -189,10 +197,11 @@ fn revoke_access_token(req: HttpRequest,
         }
     };
 
-    match AccountToken::delete(token_id, &*conn).map_err(Error::DieselError) {
+    match AccountToken::delete(token_id, account_id, &*conn).map_err(Error::DieselError) {
+        Ok(0) => HttpResponse::new(StatusCode::NOT_FOUND),
         Ok(_) => {
             let mut memcache = state.memcache.borrow_mut();
-            for token in access_tokens {
+            for token in access_tokens.iter().filter(|t| t.id == token_id as i64) {
                 memcache.delete_session_key(&token.token)
             }
             HttpResponse::Ok().finish()
@@ -231,3 +240,83 @@ fn update_account(req: HttpRequest,
         }
     }
 }
+
+// Internal - these functions should return Result<..>
+//
+
+// do_generate_access_token is also used for the tokens of service accounts so it has to be
+// public. Without a body it replaces the default token, as the endpoint always has.
+pub fn do_generate_access_token(state: &AppState,
+                                account_id: u64,
+                                flags: u32,
+                                body: &Bytes)
+                                -> Result<AccountToken> {
+    let token_req = if body.is_empty() {
+        AccessTokenReq::default()
+    } else {
+        match serde_json::from_slice::<AccessTokenReq>(body) {
+            Ok(token_req) => token_req,
+            Err(err) => {
+                debug!("Invalid access token request, err={}", err);
+                return Err(Error::Unprocessable);
+            }
+        }
+    };
+    let restrictions = token_restrictions(&token_req)?;
+
+    let conn = state.db.get_conn().map_err(Error::DbError)?;
+
+    // The token being replaced, its session is purged only AFTER the new token is saved
+    let replaced = match AccountToken::get(account_id, &token_req.name, &*conn) {
+        Ok(access_token) => Some(access_token),
+        Err(NotFound) => None,
+        Err(err) => return Err(Error::DieselError(err)),
+    };
+
+    let token = bldr_core::access_token::generate_user_token(&state.config.api.key_path,
+                                                             account_id,
+                                                             flags,
+                                                             &restrictions,
+                                                             token_req.expires_at)?;
+
+    let new_token = NewAccountToken { account_id: account_id as i64,
+                                      token:      &token,
+                                      name:       &token_req.name,
+                                      scopes:     restrictions.get_scopes()
+                                                              .iter()
+                                                              .map(ToString::to_string)
+                                                              .collect(),
+                                      origins:    token_req.origins.clone(),
+                                      channels:   token_req.channels.clone(),
+                                      expires_at: token_req.expires_at, };
+    let account_token = AccountToken::create(&new_token, &*conn)?;
+
+    if let Some(replaced) = replaced {
+        state.memcache
+             .borrow_mut()
+             .delete_session_key(&replaced.token)
+    }
+    Ok(account_token)
+}
+
+fn token_restrictions(token_req: &AccessTokenReq) -> Result<originsrv::AccessToken> {
+    if token_req.name.is_empty() || token_req.expires_at.map_or(false, |e| e <= Utc::now()) {
+        return Err(Error::Unprocessable);
+    }
+
+    let mut scopes = Vec::new();
+    for scope in token_req.scopes.iter() {
+        scopes.push(originsrv::TokenScope::from_str(scope).map_err(|_| Error::Unprocessable)?);
+    }
+    for origin in token_req.origins.iter() {
+        if !ident::is_valid_origin_name(origin) {
+            return Err(Error::Unprocessable);
+        }
+    }
+
+    let mut restrictions = originsrv::AccessToken::new();
+    restrictions.set_scopes(scopes);
+    restrictions.set_origins(RepeatedField::from_vec(token_req.origins.clone()));
+    restrictions.set_channels(RepeatedField::from_vec(token_req.channels.clone()));
+    Ok(restrictions)
+}
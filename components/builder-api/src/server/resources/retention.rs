@@ -0,0 +1,235 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::{http::{self,
+                       StatusCode},
+                web::{self,
+                      Data,
+                      Json,
+                      Path,
+                      Query,
+                      ServiceConfig},
+                HttpRequest,
+                HttpResponse};
+
+use crate::{db::models::{channel::Channel,
+                         origin::*,
+                         retention::*},
+            hab_core::ChannelIdent,
+            server::{authorize::authorize_session,
+                     error::Error,
+                     framework::headers,
+                     services::retention::{self,
+                                           RetentionReport},
+                     AppState}};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RetentionPolicyReq {
+    #[serde(default)]
+    pub channel:      Option<String>,
+    #[serde(default)]
+    pub keep_latest:  Option<i32>,
+    #[serde(default)]
+    pub max_age_days: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct RetentionScope {
+    #[serde(default)]
+    pub channel: Option<String>,
+}
+
+pub struct Retention;
+
+impl Retention {
+    // Route registration
+    //
+    pub fn register(cfg: &mut ServiceConfig) {
+        cfg.route("/depot/origins/{origin}/retention",
+                  web::get().to(list_retention_policies))
+           .route("/depot/origins/{origin}/retention",
+                  web::put().to(set_retention_policy))
+           .route("/depot/origins/{origin}/retention",
+                  web::delete().to(delete_retention_policy))
+           .route("/depot/origins/{origin}/retention/report",
+                  web::get().to(get_retention_report));
+    }
+}
+
+// Route handlers - these functions can return any Responder trait
+//
+#[allow(clippy::needless_pass_by_value)]
+fn list_retention_policies(req: HttpRequest,
+                           path: Path<String>,
+                           state: Data<AppState>)
+                           -> HttpResponse {
+    let origin = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), None) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    match OriginRetentionPolicy::list(&origin, &*conn).map_err(Error::DieselError) {
+        Ok(policies) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(policies)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn set_retention_policy(req: HttpRequest,
+                        path: Path<String>,
+                        body: Json<RetentionPolicyReq>,
+                        state: Data<AppState>)
+                        -> HttpResponse {
+    let origin = path.into_inner();
+
+    let account_id =
+        match authorize_session(&req, Some(&origin), Some(OriginMemberRole::Administrator)) {
+            Ok(session) => session.get_id(),
+            Err(err) => return err.into(),
+        };
+
+    if body.keep_latest.is_none() && body.max_age_days.is_none() {
+        return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    if body.keep_latest.map_or(false, |n| n < 1) || body.max_age_days.map_or(false, |n| n < 1) {
+        return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    if let Some(ref channel) = body.channel {
+        let channel = ChannelIdent::from(channel.as_str());
+
+        // Nothing in stable is ever removed, so a policy scoped to it can never apply
+        if channel == ChannelIdent::stable() {
+            return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+
+        if let Err(err) = Channel::get(&origin, &channel, &*conn).map_err(Error::DieselError) {
+            debug!("{}", err);
+            return err.into();
+        }
+    }
+
+    let new_policy = NewOriginRetentionPolicy { origin:       &origin,
+                                                channel:      body.channel.as_deref(),
+                                                keep_latest:  body.keep_latest,
+                                                max_age_days: body.max_age_days,
+                                                owner_id:     account_id as i64, };
+
+    match OriginRetentionPolicy::set(&new_policy, &*conn).map_err(Error::DieselError) {
+        Ok(policy) => HttpResponse::Ok().json(policy),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn delete_retention_policy(req: HttpRequest,
+                           path: Path<String>,
+                           scope: Query<RetentionScope>,
+                           state: Data<AppState>)
+                           -> HttpResponse {
+    let origin = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Administrator))
+    {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    match OriginRetentionPolicy::delete(&origin, scope.channel.as_deref(), &*conn)
+        .map_err(Error::DieselError)
+    {
+        Ok(0) => HttpResponse::NotFound().into(),
+        Ok(_) => HttpResponse::NoContent().into(),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+// Dry run of the retention policies for an origin - nothing is deleted
+#[allow(clippy::needless_pass_by_value)]
+async fn get_retention_report(req: HttpRequest,
+                              path: Path<String>,
+                              scope: Query<RetentionScope>,
+                              state: Data<AppState>)
+                              -> HttpResponse {
+    let origin = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    // The connection is released before planning, which makes requests to jobsrv
+    let policies = {
+        let conn = match state.db.get_conn().map_err(Error::DbError) {
+            Ok(conn_ref) => conn_ref,
+            Err(err) => return err.into(),
+        };
+
+        match OriginRetentionPolicy::list(&origin, &*conn).map_err(Error::DieselError) {
+            Ok(policies) => policies,
+            Err(err) => {
+                debug!("{}", err);
+                return err.into();
+            }
+        }
+    };
+
+    let mut reports = Vec::new();
+    for policy in policies.iter()
+                          .filter(|p| scope.channel.is_none() || p.channel == scope.channel)
+    {
+        match retention::plan(policy, &state.jobsrv, &state.db).await {
+            Ok(entries) => {
+                reports.push(RetentionReport { origin: policy.origin.clone(),
+                                               channel: policy.channel.clone(),
+                                               dry_run: true,
+                                               entries })
+            }
+            Err(err) => {
+                debug!("{}", err);
+                return err.into();
+            }
+        }
+    }
+
+    HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                      .json(reports)
+}
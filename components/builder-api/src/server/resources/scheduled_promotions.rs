@@ -0,0 +1,273 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::str::FromStr;
+
+use actix_web::{http::{self,
+                       StatusCode},
+                web::{self,
+                      Data,
+                      Json,
+                      Path,
+                      ServiceConfig},
+                HttpRequest,
+                HttpResponse};
+use chrono::{DateTime,
+             Utc};
+
+use crate::{db::models::{origin::*,
+                         scheduled_promotion::*},
+            hab_core::{package::{PackageIdent,
+                                 PackageTarget},
+                       ChannelIdent},
+            protocol::jobsrv,
+            server::{authorize::authorize_session,
+                     error::{Error,
+                             Result},
+                     framework::{headers,
+                                 middleware::route_message},
+                     AppState}};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScheduledPromotionReq {
+    // Either fully qualified idents of the origin or a job group
+    #[serde(default)]
+    pub idents:     Vec<String>,
+    #[serde(default)]
+    pub group_id:   Option<String>,
+    // The target of the idents; a job group has its own
+    #[serde(default)]
+    pub target:     Option<String>,
+    pub execute_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct ScheduledPromotionDetail {
+    #[serde(flatten)]
+    promotion: ScheduledPromotion,
+    items:     Vec<ScheduledPromotionItem>,
+}
+
+pub struct ScheduledPromotions;
+
+impl ScheduledPromotions {
+    // Route registration
+    //
+    pub fn register(cfg: &mut ServiceConfig) {
+        cfg.route("/depot/origins/{origin}/scheduled-promotions",
+                  web::get().to(list_scheduled_promotions))
+           .route("/depot/origins/{origin}/scheduled-promotions/{id}",
+                  web::get().to(get_scheduled_promotion))
+           .route("/depot/origins/{origin}/scheduled-promotions/{id}",
+                  web::delete().to(cancel_scheduled_promotion))
+           .route("/depot/channels/{origin}/{channel}/scheduled-promotions",
+                  web::post().to(schedule_promotion));
+    }
+}
+
+// Route handlers - these functions can return any Responder trait
+//
+#[allow(clippy::needless_pass_by_value)]
+fn list_scheduled_promotions(req: HttpRequest,
+                             path: Path<String>,
+                             state: Data<AppState>)
+                             -> HttpResponse {
+    let origin = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    match ScheduledPromotion::list(&origin, &*conn).map_err(Error::DieselError) {
+        Ok(promotions) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(promotions)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn get_scheduled_promotion(req: HttpRequest,
+                           path: Path<(String, String)>,
+                           state: Data<AppState>)
+                           -> HttpResponse {
+    let (origin, id_str) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    let id = match id_str.parse::<i64>() {
+        Ok(id) => id,
+        Err(e) => {
+            debug!("Error finding id. e = {:?}", e);
+            return HttpResponse::new(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let promotion = match ScheduledPromotion::get(id, &*conn) {
+        Ok(ref promotion) if promotion.origin != origin => {
+            return HttpResponse::new(StatusCode::NOT_FOUND)
+        }
+        Ok(promotion) => promotion,
+        Err(err) => return Error::DieselError(err).into(),
+    };
+
+    match ScheduledPromotionItem::list(id, &*conn).map_err(Error::DieselError) {
+        Ok(items) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(ScheduledPromotionDetail { promotion, items })
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn cancel_scheduled_promotion(req: HttpRequest,
+                              path: Path<(String, String)>,
+                              state: Data<AppState>)
+                              -> HttpResponse {
+    let (origin, id_str) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Maintainer)) {
+        return err.into();
+    }
+
+    let id = match id_str.parse::<i64>() {
+        Ok(id) => id,
+        Err(e) => {
+            debug!("Error finding id. e = {:?}", e);
+            return HttpResponse::new(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    match ScheduledPromotion::get(id, &*conn) {
+        Ok(ref promotion) if promotion.origin != origin => {
+            return HttpResponse::new(StatusCode::NOT_FOUND)
+        }
+        Ok(_) => (),
+        Err(err) => return Error::DieselError(err).into(),
+    }
+
+    // Once the scheduler has picked it up it can no longer be canceled
+    match ScheduledPromotion::cancel(id, &*conn).map_err(Error::DieselError) {
+        Ok(0) => HttpResponse::new(StatusCode::CONFLICT),
+        Ok(_) => HttpResponse::NoContent().into(),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+async fn schedule_promotion(req: HttpRequest,
+                            path: Path<(String, String)>,
+                            body: Json<ScheduledPromotionReq>,
+                            state: Data<AppState>)
+                            -> HttpResponse {
+    let (origin, channel) = path.into_inner();
+    let channel = ChannelIdent::from(channel);
+
+    match do_schedule_promotion(&req, &origin, &channel, &body, &state).await {
+        Ok(promotion) => HttpResponse::Created().json(promotion),
+        Err(err) => {
+            debug!("Failed to schedule promotion, err={}", err);
+            err.into()
+        }
+    }
+}
+
+// Internal - these functions should return Result<..>
+//
+async fn do_schedule_promotion(req: &HttpRequest,
+                               origin: &str,
+                               channel: &ChannelIdent,
+                               body: &ScheduledPromotionReq,
+                               state: &AppState)
+                               -> Result<ScheduledPromotion> {
+    let session = authorize_session(req, Some(origin), Some(OriginMemberRole::Maintainer))?;
+
+    // Accepting it would leave the promotion pending forever
+    if !state.config.scheduler.enabled {
+        return Err(Error::Unavailable("Scheduled promotions are disabled".to_string()));
+    }
+
+    // Nothing is ever promoted into unstable, uploads land there directly
+    if *channel == ChannelIdent::unstable() || body.execute_at < Utc::now() {
+        return Err(Error::Unprocessable);
+    }
+
+    let (idents, group_id, target) = match (body.idents.is_empty(), &body.group_id) {
+        (false, None) => {
+            for ident in body.idents.iter() {
+                let ident = PackageIdent::from_str(ident)?;
+                if !ident.fully_qualified() || ident.origin != origin {
+                    return Err(Error::Unprocessable);
+                }
+            }
+            let target = match body.target {
+                Some(ref t) => PackageTarget::from_str(t)?,
+                None => PackageTarget::from_str("x86_64-linux").unwrap(),
+            };
+            if !state.config.api.targets.contains(&target) {
+                return Err(Error::Unprocessable);
+            }
+            (body.idents.clone(), None, target.to_string())
+        }
+        (true, Some(group_id)) => {
+            let group_id = group_id.parse::<u64>().map_err(|_| Error::BadRequest)?;
+            let mut group_get = jobsrv::JobGroupGet::new();
+            group_get.set_group_id(group_id);
+            group_get.set_include_projects(false);
+            let group =
+                route_message::<jobsrv::JobGroupGet, jobsrv::JobGroup>(req, &group_get).await?;
+            (Vec::new(), Some(group_id as i64), group.get_target().to_string())
+        }
+        _ => return Err(Error::Unprocessable),
+    };
+
+    let conn = state.db.get_conn().map_err(Error::DbError)?;
+    let new_promotion = NewScheduledPromotion { origin,
+                                                channel: channel.as_str(),
+                                                target: &target,
+                                                idents,
+                                                group_id,
+                                                execute_at: body.execute_at,
+                                                requester_id: session.get_id() as i64,
+                                                requester_name: session.get_name() };
+    Ok(ScheduledPromotion::create(&new_promotion, &*conn)?)
+}
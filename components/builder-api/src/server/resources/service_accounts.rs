@@ -0,0 +1,351 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::str::FromStr;
+
+use actix_web::{http::{self,
+                       StatusCode},
+                web::{self,
+                      Bytes,
+                      Data,
+                      Json,
+                      Path,
+                      ServiceConfig},
+                HttpRequest,
+                HttpResponse};
+
+use crate::{bldr_core::privilege::FeatureFlags,
+            db::models::{account::*,
+                         origin::*},
+            hab_core::package::ident,
+            server::{authorize::authorize_session,
+                     error::{Error,
+                             Result},
+                     framework::headers,
+                     resources::profile::do_generate_access_token,
+                     AppState}};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServiceAccountReq {
+    #[serde(default)]
+    pub name: String,
+    pub role: String,
+}
+
+pub struct ServiceAccounts;
+
+impl ServiceAccounts {
+    // Route registration
+    //
+    pub fn register(cfg: &mut ServiceConfig) {
+        cfg.route("/depot/origins/{origin}/service-accounts",
+                  web::get().to(list_service_accounts))
+           .route("/depot/origins/{origin}/service-accounts",
+                  web::post().to(create_service_account))
+           .route("/depot/origins/{origin}/service-accounts/{name}",
+                  web::put().to(update_service_account))
+           .route("/depot/origins/{origin}/service-accounts/{name}",
+                  web::delete().to(delete_service_account))
+           .route("/depot/origins/{origin}/service-accounts/{name}/tokens",
+                  web::get().to(list_service_account_tokens))
+           .route("/depot/origins/{origin}/service-accounts/{name}/tokens",
+                  web::post().to(generate_service_account_token))
+           .route("/depot/origins/{origin}/service-accounts/{name}/tokens/{id}",
+                  web::delete().to(revoke_service_account_token));
+    }
+}
+
+// Route handlers - these functions can return any Responder trait
+//
+#[allow(clippy::needless_pass_by_value)]
+fn list_service_accounts(req: HttpRequest,
+                         path: Path<String>,
+                         state: Data<AppState>)
+                         -> HttpResponse {
+    let origin = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    match ServiceAccount::list(&origin, &*conn).map_err(Error::DieselError) {
+        Ok(accounts) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(accounts)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn create_service_account(req: HttpRequest,
+                          path: Path<String>,
+                          body: Json<ServiceAccountReq>,
+                          state: Data<AppState>)
+                          -> HttpResponse {
+    let origin = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Owner)) {
+        return err.into();
+    }
+
+    if !ident::is_valid_origin_name(&body.name) {
+        return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let role = match service_account_role(&body.role) {
+        Ok(role) => role,
+        Err(err) => return err.into(),
+    };
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    match ServiceAccount::create(&origin, &body.name, role, &*conn).map_err(Error::DieselError) {
+        Ok(account) => {
+            HttpResponse::Created().json(ServiceAccount { id:          account.id,
+                                                          name:        account.name,
+                                                          origin:      origin.clone(),
+                                                          member_role: role,
+                                                          created_at:  account.created_at, })
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn update_service_account(req: HttpRequest,
+                          path: Path<(String, String)>,
+                          body: Json<ServiceAccountReq>,
+                          state: Data<AppState>)
+                          -> HttpResponse {
+    let (origin, name) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Owner)) {
+        return err.into();
+    }
+
+    let role = match service_account_role(&body.role) {
+        Ok(role) => role,
+        Err(err) => return err.into(),
+    };
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let account = match ServiceAccount::get(&origin, &name, &*conn) {
+        Ok(account) => account,
+        Err(err) => return Error::DieselError(err).into(),
+    };
+
+    match OriginMember::update_member_role(&origin, account.id, &*conn, role) {
+        Ok(0) => HttpResponse::NotFound().into(),
+        Ok(_) => {
+            // Cleared once the new role is stored, so a concurrent lookup can not cache the old one
+            state.memcache
+                 .borrow_mut()
+                 .clear_cache_for_member_role(&origin, account.id as u64);
+            HttpResponse::NoContent().into()
+        }
+        Err(err) => {
+            debug!("{}", err);
+            Error::DieselError(err).into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn delete_service_account(req: HttpRequest,
+                          path: Path<(String, String)>,
+                          state: Data<AppState>)
+                          -> HttpResponse {
+    let (origin, name) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Owner)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let account = match ServiceAccount::get(&origin, &name, &*conn) {
+        Ok(account) => account,
+        Err(err) => return Error::DieselError(err).into(),
+    };
+
+    let tokens = match AccountToken::list(account.id as u64, &*conn) {
+        Ok(tokens) => tokens,
+        Err(err) => return Error::DieselError(err).into(),
+    };
+
+    match ServiceAccount::delete(account.id, &*conn).map_err(Error::DieselError) {
+        Ok(_) => {
+            let mut memcache = state.memcache.borrow_mut();
+            memcache.clear_cache_for_member_role(&origin, account.id as u64);
+            for token in tokens {
+                memcache.delete_session_key(&token.token)
+            }
+            HttpResponse::NoContent().into()
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn list_service_account_tokens(req: HttpRequest,
+                               path: Path<(String, String)>,
+                               state: Data<AppState>)
+                               -> HttpResponse {
+    let (origin, name) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Owner)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let tokens =
+        ServiceAccount::get(&origin, &name, &*conn).and_then(|account| {
+                                                       AccountToken::list(account.id as u64, &*conn)
+                                                   });
+
+    match tokens.map_err(Error::DieselError) {
+        Ok(tokens) => {
+            let json = json!({
+                "tokens": serde_json::to_value(tokens).unwrap()
+            });
+
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(json)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn generate_service_account_token(req: HttpRequest,
+                                  path: Path<(String, String)>,
+                                  body: Bytes,
+                                  state: Data<AppState>)
+                                  -> HttpResponse {
+    let (origin, name) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Owner)) {
+        return err.into();
+    }
+
+    let account = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn) => {
+            match ServiceAccount::get(&origin, &name, &*conn) {
+                Ok(account) => account,
+                Err(err) => return Error::DieselError(err).into(),
+            }
+        }
+        Err(err) => return err.into(),
+    };
+
+    match do_generate_access_token(&state,
+                                   account.id as u64,
+                                   FeatureFlags::empty().bits(),
+                                   &body)
+    {
+        Ok(account_token) => HttpResponse::Ok().json(account_token),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn revoke_service_account_token(req: HttpRequest,
+                                path: Path<(String, String, String)>,
+                                state: Data<AppState>)
+                                -> HttpResponse {
+    let (origin, name, token_id_str) = path.into_inner();
+    let token_id = match token_id_str.parse::<u64>() {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY),
+    };
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Owner)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let account = match ServiceAccount::get(&origin, &name, &*conn) {
+        Ok(account) => account,
+        Err(err) => return Error::DieselError(err).into(),
+    };
+
+    let tokens = match AccountToken::list(account.id as u64, &*conn) {
+        Ok(tokens) => tokens,
+        Err(err) => return Error::DieselError(err).into(),
+    };
+
+    match AccountToken::delete(token_id, account.id as u64, &*conn).map_err(Error::DieselError) {
+        Ok(0) => HttpResponse::new(StatusCode::NOT_FOUND),
+        Ok(_) => {
+            let mut memcache = state.memcache.borrow_mut();
+            for token in tokens.iter().filter(|t| t.id == token_id as i64) {
+                memcache.delete_session_key(&token.token)
+            }
+            HttpResponse::NoContent().into()
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+// Internal - these functions should return Result<..>
+//
+
+// Service accounts can hold any role but owner, which only comes with an origin transfer
+fn service_account_role(role: &str) -> Result<OriginMemberRole> {
+    match OriginMemberRole::from_str(role) {
+        Ok(OriginMemberRole::Owner) | Err(_) => Err(Error::Unprocessable),
+        Ok(role) => Ok(role),
+    }
+}
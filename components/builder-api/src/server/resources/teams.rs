@@ -0,0 +1,326 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::str::FromStr;
+
+use actix_web::{http::{self,
+                       StatusCode},
+                web::{self,
+                      Data,
+                      Json,
+                      Path,
+                      ServiceConfig},
+                HttpRequest,
+                HttpResponse};
+
+use crate::{db::models::{account::Account,
+                         origin::*,
+                         team::*},
+            hab_core::package::ident,
+            server::{authorize::authorize_session,
+                     error::{Error,
+                             Result},
+                     framework::headers,
+                     AppState}};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TeamReq {
+    #[serde(default)]
+    pub name: String,
+    pub role: String,
+}
+
+#[derive(Serialize)]
+struct TeamDetail {
+    #[serde(flatten)]
+    team:    Team,
+    members: Vec<String>,
+}
+
+pub struct Teams;
+
+impl Teams {
+    // Route registration
+    //
+    pub fn register(cfg: &mut ServiceConfig) {
+        cfg.route("/depot/origins/{origin}/teams", web::get().to(list_teams))
+           .route("/depot/origins/{origin}/teams", web::post().to(create_team))
+           .route("/depot/origins/{origin}/teams/{team}",
+                  web::get().to(get_team))
+           .route("/depot/origins/{origin}/teams/{team}",
+                  web::put().to(update_team))
+           .route("/depot/origins/{origin}/teams/{team}",
+                  web::delete().to(delete_team))
+           .route("/depot/origins/{origin}/teams/{team}/members/{username}",
+                  web::put().to(add_team_member))
+           .route("/depot/origins/{origin}/teams/{team}/members/{username}",
+                  web::delete().to(remove_team_member));
+    }
+}
+
+// Route handlers - these functions can return any Responder trait
+//
+#[allow(clippy::needless_pass_by_value)]
+fn list_teams(req: HttpRequest, path: Path<String>, state: Data<AppState>) -> HttpResponse {
+    let origin = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    match Team::list(&origin, &*conn).map_err(Error::DieselError) {
+        Ok(teams) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(teams)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn get_team(req: HttpRequest, path: Path<(String, String)>, state: Data<AppState>) -> HttpResponse {
+    let (origin, name) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Member)) {
+        return err.into();
+    }
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let detail = Team::get(&origin, &name, &*conn).and_then(|team| {
+                                                      let members =
+                                                          Team::member_names(team.id, &*conn)?;
+                                                      Ok(TeamDetail { team, members })
+                                                  });
+
+    match detail.map_err(Error::DieselError) {
+        Ok(detail) => {
+            HttpResponse::Ok().header(http::header::CACHE_CONTROL, headers::NO_CACHE)
+                              .json(detail)
+        }
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn create_team(req: HttpRequest,
+               path: Path<String>,
+               body: Json<TeamReq>,
+               state: Data<AppState>)
+               -> HttpResponse {
+    let origin = path.into_inner();
+
+    let session =
+        match authorize_session(&req, Some(&origin), Some(OriginMemberRole::Administrator)) {
+            Ok(session) => session,
+            Err(err) => return err.into(),
+        };
+
+    if !ident::is_valid_origin_name(&body.name) {
+        return HttpResponse::new(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let role = match team_role(&body.role) {
+        Ok(role) => role,
+        Err(err) => return err.into(),
+    };
+
+    let conn = match state.db.get_conn().map_err(Error::DbError) {
+        Ok(conn_ref) => conn_ref,
+        Err(err) => return err.into(),
+    };
+
+    let new_team = NewTeam { origin:      &origin,
+                             name:        &body.name,
+                             member_role: role,
+                             owner_id:    session.get_id() as i64, };
+
+    match Team::create(&new_team, &*conn).map_err(Error::DieselError) {
+        Ok(team) => HttpResponse::Created().json(team),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn update_team(req: HttpRequest,
+               path: Path<(String, String)>,
+               body: Json<TeamReq>,
+               state: Data<AppState>)
+               -> HttpResponse {
+    let (origin, name) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Administrator))
+    {
+        return err.into();
+    }
+
+    let role = match team_role(&body.role) {
+        Ok(role) => role,
+        Err(err) => return err.into(),
+    };
+
+    match do_update_team(&origin, &name, role, &state) {
+        Ok(()) => HttpResponse::NoContent().into(),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn delete_team(req: HttpRequest,
+               path: Path<(String, String)>,
+               state: Data<AppState>)
+               -> HttpResponse {
+    let (origin, name) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Administrator))
+    {
+        return err.into();
+    }
+
+    match do_delete_team(&origin, &name, &state) {
+        Ok(()) => HttpResponse::NoContent().into(),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn add_team_member(req: HttpRequest,
+                   path: Path<(String, String, String)>,
+                   state: Data<AppState>)
+                   -> HttpResponse {
+    let (origin, name, username) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Administrator))
+    {
+        return err.into();
+    }
+
+    match do_change_team_member(&origin, &name, &username, true, &state) {
+        Ok(()) => HttpResponse::NoContent().into(),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn remove_team_member(req: HttpRequest,
+                      path: Path<(String, String, String)>,
+                      state: Data<AppState>)
+                      -> HttpResponse {
+    let (origin, name, username) = path.into_inner();
+
+    if let Err(err) = authorize_session(&req, Some(&origin), Some(OriginMemberRole::Administrator))
+    {
+        return err.into();
+    }
+
+    match do_change_team_member(&origin, &name, &username, false, &state) {
+        Ok(()) => HttpResponse::NoContent().into(),
+        Err(err) => {
+            debug!("{}", err);
+            err.into()
+        }
+    }
+}
+
+// Internal - these functions should return Result<..>
+//
+
+// Teams can carry any role but owner, which only comes with an origin transfer
+fn team_role(role: &str) -> Result<OriginMemberRole> {
+    match OriginMemberRole::from_str(role) {
+        Ok(OriginMemberRole::Owner) | Err(_) => Err(Error::Unprocessable),
+        Ok(role) => Ok(role),
+    }
+}
+
+// The cached roles of the accounts are dropped after every change to a team, as their
+// effective roles may have changed with it
+fn clear_member_roles(state: &AppState, origin: &str, account_ids: &[i64]) {
+    let mut memcache = state.memcache.borrow_mut();
+    for account_id in account_ids {
+        memcache.clear_cache_for_member_role(origin, *account_id as u64);
+    }
+}
+
+fn do_update_team(origin: &str,
+                  name: &str,
+                  role: OriginMemberRole,
+                  state: &AppState)
+                  -> Result<()> {
+    let conn = state.db.get_conn().map_err(Error::DbError)?;
+    let team = Team::get(origin, name, &*conn)?;
+
+    Team::update_role(team.id, role, &*conn)?;
+    clear_member_roles(state, origin, &Team::member_ids(team.id, &*conn)?);
+    Ok(())
+}
+
+fn do_delete_team(origin: &str, name: &str, state: &AppState) -> Result<()> {
+    let conn = state.db.get_conn().map_err(Error::DbError)?;
+    let team = Team::get(origin, name, &*conn)?;
+    let member_ids = Team::member_ids(team.id, &*conn)?;
+
+    Team::delete(team.id, &*conn)?;
+    clear_member_roles(state, origin, &member_ids);
+    Ok(())
+}
+
+fn do_change_team_member(origin: &str,
+                         name: &str,
+                         username: &str,
+                         add: bool,
+                         state: &AppState)
+                         -> Result<()> {
+    let conn = state.db.get_conn().map_err(Error::DbError)?;
+    let team = Team::get(origin, name, &*conn)?;
+    let account = Account::get(username, &*conn)?;
+
+    if add {
+        // Teams group the members of the origin, they do not make anyone a member
+        if !Origin::check_membership(origin, account.id, &*conn)? {
+            return Err(Error::Unprocessable);
+        }
+        Team::add_member(team.id, account.id, &*conn)?;
+    } else if Team::remove_member(team.id, account.id, &*conn)? == 0 {
+        return Err(Error::NotFound);
+    }
+
+    clear_member_roles(state, origin, &[account.id]);
+    Ok(())
+}
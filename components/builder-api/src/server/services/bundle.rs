@@ -0,0 +1,376 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Offline channel bundles.
+//!
+//! A bundle is a gzipped tarball holding every hart of an origin's channel
+//! for a single target, optionally along with the runtime dependencies of
+//! those packages from any origin, plus the public origin signing keys needed
+//! to verify them. An `index.json` at the end of the tarball records which
+//! of the packages are members of the channel.
+//!
+//! Harts are fetched from the package storage one at a time and appended to
+//! the tarball, so exports need no more scratch space than the largest hart.
+//! On import each hart is checked against the index and verified with the
+//! bundled keys before anything is changed.
+
+use std::{collections::{BTreeSet,
+                        HashSet},
+          fs::{self,
+               File},
+          path::{Component,
+                 Path,
+                 PathBuf}};
+
+use chrono::{NaiveDateTime,
+             Utc};
+use diesel::pg::PgConnection;
+use flate2::{read::GzDecoder,
+             write::GzEncoder,
+             Compression};
+use tar::EntryType;
+use tempfile::tempdir_in;
+
+use super::storage::{self,
+                     PackageStorage};
+use crate::{config::Config,
+            db::{models::{channel::{Channel,
+                                    ListAllChannelPackages},
+                          keys::OriginPublicSigningKey,
+                          package::{BuilderPackageIdent,
+                                    GetPackageGroup,
+                                    Package}},
+                 DbPool},
+            hab_core::{crypto::{artifact,
+                                keys::parse_name_with_rev},
+                       package::{PackageArchive,
+                                 PackageIdent,
+                                 PackageTarget},
+                       ChannelIdent},
+            server::{error::{Error,
+                             Result},
+                     helpers}};
+
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+pub const BUNDLE_INDEX: &str = "index.json";
+pub const BUNDLE_ARTIFACTS_DIR: &str = "artifacts";
+pub const BUNDLE_KEYS_DIR: &str = "keys";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleIndex {
+    pub format_version: u32,
+    pub origin:         String,
+    pub channel:        String,
+    pub target:         String,
+    // Whether the runtime dependencies of the channel members were included
+    pub closure:        bool,
+    pub created_at:     NaiveDateTime,
+    pub packages:       Vec<BundlePackage>,
+    pub keys:           Vec<BundleKey>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundlePackage {
+    pub ident:      String,
+    pub checksum:   String,
+    // Path of the hart within the bundle
+    pub file:       String,
+    // False for dependencies brought along in closure mode
+    pub in_channel: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleKey {
+    // Signer name with revision, eg. core-20200101000000
+    pub name: String,
+    pub file: String,
+}
+
+pub struct BundleExport<'a> {
+    pub origin:  &'a str,
+    pub channel: &'a ChannelIdent,
+    pub target:  PackageTarget,
+    pub closure: bool,
+    pub output:  &'a Path,
+}
+
+// The runtime dependencies of the members which are not members themselves, in the order
+// they are first depended on
+fn outside_deps(members: &[(&BuilderPackageIdent, &[BuilderPackageIdent])])
+                -> Vec<BuilderPackageIdent> {
+    let mut seen: HashSet<String> = members.iter().map(|(i, _)| i.to_string()).collect();
+    let mut deps = Vec::new();
+
+    for (_, tdeps) in members {
+        for dep in tdeps.iter() {
+            if seen.insert(dep.to_string()) {
+                deps.push(dep.clone());
+            }
+        }
+    }
+
+    deps
+}
+
+// The packages of the bundle, each with whether it is a member of the channel
+fn bundle_packages(req: &BundleExport, conn: &PgConnection) -> Result<Vec<(Package, bool)>> {
+    let visibility = helpers::all_visibilities();
+    let target = req.target.to_string();
+
+    let idents = Channel::list_all_packages(&ListAllChannelPackages { visibility: &visibility,
+                                                                      channel:    req.channel,
+                                                                      origin:     req.origin, },
+                                            conn).map_err(Error::DieselError)?;
+    if idents.is_empty() {
+        return Err(Error::NotFound);
+    }
+
+    let members: Vec<Package> =
+        Package::get_group(GetPackageGroup { pkgs:       idents,
+                                             visibility: visibility.clone(), },
+                           conn).map_err(Error::DieselError)?
+                                .into_iter()
+                                .filter(|p| p.target.to_string() == target)
+                                .collect();
+
+    let deps = if req.closure {
+        let by_member: Vec<(&BuilderPackageIdent, &[BuilderPackageIdent])> =
+            members.iter()
+                   .map(|p| (&p.ident, p.tdeps.as_slice()))
+                   .collect();
+        let wanted = outside_deps(&by_member);
+
+        let found: Vec<Package> =
+            Package::get_group(GetPackageGroup { pkgs: wanted.clone(),
+                                                 visibility },
+                               conn).map_err(Error::DieselError)?
+                                    .into_iter()
+                                    .filter(|p| p.target.to_string() == target)
+                                    .collect();
+
+        let found_idents: HashSet<String> = found.iter().map(|p| p.ident.to_string()).collect();
+        let missing: Vec<String> = wanted.iter()
+                                         .map(ToString::to_string)
+                                         .filter(|i| !found_idents.contains(i))
+                                         .collect();
+        if !missing.is_empty() {
+            return Err(Error::Bundle(format!("runtime dependencies not found \
+                                              for {}: {}",
+                                             target,
+                                             missing.join(", "))));
+        }
+        found
+    } else {
+        Vec::new()
+    };
+
+    Ok(members.into_iter()
+              .map(|p| (p, true))
+              .chain(deps.into_iter().map(|p| (p, false)))
+              .collect())
+}
+
+fn append_bytes<W>(tar: &mut tar::Builder<W>, path: &str, data: &[u8]) -> Result<()>
+    where W: std::io::Write
+{
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp() as u64);
+    header.set_cksum();
+    tar.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+// Writes the bundle to the output path, returning its index
+pub async fn export(config: &Config, req: &BundleExport<'_>) -> Result<BundleIndex> {
+    let db = DbPool::new(&config.datastore);
+    let conn = db.get_conn().map_err(Error::DbError)?;
+    let packages = bundle_packages(req, &*conn)?;
+    let storage: Box<dyn PackageStorage> = storage::new(config)?;
+
+    let mut tar =
+        tar::Builder::new(GzEncoder::new(File::create(req.output)?, Compression::default()));
+    let scratch = tempdir_in(&config.api.data_path)?;
+    let mut signers = BTreeSet::new();
+    let mut entries = Vec::new();
+
+    for (package, in_channel) in packages {
+        let file_name = package.ident.archive_name_with_target(req.target)?;
+        let hart_path = scratch.path().join(&file_name);
+
+        info!("Bundling {} ({})", *package.ident, req.target);
+        storage.download(&hart_path, &package.ident, req.target)
+               .await?;
+
+        let header = artifact::get_artifact_header(&hart_path)?;
+        signers.insert(header.key_name);
+
+        let file = format!("{}/{}", BUNDLE_ARTIFACTS_DIR, file_name);
+        tar.append_path_with_name(&hart_path, &file)?;
+        fs::remove_file(&hart_path)?;
+
+        entries.push(BundlePackage { ident: package.ident.to_string(),
+                                     checksum: package.checksum,
+                                     file,
+                                     in_channel });
+    }
+
+    let mut keys = Vec::new();
+    for signer in signers {
+        let (name, rev) = parse_name_with_rev(&signer)?;
+        let key = OriginPublicSigningKey::get(&name, &rev, &*conn).map_err(Error::DieselError)?;
+        let file = format!("{}/{}.pub", BUNDLE_KEYS_DIR, signer);
+        append_bytes(&mut tar, &file, &key.body)?;
+        keys.push(BundleKey { name: signer, file });
+    }
+
+    let index = BundleIndex { format_version: BUNDLE_FORMAT_VERSION,
+                              origin: req.origin.to_string(),
+                              channel: req.channel.to_string(),
+                              target: req.target.to_string(),
+                              closure: req.closure,
+                              created_at: Utc::now().naive_utc(),
+                              packages: entries,
+                              keys };
+    append_bytes(&mut tar, BUNDLE_INDEX, &serde_json::to_vec_pretty(&index)?)?;
+    tar.into_inner()?.finish()?;
+
+    Ok(index)
+}
+
+// The path of a file named in the index, which has to stay within the bundle
+pub fn bundle_path(dir: &Path, file: &str) -> Result<PathBuf> {
+    let rel = Path::new(file);
+    if rel.components().next().is_some()
+       && rel.components().all(|c| matches!(c, Component::Normal(_)))
+    {
+        Ok(dir.join(rel))
+    } else {
+        Err(Error::Bundle(format!("invalid path {} in the index", file)))
+    }
+}
+
+// Unpacks the bundle into the directory, returning its index. Only plain files and
+// directories are accepted, and unpacking stops once the files add up to more than
+// `max_bytes`.
+pub fn unpack(bundle: &Path, dst: &Path, max_bytes: u64) -> Result<BundleIndex> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(bundle)?));
+    fs::create_dir_all(dst)?;
+
+    let mut unpacked: u64 = 0;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        match entry.header().entry_type() {
+            EntryType::Regular | EntryType::Directory => (),
+            other => {
+                return Err(Error::Bundle(format!("unsupported {:?} entry in the \
+                                                  bundle",
+                                                 other)))
+            }
+        }
+
+        unpacked += entry.size();
+        if unpacked > max_bytes {
+            return Err(Error::Bundle(format!("bundle unpacks to more than {} \
+                                              bytes",
+                                             max_bytes)));
+        }
+        entry.unpack_in(dst)?;
+    }
+
+    let index = match fs::read(dst.join(BUNDLE_INDEX)) {
+        Ok(bytes) => serde_json::from_slice::<BundleIndex>(&bytes)?,
+        Err(_) => return Err(Error::Bundle(format!("{} is missing", BUNDLE_INDEX))),
+    };
+    if index.format_version != BUNDLE_FORMAT_VERSION {
+        return Err(Error::Bundle(format!("unsupported format version {}", index.format_version)));
+    }
+
+    Ok(index)
+}
+
+// Checks an unpacked hart against its index entry and verifies its signature with the
+// bundled keys. Returns the ident, runtime dependencies and path of the hart.
+pub fn verify_package(dir: &Path,
+                      target: PackageTarget,
+                      package: &BundlePackage)
+                      -> Result<(PackageIdent, Vec<PackageIdent>, PathBuf)> {
+    let path = bundle_path(dir, &package.file)?;
+    let mut archive = PackageArchive::new(&path)?;
+
+    let ident = archive.ident()?;
+    if ident.to_string() != package.ident {
+        return Err(Error::Bundle(format!("{} holds {} rather than {}",
+                                         package.file, ident, package.ident)));
+    }
+    if archive.target()? != target {
+        return Err(Error::Bundle(format!("{} is not built for {}", ident, target)));
+    }
+    if archive.checksum()? != package.checksum {
+        return Err(Error::Bundle(format!("checksum of {} does not match the \
+                                          index",
+                                         ident)));
+    }
+
+    if let Err(err) = artifact::verify(&path, &dir.join(BUNDLE_KEYS_DIR)) {
+        return Err(Error::Bundle(format!("signature of {} failed \
+                                          verification: {}",
+                                         ident, err)));
+    }
+
+    let tdeps = archive.tdeps()?;
+    Ok((ident, tdeps, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hab_core::package::PackageIdent;
+    use std::str::FromStr;
+
+    fn ident(value: &str) -> BuilderPackageIdent {
+        BuilderPackageIdent(PackageIdent::from_str(value).unwrap())
+    }
+
+    #[test]
+    fn outside_deps_skips_members_and_duplicates() {
+        let nginx = ident("core/nginx/1.17.4/20191115184838");
+        let nginx_deps = vec![ident("core/glibc/2.29/20200305172459"),
+                              ident("core/openssl/1.0.2t/20200109000414")];
+        let openssl = ident("core/openssl/1.0.2t/20200109000414");
+        let openssl_deps = vec![ident("core/glibc/2.29/20200305172459"),
+                                ident("acme/zlib/1.2.11/20190115003728")];
+
+        let deps: Vec<String> =
+            outside_deps(&[(&nginx, nginx_deps.as_slice()),
+                           (&openssl, openssl_deps.as_slice())]).iter()
+                                                                .map(ToString::to_string)
+                                                                .collect();
+        assert_eq!(deps,
+                   vec!["core/glibc/2.29/20200305172459".to_string(),
+                        "acme/zlib/1.2.11/20190115003728".to_string(),]);
+    }
+
+    #[test]
+    fn bundle_path_stays_within_bundle() {
+        let dir = Path::new("/hab/svc/builder-api/data/import");
+        assert_eq!(bundle_path(dir, "artifacts/core-nginx.hart").unwrap(),
+                   dir.join("artifacts/core-nginx.hart"));
+        assert!(bundle_path(dir, "").is_err());
+        assert!(bundle_path(dir, "../core-nginx.hart").is_err());
+        assert!(bundle_path(dir, "artifacts/../../core-nginx.hart").is_err());
+        assert!(bundle_path(dir, "/etc/passwd").is_err());
+    }
+}
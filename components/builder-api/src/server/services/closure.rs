@@ -0,0 +1,278 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Dependency closure promotion.
+//!
+//! Promoting a package whose runtime dependencies are not in the channel leaves
+//! the channel unable to install it. In closure mode the missing dependencies
+//! are promoted along with it. A dependency can only be brought along when it
+//! exists for the same target and the caller is a maintainer of its origin;
+//! every one that can not is reported and nothing is promoted.
+
+use std::collections::{HashMap,
+                       HashSet};
+
+use actix_web::HttpRequest;
+use diesel::{pg::PgConnection,
+             result::Error::NotFound,
+             Connection};
+
+use crate::{db::models::{channel::{Channel,
+                                   CreateChannel,
+                                   PackageChannelAudit,
+                                   PackageChannelOperation,
+                                   PackageChannelTrigger,
+                                   PackageGroupChannelAudit},
+                         origin::OriginMemberRole,
+                         package::{BuilderPackageIdent,
+                                   BuilderPackageTarget,
+                                   GetPackage,
+                                   Package}},
+            hab_core::ChannelIdent,
+            server::{authorize::check_origin_member_role,
+                     error::{Error,
+                             Result},
+                     helpers,
+                     services::policy::Candidate}};
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnpromotableReason {
+    NotFound,
+    NotPermitted,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct UnpromotableDep {
+    pub ident:   BuilderPackageIdent,
+    pub target:  BuilderPackageTarget,
+    pub reason:  UnpromotableReason,
+    pub message: String,
+}
+
+pub struct ClosureRequester<'a> {
+    pub trigger: PackageChannelTrigger,
+    pub id:      i64,
+    pub name:    &'a str,
+    // Record the promotions against this job group rather than as single packages
+    pub group:   Option<i64>,
+}
+
+// The runtime deps of the candidates which are neither in the channel nor among the
+// candidates themselves, in the order they are first depended on. `in_channel` holds
+// idents along with their target.
+pub fn unmet(candidates: &[Candidate],
+             in_channel: &HashSet<(String, String)>)
+             -> Vec<(BuilderPackageIdent, BuilderPackageTarget)> {
+    let mut seen: HashSet<(String, String)> =
+        candidates.iter()
+                  .map(|c| (c.ident.to_string(), c.target.to_string()))
+                  .collect();
+    let mut unmet = Vec::new();
+
+    for c in candidates {
+        for dep in c.tdeps.iter() {
+            let key = (dep.to_string(), c.target.to_string());
+            if in_channel.contains(&key) || !seen.insert(key) {
+                continue;
+            }
+            unmet.push((dep.clone(), c.target.clone()));
+        }
+    }
+
+    unmet
+}
+
+// Looks up the runtime deps of the packages that are missing from the channel. Fails with
+// every dep which can not be promoted.
+pub fn missing_deps(req: &HttpRequest,
+                    account_id: u64,
+                    channel: &ChannelIdent,
+                    packages: &[Package],
+                    conn: &PgConnection)
+                    -> Result<Vec<Package>> {
+    let candidates: Vec<Candidate> = packages.iter().map(Candidate::from).collect();
+
+    let mut by_target: HashMap<String, Vec<String>> = HashMap::new();
+    for c in candidates.iter() {
+        by_target.entry(c.target.to_string())
+                 .or_insert_with(Vec::new)
+                 .extend(c.tdeps.iter().map(ToString::to_string));
+    }
+
+    let mut in_channel = HashSet::new();
+    for (target, idents) in by_target.iter_mut() {
+        idents.sort();
+        idents.dedup();
+        let members = Channel::filter_members(channel, idents, target, conn)?;
+        in_channel.extend(members.into_iter().map(|i| (i.to_string(), target.clone())));
+    }
+
+    let mut deps = Vec::new();
+    let mut errors = Vec::new();
+    let mut permitted: HashMap<String, bool> = HashMap::new();
+
+    for (ident, target) in unmet(&candidates, &in_channel) {
+        let origin = ident.origin.clone();
+        let can_promote = match permitted.get(&origin) {
+            Some(can_promote) => *can_promote,
+            None => {
+                let role = check_origin_member_role(req, &origin, account_id);
+                let can_promote = role.map_or(false, |r| r >= OriginMemberRole::Maintainer);
+                permitted.insert(origin.clone(), can_promote);
+                can_promote
+            }
+        };
+        if !can_promote {
+            errors.push(UnpromotableDep { ident,
+                                          target,
+                                          reason: UnpromotableReason::NotPermitted,
+                                          message: format!("Not a maintainer of the {} origin",
+                                                           origin) });
+            continue;
+        }
+
+        match Package::get(GetPackage { ident:      ident.clone(),
+                                        visibility: helpers::all_visibilities(),
+                                        target:     target.clone(), },
+                           conn)
+        {
+            Ok(package) => deps.push(package),
+            Err(NotFound) => {
+                let message = format!("Not found for {}", *target);
+                errors.push(UnpromotableDep { ident,
+                                              target,
+                                              reason: UnpromotableReason::NotFound,
+                                              message });
+            }
+            Err(err) => return Err(Error::DieselError(err)),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(deps)
+    } else {
+        debug!("Unable to promote the dependency closure into {}: {:?}",
+               channel, errors);
+        Err(Error::UnpromotableDeps(errors))
+    }
+}
+
+// Promotes the packages into the channel of each of their origins in one transaction,
+// creating the channel in an origin which does not have it yet.
+pub fn promote(channel: &ChannelIdent,
+               packages: &[Package],
+               requester: &ClosureRequester,
+               conn: &PgConnection)
+               -> Result<()> {
+    let mut by_origin: HashMap<&str, Vec<&Package>> = HashMap::new();
+    for package in packages {
+        by_origin.entry(package.origin.as_str())
+                 .or_insert_with(Vec::new)
+                 .push(package);
+    }
+
+    let operation = PackageChannelOperation::Promote;
+
+    conn.transaction::<_, Error, _>(|| {
+            for (origin, packages) in by_origin.iter() {
+                let origin_channel = match Channel::get(origin, channel, conn) {
+                    Ok(origin_channel) => origin_channel,
+                    Err(NotFound)
+                        if channel != &ChannelIdent::stable()
+                           && channel != &ChannelIdent::unstable() =>
+                    {
+                        Channel::create(&CreateChannel { name: channel.as_str(),
+                                                         origin,
+                                                         owner_id: requester.id },
+                                        conn)?
+                    }
+                    Err(err) => return Err(Error::DieselError(err)),
+                };
+
+                let ids: Vec<i64> = packages.iter().map(|p| p.id).collect();
+                Channel::promote_packages(origin_channel.id, &ids, conn)?;
+
+                match requester.group {
+                    Some(group_id) => {
+                        let trigger = requester.trigger.clone();
+                        let audit = PackageGroupChannelAudit { origin,
+                                                               channel: channel.as_str(),
+                                                               package_ids: ids,
+                                                               operation,
+                                                               trigger,
+                                                               requester_id: requester.id,
+                                                               requester_name: requester.name,
+                                                               group_id };
+                        PackageGroupChannelAudit::audit(audit, conn)?;
+                    }
+                    None => {
+                        for package in packages.iter() {
+                            let audit =
+                                PackageChannelAudit { package_ident: package.ident.clone(),
+                                                      channel: channel.as_str(),
+                                                      operation,
+                                                      trigger: requester.trigger.clone(),
+                                                      requester_id: requester.id,
+                                                      requester_name: requester.name,
+                                                      origin,
+                                                      target: Some(package.target.clone()) };
+                            PackageChannelAudit::audit(&audit, conn)?;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hab_core::package::{PackageIdent,
+                                   PackageTarget};
+    use std::str::FromStr;
+
+    fn ident(value: &str) -> BuilderPackageIdent {
+        BuilderPackageIdent(PackageIdent::from_str(value).unwrap())
+    }
+
+    fn candidate(value: &str, tdeps: &[&str]) -> Candidate {
+        Candidate { id:     1,
+                    ident:  ident(value),
+                    target: BuilderPackageTarget(PackageTarget::from_str("x86_64-linux").unwrap()),
+                    tdeps:  tdeps.iter().map(|d| ident(d)).collect(), }
+    }
+
+    #[test]
+    fn unmet_skips_channel_members_and_candidates() {
+        let candidates = vec![candidate("core/nginx/1.17.4/20191115184838",
+                                        &["core/glibc/2.29/20200305172459",
+                                          "core/openssl/1.0.2t/20200109000414",
+                                          "core/pcre/8.42/20190115012526"]),
+                              candidate("core/openssl/1.0.2t/20200109000414",
+                                        &["core/glibc/2.29/20200305172459",
+                                          "core/zlib/1.2.11/20190115003728"]),];
+        let mut in_channel = HashSet::new();
+        in_channel.insert(("core/glibc/2.29/20200305172459".to_string(),
+                           "x86_64-linux".to_string()));
+
+        let unmet: Vec<String> = unmet(&candidates, &in_channel).into_iter()
+                                                                .map(|(i, _)| i.to_string())
+                                                                .collect();
+        assert_eq!(unmet,
+                   vec!["core/pcre/8.42/20190115012526".to_string(),
+                        "core/zlib/1.2.11/20190115003728".to_string(),]);
+    }
+}
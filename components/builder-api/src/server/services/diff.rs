@@ -0,0 +1,301 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Structured diffs between two releases of a package, and between the
+//! latest packages of two channels.
+//!
+//! Everything is computed from the metadata stored alongside each release, so
+//! the archives themselves are never fetched. Dependencies are matched up by
+//! origin and name, so a dependency present in both releases with a different
+//! version or release shows up as changed rather than as a removal and an
+//! addition.
+
+use std::{cmp::Ordering,
+          collections::{BTreeMap,
+                        BTreeSet}};
+
+use crate::{db::models::{channel::ChannelDiffRow,
+                         package::{BuilderPackageIdent,
+                                   BuilderPackageTarget,
+                                   Package}},
+            hab_core::package::{Identifiable,
+                                PackageIdent}};
+
+#[derive(Debug, Serialize)]
+pub struct PackageDiff {
+    pub from:       BuilderPackageIdent,
+    pub to:         BuilderPackageIdent,
+    pub target:     BuilderPackageTarget,
+    pub deps:       DepsDiff,
+    pub tdeps:      DepsDiff,
+    pub build_deps: DepsDiff,
+    pub exposes:    ExposesDiff,
+    pub config:     Vec<String>,
+    pub manifest:   Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct DepsDiff {
+    pub added:   Vec<PackageIdent>,
+    pub removed: Vec<PackageIdent>,
+    pub changed: Vec<DepChange>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct DepChange {
+    pub from: PackageIdent,
+    pub to:   PackageIdent,
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct ExposesDiff {
+    pub added:   Vec<i32>,
+    pub removed: Vec<i32>,
+}
+
+pub fn diff_packages(from: &Package, to: &Package) -> PackageDiff {
+    PackageDiff { from:       from.ident.clone(),
+                  to:         to.ident.clone(),
+                  target:     to.target.clone(),
+                  deps:       diff_deps(&from.deps, &to.deps),
+                  tdeps:      diff_deps(&from.tdeps, &to.tdeps),
+                  build_deps: diff_deps(&from.build_deps, &to.build_deps),
+                  exposes:    diff_exposes(&from.exposes, &to.exposes),
+                  config:     diff_lines(&from.config, &to.config),
+                  manifest:   diff_lines(&from.manifest, &to.manifest), }
+}
+
+pub fn diff_deps(from: &[BuilderPackageIdent], to: &[BuilderPackageIdent]) -> DepsDiff {
+    let by_name = |deps: &[BuilderPackageIdent]| {
+        deps.iter()
+            .map(|d| (format!("{}/{}", d.origin(), d.name()), d.0.clone()))
+            .collect::<BTreeMap<String, PackageIdent>>()
+    };
+    let from = by_name(from);
+    let mut to = by_name(to);
+
+    let mut diff = DepsDiff::default();
+    for (name, old) in from {
+        match to.remove(&name) {
+            Some(new) => {
+                if old != new {
+                    diff.changed.push(DepChange { from: old,
+                                                  to:   new, });
+                }
+            }
+            None => diff.removed.push(old),
+        }
+    }
+    diff.added = to.into_iter().map(|(_, new)| new).collect();
+    diff
+}
+
+pub fn diff_exposes(from: &[i32], to: &[i32]) -> ExposesDiff {
+    let from: BTreeSet<i32> = from.iter().cloned().collect();
+    let to: BTreeSet<i32> = to.iter().cloned().collect();
+
+    ExposesDiff { added:   to.difference(&from).cloned().collect(),
+                  removed: from.difference(&to).cloned().collect(), }
+}
+
+// Changed regions which would need a larger comparison table than this are reported
+// as removed and added wholesale, to bound the memory used by large plan files
+const MAX_DIFF_CELLS: usize = 1 << 22;
+
+// Line based diff of two texts, returning only the changed lines prefixed with
+// `-` for removals and `+` for additions, in the order they occur
+pub fn diff_lines(from: &str, to: &str) -> Vec<String> {
+    let a: Vec<&str> = from.lines().collect();
+    let b: Vec<&str> = to.lines().collect();
+
+    // Lines shared at the start and the end never show up in the diff
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let suffix = a.iter()
+                  .rev()
+                  .zip(b.iter().rev())
+                  .take_while(|(x, y)| x == y)
+                  .count();
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+
+    let mut result = Vec::new();
+    if (a.len() + 1).saturating_mul(b.len() + 1) > MAX_DIFF_CELLS {
+        result.extend(a.iter().map(|l| format!("-{}", l)));
+        result.extend(b.iter().map(|l| format!("+{}", l)));
+        return result;
+    }
+
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            result.push(format!("-{}", a[i]));
+            i += 1;
+        } else {
+            result.push(format!("+{}", b[j]));
+            j += 1;
+        }
+    }
+    result.extend(a[i..].iter().map(|l| format!("-{}", l)));
+    result.extend(b[j..].iter().map(|l| format!("+{}", l)));
+    result
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChannelDiffStatus {
+    OnlyInA,
+    OnlyInB,
+    NewerInA,
+    NewerInB,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelDiffEntry {
+    pub name:   String,
+    pub target: String,
+    pub a:      Option<BuilderPackageIdent>,
+    pub b:      Option<BuilderPackageIdent>,
+    pub status: ChannelDiffStatus,
+}
+
+impl From<ChannelDiffRow> for ChannelDiffEntry {
+    fn from(row: ChannelDiffRow) -> ChannelDiffEntry {
+        let status = channel_diff_status(row.a.as_ref(), row.b.as_ref());
+        ChannelDiffEntry { name: row.name,
+                           target: row.target,
+                           a: row.a,
+                           b: row.b,
+                           status }
+    }
+}
+
+// Where the latest release of a name differs between two channels. Versions which can not
+// be compared are told apart by their release.
+pub fn channel_diff_status(a: Option<&BuilderPackageIdent>,
+                           b: Option<&BuilderPackageIdent>)
+                           -> ChannelDiffStatus {
+    match (a, b) {
+        (Some(x), Some(y)) => {
+            match x.0.partial_cmp(&y.0) {
+                Some(Ordering::Greater) => ChannelDiffStatus::NewerInA,
+                Some(Ordering::Less) => ChannelDiffStatus::NewerInB,
+                _ if x.0.release < y.0.release => ChannelDiffStatus::NewerInB,
+                _ => ChannelDiffStatus::NewerInA,
+            }
+        }
+        (Some(_), None) => ChannelDiffStatus::OnlyInA,
+        _ => ChannelDiffStatus::OnlyInB,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn idents(values: &[&str]) -> Vec<BuilderPackageIdent> {
+        values.iter()
+              .map(|v| BuilderPackageIdent(PackageIdent::from_str(v).unwrap()))
+              .collect()
+    }
+
+    #[test]
+    fn diff_deps_matches_by_origin_and_name() {
+        let from = idents(&["core/glibc/2.27/20190115002733",
+                            "core/openssl/1.0.2/20190115012345",
+                            "core/zlib/1.2.11/20190115003728"]);
+        let to = idents(&["core/glibc/2.27/20190115002733",
+                          "core/openssl/1.0.2/20200101000000",
+                          "core/pcre/8.42/20190115012526"]);
+
+        let diff = diff_deps(&from, &to);
+        let ident = |v: &str| PackageIdent::from_str(v).unwrap();
+        assert_eq!(diff.added, vec![ident("core/pcre/8.42/20190115012526")]);
+        assert_eq!(diff.removed, vec![ident("core/zlib/1.2.11/20190115003728")]);
+        assert_eq!(diff.changed,
+                   vec![DepChange { from: ident("core/openssl/1.0.2/20190115012345"),
+                                    to:   ident("core/openssl/1.0.2/20200101000000"), }]);
+    }
+
+    #[test]
+    fn diff_exposes_sets() {
+        let diff = diff_exposes(&[80, 443], &[443, 8080]);
+        assert_eq!(diff.added, vec![8080]);
+        assert_eq!(diff.removed, vec![80]);
+    }
+
+    #[test]
+    fn diff_lines_changed_only() {
+        assert!(diff_lines("a\nb\n", "a\nb\n").is_empty());
+        assert_eq!(diff_lines("port = 80\nhost = \"0.0.0.0\"\n",
+                              "port = 8080\nhost = \"0.0.0.0\"\nworkers = 4\n"),
+                   vec!["-port = 80", "+port = 8080", "+workers = 4"]);
+        assert_eq!(diff_lines("", "a"), vec!["+a"]);
+        assert_eq!(diff_lines("a", ""), vec!["-a"]);
+    }
+
+    #[test]
+    fn diff_lines_large_inputs() {
+        let lines = |prefix: &str, n: usize| {
+            (0..n).map(|i| format!("{} {}", prefix, i))
+                  .collect::<Vec<String>>()
+                  .join("\n")
+        };
+
+        // A single change in a large file is still pinpointed
+        let from = format!("{}\nold\n{}", lines("a", 5000), lines("b", 5000));
+        let to = format!("{}\nnew\n{}", lines("a", 5000), lines("b", 5000));
+        assert_eq!(diff_lines(&from, &to), vec!["-old", "+new"]);
+
+        // Too large a changed region is reported as replaced wholesale
+        let diff = diff_lines(&lines("a", 3000), &lines("b", 3000));
+        assert_eq!(diff.len(), 6000);
+        assert_eq!(diff[0], "-a 0");
+        assert_eq!(diff[2999], "-a 2999");
+        assert_eq!(diff[3000], "+b 0");
+    }
+
+    #[test]
+    fn channel_diff_status_compares_releases() {
+        let ident = |v: &str| BuilderPackageIdent(PackageIdent::from_str(v).unwrap());
+        let nginx_a = ident("core/nginx/1.17.4/20200110173421");
+        let nginx_b = ident("core/nginx/1.17.4/20191115184838");
+        let zlib_a = ident("core/zlib/1.2.11/20190115003728");
+        let zlib_b = ident("core/zlib/1.2.12/20200601000000");
+
+        assert_eq!(channel_diff_status(Some(&nginx_a), Some(&nginx_b)),
+                   ChannelDiffStatus::NewerInA);
+        assert_eq!(channel_diff_status(Some(&zlib_a), Some(&zlib_b)),
+                   ChannelDiffStatus::NewerInB);
+        assert_eq!(channel_diff_status(Some(&nginx_a), None),
+                   ChannelDiffStatus::OnlyInA);
+        assert_eq!(channel_diff_status(None, Some(&zlib_b)),
+                   ChannelDiffStatus::OnlyInB);
+    }
+}
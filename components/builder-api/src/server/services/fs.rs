@@ -0,0 +1,122 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pkg storage backend variant which keeps harts on the local filesystem.
+//!
+//! Intended for on-prem and development installs which do not run an S3
+//! compatible store. Packages are stored under the api `data_path`, using
+//! the same key layout as the S3 backend.
+
+use std::{fs,
+          io,
+          path::{Path,
+                 PathBuf}};
+
+use actix_web::{error::BlockingError,
+                web};
+
+use super::{metrics::Counter,
+            s3::s3_key};
+use crate::{bldr_core::metrics::CounterMetric,
+            hab_core::package::{PackageArchive,
+                                PackageIdent,
+                                PackageTarget},
+            server::error::{Error,
+                            Result}};
+
+pub struct FilesystemHandler {
+    root: PathBuf,
+}
+
+impl FilesystemHandler {
+    pub fn new(root: &Path) -> Self { FilesystemHandler { root: root.to_path_buf(), } }
+
+    pub async fn upload(&self,
+                        hart_path: &PathBuf,
+                        ident: &PackageIdent,
+                        target: PackageTarget)
+                        -> Result<()> {
+        Counter::UploadRequests.increment();
+        let dest = self.path_for(ident, target)?;
+
+        info!("FilesystemHandler::upload request started for {:?}", dest);
+
+        let src = hart_path.clone();
+        let copy_dest = dest.clone();
+        let result = web::block(move || {
+                         if let Some(parent) = copy_dest.parent() {
+                             fs::create_dir_all(parent)?;
+                         }
+                         fs::copy(&src, &copy_dest)
+                     }).await;
+
+        match result {
+            Ok(_) => {
+                info!("Upload completed for {:?}", dest);
+                Ok(())
+            }
+            Err(e) => {
+                Counter::UploadFailures.increment();
+                warn!("Upload failed for {:?}: ({:?})", dest, e);
+                Err(blocking_error(e))
+            }
+        }
+    }
+
+    pub async fn download(&self,
+                          loc: &PathBuf,
+                          ident: &PackageIdent,
+                          target: PackageTarget)
+                          -> Result<PackageArchive> {
+        Counter::DownloadRequests.increment();
+        let src = self.path_for(ident, target)?;
+
+        let copy_src = src.clone();
+        let dest = loc.clone();
+        if let Err(e) = web::block(move || fs::copy(&copy_src, &dest)).await {
+            warn!("Failed to retrieve {:?}, ident={}: {:?}", src, ident, e);
+            return Err(blocking_error(e));
+        }
+
+        Ok(PackageArchive::new(loc)?)
+    }
+
+    pub async fn delete(&self, ident: &PackageIdent, target: PackageTarget) -> Result<()> {
+        Counter::DeleteRequests.increment();
+        let path = self.path_for(ident, target)?;
+
+        let remove_path = path.clone();
+        match web::block(move || fs::remove_file(&remove_path)).await {
+            Ok(_) => {
+                info!("FilesystemHandler::delete completed for {:?}", path);
+                Ok(())
+            }
+            Err(e) => {
+                warn!("Failed to delete {:?}, ident={}: {:?}", path, ident, e);
+                Err(blocking_error(e))
+            }
+        }
+    }
+
+    fn path_for(&self, ident: &PackageIdent, target: PackageTarget) -> Result<PathBuf> {
+        Ok(self.root.join(s3_key(ident, target)?))
+    }
+}
+
+fn blocking_error(err: BlockingError<io::Error>) -> Error {
+    match err {
+        BlockingError::Error(e) => Error::IO(e),
+        BlockingError::Canceled => Error::System,
+    }
+}
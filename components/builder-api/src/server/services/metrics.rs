@@ -28,8 +28,10 @@ pub enum Counter {
     SingleUploadRequests,
     MultipartUploadRequests,
     DownloadRequests,
+    DeleteRequests,
     UploadFailures,
     AtomicChannelRequests,
+    RetentionDeletes,
     MemcacheMemberRoleHit,
     MemcacheMemberRoleMiss,
     MemcachePackageHit,
@@ -54,8 +56,10 @@ impl metrics::Metric for Counter {
             Counter::SingleUploadRequests => "upload-single".into(),
             Counter::MultipartUploadRequests => "upload-multi".into(),
             Counter::DownloadRequests => "download-packages".into(),
+            Counter::DeleteRequests => "delete-packages".into(),
             Counter::UploadFailures => "upload-failures".into(),
             Counter::AtomicChannelRequests => "channel-to-channel".into(),
+            Counter::RetentionDeletes => "retention.deletes".into(),
             Counter::MemcacheMemberRoleHit => "memcache-session.hit".into(),
             Counter::MemcacheMemberRoleMiss => "memcache-session.miss".into(),
             Counter::MemcachePackageHit => "memcache-package.hit".into(),
@@ -0,0 +1,324 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Background sync of channels mirrored from an upstream Builder.
+//!
+//! For every configured mirror the latest packages of the upstream channel
+//! are listed per target. Packages missing here are downloaded, along with
+//! their signing key when it is not known yet, and uploaded through this
+//! Builder's own API so they go through the usual upload checks. Packages
+//! which are not yet in the local channel are then promoted into it. Nothing
+//! is ever demoted by a sync. Mirrors of hosts which are no longer in
+//! `mirror.upstream_hosts` are not synced. Each API node claims a mirror
+//! before syncing it, and a run still going once its lease is over is failed,
+//! since the node which started it went away part way through.
+
+use std::time::Duration;
+
+use diesel::result::Error::NotFound;
+use tempfile::tempdir_in;
+
+use crate::{bldr_core::api_client::ApiClient,
+            config::Config,
+            db::{models::{channel::Channel,
+                          keys::OriginPublicSigningKey,
+                          mirror::{OriginChannelMirror,
+                                   OriginChannelMirrorRun},
+                          package::{BuilderPackageIdent,
+                                    BuilderPackageTarget,
+                                    GetPackage,
+                                    Package}},
+                 DbPool},
+            hab_core::{crypto::{artifact,
+                                keys::parse_name_with_rev},
+                       package::{PackageIdent,
+                                 PackageTarget},
+                       ChannelIdent},
+            server::{error::{Error,
+                             Result},
+                     helpers}};
+
+#[derive(Debug, Default)]
+pub struct MirrorSync {
+    pub uploaded: i32,
+    pub promoted: i32,
+    pub errors:   Vec<String>,
+}
+
+// Brings the local channel up to date with the upstream one, collecting an error for each
+// package which could not be mirrored. A database connection is only held for the queries,
+// never across requests to either Builder.
+pub async fn sync(config: &Config,
+                  mirror: &OriginChannelMirror,
+                  db: &DbPool)
+                  -> Result<MirrorSync> {
+    let mut result = MirrorSync::default();
+
+    // The allowed hosts may have changed since the mirror was set
+    if !config.mirror.allows_upstream(&mirror.upstream_url) {
+        result.errors
+              .push(format!("Upstream {} is not an allowed host", mirror.upstream_url));
+        return Ok(result);
+    }
+
+    let upstream = ApiClient::new(&mirror.upstream_url)?;
+    let local = ApiClient::new(&config.mirror.local_url)?;
+    let channel = ChannelIdent::from(mirror.channel.as_str());
+
+    for target in config.api.targets.iter() {
+        let idents = match upstream.list_channel_packages(&mirror.origin, &channel, target, None)
+                                   .await
+        {
+            Ok(idents) => idents,
+            Err(err) => {
+                result.errors
+                      .push(format!("Unable to list upstream packages for {}: {}", target, err));
+                continue;
+            }
+        };
+
+        let idents = wanted(mirror, idents.into_iter().map(Into::into).collect());
+        if idents.is_empty() {
+            continue;
+        }
+
+        let names: Vec<String> = idents.iter().map(ToString::to_string).collect();
+        let members: Vec<String> = {
+            let conn = db.get_conn().map_err(Error::DbError)?;
+            Channel::filter_members(&channel, &names, target, &*conn).map_err(Error::DieselError)?
+                                                                     .iter()
+                                                                     .map(ToString::to_string)
+                                                                     .collect()
+        };
+        let missing = missing(&idents, &members);
+        if missing.is_empty() {
+            continue;
+        }
+
+        if channel != ChannelIdent::stable() && channel != ChannelIdent::unstable() {
+            if let Err(err) =
+                local.create_channel(&mirror.origin, &channel, &config.mirror.local_token)
+                     .await
+            {
+                result.errors
+                      .push(format!("Unable to create channel {}: {}", channel, err));
+                continue;
+            }
+        }
+
+        for ident in missing {
+            match mirror_package(config, &upstream, &local, ident, *target, db).await {
+                Ok(true) => result.uploaded += 1,
+                Ok(false) => (),
+                Err(err) => {
+                    result.errors
+                          .push(format!("Unable to mirror {} ({}): {}", ident, target, err));
+                    continue;
+                }
+            }
+
+            match local.promote_package((ident, *target), &channel, &config.mirror.local_token)
+                       .await
+            {
+                Ok(_) => result.promoted += 1,
+                Err(err) => {
+                    result.errors
+                          .push(format!("Unable to promote {} ({}): {}", ident, target, err))
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+// The upstream packages the mirror is limited to
+fn wanted(mirror: &OriginChannelMirror, idents: Vec<PackageIdent>) -> Vec<PackageIdent> {
+    idents.into_iter()
+          .filter(|i| mirror.wants(&i.name))
+          .collect()
+}
+
+// The packages which are not in the local channel yet
+fn missing<'a>(idents: &'a [PackageIdent], members: &[String]) -> Vec<&'a PackageIdent> {
+    idents.iter()
+          .filter(|i| !members.contains(&i.to_string()))
+          .collect()
+}
+
+// Uploads the package from upstream unless it is already here. Returns whether it was
+// uploaded.
+async fn mirror_package(config: &Config,
+                        upstream: &ApiClient,
+                        local: &ApiClient,
+                        ident: &PackageIdent,
+                        target: PackageTarget,
+                        db: &DbPool)
+                        -> Result<bool> {
+    let req = GetPackage { ident:      BuilderPackageIdent(ident.clone()),
+                           visibility: helpers::all_visibilities(),
+                           target:     BuilderPackageTarget(target), };
+    let found = Package::get(req, &*db.get_conn().map_err(Error::DbError)?);
+    match found {
+        Ok(_) => return Ok(false),
+        Err(NotFound) => (),
+        Err(err) => return Err(Error::DieselError(err)),
+    }
+
+    let dir = tempdir_in(&config.api.data_path)?;
+    let mut archive = upstream.fetch_package(ident, &target, dir.path(), None)
+                              .await?;
+
+    let header = artifact::get_artifact_header(&archive.path)?;
+    let (name, rev) = parse_name_with_rev(&header.key_name)?;
+    let key = OriginPublicSigningKey::get(&name, &rev, &*db.get_conn().map_err(Error::DbError)?);
+    match key {
+        Ok(_) => (),
+        Err(NotFound) => {
+            let key_path = upstream.fetch_origin_public_key(&name, &rev, dir.path())
+                                   .await?;
+            local.put_origin_public_key(&name, &rev, &key_path, &config.mirror.local_token)
+                 .await?;
+        }
+        Err(err) => return Err(Error::DieselError(err)),
+    }
+
+    local.x_put_package(&mut archive, &config.mirror.local_token)
+         .await?;
+    Ok(true)
+}
+
+pub async fn sync_all(config: &Config, db: &DbPool) -> Result<()> {
+    let mirrors = {
+        let conn = db.get_conn().map_err(Error::DbError)?;
+
+        let expired = OriginChannelMirrorRun::fail_expired(config.mirror.lease_sec, &*conn)
+            .map_err(Error::DieselError)?;
+        if expired > 0 {
+            warn!("Failed {} mirror runs which were interrupted", expired);
+        }
+
+        OriginChannelMirror::list_all(&*conn).map_err(Error::DieselError)?
+    };
+
+    for mirror in mirrors {
+        // Another API node may be syncing the mirror already
+        let run = {
+            let conn = db.get_conn().map_err(Error::DbError)?;
+            if !OriginChannelMirror::claim(mirror.id, config.mirror.interval_sec, &*conn)
+                .map_err(Error::DieselError)?
+            {
+                continue;
+            }
+            OriginChannelMirrorRun::start(mirror.id, &*conn).map_err(Error::DieselError)?
+        };
+
+        let result = match sync(config, &mirror, db).await {
+            Ok(result) => result,
+            Err(err) => {
+                MirrorSync { errors: vec![format!("{}", err)],
+                             ..Default::default() }
+            }
+        };
+
+        if result.errors.is_empty() {
+            info!("Mirror of {}/{} synced, uploaded = {}, promoted = {}",
+                  mirror.origin, mirror.channel, result.uploaded, result.promoted);
+        } else {
+            warn!("Mirror of {}/{} synced with {} errors, uploaded = {}, promoted = {}",
+                  mirror.origin,
+                  mirror.channel,
+                  result.errors.len(),
+                  result.uploaded,
+                  result.promoted);
+        }
+
+        let conn = db.get_conn().map_err(Error::DbError)?;
+        OriginChannelMirrorRun::finish(run.id,
+                                       result.uploaded,
+                                       result.promoted,
+                                       &result.errors,
+                                       &*conn).map_err(Error::DieselError)?;
+    }
+
+    Ok(())
+}
+
+// Runs the sync on the configured interval for the lifetime of the server
+pub async fn start(config: Config, db: DbPool) {
+    let interval = Duration::from_secs(config.mirror.interval_sec);
+
+    info!("Channel mirror started, interval = {}s",
+          config.mirror.interval_sec);
+
+    loop {
+        actix_rt::time::delay_for(interval).await;
+
+        if let Err(err) = sync_all(&config, &db).await {
+            warn!("Channel mirror sync failed, err={}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn idents(values: &[&str]) -> Vec<PackageIdent> {
+        values.iter()
+              .map(|v| PackageIdent::from_str(v).unwrap())
+              .collect()
+    }
+
+    fn mirror(include: &[&str], exclude: &[&str]) -> OriginChannelMirror {
+        OriginChannelMirror { id:           1,
+                              origin:       "core".to_string(),
+                              channel:      "stable".to_string(),
+                              upstream_url: "https://bldr.habitat.sh".to_string(),
+                              include:      include.iter().map(|n| n.to_string()).collect(),
+                              exclude:      exclude.iter().map(|n| n.to_string()).collect(),
+                              owner_id:     1,
+                              created_at:   None,
+                              updated_at:   None,
+                              claimed_at:   None, }
+    }
+
+    #[test]
+    fn wanted_applies_the_mirror_filters() {
+        let upstream = idents(&["core/nginx/1.19.2/20200901000000",
+                                "core/redis/6.0.6/20200901000000",
+                                "core/hab-studio/1.6.0/20200901000000"]);
+
+        assert_eq!(wanted(&mirror(&[], &["hab-studio"]), upstream.clone()),
+                   idents(&["core/nginx/1.19.2/20200901000000",
+                            "core/redis/6.0.6/20200901000000"]));
+        assert_eq!(wanted(&mirror(&["redis"], &[]), upstream.clone()),
+                   idents(&["core/redis/6.0.6/20200901000000"]));
+        assert!(wanted(&mirror(&["redis"], &["redis"]), upstream).is_empty());
+    }
+
+    #[test]
+    fn missing_skips_packages_already_in_the_channel() {
+        let upstream = idents(&["core/nginx/1.19.2/20200901000000",
+                                "core/redis/6.0.6/20200901000000"]);
+        let members = vec!["core/nginx/1.19.2/20200901000000".to_string(),
+                           "core/redis/6.0.5/20200801000000".to_string()];
+
+        assert_eq!(missing(&upstream, &members), vec![&upstream[1]]);
+
+        let all: Vec<String> = upstream.iter().map(ToString::to_string).collect();
+        assert!(missing(&upstream, &all).is_empty());
+    }
+}
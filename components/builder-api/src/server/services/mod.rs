@@ -1,4 +1,16 @@
+pub mod bundle;
+pub mod closure;
+pub mod diff;
+pub mod fs;
 pub mod github;
 pub mod memcache;
 pub mod metrics;
+pub mod mirror;
+pub mod policy;
+pub mod protection;
+pub mod retention;
 pub mod s3;
+pub mod sbom;
+pub mod scheduler;
+pub mod storage;
+pub mod uploads;
@@ -0,0 +1,387 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Promotion policies for channels.
+//!
+//! A channel may carry a policy that every promotion into it has to satisfy.
+//! All of the packages in a promotion are checked and every violation found is
+//! reported, rather than stopping at the first one, so the caller gets the
+//! full picture in a single request. Nothing is promoted when there is a
+//! violation.
+
+use std::collections::{HashMap,
+                       HashSet};
+
+use diesel::{pg::PgConnection,
+             result::Error::NotFound};
+
+use crate::{db::models::{channel::Channel,
+                         channel_policy::OriginChannelPolicy,
+                         origin::OriginMemberRole,
+                         package::{BuilderPackageIdent,
+                                   BuilderPackageTarget,
+                                   Package},
+                         provenance::PackageProvenance},
+            hab_core::ChannelIdent,
+            server::error::{Error,
+                            Result}};
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyRule {
+    MinMemberRole,
+    RequireChannel,
+    RequireTdepsInChannel,
+    RequireWorkerBuild,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PolicyViolation {
+    pub rule:    PolicyRule,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ident:   Option<BuilderPackageIdent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target:  Option<BuilderPackageTarget>,
+    pub message: String,
+    // Runtime dependencies which are not yet in the channel
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<BuilderPackageIdent>,
+}
+
+// The parts of a package the policy rules look at
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub id:     i64,
+    pub ident:  BuilderPackageIdent,
+    pub target: BuilderPackageTarget,
+    pub tdeps:  Vec<BuilderPackageIdent>,
+}
+
+impl From<&Package> for Candidate {
+    fn from(package: &Package) -> Candidate {
+        Candidate { id:     package.id,
+                    ident:  package.ident.clone(),
+                    target: package.target.clone(),
+                    tdeps:  package.tdeps.clone(), }
+    }
+}
+
+// What is already known about the candidates, looked up ahead of checking them.
+// Idents are keyed along with their target.
+#[derive(Debug, Default)]
+pub struct PolicyFacts {
+    pub in_required_channel: HashSet<(String, String)>,
+    pub in_channel:          HashSet<(String, String)>,
+    pub attested:            HashSet<i64>,
+}
+
+fn key(ident: &BuilderPackageIdent, target: &BuilderPackageTarget) -> (String, String) {
+    (ident.to_string(), target.to_string())
+}
+
+pub fn check(policy: &OriginChannelPolicy,
+             role: Option<OriginMemberRole>,
+             candidates: &[Candidate],
+             facts: &PolicyFacts)
+             -> Vec<PolicyViolation> {
+    let mut violations = Vec::new();
+
+    if let Some(min_role) = policy.min_member_role {
+        if role.map_or(true, |r| r < min_role) {
+            violations.push(PolicyViolation { rule:    PolicyRule::MinMemberRole,
+                                              ident:   None,
+                                              target:  None,
+                                              message: format!("Promoting into {} requires the \
+                                                                {} role",
+                                                               policy.channel, min_role),
+                                              missing: Vec::new(), });
+        }
+    }
+
+    // Packages promoted together satisfy each other's dependencies
+    let promoted: HashSet<(String, String)> = candidates.iter()
+                                                        .map(|c| key(&c.ident, &c.target))
+                                                        .collect();
+
+    for c in candidates {
+        let violation = |rule, message| {
+            PolicyViolation { rule,
+                              ident: Some(c.ident.clone()),
+                              target: Some(c.target.clone()),
+                              message,
+                              missing: Vec::new() }
+        };
+
+        if let Some(ref required) = policy.require_channel {
+            if !facts.in_required_channel
+                     .contains(&key(&c.ident, &c.target))
+            {
+                violations.push(violation(PolicyRule::RequireChannel,
+                                          format!("Not in the {} channel", required)));
+            }
+        }
+
+        if policy.require_worker_build && !facts.attested.contains(&c.id) {
+            violations.push(violation(PolicyRule::RequireWorkerBuild,
+                                      "Not built by a worker".to_string()));
+        }
+
+        if policy.require_tdeps_in_channel {
+            let missing: Vec<BuilderPackageIdent> = c.tdeps
+                                                     .iter()
+                                                     .filter(|d| {
+                                                         let k = key(d, &c.target);
+                                                         !facts.in_channel.contains(&k)
+                                                         && !promoted.contains(&k)
+                                                     })
+                                                     .cloned()
+                                                     .collect();
+            if !missing.is_empty() {
+                let mut v = violation(PolicyRule::RequireTdepsInChannel,
+                                      format!("Runtime dependencies are not in the {} channel",
+                                              policy.channel));
+                v.missing = missing;
+                violations.push(v);
+            }
+        }
+    }
+
+    violations
+}
+
+// Looks up the facts needed by the rules set on the policy, one query per target
+fn gather_facts(policy: &OriginChannelPolicy,
+                candidates: &[Candidate],
+                conn: &PgConnection)
+                -> Result<PolicyFacts> {
+    let mut facts = PolicyFacts::default();
+
+    let mut by_target: HashMap<String, Vec<&Candidate>> = HashMap::new();
+    for c in candidates {
+        by_target.entry(c.target.to_string())
+                 .or_insert_with(Vec::new)
+                 .push(c);
+    }
+
+    for (target, candidates) in by_target.iter() {
+        if let Some(ref required) = policy.require_channel {
+            let idents: Vec<String> = candidates.iter().map(|c| c.ident.to_string()).collect();
+            let members = Channel::filter_members(&ChannelIdent::from(required.as_str()),
+                                                  &idents,
+                                                  target,
+                                                  conn)?;
+            facts.in_required_channel
+                 .extend(members.into_iter().map(|i| (i.to_string(), target.clone())));
+        }
+
+        if policy.require_tdeps_in_channel {
+            let mut idents: Vec<String> = candidates.iter()
+                                                    .flat_map(|c| c.tdeps.iter())
+                                                    .map(ToString::to_string)
+                                                    .collect();
+            idents.sort();
+            idents.dedup();
+            let members = Channel::filter_members(&ChannelIdent::from(policy.channel.as_str()),
+                                                  &idents,
+                                                  target,
+                                                  conn)?;
+            facts.in_channel
+                 .extend(members.into_iter().map(|i| (i.to_string(), target.clone())));
+        }
+    }
+
+    if policy.require_worker_build {
+        let ids: Vec<i64> = candidates.iter().map(|c| c.id).collect();
+        facts.attested = PackageProvenance::attested(&ids, conn)?.into_iter()
+                                                                 .collect();
+    }
+
+    Ok(facts)
+}
+
+// Checks a promotion into a channel of the origin against the channel's policy, if it has
+// one. Fails with every violation found.
+pub fn enforce(origin: &str,
+               channel: &ChannelIdent,
+               role: Option<OriginMemberRole>,
+               packages: &[Package],
+               conn: &PgConnection)
+               -> Result<()> {
+    let candidates: Vec<Candidate> = packages.iter().map(Candidate::from).collect();
+    enforce_candidates(origin, channel, role, &candidates, conn)
+}
+
+// Like enforce, for packages which may not have been uploaded yet. Those have an id of 0,
+// which is never attested.
+pub fn enforce_candidates(origin: &str,
+                          channel: &ChannelIdent,
+                          role: Option<OriginMemberRole>,
+                          candidates: &[Candidate],
+                          conn: &PgConnection)
+                          -> Result<()> {
+    let policy = match OriginChannelPolicy::get(origin, channel.as_str(), conn) {
+        Ok(policy) => policy,
+        Err(NotFound) => return Ok(()),
+        Err(err) => return Err(Error::DieselError(err)),
+    };
+
+    let facts = gather_facts(&policy, candidates, conn)?;
+    let violations = check(&policy, role, candidates, &facts);
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        debug!("Promotion into {}/{} violates its policy: {:?}",
+               origin, channel, violations);
+        Err(Error::PolicyViolation(violations))
+    }
+}
+
+// Checks a promotion which may span several origins against the policy of the channel in
+// each of them, with the role `role_in` gives for the origin. Fails with the violations
+// found in every origin.
+pub fn enforce_each<F>(channel: &ChannelIdent,
+                       packages: &[Package],
+                       role_in: F,
+                       conn: &PgConnection)
+                       -> Result<()>
+    where F: Fn(&str) -> Option<OriginMemberRole>
+{
+    let mut by_origin: HashMap<&str, Vec<Package>> = HashMap::new();
+    for package in packages {
+        by_origin.entry(package.origin.as_str())
+                 .or_insert_with(Vec::new)
+                 .push(package.clone());
+    }
+
+    let mut violations = Vec::new();
+    for (origin, packages) in by_origin.iter() {
+        match enforce(origin, channel, role_in(origin), packages, conn) {
+            Ok(()) => {}
+            Err(Error::PolicyViolation(mut v)) => violations.append(&mut v),
+            Err(err) => return Err(err),
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::PolicyViolation(violations))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hab_core::package::{PackageIdent,
+                                   PackageTarget};
+    use std::str::FromStr;
+
+    fn ident(value: &str) -> BuilderPackageIdent {
+        BuilderPackageIdent(PackageIdent::from_str(value).unwrap())
+    }
+
+    fn candidate(id: i64, value: &str, tdeps: &[&str]) -> Candidate {
+        Candidate { id,
+                    ident: ident(value),
+                    target:
+                        BuilderPackageTarget(PackageTarget::from_str("x86_64-linux").unwrap()),
+                    tdeps: tdeps.iter().map(|d| ident(d)).collect() }
+    }
+
+    fn policy() -> OriginChannelPolicy {
+        OriginChannelPolicy { id: 1,
+                              origin: "core".to_string(),
+                              channel: "stable".to_string(),
+                              require_channel: None,
+                              require_tdeps_in_channel: false,
+                              require_worker_build: false,
+                              min_member_role: None,
+                              owner_id: 1,
+                              created_at: None,
+                              updated_at: None, }
+    }
+
+    #[test]
+    fn empty_policy_allows_anything() {
+        let candidates = vec![candidate(1, "core/nginx/1.17.4/20191115184838", &[])];
+        assert!(check(&policy(), None, &candidates, &PolicyFacts::default()).is_empty());
+    }
+
+    #[test]
+    fn min_member_role() {
+        let mut policy = policy();
+        policy.min_member_role = Some(OriginMemberRole::Administrator);
+
+        let violations = check(&policy,
+                               Some(OriginMemberRole::Maintainer),
+                               &[],
+                               &PolicyFacts::default());
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, PolicyRule::MinMemberRole);
+
+        assert!(check(&policy,
+                      Some(OriginMemberRole::Owner),
+                      &[],
+                      &PolicyFacts::default()).is_empty());
+    }
+
+    #[test]
+    fn require_channel_and_worker_build() {
+        let mut policy = policy();
+        policy.require_channel = Some("unstable".to_string());
+        policy.require_worker_build = true;
+
+        let candidates = vec![candidate(1, "core/nginx/1.17.4/20191115184838", &[]),
+                              candidate(2, "core/redis/5.0.7/20200110191239", &[])];
+        let mut facts = PolicyFacts::default();
+        facts.in_required_channel
+             .insert(("core/nginx/1.17.4/20191115184838".to_string(), "x86_64-linux".to_string()));
+        facts.attested.insert(2);
+
+        let rules: Vec<(PolicyRule, String)> =
+            check(&policy, None, &candidates, &facts).into_iter()
+                                                     .map(|v| {
+                                                         (v.rule, v.ident.unwrap().to_string())
+                                                     })
+                                                     .collect();
+        assert_eq!(rules,
+                   vec![(PolicyRule::RequireWorkerBuild,
+                         "core/nginx/1.17.4/20191115184838".to_string()),
+                        (PolicyRule::RequireChannel,
+                         "core/redis/5.0.7/20200110191239".to_string()),]);
+    }
+
+    #[test]
+    fn require_tdeps_counts_packages_promoted_together() {
+        let mut policy = policy();
+        policy.require_tdeps_in_channel = true;
+
+        let candidates = vec![candidate(1,
+                                        "core/nginx/1.17.4/20191115184838",
+                                        &["core/glibc/2.29/20200305172459",
+                                          "core/openssl/1.0.2t/20200109000414",
+                                          "core/pcre/8.42/20190115012526"]),
+                              candidate(2, "core/openssl/1.0.2t/20200109000414", &[])];
+        let mut facts = PolicyFacts::default();
+        facts.in_channel
+             .insert(("core/glibc/2.29/20200305172459".to_string(), "x86_64-linux".to_string()));
+
+        let violations = check(&policy, None, &candidates, &facts);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, PolicyRule::RequireTdepsInChannel);
+        assert_eq!(violations[0].missing,
+                   vec![ident("core/pcre/8.42/20190115012526")]);
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Write protection of channels.
+//!
+//! A channel may require a minimum origin role to promote into it or to demote
+//! from it, restrict writes to an allow-list of accounts, and refuse to be
+//! deleted along with the packages in it. These are checked on top of the role
+//! the endpoint itself requires. Channels which do not exist yet are not
+//! protected.
+
+use std::{collections::HashSet,
+          fmt};
+
+use diesel::{pg::PgConnection,
+             result::Error::NotFound};
+
+use crate::{db::models::{channel::Channel,
+                         origin::OriginMemberRole,
+                         package::Package},
+            hab_core::ChannelIdent,
+            server::error::{Error,
+                            Result}};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelWrite {
+    Promote,
+    Demote,
+    Delete,
+}
+
+impl fmt::Display for ChannelWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match *self {
+            ChannelWrite::Promote => "Promoting into",
+            ChannelWrite::Demote => "Demoting from",
+            ChannelWrite::Delete => "Deleting",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+// Returns why the account may not make the write to the channel, if it may not
+pub fn check(channel: &Channel,
+             write: ChannelWrite,
+             account_id: i64,
+             role: Option<OriginMemberRole>)
+             -> Option<String> {
+    let min_role = match write {
+        ChannelWrite::Promote => channel.promote_min_role,
+        ChannelWrite::Demote => channel.demote_min_role,
+        ChannelWrite::Delete => None,
+    };
+
+    if write == ChannelWrite::Delete && channel.no_delete {
+        return Some(format!("The {} channel is protected from deletion", channel.name));
+    }
+
+    if let Some(min_role) = min_role {
+        if role.map_or(true, |r| r < min_role) {
+            return Some(format!("{} the {} channel requires the {} role",
+                                write, channel.name, min_role));
+        }
+    }
+
+    if !channel.allowed_accounts.is_empty() && !channel.allowed_accounts.contains(&account_id) {
+        return Some(format!("{} the {} channel is limited to its allowed accounts",
+                            write, channel.name));
+    }
+
+    None
+}
+
+// Checks a write to a channel of the origin against the channel's protection
+pub fn enforce(origin: &str,
+               channel: &ChannelIdent,
+               write: ChannelWrite,
+               account_id: u64,
+               role: Option<OriginMemberRole>,
+               conn: &PgConnection)
+               -> Result<()> {
+    let channel = match Channel::get(origin, channel, conn) {
+        Ok(channel) => channel,
+        Err(NotFound) => return Ok(()),
+        Err(err) => return Err(Error::DieselError(err)),
+    };
+
+    match check(&channel, write, account_id as i64, role) {
+        Some(reason) => {
+            debug!("Protection of {}/{} refused a write: {}",
+                   origin, channel.name, reason);
+            Err(Error::ChannelProtected(reason))
+        }
+        None => Ok(()),
+    }
+}
+
+// Checks a write which may span several origins against the channel in each of them, with
+// the role `role_in` gives for the origin
+pub fn enforce_each<F>(channel: &ChannelIdent,
+                       write: ChannelWrite,
+                       packages: &[Package],
+                       account_id: u64,
+                       role_in: F,
+                       conn: &PgConnection)
+                       -> Result<()>
+    where F: Fn(&str) -> Option<OriginMemberRole>
+{
+    let origins: HashSet<&str> = packages.iter().map(|p| p.origin.as_str()).collect();
+
+    for origin in origins {
+        enforce(origin, channel, write, account_id, role_in(origin), conn)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel() -> Channel {
+        Channel { id:               1,
+                  owner_id:         1,
+                  name:             "stable".to_string(),
+                  created_at:       None,
+                  updated_at:       None,
+                  origin:           "core".to_string(),
+                  promote_min_role: None,
+                  demote_min_role:  None,
+                  no_delete:        false,
+                  allowed_accounts: Vec::new(), }
+    }
+
+    #[test]
+    fn unprotected_channel_allows_anything() {
+        let channel = channel();
+        for write in &[ChannelWrite::Promote,
+                       ChannelWrite::Demote,
+                       ChannelWrite::Delete]
+        {
+            assert_eq!(check(&channel, *write, 2, Some(OriginMemberRole::Maintainer)),
+                       None);
+        }
+    }
+
+    #[test]
+    fn min_roles_apply_per_write() {
+        let mut channel = channel();
+        channel.demote_min_role = Some(OriginMemberRole::Administrator);
+
+        assert!(check(&channel,
+                      ChannelWrite::Demote,
+                      2,
+                      Some(OriginMemberRole::Maintainer)).is_some());
+        assert!(check(&channel, ChannelWrite::Demote, 2, None).is_some());
+        assert_eq!(check(&channel,
+                         ChannelWrite::Demote,
+                         2,
+                         Some(OriginMemberRole::Owner)),
+                   None);
+        assert_eq!(check(&channel,
+                         ChannelWrite::Promote,
+                         2,
+                         Some(OriginMemberRole::Maintainer)),
+                   None);
+    }
+
+    #[test]
+    fn allowed_accounts_and_no_delete() {
+        let mut channel = channel();
+        channel.allowed_accounts = vec![3];
+        channel.no_delete = true;
+
+        assert!(check(&channel,
+                      ChannelWrite::Promote,
+                      2,
+                      Some(OriginMemberRole::Owner)).is_some());
+        assert_eq!(check(&channel,
+                         ChannelWrite::Promote,
+                         3,
+                         Some(OriginMemberRole::Maintainer)),
+                   None);
+        assert!(check(&channel,
+                      ChannelWrite::Delete,
+                      3,
+                      Some(OriginMemberRole::Owner)).is_some());
+    }
+}
@@ -0,0 +1,223 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Background sweeper which applies the per-origin package retention
+//! policies.
+//!
+//! Candidate releases are computed by the database layer and then run
+//! through the same checks that guard a package delete from the API, so
+//! releases in the stable channel or with reverse dependencies are never
+//! removed. Deleted releases are also removed from package storage. Each
+//! policy is claimed before it is applied, so it is only swept by one API
+//! node per interval.
+
+use std::time::Duration;
+
+use super::{memcache::MemcacheClient,
+            metrics::Counter,
+            storage::{self,
+                      PackageStorage}};
+use crate::{bldr_core::{metrics::CounterMetric,
+                        rpc::RpcClient},
+            config::Config,
+            db::{models::{package::{BuilderPackageIdent,
+                                    BuilderPackageTarget},
+                          retention::{OriginRetentionPolicy,
+                                      RetentionReason}},
+                 DbPool},
+            server::{error::{Error,
+                             Result},
+                     resources::pkgs::{check_package_deletable,
+                                       do_delete_package,
+                                       DeleteBlocker}}};
+
+#[derive(Debug, Serialize)]
+pub struct RetentionPlanEntry {
+    pub ident:    BuilderPackageIdent,
+    pub target:   BuilderPackageTarget,
+    pub channels: Vec<String>,
+    pub reason:   RetentionReason,
+    pub blocked:  Option<DeleteBlocker>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RetentionReport {
+    pub origin:  String,
+    pub channel: Option<String>,
+    pub dry_run: bool,
+    pub entries: Vec<RetentionPlanEntry>,
+}
+
+// Works out which releases a policy would remove, and which of those are blocked
+pub async fn plan(policy: &OriginRetentionPolicy,
+                  jobsrv: &RpcClient,
+                  db: &DbPool)
+                  -> Result<Vec<RetentionPlanEntry>> {
+    let candidates = {
+        let conn = db.get_conn().map_err(Error::DbError)?;
+        policy.candidates(&*conn).map_err(Error::DieselError)?
+    };
+    let mut entries = Vec::new();
+
+    for candidate in candidates {
+        let blocked =
+            check_package_deletable(jobsrv, &candidate.ident, *candidate.target, db).await?;
+        entries.push(RetentionPlanEntry { ident: candidate.ident,
+                                          target: candidate.target,
+                                          channels: candidate.channels,
+                                          reason: candidate.reason,
+                                          blocked });
+    }
+
+    Ok(entries)
+}
+
+#[derive(Debug, Default)]
+pub struct RetentionResult {
+    pub deleted: usize,
+    pub failed:  usize,
+}
+
+// Deletes every unblocked entry of the plan. A release which cannot be deleted is logged and
+// counted, and does not stop the rest of the plan.
+pub async fn apply(entries: &[RetentionPlanEntry],
+                   packages: &dyn PackageStorage,
+                   memcache: &mut MemcacheClient,
+                   db: &DbPool)
+                   -> RetentionResult {
+    let mut result = RetentionResult::default();
+
+    for entry in entries.iter().filter(|e| e.blocked.is_none()) {
+        let deleted = db.get_conn().map_err(Error::DbError).and_then(|conn| {
+                                                               do_delete_package(&entry.ident,
+                                                                                 *entry.target,
+                                                                                 &*conn)
+                                                           });
+        if let Err(err) = deleted {
+            warn!("Retention unable to delete {} ({}), err={}",
+                  *entry.ident, *entry.target, err);
+            result.failed += 1;
+            continue;
+        }
+
+        memcache.clear_cache_for_package(&entry.ident);
+        Counter::RetentionDeletes.increment();
+        result.deleted += 1;
+
+        // Package storage is cleaned up on a best effort basis, the package is already gone
+        if let Err(err) = packages.delete(&entry.ident, *entry.target).await {
+            warn!("Retention unable to remove artifact for {}, err={}",
+                  *entry.ident, err);
+        }
+    }
+
+    result
+}
+
+// Applies every policy which no other API node has swept within the interval. A database
+// connection is only held for the queries, never across the reverse dependencies requests.
+pub async fn sweep(config: &Config,
+                   db: &DbPool,
+                   jobsrv: &RpcClient,
+                   packages: &dyn PackageStorage,
+                   memcache: &mut MemcacheClient)
+                   -> Result<()> {
+    let policies = {
+        let conn = db.get_conn().map_err(Error::DbError)?;
+        OriginRetentionPolicy::list_all(&*conn).map_err(Error::DieselError)?
+    };
+
+    for policy in policies {
+        let claimed = {
+            let conn = db.get_conn().map_err(Error::DbError)?;
+            OriginRetentionPolicy::claim(policy.id, config.retention.interval_sec, &*conn)
+                .map_err(Error::DieselError)?
+        };
+        if !claimed {
+            debug!("Retention skipping origin {}, channel {:?}, already swept",
+                   policy.origin, policy.channel);
+            continue;
+        }
+
+        let entries = match plan(&policy, jobsrv, db).await {
+            Ok(entries) => entries,
+            Err(err) => {
+                warn!("Retention unable to plan for origin {}, channel {:?}, err={}",
+                      policy.origin, policy.channel, err);
+                continue;
+            }
+        };
+
+        for entry in entries.iter() {
+            match entry.blocked {
+                Some(ref blocker) => {
+                    debug!("Retention skipping {} ({}): {}",
+                           *entry.ident, *entry.target, blocker)
+                }
+                None => {
+                    info!("Retention {} {} ({}), reason = {:?}",
+                          if config.retention.dry_run {
+                              "would delete"
+                          } else {
+                              "deleting"
+                          },
+                          *entry.ident,
+                          *entry.target,
+                          entry.reason)
+                }
+            }
+        }
+
+        if config.retention.dry_run {
+            continue;
+        }
+
+        let result = apply(&entries, packages, memcache, db).await;
+        if result.failed == 0 {
+            info!("Retention removed {} releases from origin {}",
+                  result.deleted, policy.origin)
+        } else {
+            warn!("Retention removed {} releases from origin {}, channel {:?}, {} failed",
+                  result.deleted, policy.origin, policy.channel, result.failed)
+        }
+    }
+
+    Ok(())
+}
+
+// Runs the sweeper on the configured interval for the lifetime of the server
+pub async fn start(config: Config, db: DbPool) {
+    let jobsrv = RpcClient::new(&format!("{}", config.jobsrv));
+    let packages = match storage::new(&config) {
+        Ok(packages) => packages,
+        Err(err) => {
+            error!("Retention sweeper unable to create package storage, err={}",
+                   err);
+            return;
+        }
+    };
+    let mut memcache = MemcacheClient::new(&config.memcache);
+    let interval = Duration::from_secs(config.retention.interval_sec);
+
+    info!("Retention sweeper started, interval = {}s, dry_run = {}",
+          config.retention.interval_sec, config.retention.dry_run);
+
+    loop {
+        actix_rt::time::delay_for(interval).await;
+
+        if let Err(err) = sweep(&config, &db, &jobsrv, &*packages, &mut memcache).await {
+            warn!("Retention sweep failed, err={}", err);
+        }
+    }
+}
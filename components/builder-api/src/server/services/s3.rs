@@ -32,7 +32,8 @@ use std::{fmt::Display,
                Write},
           path::PathBuf,
           str::FromStr,
-          time::Instant};
+          time::{Duration,
+                 Instant}};
 
 use futures::StreamExt;
 
@@ -41,6 +42,7 @@ use rusoto_s3::{CompleteMultipartUploadRequest,
                 CompletedPart,
                 CreateBucketRequest,
                 CreateMultipartUploadRequest,
+                DeleteObjectRequest,
                 GetObjectRequest,
                 HeadObjectRequest,
                 PutObjectRequest,
@@ -48,19 +50,27 @@ use rusoto_s3::{CompleteMultipartUploadRequest,
                 UploadPartRequest,
                 S3};
 
-use rusoto_core::HttpClient;
+use rusoto_s3::util::{PreSignedRequest,
+                      PreSignedRequestOption};
 
-use super::metrics::Counter;
+use rusoto_core::{ByteStream,
+                  HttpClient};
+
+use super::{metrics::Counter,
+            storage::RangedDownload};
 use crate::{bldr_core::metrics::CounterMetric,
             config::{S3Backend,
                      S3Cfg},
             hab_core::package::{PackageArchive,
                                 PackageIdent,
                                 PackageTarget},
-            rusoto::{credential::StaticProvider,
+            rusoto::{credential::{AwsCredentials,
+                                  StaticProvider},
                      Region},
-            server::error::{Error,
-                            Result}};
+            server::{error::{Error,
+                             Result},
+                     helpers::{self,
+                               ByteRange}}};
 
 // This const is equal to 6MB which is slightly above
 // the minimum limit for a multipart upload request
@@ -68,33 +78,50 @@ use crate::{bldr_core::metrics::CounterMetric,
 const MINLIMIT: usize = 10240 * 1024;
 
 pub struct S3Handler {
-    client: S3Client,
-    bucket: String,
+    client:      S3Client,
+    bucket:      String,
+    region:      Region,
+    credentials: AwsCredentials,
+    presign_ttl: Option<Duration>,
 }
 
 impl S3Handler {
     // The S3 Handler struct contains all of the credential
     // and target information that we should need to perfom
     // any backend operations
-    pub fn new(config: S3Cfg) -> Self {
+    pub fn new(config: S3Cfg) -> Result<Self> {
         let region = match config.backend {
             S3Backend::Minio => {
                 Region::Custom { name:     "minio_s3".to_owned(),
                                  endpoint: config.endpoint.to_string(), }
             }
             S3Backend::Aws => Region::from_str(config.endpoint.as_str()).unwrap(),
+            S3Backend::Filesystem => {
+                error!("S3Handler cannot be used with the filesystem backend");
+                return Err(Error::System);
+            }
         };
         let aws_id = config.key_id;
         let aws_secret = config.secret_key;
+        let credentials = AwsCredentials::new(aws_id.clone(), aws_secret.clone(), None, None);
         let cred_provider = StaticProvider::new_minimal(aws_id, aws_secret);
         let http_client = match HttpClient::new() {
             Ok(client) => client,
             Err(err) => panic!("Unable to create Rusoto http client, err = {}", err),
         };
-        let client = S3Client::new_with(http_client, cred_provider, region);
+        let client = S3Client::new_with(http_client, cred_provider, region.clone());
         let bucket = config.bucket_name;
+        let presign_ttl = if config.presigned_downloads {
+            Some(Duration::from_secs(config.presigned_url_ttl))
+        } else {
+            None
+        };
 
-        S3Handler { client, bucket }
+        Ok(S3Handler { client,
+                       bucket,
+                       region,
+                       credentials,
+                       presign_ttl })
     }
 
     // This function checks whether or not the
@@ -210,6 +237,96 @@ impl S3Handler {
         }
     }
 
+    // Fetches only the bytes of the archive selected by a Range header
+    pub async fn download_range(&self,
+                                ident: &PackageIdent,
+                                target: PackageTarget,
+                                range: &str)
+                                -> Result<RangedDownload> {
+        Counter::DownloadRequests.increment();
+        let key = s3_key(ident, target)?;
+
+        let mut head = HeadObjectRequest::default();
+        head.bucket = self.bucket.to_owned();
+        head.key = key.clone();
+        let len = match self.client.head_object(head).await {
+            Ok(object) => object.content_length.unwrap_or(0) as u64,
+            Err(e) => {
+                warn!("Failed to retrieve object size from S3, ident={}: {:?}",
+                      ident, e);
+                return Err(Error::HeadObject(e));
+            }
+        };
+
+        let (start, end) = match helpers::byte_range(Some(range), len) {
+            ByteRange::Full => return Ok(RangedDownload::Full),
+            ByteRange::Unsatisfiable => return Ok(RangedDownload::Unsatisfiable(len)),
+            ByteRange::Partial(start, end) => (start, end),
+        };
+
+        let mut request = GetObjectRequest::default();
+        request.bucket = self.bucket.to_owned();
+        request.key = key;
+        request.range = Some(format!("bytes={}-{}", start, end));
+
+        // The body is handed on as it arrives rather than buffered, since a range can be most
+        // of a large archive
+        let body = match self.client.get_object(request).await {
+            Ok(response) => response.body,
+            Err(e) => {
+                warn!("Failed to retrieve object range from S3, ident={}: {:?}",
+                      ident, e);
+                return Err(Error::PackageDownload(e));
+            }
+        };
+        let body = body.unwrap_or_else(|| ByteStream::from(Vec::new()));
+
+        Ok(RangedDownload::Partial { start,
+                                     end,
+                                     len,
+                                     body })
+    }
+
+    // Returns a short lived URL the archive can be fetched from without going through
+    // the API, when pre-signed downloads are enabled
+    pub fn presigned_download_url(&self,
+                                  ident: &PackageIdent,
+                                  target: PackageTarget)
+                                  -> Result<Option<String>> {
+        let expires_in = match self.presign_ttl {
+            Some(ttl) => ttl,
+            None => return Ok(None),
+        };
+
+        let mut request = GetObjectRequest::default();
+        request.bucket = self.bucket.to_owned();
+        request.key = s3_key(ident, target)?;
+
+        let option = PreSignedRequestOption { expires_in };
+        Ok(Some(request.get_presigned_url(&self.region,
+                                          &self.credentials,
+                                          &option)))
+    }
+
+    pub async fn delete(&self, ident: &PackageIdent, target: PackageTarget) -> Result<()> {
+        Counter::DeleteRequests.increment();
+        let mut request = DeleteObjectRequest::default();
+        let key = s3_key(ident, target)?;
+        request.bucket = self.bucket.to_owned();
+        request.key = key.clone();
+
+        match self.client.delete_object(request).await {
+            Ok(_) => {
+                info!("S3Handler::delete completed for s3_key: {}", key);
+                Ok(())
+            }
+            Err(e) => {
+                warn!("Failed to delete object from S3, ident={}: {:?}", ident, e);
+                Err(Error::PackageDelete(e))
+            }
+        }
+    }
+
     async fn single_upload<P: Into<PathBuf>>(&self,
                                              key: &str,
                                              hart: File,
@@ -329,7 +446,7 @@ impl S3Handler {
 
 // Helper function for programmatic creation of
 // the s3 object key
-fn s3_key(ident: &PackageIdent, target: PackageTarget) -> Result<String> {
+pub fn s3_key(ident: &PackageIdent, target: PackageTarget) -> Result<String> {
     // Calling this method first ensures that the ident is fully qualified and the correct errors
     // are returned in case of failure
     let hart_name = ident.archive_name_with_target(target)
@@ -0,0 +1,475 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Software bill of materials documents for a package.
+//!
+//! The components are the package itself and its transitive runtime
+//! dependencies, optionally followed by the transitive build dependencies which
+//! are not also needed at runtime. Everything comes from the package metadata
+//! we already store, so no archives are fetched. Documents are produced in the
+//! SPDX 2.3 and CycloneDX 1.4 JSON formats.
+
+use std::collections::{HashMap,
+                       HashSet};
+
+use crate::{db::models::package::Package,
+            hab_core::package::{Identifiable,
+                                PackageIdent}};
+
+const CREATOR: &str = "habitat-builder";
+const CHECKSUM_ALGORITHM: &str = "BLAKE2b-256";
+const NOASSERTION: &str = "NOASSERTION";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub ident:    PackageIdent,
+    pub target:   String,
+    // None when the dependency is not visible to the caller
+    pub checksum: Option<String>,
+    pub licenses: Vec<String>,
+    pub deps:     Vec<PackageIdent>,
+    // Only needed to build the package, not to run it
+    pub build:    bool,
+}
+
+// The components of the bill of materials for `root`, which is always first. `packages` are
+// the dependencies of `root` that could be found, dependencies missing from it are included
+// with only their ident.
+pub fn components(root: &Package, packages: &[Package], include_build: bool) -> Vec<Component> {
+    let found: HashMap<String, &Package> =
+        packages.iter().map(|p| (p.ident.to_string(), p)).collect();
+
+    let mut result = vec![component(root, false)];
+    let mut seen = HashSet::new();
+    seen.insert(root.ident.to_string());
+
+    let build_tdeps: &[_] = if include_build {
+        &root.build_tdeps
+    } else {
+        &[]
+    };
+    let deps = root.tdeps
+                   .iter()
+                   .map(|d| (d, false))
+                   .chain(build_tdeps.iter().map(|d| (d, true)));
+
+    for (dep, build) in deps {
+        if !seen.insert(dep.to_string()) {
+            continue;
+        }
+        match found.get(&dep.to_string()) {
+            Some(pkg) => result.push(component(pkg, build)),
+            None => {
+                result.push(Component { ident: dep.0.clone(),
+                                        target: root.target.to_string(),
+                                        checksum: None,
+                                        licenses: Vec::new(),
+                                        deps: Vec::new(),
+                                        build })
+            }
+        }
+    }
+    result
+}
+
+fn component(pkg: &Package, build: bool) -> Component {
+    Component { ident: pkg.ident.0.clone(),
+                target: pkg.target.to_string(),
+                checksum: Some(pkg.checksum.clone()),
+                licenses: licenses(&pkg.manifest),
+                deps: pkg.deps.iter().map(|d| d.0.clone()).collect(),
+                build }
+}
+
+// The license values from the `License` line of a package manifest
+pub fn licenses(manifest: &str) -> Vec<String> {
+    manifest.lines()
+            .map(str::trim)
+            .find(|l| l.starts_with("* __License__:"))
+            .map(|l| {
+                l["* __License__:".len()..].split_whitespace()
+                                           .map(|v| v.trim_matches(|c| c == '`' || c == ','))
+                                           .filter(|v| !v.is_empty())
+                                           .map(str::to_string)
+                                           .collect()
+            })
+            .unwrap_or_default()
+}
+
+// Licenses which are not valid SPDX license ids are referenced as `LicenseRef-`s
+fn spdx_license(license: &str) -> String {
+    if license.chars()
+              .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+')
+    {
+        license.to_string()
+    } else {
+        format!("LicenseRef-{}", spdx_id_part(license))
+    }
+}
+
+fn spdx_id_part(value: &str) -> String {
+    value.chars()
+         .map(|c| {
+             if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                 c
+             } else {
+                 '-'
+             }
+         })
+         .collect()
+}
+
+fn spdx_package_id(index: usize, ident: &PackageIdent) -> String {
+    format!("SPDXRef-Package-{}-{}-{}",
+            index,
+            spdx_id_part(ident.origin()),
+            spdx_id_part(ident.name()))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxDocument {
+    spdx_version:       &'static str,
+    data_license:       &'static str,
+    #[serde(rename = "SPDXID")]
+    spdx_id:            &'static str,
+    name:               String,
+    document_namespace: String,
+    creation_info:      SpdxCreationInfo,
+    packages:           Vec<SpdxPackage>,
+    relationships:      Vec<SpdxRelationship>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpdxCreationInfo {
+    created:  String,
+    creators: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxPackage {
+    #[serde(rename = "SPDXID")]
+    spdx_id:           String,
+    name:              String,
+    version_info:      String,
+    download_location: &'static str,
+    files_analyzed:    bool,
+    license_concluded: &'static str,
+    license_declared:  String,
+    copyright_text:    &'static str,
+    checksums:         Vec<SpdxChecksum>,
+    comment:           String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpdxChecksum {
+    algorithm:      &'static str,
+    #[serde(rename = "checksumValue")]
+    checksum_value: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxRelationship {
+    spdx_element_id:      String,
+    relationship_type:    &'static str,
+    related_spdx_element: String,
+}
+
+fn spdx_package(c: &Component, spdx_id: String) -> SpdxPackage {
+    let license_declared = if c.licenses.is_empty() {
+        NOASSERTION.to_string()
+    } else {
+        let licenses: Vec<String> = c.licenses.iter().map(|l| spdx_license(l)).collect();
+        licenses.join(" AND ")
+    };
+    let checksums = c.checksum
+                     .iter()
+                     .map(|v| {
+                         SpdxChecksum { algorithm:      CHECKSUM_ALGORITHM,
+                                        checksum_value: v.clone(), }
+                     })
+                     .collect();
+
+    SpdxPackage { spdx_id,
+                  name: format!("{}/{}", c.ident.origin(), c.ident.name()),
+                  version_info: version_info(&c.ident),
+                  download_location: NOASSERTION,
+                  files_analyzed: false,
+                  license_concluded: NOASSERTION,
+                  license_declared,
+                  copyright_text: NOASSERTION,
+                  checksums,
+                  comment: format!("target: {}", c.target) }
+}
+
+fn version_info(ident: &PackageIdent) -> String {
+    format!("{}/{}",
+            ident.version().unwrap_or_default(),
+            ident.release().unwrap_or_default())
+}
+
+pub fn spdx(components: &[Component], namespace: &str, created: &str) -> SpdxDocument {
+    let ids: HashMap<String, String> =
+        components.iter()
+                  .enumerate()
+                  .map(|(i, c)| (c.ident.to_string(), spdx_package_id(i, &c.ident)))
+                  .collect();
+    let root_id = spdx_package_id(0, &components[0].ident);
+
+    let packages = components.iter()
+                             .map(|c| spdx_package(c, ids[&c.ident.to_string()].clone()))
+                             .collect();
+
+    let relationship = |from: &str, kind, to: &str| {
+        SpdxRelationship { spdx_element_id:      from.to_string(),
+                           relationship_type:    kind,
+                           related_spdx_element: to.to_string(), }
+    };
+
+    let mut relationships = vec![relationship("SPDXRef-DOCUMENT", "DESCRIBES", &root_id)];
+    for c in components.iter() {
+        let id = &ids[&c.ident.to_string()];
+        if c.build {
+            relationships.push(relationship(id, "BUILD_DEPENDENCY_OF", &root_id));
+        }
+        for dep in c.deps.iter() {
+            if let Some(dep_id) = ids.get(&dep.to_string()) {
+                relationships.push(relationship(id, "DEPENDS_ON", dep_id));
+            }
+        }
+    }
+
+    SpdxDocument { spdx_version: "SPDX-2.3",
+                   data_license: "CC0-1.0",
+                   spdx_id: "SPDXRef-DOCUMENT",
+                   name: components[0].ident.to_string(),
+                   document_namespace: namespace.to_string(),
+                   creation_info: SpdxCreationInfo { created:  created.to_string(),
+                                                     creators: vec![format!("Tool: {}", CREATOR)], },
+                   packages,
+                   relationships }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxBom {
+    bom_format:    &'static str,
+    spec_version:  &'static str,
+    serial_number: String,
+    version:       u32,
+    metadata:      CycloneDxMetadata,
+    components:    Vec<CycloneDxComponent>,
+    dependencies:  Vec<CycloneDxDependency>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CycloneDxMetadata {
+    timestamp: String,
+    tools:     Vec<CycloneDxTool>,
+    component: CycloneDxComponent,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CycloneDxTool {
+    name: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CycloneDxComponent {
+    #[serde(rename = "type")]
+    kind:       &'static str,
+    #[serde(rename = "bom-ref")]
+    bom_ref:    String,
+    group:      String,
+    name:       String,
+    version:    String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope:      Option<&'static str>,
+    hashes:     Vec<CycloneDxHash>,
+    licenses:   Vec<CycloneDxLicenseChoice>,
+    properties: Vec<CycloneDxProperty>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CycloneDxHash {
+    alg:     &'static str,
+    content: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CycloneDxLicenseChoice {
+    license: CycloneDxLicense,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CycloneDxLicense {
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CycloneDxProperty {
+    name:  &'static str,
+    value: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxDependency {
+    #[serde(rename = "ref")]
+    bom_ref:    String,
+    depends_on: Vec<String>,
+}
+
+fn cyclonedx_component(c: &Component, scope: Option<&'static str>) -> CycloneDxComponent {
+    // The package the bill of materials is for has no scope
+    let kind = if scope.is_none() {
+        "application"
+    } else {
+        "library"
+    };
+    let hashes = c.checksum
+                  .iter()
+                  .map(|v| {
+                      CycloneDxHash { alg:     CHECKSUM_ALGORITHM,
+                                      content: v.clone(), }
+                  })
+                  .collect();
+    let licenses =
+        c.licenses
+         .iter()
+         .map(|l| CycloneDxLicenseChoice { license: CycloneDxLicense { name: l.clone() }, })
+         .collect();
+
+    CycloneDxComponent { kind,
+                         bom_ref: c.ident.to_string(),
+                         group: c.ident.origin().to_string(),
+                         name: c.ident.name().to_string(),
+                         version: version_info(&c.ident),
+                         scope,
+                         hashes,
+                         licenses,
+                         properties: vec![CycloneDxProperty { name:  "habitat:target",
+                                                              value: c.target.clone(), }] }
+}
+
+pub fn cyclonedx(components: &[Component], serial: &str, created: &str) -> CycloneDxBom {
+    let refs: HashSet<String> = components.iter().map(|c| c.ident.to_string()).collect();
+
+    let dependencies = components.iter()
+                                 .map(|c| {
+                                     let depends_on = c.deps
+                                                       .iter()
+                                                       .map(ToString::to_string)
+                                                       .filter(|d| refs.contains(d))
+                                                       .collect();
+                                     CycloneDxDependency { bom_ref: c.ident.to_string(),
+                                                           depends_on }
+                                 })
+                                 .collect();
+    let scoped = components[1..].iter()
+                                .map(|c| {
+                                    let scope = if c.build { "excluded" } else { "required" };
+                                    cyclonedx_component(c, Some(scope))
+                                })
+                                .collect();
+
+    CycloneDxBom { bom_format: "CycloneDX",
+                   spec_version: "1.4",
+                   serial_number: format!("urn:uuid:{}", serial),
+                   version: 1,
+                   metadata: CycloneDxMetadata { timestamp: created.to_string(),
+                                                 tools:     vec![CycloneDxTool { name: CREATOR }],
+                                                 component: cyclonedx_component(&components[0],
+                                                                                None), },
+                   components: scoped,
+                   dependencies }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn fixture(ident: &str, deps: &[&str], build: bool) -> Component {
+        Component { ident: PackageIdent::from_str(ident).unwrap(),
+                    target: "x86_64-linux".to_string(),
+                    checksum: Some("abc".to_string()),
+                    licenses: vec!["MIT".to_string()],
+                    deps: deps.iter()
+                              .map(|d| PackageIdent::from_str(d).unwrap())
+                              .collect(),
+                    build }
+    }
+
+    #[test]
+    fn licenses_from_manifest() {
+        let manifest = "# core / nginx\n\n* __Maintainer__: The Habitat Maintainers\n* \
+                        __License__: BSD-2-Clause `Apache-2.0`\n* __Source__: \
+                        [nginx.tar.gz](http://nginx.org)\n";
+        assert_eq!(licenses(manifest), vec!["BSD-2-Clause", "Apache-2.0"]);
+        assert!(licenses("# core / nginx\n").is_empty());
+    }
+
+    #[test]
+    fn spdx_license_refs() {
+        assert_eq!(spdx_license("GPL-2.0+"), "GPL-2.0+");
+        assert_eq!(spdx_license("Public Domain"), "LicenseRef-Public-Domain");
+    }
+
+    #[test]
+    fn spdx_relationships() {
+        let components = vec![fixture("core/nginx/1.17.4/20191115184838",
+                                      &["core/glibc/2.29/20200305172459"],
+                                      false),
+                              fixture("core/glibc/2.29/20200305172459", &[], false),
+                              fixture("core/gcc/9.1.0/20200305225533", &[], true),];
+        let doc = spdx(&components,
+                       "https://example.com/spdx",
+                       "2020-09-01T00:00:00Z");
+
+        assert_eq!(doc.packages.len(), 3);
+        assert_eq!(doc.packages[0].spdx_id, "SPDXRef-Package-0-core-nginx");
+        assert_eq!(doc.packages[0].version_info, "1.17.4/20191115184838");
+
+        let kinds: Vec<(&str, &str)> =
+            doc.relationships
+               .iter()
+               .map(|r| (r.relationship_type, r.related_spdx_element.as_str()))
+               .collect();
+        assert_eq!(kinds,
+                   vec![("DESCRIBES", "SPDXRef-Package-0-core-nginx"),
+                        ("DEPENDS_ON", "SPDXRef-Package-1-core-glibc"),
+                        ("BUILD_DEPENDENCY_OF", "SPDXRef-Package-0-core-nginx"),]);
+    }
+
+    #[test]
+    fn cyclonedx_scopes() {
+        let components = vec![fixture("core/nginx/1.17.4/20191115184838",
+                                      &["core/glibc/2.29/20200305172459"],
+                                      false),
+                              fixture("core/glibc/2.29/20200305172459", &[], false),
+                              fixture("core/gcc/9.1.0/20200305225533", &[], true),];
+        let bom = cyclonedx(&components, "3e671687-395b-41f5-a30f-a58921a69b79", "now");
+
+        assert_eq!(bom.metadata.component.bom_ref,
+                   "core/nginx/1.17.4/20191115184838");
+        assert_eq!(bom.components.len(), 2);
+        assert_eq!(bom.components[0].scope, Some("required"));
+        assert_eq!(bom.components[1].scope, Some("excluded"));
+        assert_eq!(bom.dependencies[0].depends_on,
+                   vec!["core/glibc/2.29/20200305172459"]);
+    }
+}
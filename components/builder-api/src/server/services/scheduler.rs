@@ -0,0 +1,259 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Background runner of the scheduled channel promotions.
+//!
+//! Promotions which are due are claimed and run as the account which
+//! scheduled them, through the same protection and policy checks as a
+//! promotion made from the API. A job group is resolved to its successful
+//! projects only when the promotion runs. Every package is recorded as an item
+//! of the promotion along with whether it made it into the channel. A promotion
+//! still running once its lease is over is failed rather than run again, since
+//! the scheduler which claimed it went away part way through.
+
+use std::{collections::HashMap,
+          str::FromStr,
+          time::Duration};
+
+use diesel::{pg::PgConnection,
+             result::Error::NotFound};
+
+use super::{closure::{self,
+                      ClosureRequester},
+            memcache::MemcacheClient,
+            policy,
+            protection::{self,
+                         ChannelWrite}};
+use crate::{bldr_core::rpc::RpcClient,
+            config::Config,
+            db::{models::{channel::PackageChannelTrigger,
+                          origin::{OriginMember,
+                                   OriginMemberRole},
+                          package::{BuilderPackageIdent,
+                                    BuilderPackageTarget,
+                                    GetPackage,
+                                    Package},
+                          scheduled_promotion::{NewScheduledPromotionItem,
+                                                ScheduledPromotion,
+                                                ScheduledPromotionItem,
+                                                ScheduledPromotionState}},
+                 DbPool},
+            hab_core::{package::{PackageIdent,
+                                 PackageTarget},
+                       ChannelIdent},
+            protocol::jobsrv,
+            server::{error::{Error,
+                             Result},
+                     helpers}};
+
+// The idents the promotion is of, the successful projects of its job group if it has one
+async fn idents(promotion: &ScheduledPromotion, jobsrv: &RpcClient) -> Result<Vec<String>> {
+    let group_id = match promotion.group_id {
+        Some(group_id) => group_id,
+        None => return Ok(promotion.idents.clone()),
+    };
+
+    let mut group_get = jobsrv::JobGroupGet::new();
+    group_get.set_group_id(group_id as u64);
+    group_get.set_include_projects(true);
+    let group = jobsrv.rpc::<jobsrv::JobGroupGet, jobsrv::JobGroup>(&group_get)
+                      .await
+                      .map_err(Error::BuilderCore)?;
+
+    Ok(group.get_projects()
+            .iter()
+            .filter(|p| p.get_state() == jobsrv::JobGroupProjectState::Success)
+            .map(|p| p.get_ident().to_string())
+            .collect())
+}
+
+// Looks up a package of the promotion, failing when the requester may not promote it
+fn candidate(ident: &str,
+             target: PackageTarget,
+             channel: &ChannelIdent,
+             requester_id: i64,
+             roles: &mut HashMap<String, Option<OriginMemberRole>>,
+             conn: &PgConnection)
+             -> Result<Package> {
+    let ident = PackageIdent::from_str(ident)?;
+    if !ident.fully_qualified() {
+        return Err(Error::BadRequest);
+    }
+
+    let role = match roles.get(&ident.origin) {
+        Some(role) => *role,
+        None => {
+            let role = match OriginMember::member_role(&ident.origin, requester_id, conn) {
+                Ok(role) => Some(role),
+                Err(NotFound) => None,
+                Err(err) => return Err(Error::DieselError(err)),
+            };
+            roles.insert(ident.origin.clone(), role);
+            role
+        }
+    };
+    if role.map_or(true, |r| r < OriginMemberRole::Maintainer) {
+        return Err(Error::Authorization);
+    }
+
+    protection::enforce(&ident.origin,
+                        channel,
+                        ChannelWrite::Promote,
+                        requester_id as u64,
+                        role,
+                        conn)?;
+
+    let package = Package::get(GetPackage { ident:      BuilderPackageIdent(ident),
+                                            visibility: helpers::all_visibilities(),
+                                            target:     BuilderPackageTarget(target), },
+                               conn)?;
+    Ok(package)
+}
+
+fn record(promotion: &ScheduledPromotion,
+          ident: &str,
+          error: Option<&str>,
+          conn: &PgConnection)
+          -> Result<()> {
+    let item = NewScheduledPromotionItem { promotion_id: promotion.id,
+                                           ident,
+                                           target: &promotion.target,
+                                           succeeded: error.is_none(),
+                                           error };
+    ScheduledPromotionItem::create(&item, conn)?;
+    Ok(())
+}
+
+// Runs the promotion, recording an item for each package. Returns the state it ends in.
+pub async fn run(promotion: &ScheduledPromotion,
+                 jobsrv: &RpcClient,
+                 memcache: &mut MemcacheClient,
+                 db: &DbPool)
+                 -> Result<ScheduledPromotionState> {
+    let channel = ChannelIdent::from(promotion.channel.as_str());
+    let target = PackageTarget::from_str(&promotion.target)?;
+    let idents = idents(promotion, jobsrv).await?;
+
+    // Only taken once jobsrv has answered, so no connection waits on it
+    let conn = db.get_conn().map_err(Error::DbError)?;
+    let conn = &*conn;
+
+    let mut roles = HashMap::new();
+    let mut failures = Vec::new();
+    let mut packages = Vec::new();
+    for ident in idents.iter() {
+        match candidate(ident,
+                        target,
+                        &channel,
+                        promotion.requester_id,
+                        &mut roles,
+                        conn)
+        {
+            Ok(package) => packages.push(package),
+            Err(err) => failures.push((ident.clone(), err.to_string())),
+        }
+    }
+
+    // Like from the API, nothing is promoted when the policy of the channel is violated
+    let role_in = |origin: &str| roles.get(origin).copied().flatten();
+    let requester = ClosureRequester { trigger: PackageChannelTrigger::Scheduled,
+                                       id:      promotion.requester_id,
+                                       name:    &promotion.requester_name,
+                                       group:   promotion.group_id, };
+    let promoted = match policy::enforce_each(&channel, &packages, role_in, conn) {
+        Ok(()) => closure::promote(&channel, &packages, &requester, conn),
+        Err(err) => Err(err),
+    };
+
+    match promoted {
+        Ok(()) => {
+            for package in packages.iter() {
+                memcache.clear_cache_for_package(&package.ident);
+                memcache.clear_cache_for_channel(&package.origin, &channel);
+                record(promotion, &package.ident.to_string(), None, conn)?;
+            }
+        }
+        Err(err) => {
+            let error = err.to_string();
+            failures.extend(packages.iter()
+                                    .map(|p| (p.ident.to_string(), error.clone())));
+        }
+    }
+
+    for (ident, error) in failures.iter() {
+        record(promotion, ident, Some(error), conn)?;
+    }
+
+    if failures.is_empty() {
+        Ok(ScheduledPromotionState::Succeeded)
+    } else {
+        Ok(ScheduledPromotionState::Failed)
+    }
+}
+
+pub async fn run_due(db: &DbPool,
+                     lease_sec: u64,
+                     jobsrv: &RpcClient,
+                     memcache: &mut MemcacheClient)
+                     -> Result<()> {
+    let promotions = {
+        let conn = db.get_conn().map_err(Error::DbError)?;
+
+        let expired =
+            ScheduledPromotion::fail_expired(lease_sec, &*conn).map_err(Error::DieselError)?;
+        if expired > 0 {
+            warn!("Failed {} scheduled promotions which were interrupted",
+                  expired);
+        }
+
+        ScheduledPromotion::claim_due(&*conn).map_err(Error::DieselError)?
+    };
+
+    for promotion in promotions {
+        let (state, error) = match run(&promotion, jobsrv, memcache, db).await {
+            Ok(state) => (state, None),
+            Err(err) => (ScheduledPromotionState::Failed, Some(err.to_string())),
+        };
+
+        info!("Scheduled promotion {} into {}/{} finished, state = {:?}",
+              promotion.id, promotion.origin, promotion.channel, state);
+        if let Some(ref error) = error {
+            warn!("Scheduled promotion {} failed, err={}", promotion.id, error);
+        }
+
+        let conn = db.get_conn().map_err(Error::DbError)?;
+        ScheduledPromotion::finish(promotion.id, state, error.as_deref(), &*conn)
+            .map_err(Error::DieselError)?;
+    }
+
+    Ok(())
+}
+
+// Runs the promotions which are due on the configured interval for the lifetime of the server
+pub async fn start(config: Config, db: DbPool) {
+    let jobsrv = RpcClient::new(&format!("{}", config.jobsrv));
+    let mut memcache = MemcacheClient::new(&config.memcache);
+    let interval = Duration::from_secs(config.scheduler.interval_sec);
+
+    info!("Promotion scheduler started, interval = {}s",
+          config.scheduler.interval_sec);
+
+    loop {
+        actix_rt::time::delay_for(interval).await;
+
+        if let Err(err) = run_due(&db, config.scheduler.lease_sec, &jobsrv, &mut memcache).await {
+            warn!("Running scheduled promotions failed, err={}", err);
+        }
+    }
+}
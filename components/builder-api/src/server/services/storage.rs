@@ -0,0 +1,189 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Common interface over the pkg storage backends.
+//!
+//! The backend is picked once at startup: Artifactory when that feature is
+//! enabled, otherwise the `backend` set in the `[s3]` config section, which
+//! is either an S3 API compatible store or the local filesystem.
+
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use rusoto_core::ByteStream;
+
+use artifactory_client::client::ArtifactoryClient;
+
+use super::{fs::FilesystemHandler,
+            s3::S3Handler};
+use crate::{config::{Config,
+                     S3Backend},
+            hab_core::package::{PackageArchive,
+                                PackageIdent,
+                                PackageTarget},
+            server::{error::{Error,
+                             Result},
+                     feat}};
+
+// The part of an archive selected by a Range header
+pub enum RangedDownload {
+    // The header does not select a single range, so the whole archive is sent
+    Full,
+    // The inclusive `start..=end` bytes of an archive of `len` bytes, streamed from the backend
+    Partial {
+        start: u64,
+        end:   u64,
+        len:   u64,
+        body:  ByteStream,
+    },
+    // The range lies outside of an archive of the given size
+    Unsatisfiable(u64),
+}
+
+#[async_trait]
+pub trait PackageStorage: Sync + Send {
+    async fn upload(&self,
+                    hart_path: &PathBuf,
+                    ident: &PackageIdent,
+                    target: PackageTarget)
+                    -> Result<()>;
+
+    async fn download(&self,
+                      loc: &PathBuf,
+                      ident: &PackageIdent,
+                      target: PackageTarget)
+                      -> Result<PackageArchive>;
+
+    async fn delete(&self, ident: &PackageIdent, target: PackageTarget) -> Result<()>;
+
+    // Fetches only the part of the archive selected by a Range header, if the backend
+    // supports it. Otherwise the range is cut from a full download.
+    async fn download_range(&self,
+                            _ident: &PackageIdent,
+                            _target: PackageTarget,
+                            _range: &str)
+                            -> Result<Option<RangedDownload>> {
+        Ok(None)
+    }
+
+    // A short lived URL the archive can be downloaded from directly, if the backend
+    // supports it and it is enabled
+    async fn presigned_download_url(&self,
+                                    _ident: &PackageIdent,
+                                    _target: PackageTarget)
+                                    -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+pub fn new(config: &Config) -> Result<Box<dyn PackageStorage>> {
+    let storage: Box<dyn PackageStorage> = if feat::is_enabled(feat::Artifactory) {
+        Box::new(ArtifactoryClient::new(config.artifactory.clone())?)
+    } else {
+        match config.s3.backend {
+            S3Backend::Filesystem => Box::new(FilesystemHandler::new(&config.api.data_path)),
+            S3Backend::Aws | S3Backend::Minio => Box::new(S3Handler::new(config.s3.clone())?),
+        }
+    };
+    Ok(storage)
+}
+
+#[async_trait]
+impl PackageStorage for S3Handler {
+    async fn upload(&self,
+                    hart_path: &PathBuf,
+                    ident: &PackageIdent,
+                    target: PackageTarget)
+                    -> Result<()> {
+        S3Handler::upload(self, hart_path, ident, target).await
+    }
+
+    async fn download(&self,
+                      loc: &PathBuf,
+                      ident: &PackageIdent,
+                      target: PackageTarget)
+                      -> Result<PackageArchive> {
+        S3Handler::download(self, loc, ident, target).await
+    }
+
+    async fn delete(&self, ident: &PackageIdent, target: PackageTarget) -> Result<()> {
+        S3Handler::delete(self, ident, target).await
+    }
+
+    async fn download_range(&self,
+                            ident: &PackageIdent,
+                            target: PackageTarget,
+                            range: &str)
+                            -> Result<Option<RangedDownload>> {
+        Ok(Some(S3Handler::download_range(self, ident, target, range).await?))
+    }
+
+    async fn presigned_download_url(&self,
+                                    ident: &PackageIdent,
+                                    target: PackageTarget)
+                                    -> Result<Option<String>> {
+        S3Handler::presigned_download_url(self, ident, target)
+    }
+}
+
+#[async_trait]
+impl PackageStorage for FilesystemHandler {
+    async fn upload(&self,
+                    hart_path: &PathBuf,
+                    ident: &PackageIdent,
+                    target: PackageTarget)
+                    -> Result<()> {
+        FilesystemHandler::upload(self, hart_path, ident, target).await
+    }
+
+    async fn download(&self,
+                      loc: &PathBuf,
+                      ident: &PackageIdent,
+                      target: PackageTarget)
+                      -> Result<PackageArchive> {
+        FilesystemHandler::download(self, loc, ident, target).await
+    }
+
+    async fn delete(&self, ident: &PackageIdent, target: PackageTarget) -> Result<()> {
+        FilesystemHandler::delete(self, ident, target).await
+    }
+}
+
+#[async_trait]
+impl PackageStorage for ArtifactoryClient {
+    async fn upload(&self,
+                    hart_path: &PathBuf,
+                    ident: &PackageIdent,
+                    target: PackageTarget)
+                    -> Result<()> {
+        ArtifactoryClient::upload(self, hart_path, ident, target).await
+                                                                 .map_err(Error::Artifactory)?;
+        Ok(())
+    }
+
+    async fn download(&self,
+                      loc: &PathBuf,
+                      ident: &PackageIdent,
+                      target: PackageTarget)
+                      -> Result<PackageArchive> {
+        ArtifactoryClient::download(self, loc, ident, target).await
+                                                             .map_err(Error::Artifactory)
+    }
+
+    async fn delete(&self, ident: &PackageIdent, target: PackageTarget) -> Result<()> {
+        ArtifactoryClient::delete(self, ident, target).await
+                                                      .map_err(Error::Artifactory)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright (c) 2020 Chef Software Inc. and/or applicable contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Housekeeping for resumable package uploads.
+//!
+//! The chunks of an upload session are appended to a file under the api
+//! `data_path`. Sessions which stop receiving chunks for longer than the
+//! configured `upload_session_ttl` are expired, removing both the session
+//! and whatever was received so far. Sessions locked by a writer are only
+//! expired once the lock is older than the ttl as well.
+
+use std::{fs,
+          io,
+          path::{Path,
+                 PathBuf},
+          time::Duration};
+
+use chrono::{self,
+             DateTime,
+             Utc};
+
+use crate::{config::Config,
+            db::{models::upload::PackageUpload,
+                 DbPool},
+            server::error::{Error,
+                            Result}};
+
+// Held while an upload session is being written to or finalized, so that concurrent
+// requests for the same session cannot interleave their writes, whichever API node they
+// reach. The lock is a column of the session row and is released on drop.
+pub struct SessionLock {
+    id: i64,
+    db: DbPool,
+}
+
+impl SessionLock {
+    // Returns None if another request already holds the lock of the session
+    pub fn try_acquire(id: i64, ttl: u64, db: &DbPool) -> Result<Option<SessionLock>> {
+        let conn = db.get_conn().map_err(Error::DbError)?;
+        if PackageUpload::lock(id, cutoff(ttl), &*conn).map_err(Error::DieselError)? {
+            Ok(Some(SessionLock { id, db: db.clone() }))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Drop for SessionLock {
+    fn drop(&mut self) {
+        let unlocked = self.db
+                           .get_conn()
+                           .map_err(Error::DbError)
+                           .and_then(|conn| {
+                               PackageUpload::unlock(self.id, &*conn).map_err(Error::DieselError)
+                           });
+        if let Err(err) = unlocked {
+            warn!("Unable to unlock upload session {}, err={}", self.id, err);
+        }
+    }
+}
+
+// Sessions, and locks, last touched before this are expired
+fn cutoff(ttl: u64) -> DateTime<Utc> { Utc::now() - chrono::Duration::seconds(ttl as i64) }
+
+// Location of the partially received archive for an upload session
+pub fn session_path(data_path: &Path, id: i64) -> PathBuf {
+    data_path.join(format!("{}.upload", id))
+}
+
+pub fn is_expired(upload: &PackageUpload, ttl: u64) -> bool {
+    upload.updated_at
+          .map_or(false, |t| t < cutoff(ttl).naive_utc())
+}
+
+// Removes whatever was received for an upload session
+pub fn remove_session_file(data_path: &Path, id: i64) {
+    let path = session_path(data_path, id);
+    match fs::remove_file(&path) {
+        Ok(_) => {}
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Unable to remove upload file {:?}, err={}", path, e),
+    }
+}
+
+// Removes an upload session along with any data received for it
+pub fn discard(upload: &PackageUpload, data_path: &Path, db: &DbPool) -> Result<()> {
+    remove_session_file(data_path, upload.id);
+
+    let conn = db.get_conn().map_err(Error::DbError)?;
+    PackageUpload::delete(upload.id, &*conn).map_err(Error::DieselError)?;
+    Ok(())
+}
+
+// Removes an expired upload session unless a writer holds its lock
+pub fn expire(upload: &PackageUpload, ttl: u64, data_path: &Path, db: &DbPool) -> Result<()> {
+    let conn = db.get_conn().map_err(Error::DbError)?;
+    if PackageUpload::delete_if_expired(upload.id, cutoff(ttl), &*conn).map_err(Error::DieselError)?
+    {
+        remove_session_file(data_path, upload.id);
+    }
+    Ok(())
+}
+
+pub fn sweep(config: &Config, db: &DbPool) -> Result<usize> {
+    let conn = db.get_conn().map_err(Error::DbError)?;
+    let expired = PackageUpload::delete_expired(cutoff(config.api.upload_session_ttl), &*conn)
+        .map_err(Error::DieselError)?;
+
+    for id in expired.iter() {
+        debug!("Expired upload session {}", id);
+        remove_session_file(&config.api.data_path, *id);
+    }
+
+    Ok(expired.len())
+}
+
+// Expires abandoned upload sessions for the lifetime of the server
+pub async fn start(config: Config, db: DbPool) {
+    let interval = Duration::from_secs(config.uploads.interval_sec);
+
+    loop {
+        actix_rt::time::delay_for(interval).await;
+
+        match sweep(&config, &db) {
+            Ok(0) => {}
+            Ok(count) => info!("Expired {} abandoned upload sessions", count),
+            Err(err) => warn!("Upload session sweep failed, err={}", err),
+        }
+    }
+}
@@ -15,6 +15,7 @@
 use std::path::PathBuf;
 
 use chrono::{self,
+             DateTime,
              Duration,
              LocalResult::Single,
              TimeZone,
@@ -47,11 +48,21 @@ pub fn generate_bldr_token(key_dir: &PathBuf) -> Result<String> {
                           Duration::hours(BUILDER_TOKEN_LIFETIME_HOURS))
 }
 
-pub fn generate_user_token(key_dir: &PathBuf, account_id: u64, privileges: u32) -> Result<String> {
-    generate_access_token(key_dir,
-                          account_id,
-                          privileges,
-                          Duration::max_value() /* User tokens never expire, can only be revoked */)
+// The scopes, origins and channels of `restrictions` are carried in the token. Without an
+// expiry the token never expires and can only be revoked.
+pub fn generate_user_token(key_dir: &PathBuf,
+                           account_id: u64,
+                           privileges: u32,
+                           restrictions: &originsrv::AccessToken,
+                           expires: Option<DateTime<Utc>>)
+                           -> Result<String> {
+    let mut token = restrictions.clone();
+    token.set_account_id(account_id);
+    token.set_flags(privileges);
+    token.set_expires(expires.unwrap_or_else(|| chrono::MAX_DATE.and_hms(0, 0, 0))
+                             .timestamp());
+
+    encode_access_token(key_dir, &token)
 }
 
 pub fn generate_access_token(key_dir: &PathBuf,
@@ -68,7 +79,11 @@ pub fn generate_access_token(key_dir: &PathBuf,
     token.set_flags(flags);
     token.set_expires(expires);
 
-    let bytes = message::encode(&token).map_err(Error::Protocol)?;
+    encode_access_token(key_dir, &token)
+}
+
+fn encode_access_token(key_dir: &PathBuf, token: &originsrv::AccessToken) -> Result<String> {
+    let bytes = message::encode(token).map_err(Error::Protocol)?;
     let (ciphertext, _) = encrypt(key_dir, &bytes)?;
 
     Ok(format!("{}{}", ACCESS_TOKEN_PREFIX, ciphertext))
@@ -22,7 +22,8 @@ use rand::{distributions::Alphanumeric,
            thread_rng,
            Rng};
 
-use reqwest::{header::HeaderMap,
+use reqwest::{header::{HeaderMap,
+                       CONTENT_TYPE},
               Body,
               Response,
               StatusCode};
@@ -74,6 +75,26 @@ pub struct Package {
     pub config:      String,
 }
 
+// How a package was built, recorded by the worker which built it
+#[derive(Clone, Debug, Serialize)]
+pub struct PackageProvenance {
+    pub job_id:         u64,
+    pub group_id:       u64,
+    pub vcs_url:        String,
+    pub vcs_sha:        Option<String>,
+    pub worker:         String,
+    pub plan_path:      String,
+    pub studio_version: String,
+    pub hab_version:    String,
+    pub deps:           Vec<String>,
+}
+
+// The latest release of each package in a channel, for a single target
+#[derive(Clone, Deserialize)]
+struct ChannelPackages {
+    data: Vec<PackageIdent>,
+}
+
 #[derive(Clone)]
 pub struct ApiClient {
     inner:   HttpClient,
@@ -127,6 +148,34 @@ impl ApiClient {
         Ok(package)
     }
 
+    pub async fn list_channel_packages(&self,
+                                       origin: &str,
+                                       channel: &ChannelIdent,
+                                       target: &str,
+                                       token: Option<&str>)
+                                       -> Result<Vec<PackageIdent>> {
+        let url_path = format!("{}/v1/{}", self.url, channel_latest_path(origin, channel));
+        let mut query = HashMap::new();
+        query.insert("target", target);
+
+        let mut request = self.inner.get(&url_path).query(&query);
+
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+
+        let resp = request.send().await.map_err(Error::HttpClient)?;
+
+        if resp.status() != StatusCode::OK {
+            return Err(err_from_response(resp).await);
+        }
+
+        let body = resp.text().await?;
+        let packages: ChannelPackages =
+            serde_json::from_str::<ChannelPackages>(&body).map_err(Error::Serialization)?;
+        Ok(packages.data)
+    }
+
     pub async fn fetch_package<I, P>(&self,
                                      ident: &I,
                                      target: &str,
@@ -234,6 +283,38 @@ impl ApiClient {
         Ok(())
     }
 
+    pub async fn put_package_provenance(&self,
+                                        pa: &mut PackageArchive,
+                                        provenance: &PackageProvenance,
+                                        token: &str)
+                                        -> Result<()> {
+        let ident = pa.ident()?;
+        let target = pa.target()?;
+
+        let url_path = format!("{}/v1/{}/provenance", self.url, package_path(&ident));
+
+        let mut qparams: HashMap<&str, &str> = HashMap::new();
+        qparams.insert("target", &target);
+
+        let body = serde_json::to_string(provenance)?;
+
+        let resp = self.inner
+                       .put(&url_path)
+                       .query(&qparams)
+                       .header(CONTENT_TYPE, "application/json")
+                       .body(body)
+                       .bearer_auth(token)
+                       .send()
+                       .await
+                       .map_err(Error::HttpClient)?;
+
+        if resp.status() != StatusCode::OK {
+            return Err(err_from_response(resp).await);
+        }
+
+        Ok(())
+    }
+
     pub async fn fetch_origin_secret_key<P>(&self,
                                             origin: &str,
                                             token: &str,
@@ -248,6 +329,50 @@ impl ApiClient {
             .await
     }
 
+    pub async fn fetch_origin_public_key<P>(&self,
+                                            origin: &str,
+                                            revision: &str,
+                                            dst_path: P)
+                                            -> Result<PathBuf>
+        where P: AsRef<Path>
+    {
+        self.download(&origin_keys(origin, revision),
+                      &HashMap::new(),
+                      dst_path.as_ref(),
+                      None)
+            .await
+    }
+
+    pub async fn put_origin_public_key<P>(&self,
+                                          origin: &str,
+                                          revision: &str,
+                                          src_path: P,
+                                          token: &str)
+                                          -> Result<()>
+        where P: AsRef<Path>
+    {
+        let url_path = format!("{}/v1/{}", self.url, origin_keys(origin, revision));
+        let body: Body = tokio::fs::read(src_path.as_ref()).await
+                                                           .map_err(Error::IO)?
+                                                           .into();
+
+        let resp = self.inner
+                       .post(&url_path)
+                       .body(body)
+                       .bearer_auth(token)
+                       .send()
+                       .await
+                       .map_err(Error::HttpClient)?;
+
+        match resp.status() {
+            StatusCode::CREATED | StatusCode::CONFLICT => (), // Conflict means key already
+            // uploaded - return Ok
+            _ => return Err(err_from_response(resp).await),
+        }
+
+        Ok(())
+    }
+
     pub async fn create_channel(&self,
                                 origin: &str,
                                 channel: &ChannelIdent,
@@ -334,6 +459,14 @@ fn package_path<I>(package: &I) -> String
     format!("depot/pkgs/{}", package)
 }
 
+fn channel_latest_path(origin: &str, channel: &ChannelIdent) -> String {
+    format!("depot/channels/{}/{}/pkgs/_latest", origin, channel)
+}
+
+fn origin_keys(origin: &str, revision: &str) -> String {
+    format!("depot/origins/{}/keys/{}", origin, revision)
+}
+
 fn origin_secret_keys_latest(origin: &str) -> String {
     format!("depot/origins/{}/secret_keys/latest", origin)
 }
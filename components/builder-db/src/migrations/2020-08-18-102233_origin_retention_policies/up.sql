@@ -0,0 +1,16 @@
+CREATE SEQUENCE IF NOT EXISTS origin_retention_policies_id_seq;
+CREATE TABLE IF NOT EXISTS origin_retention_policies (
+    id bigint DEFAULT next_id_v1('origin_retention_policies_id_seq') PRIMARY KEY NOT NULL,
+    origin text NOT NULL,
+    channel text,
+    keep_latest integer,
+    max_age_days integer,
+    owner_id bigint NOT NULL,
+    created_at timestamp with time zone DEFAULT now(),
+    updated_at timestamp with time zone DEFAULT now(),
+    swept_at timestamp with time zone
+);
+
+-- A policy is either origin wide (channel is null) or scoped to a single channel
+CREATE UNIQUE INDEX IF NOT EXISTS origin_retention_policies_origin_channel_idx
+    ON origin_retention_policies (origin, COALESCE(channel, ''));
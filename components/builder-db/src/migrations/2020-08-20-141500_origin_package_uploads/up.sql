@@ -0,0 +1,19 @@
+CREATE SEQUENCE IF NOT EXISTS origin_package_uploads_id_seq;
+CREATE TABLE IF NOT EXISTS origin_package_uploads (
+    id bigint DEFAULT next_id_v1('origin_package_uploads_id_seq') PRIMARY KEY NOT NULL,
+    origin text NOT NULL,
+    ident text NOT NULL,
+    target text NOT NULL,
+    checksum text NOT NULL,
+    forced boolean NOT NULL DEFAULT false,
+    builder text,
+    owner_id bigint NOT NULL,
+    received_bytes bigint NOT NULL DEFAULT 0,
+    next_chunk integer NOT NULL DEFAULT 0,
+    created_at timestamp with time zone DEFAULT now(),
+    updated_at timestamp with time zone DEFAULT now(),
+    locked_at timestamp with time zone
+);
+
+CREATE INDEX IF NOT EXISTS origin_package_uploads_updated_at_idx
+    ON origin_package_uploads (updated_at);
@@ -0,0 +1,30 @@
+-- Whether uploads signed with a key revision the origin has not registered are rejected,
+-- or accepted and flagged for review
+ALTER TABLE origins ADD COLUMN IF NOT EXISTS reject_unknown_key_revisions boolean NOT NULL DEFAULT false;
+
+CREATE SEQUENCE IF NOT EXISTS origin_package_signer_flags_id_seq;
+CREATE TABLE IF NOT EXISTS origin_package_signer_flags (
+    id bigint DEFAULT next_id_v1('origin_package_signer_flags_id_seq') PRIMARY KEY NOT NULL,
+    origin text NOT NULL,
+    package_id bigint NOT NULL REFERENCES origin_packages(id) ON DELETE CASCADE,
+    ident text NOT NULL,
+    target text NOT NULL,
+    signer text NOT NULL,
+    owner_id bigint NOT NULL,
+    created_at timestamp with time zone DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS origin_package_signer_flags_origin_idx
+    ON origin_package_signer_flags (origin);
+
+CREATE OR REPLACE VIEW origins_with_secret_key AS
+  SELECT origins.name,
+     origins.owner_id,
+     origin_secret_keys.full_name AS private_key_name,
+     origins.default_package_visibility,
+     accounts.name AS owner_account,
+     origins.reject_unknown_key_revisions
+    FROM (origins
+     LEFT JOIN origin_secret_keys ON ((origins.name = origin_secret_keys.origin))
+     LEFT JOIN accounts ON ((origins.owner_id = accounts.id)))
+   ORDER BY origins.name, origin_secret_keys.full_name DESC;
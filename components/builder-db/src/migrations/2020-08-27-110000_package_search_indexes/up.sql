@@ -0,0 +1,28 @@
+--
+-- Indexes backing the package search filters. Filtering by origin is already
+-- covered by origin_packages_origin_name_target_index.
+--
+CREATE INDEX IF NOT EXISTS origin_packages_target_index ON origin_packages USING btree (target);
+CREATE INDEX IF NOT EXISTS origin_packages_created_at_index ON origin_packages USING btree (created_at);
+
+--
+-- Channel filters may span origins, so we need to find channels by name alone
+--
+CREATE INDEX IF NOT EXISTS origin_channels_name_index ON origin_channels USING btree (name);
+
+--
+-- Dependency filters. Fully qualified idents are matched with an array contains
+-- against deps directly. Anything less specific is first narrowed down by the
+-- origin/name of each dependency, which needs an immutable function to be used
+-- in an expression index.
+--
+CREATE INDEX IF NOT EXISTS origin_packages_deps_index ON origin_packages USING GIN (deps);
+
+CREATE OR REPLACE FUNCTION package_dep_names_v1(deps text[]) RETURNS text[]
+    LANGUAGE sql IMMUTABLE
+    AS $$
+        SELECT COALESCE(array_agg(split_part(dep, '/', 1) || '/' || split_part(dep, '/', 2)), '{}')
+        FROM unnest(deps) AS dep
+    $$;
+
+CREATE INDEX IF NOT EXISTS origin_packages_dep_names_index ON origin_packages USING GIN (package_dep_names_v1(deps));
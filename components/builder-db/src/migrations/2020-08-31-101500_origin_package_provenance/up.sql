@@ -0,0 +1,19 @@
+-- How a package was built. Packages built by a worker are attested with the details of the
+-- build job, anything else uploaded is recorded as unattested with no build details.
+CREATE SEQUENCE IF NOT EXISTS origin_package_provenance_id_seq;
+CREATE TABLE IF NOT EXISTS origin_package_provenance (
+    id bigint DEFAULT next_id_v1('origin_package_provenance_id_seq') PRIMARY KEY NOT NULL,
+    package_id bigint NOT NULL UNIQUE REFERENCES origin_packages(id) ON DELETE CASCADE,
+    attested boolean NOT NULL DEFAULT false,
+    job_id bigint,
+    group_id bigint,
+    vcs_url text,
+    vcs_sha text,
+    worker text,
+    plan_path text,
+    studio_version text,
+    hab_version text,
+    deps text[] NOT NULL DEFAULT '{}',
+    created_at timestamp with time zone DEFAULT now(),
+    updated_at timestamp with time zone DEFAULT now()
+);
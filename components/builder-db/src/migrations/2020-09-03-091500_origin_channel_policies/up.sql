@@ -0,0 +1,16 @@
+-- Gates a promotion into a channel has to pass. Every rule is optional, a policy with none
+-- of them set allows anything.
+CREATE SEQUENCE IF NOT EXISTS origin_channel_policies_id_seq;
+CREATE TABLE IF NOT EXISTS origin_channel_policies (
+    id bigint DEFAULT next_id_v1('origin_channel_policies_id_seq') PRIMARY KEY NOT NULL,
+    origin text NOT NULL,
+    channel text NOT NULL,
+    require_channel text,
+    require_tdeps_in_channel boolean NOT NULL DEFAULT false,
+    require_worker_build boolean NOT NULL DEFAULT false,
+    min_member_role origin_member_role,
+    owner_id bigint NOT NULL,
+    created_at timestamp with time zone DEFAULT now(),
+    updated_at timestamp with time zone DEFAULT now(),
+    UNIQUE (origin, channel)
+);
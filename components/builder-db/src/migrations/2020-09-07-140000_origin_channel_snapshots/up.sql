@@ -0,0 +1,13 @@
+-- Named copies of a channel's package membership at a point in time. Snapshots are never
+-- updated, a channel can be reset to one of them later.
+CREATE SEQUENCE IF NOT EXISTS origin_channel_snapshots_id_seq;
+CREATE TABLE IF NOT EXISTS origin_channel_snapshots (
+    id bigint DEFAULT next_id_v1('origin_channel_snapshots_id_seq') PRIMARY KEY NOT NULL,
+    origin text NOT NULL,
+    channel text NOT NULL,
+    name text NOT NULL,
+    package_ids bigint[] NOT NULL DEFAULT '{}',
+    owner_id bigint NOT NULL,
+    created_at timestamp with time zone DEFAULT now(),
+    UNIQUE (origin, channel, name)
+);
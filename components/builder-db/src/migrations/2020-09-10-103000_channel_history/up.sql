@@ -0,0 +1,14 @@
+-- Lets the entries of the channel audit tables be referred to, eg. to revert them. Both
+-- tables draw from the same sequence so an id identifies an entry in either of them.
+CREATE SEQUENCE IF NOT EXISTS audit_package_channel_id_seq;
+ALTER TABLE audit_package ADD COLUMN IF NOT EXISTS id bigint NOT NULL DEFAULT next_id_v1('audit_package_channel_id_seq');
+ALTER TABLE audit_package_group ADD COLUMN IF NOT EXISTS id bigint NOT NULL DEFAULT next_id_v1('audit_package_channel_id_seq');
+CREATE UNIQUE INDEX IF NOT EXISTS audit_package_id_idx ON audit_package(id);
+CREATE UNIQUE INDEX IF NOT EXISTS audit_package_group_id_idx ON audit_package_group(id);
+
+-- Older entries do not record the target of the package
+ALTER TABLE audit_package ADD COLUMN IF NOT EXISTS target text;
+
+CREATE INDEX IF NOT EXISTS audit_package_origin_channel_idx ON audit_package(origin, channel, created_at);
+CREATE INDEX IF NOT EXISTS audit_package_group_origin_channel_idx ON audit_package_group(origin, channel, created_at);
+CREATE INDEX IF NOT EXISTS audit_package_group_group_id_idx ON audit_package_group(group_id);
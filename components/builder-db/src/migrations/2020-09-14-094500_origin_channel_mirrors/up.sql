@@ -0,0 +1,31 @@
+CREATE SEQUENCE IF NOT EXISTS origin_channel_mirrors_id_seq;
+CREATE TABLE IF NOT EXISTS origin_channel_mirrors (
+    id bigint DEFAULT next_id_v1('origin_channel_mirrors_id_seq') PRIMARY KEY NOT NULL,
+    origin text NOT NULL,
+    channel text NOT NULL,
+    upstream_url text NOT NULL,
+    include text[] NOT NULL DEFAULT '{}',
+    exclude text[] NOT NULL DEFAULT '{}',
+    owner_id bigint NOT NULL,
+    created_at timestamp with time zone DEFAULT now(),
+    updated_at timestamp with time zone DEFAULT now(),
+    claimed_at timestamp with time zone,
+    UNIQUE (origin, channel)
+);
+
+CREATE TYPE origin_channel_mirror_status AS ENUM ('running', 'succeeded', 'failed');
+
+CREATE SEQUENCE IF NOT EXISTS origin_channel_mirror_runs_id_seq;
+CREATE TABLE IF NOT EXISTS origin_channel_mirror_runs (
+    id bigint DEFAULT next_id_v1('origin_channel_mirror_runs_id_seq') PRIMARY KEY NOT NULL,
+    mirror_id bigint NOT NULL REFERENCES origin_channel_mirrors(id) ON DELETE CASCADE,
+    status origin_channel_mirror_status NOT NULL DEFAULT 'running',
+    uploaded integer NOT NULL DEFAULT 0,
+    promoted integer NOT NULL DEFAULT 0,
+    errors text[] NOT NULL DEFAULT '{}',
+    started_at timestamp with time zone DEFAULT now(),
+    finished_at timestamp with time zone
+);
+
+CREATE INDEX IF NOT EXISTS origin_channel_mirror_runs_mirror_id_idx
+    ON origin_channel_mirror_runs (mirror_id, started_at);
@@ -0,0 +1,10 @@
+-- Channel changes made by importing an offline bundle
+ALTER TYPE package_channel_trigger RENAME TO package_channel_trigger_old;
+CREATE TYPE package_channel_trigger AS ENUM ('unknown', 'builder_ui', 'hab_client', 'import');
+
+ALTER TABLE audit_package ALTER COLUMN trigger SET DATA TYPE package_channel_trigger
+    USING trigger::text::package_channel_trigger;
+ALTER TABLE audit_package_group ALTER COLUMN trigger SET DATA TYPE package_channel_trigger
+    USING trigger::text::package_channel_trigger;
+
+DROP TYPE package_channel_trigger_old;
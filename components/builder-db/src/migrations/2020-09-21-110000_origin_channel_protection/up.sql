@@ -0,0 +1,6 @@
+-- Write protection of a channel. The minimum roles are in addition to the role the endpoint
+-- itself requires, and an empty allow-list lets every member with the role through.
+ALTER TABLE origin_channels ADD COLUMN IF NOT EXISTS promote_min_role origin_member_role;
+ALTER TABLE origin_channels ADD COLUMN IF NOT EXISTS demote_min_role origin_member_role;
+ALTER TABLE origin_channels ADD COLUMN IF NOT EXISTS no_delete boolean NOT NULL DEFAULT false;
+ALTER TABLE origin_channels ADD COLUMN IF NOT EXISTS allowed_accounts bigint[] NOT NULL DEFAULT '{}';
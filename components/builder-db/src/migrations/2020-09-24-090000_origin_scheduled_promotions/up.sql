@@ -0,0 +1,52 @@
+-- Channel changes made by a scheduled promotion
+ALTER TYPE package_channel_trigger RENAME TO package_channel_trigger_old;
+CREATE TYPE package_channel_trigger AS ENUM ('unknown', 'builder_ui', 'hab_client', 'import', 'scheduled');
+
+ALTER TABLE audit_package ALTER COLUMN trigger SET DATA TYPE package_channel_trigger
+    USING trigger::text::package_channel_trigger;
+ALTER TABLE audit_package_group ALTER COLUMN trigger SET DATA TYPE package_channel_trigger
+    USING trigger::text::package_channel_trigger;
+
+DROP TYPE package_channel_trigger_old;
+
+CREATE TYPE origin_scheduled_promotion_state AS ENUM ('pending', 'running', 'succeeded', 'failed', 'canceled');
+
+-- A promotion of either the idents or the job group, run once execute_at has passed
+CREATE SEQUENCE IF NOT EXISTS origin_scheduled_promotions_id_seq;
+CREATE TABLE IF NOT EXISTS origin_scheduled_promotions (
+    id bigint DEFAULT next_id_v1('origin_scheduled_promotions_id_seq') PRIMARY KEY NOT NULL,
+    origin text NOT NULL,
+    channel text NOT NULL,
+    target text NOT NULL,
+    idents text[] NOT NULL DEFAULT '{}',
+    group_id bigint,
+    execute_at timestamp with time zone NOT NULL,
+    state origin_scheduled_promotion_state NOT NULL DEFAULT 'pending',
+    requester_id bigint NOT NULL,
+    requester_name text NOT NULL,
+    -- Why the promotion as a whole could not run, the items record their own failures
+    error text,
+    created_at timestamp with time zone DEFAULT now(),
+    finished_at timestamp with time zone,
+    -- When a scheduler last claimed the promotion, a run is given up on once its lease is over
+    claimed_at timestamp with time zone
+);
+
+CREATE INDEX IF NOT EXISTS origin_scheduled_promotions_due_idx
+    ON origin_scheduled_promotions (state, execute_at);
+CREATE INDEX IF NOT EXISTS origin_scheduled_promotions_origin_idx
+    ON origin_scheduled_promotions (origin, execute_at);
+
+CREATE SEQUENCE IF NOT EXISTS origin_scheduled_promotion_items_id_seq;
+CREATE TABLE IF NOT EXISTS origin_scheduled_promotion_items (
+    id bigint DEFAULT next_id_v1('origin_scheduled_promotion_items_id_seq') PRIMARY KEY NOT NULL,
+    promotion_id bigint NOT NULL REFERENCES origin_scheduled_promotions(id) ON DELETE CASCADE,
+    ident text NOT NULL,
+    target text NOT NULL,
+    succeeded boolean NOT NULL,
+    error text,
+    created_at timestamp with time zone DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS origin_scheduled_promotion_items_promotion_id_idx
+    ON origin_scheduled_promotion_items (promotion_id);
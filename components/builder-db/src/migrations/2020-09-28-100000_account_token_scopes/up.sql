@@ -0,0 +1,11 @@
+ALTER TABLE account_tokens DROP CONSTRAINT IF EXISTS account_tokens_account_id_key;
+
+-- Tokens made before tokens were named keep the name the legacy endpoint replaces
+ALTER TABLE account_tokens ADD COLUMN IF NOT EXISTS name text NOT NULL DEFAULT 'default';
+ALTER TABLE account_tokens ADD COLUMN IF NOT EXISTS scopes text[] NOT NULL DEFAULT '{}';
+ALTER TABLE account_tokens ADD COLUMN IF NOT EXISTS origins text[] NOT NULL DEFAULT '{}';
+ALTER TABLE account_tokens ADD COLUMN IF NOT EXISTS channels text[] NOT NULL DEFAULT '{}';
+ALTER TABLE account_tokens ADD COLUMN IF NOT EXISTS expires_at timestamptz;
+ALTER TABLE account_tokens ADD COLUMN IF NOT EXISTS last_used_at timestamptz;
+
+ALTER TABLE account_tokens ADD CONSTRAINT account_tokens_account_id_name_key UNIQUE (account_id, name);
@@ -0,0 +1,4 @@
+-- Service accounts are accounts owned by an origin rather than by an OAuth identity. They are
+-- removed along with their tokens and membership when the origin is deleted or transferred.
+ALTER TABLE accounts ADD COLUMN IF NOT EXISTS service_origin text;
+CREATE INDEX IF NOT EXISTS accounts_service_origin_idx ON accounts(service_origin);
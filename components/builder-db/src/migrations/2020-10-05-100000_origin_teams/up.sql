@@ -0,0 +1,22 @@
+-- Named groups of origin members. A member's effective role is the highest of their own role
+-- and the roles of the teams they are in.
+CREATE SEQUENCE IF NOT EXISTS origin_teams_id_seq;
+CREATE TABLE IF NOT EXISTS origin_teams (
+    id bigint DEFAULT next_id_v1('origin_teams_id_seq') PRIMARY KEY NOT NULL,
+    origin text NOT NULL REFERENCES origins(name) ON DELETE CASCADE,
+    name text NOT NULL,
+    member_role origin_member_role NOT NULL,
+    owner_id bigint NOT NULL,
+    created_at timestamp with time zone DEFAULT now(),
+    updated_at timestamp with time zone DEFAULT now(),
+    UNIQUE (origin, name)
+);
+
+CREATE TABLE IF NOT EXISTS origin_team_members (
+    team_id bigint NOT NULL REFERENCES origin_teams(id) ON DELETE CASCADE,
+    account_id bigint NOT NULL,
+    created_at timestamp with time zone DEFAULT now(),
+    PRIMARY KEY (team_id, account_id)
+);
+
+CREATE INDEX IF NOT EXISTS origin_team_members_account_id_idx ON origin_team_members(account_id);
@@ -0,0 +1,26 @@
+-- Key rotation and revocation are recorded in the origin audit log
+ALTER TYPE origin_operation RENAME TO origin_operation_old;
+CREATE TYPE origin_operation AS ENUM ('origin_create', 'origin_delete', 'owner_transfer', 'key_rotate', 'key_revoke');
+
+ALTER TABLE audit_origin ALTER COLUMN operation SET DATA TYPE origin_operation
+    USING operation::text::origin_operation;
+
+DROP TYPE origin_operation_old;
+
+-- Retired revisions are still served for verification, but their secret keys are no longer
+-- handed out. Uploads signed with a revoked revision are refused.
+CREATE TYPE origin_key_state AS ENUM ('active', 'retired', 'revoked');
+
+ALTER TABLE origin_public_keys ADD COLUMN IF NOT EXISTS state origin_key_state NOT NULL DEFAULT 'active';
+ALTER TABLE origin_public_keys ADD COLUMN IF NOT EXISTS state_changed_at timestamp with time zone;
+
+-- The key revision each uploaded package was signed with
+CREATE TABLE IF NOT EXISTS origin_package_signers (
+    package_id bigint PRIMARY KEY NOT NULL REFERENCES origin_packages(id) ON DELETE CASCADE,
+    origin text NOT NULL,
+    signer text NOT NULL,
+    created_at timestamp with time zone DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS origin_package_signers_origin_signer_idx
+    ON origin_package_signers (origin, signer);
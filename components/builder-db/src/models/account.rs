@@ -1,36 +1,66 @@
 use super::db_id_format;
-use chrono::NaiveDateTime;
+use chrono::{DateTime,
+             NaiveDateTime,
+             Utc};
 use diesel::{self,
+             dsl::now,
              pg::PgConnection,
-             result::QueryResult,
+             result::{Error,
+                      QueryResult},
+             Connection,
              ExpressionMethods,
+             NullableExpressionMethods,
              QueryDsl,
              RunQueryDsl};
 
 use crate::{bldr_core::metrics::CounterMetric,
             metrics::Counter,
-            schema::account::{account_tokens,
-                              accounts}};
+            models::origin::{OriginMember,
+                             OriginMemberRole},
+            schema::{account::{account_tokens,
+                               accounts},
+                     member::origin_members,
+                     team::origin_team_members}};
 
 #[derive(Debug, Identifiable, Serialize, Queryable)]
 pub struct Account {
     #[serde(with = "db_id_format")]
-    pub id:         i64,
-    pub email:      String,
-    pub name:       String,
-    pub created_at: Option<NaiveDateTime>,
-    pub updated_at: Option<NaiveDateTime>,
+    pub id:             i64,
+    pub email:          String,
+    pub name:           String,
+    pub created_at:     Option<NaiveDateTime>,
+    pub updated_at:     Option<NaiveDateTime>,
+    // The origin owning the account when it is a service account
+    pub service_origin: Option<String>,
 }
 
 #[derive(Identifiable, Debug, Serialize, Queryable)]
 #[table_name = "account_tokens"]
 pub struct AccountToken {
     #[serde(with = "db_id_format")]
-    pub id:         i64,
+    pub id:           i64,
     #[serde(with = "db_id_format")]
-    pub account_id: i64,
-    pub token:      String,
-    pub created_at: Option<NaiveDateTime>,
+    pub account_id:   i64,
+    pub token:        String,
+    pub created_at:   Option<NaiveDateTime>,
+    pub name:         String,
+    // Empty when the token may do anything the account can do
+    pub scopes:       Vec<String>,
+    pub origins:      Vec<String>,
+    pub channels:     Vec<String>,
+    pub expires_at:   Option<DateTime<Utc>>,
+    // Updated when the token is looked up, not on every request made with it
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Queryable)]
+pub struct ServiceAccount {
+    #[serde(with = "db_id_format")]
+    pub id:          i64,
+    pub name:        String,
+    pub origin:      String,
+    pub member_role: OriginMemberRole,
+    pub created_at:  Option<NaiveDateTime>,
 }
 
 #[derive(Insertable)]
@@ -80,31 +110,157 @@ impl Account {
     }
 }
 
+impl ServiceAccount {
+    // The account name of a service account of the origin. OAuth providers do not allow an @
+    // in user names, so it can not clash with the account of a person.
+    pub fn account_name(origin: &str, name: &str) -> String { format!("{}@{}", name, origin) }
+
+    pub fn list(origin: &str, conn: &PgConnection) -> QueryResult<Vec<ServiceAccount>> {
+        Counter::DBCall.increment();
+        accounts::table.inner_join(origin_members::table)
+                       .select((accounts::id,
+                                accounts::name,
+                                origin_members::origin,
+                                origin_members::member_role,
+                                accounts::created_at))
+                       .filter(accounts::service_origin.eq(origin))
+                       .filter(origin_members::origin.eq(origin))
+                       .order(accounts::name.asc())
+                       .get_results(conn)
+    }
+
+    pub fn get(origin: &str, name: &str, conn: &PgConnection) -> QueryResult<Account> {
+        Counter::DBCall.increment();
+        accounts::table.filter(accounts::name.eq(ServiceAccount::account_name(origin, name)))
+                       .filter(accounts::service_origin.eq(origin))
+                       .get_result(conn)
+    }
+
+    // Creates the account and makes it a member of the origin with the role
+    pub fn create(origin: &str,
+                  name: &str,
+                  member_role: OriginMemberRole,
+                  conn: &PgConnection)
+                  -> QueryResult<Account> {
+        Counter::DBCall.increment();
+        conn.transaction::<_, Error, _>(|| {
+                let account: Account =
+                    diesel::insert_into(accounts::table)
+                        .values((accounts::name.eq(ServiceAccount::account_name(origin, name)),
+                                 accounts::email.eq(""),
+                                 accounts::service_origin.eq(origin)))
+                        .get_result(conn)?;
+                OriginMember::add(origin, account.id, conn, member_role)?;
+                Ok(account)
+            })
+    }
+
+    // The tokens of the service accounts of the origin, to purge their sessions once the
+    // accounts are deleted
+    pub fn tokens(origin: &str, conn: &PgConnection) -> QueryResult<Vec<AccountToken>> {
+        Counter::DBCall.increment();
+        account_tokens::table
+            .filter(account_tokens::account_id.eq_any(
+                accounts::table.select(accounts::id)
+                               .filter(accounts::service_origin.eq(origin)),
+            ))
+            .select(account_tokens::all_columns)
+            .get_results(conn)
+    }
+
+    // Deletes the service account along with its tokens, teams and membership
+    pub fn delete(account_id: i64, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        conn.transaction::<_, Error, _>(|| {
+                diesel::delete(account_tokens::table.filter(account_tokens::account_id.eq(account_id)))
+                    .execute(conn)?;
+                diesel::delete(origin_team_members::table.filter(origin_team_members::account_id.eq(account_id)))
+                    .execute(conn)?;
+                diesel::delete(origin_members::table.filter(origin_members::account_id.eq(account_id)))
+                    .execute(conn)?;
+                diesel::delete(accounts::table.find(account_id).filter(accounts::service_origin.is_not_null()))
+                    .execute(conn)
+            })
+    }
+
+    // Deletes every service account of the origin, used when the origin changes hands or is
+    // deleted
+    pub fn delete_all(origin: &str, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        let ids = || {
+            accounts::table.select(accounts::id)
+                           .filter(accounts::service_origin.eq(origin))
+        };
+        conn.transaction::<_, Error, _>(|| {
+            diesel::delete(account_tokens::table.filter(account_tokens::account_id.eq_any(ids())))
+                    .execute(conn)?;
+            diesel::delete(origin_team_members::table.filter(origin_team_members::account_id.eq_any(ids())))
+                    .execute(conn)?;
+            diesel::delete(origin_members::table.filter(origin_members::account_id.eq_any(ids())))
+                    .execute(conn)?;
+            diesel::delete(accounts::table.filter(accounts::service_origin.eq(origin)))
+                    .execute(conn)
+        })
+    }
+}
+
 #[derive(Insertable)]
 #[table_name = "account_tokens"]
 pub struct NewAccountToken<'a> {
     pub account_id: i64,
     pub token:      &'a str,
+    pub name:       &'a str,
+    pub scopes:     Vec<String>,
+    pub origins:    Vec<String>,
+    pub channels:   Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 impl AccountToken {
     pub fn list(account_id: u64, conn: &PgConnection) -> QueryResult<Vec<AccountToken>> {
         Counter::DBCall.increment();
         account_tokens::table.filter(account_tokens::account_id.eq(account_id as i64))
+                             .order(account_tokens::name.asc())
                              .get_results(conn)
     }
 
+    pub fn get(account_id: u64, name: &str, conn: &PgConnection) -> QueryResult<AccountToken> {
+        Counter::DBCall.increment();
+        account_tokens::table.filter(account_tokens::account_id.eq(account_id as i64))
+                             .filter(account_tokens::name.eq(name))
+                             .get_result(conn)
+    }
+
+    // Creates the token, replacing the account's token of the same name
     pub fn create(req: &NewAccountToken, conn: &PgConnection) -> QueryResult<AccountToken> {
         Counter::DBCall.increment();
-        diesel::insert_into(account_tokens::table).values(req)
-                                                  .on_conflict(account_tokens::account_id)
-                                                  .do_update()
-                                                  .set(account_tokens::token.eq(req.token))
-                                                  .get_result(conn)
+        diesel::insert_into(account_tokens::table)
+            .values(req)
+            .on_conflict((account_tokens::account_id, account_tokens::name))
+            .do_update()
+            .set((account_tokens::token.eq(req.token),
+                  account_tokens::scopes.eq(&req.scopes),
+                  account_tokens::origins.eq(&req.origins),
+                  account_tokens::channels.eq(&req.channels),
+                  account_tokens::expires_at.eq(req.expires_at),
+                  account_tokens::created_at.eq(now.nullable()),
+                  account_tokens::last_used_at.eq(None::<DateTime<Utc>>)))
+            .get_result(conn)
+    }
+
+    pub fn touch(id: i64, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::update(account_tokens::table.find(id)).set(account_tokens::last_used_at.eq(now.nullable()))
+                                                      .execute(conn)
     }
 
-    pub fn delete(id: u64, conn: &PgConnection) -> QueryResult<usize> {
+    // Deletes a token of the account. Returns the number of rows deleted.
+    pub fn delete(id: u64, account_id: u64, conn: &PgConnection) -> QueryResult<usize> {
         Counter::DBCall.increment();
-        diesel::delete(account_tokens::table.find(id as i64)).execute(conn)
+        diesel::delete(
+            account_tokens::table.find(id as i64)
+                                 .filter(account_tokens::account_id.eq(account_id as i64)),
+        )
+        .execute(conn)
     }
 }
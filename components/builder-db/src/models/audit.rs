@@ -0,0 +1,502 @@
+use super::db_id_format;
+use chrono::{DateTime,
+             TimeZone,
+             Utc};
+use std::{fmt,
+          str::FromStr};
+
+use diesel::{pg::PgConnection,
+             result::QueryResult,
+             BoolExpressionMethods,
+             ExpressionMethods,
+             QueryDsl,
+             RunQueryDsl,
+             TextExpressionMethods};
+
+use crate::{models::{channel::{PackageChannelOperation,
+                               PackageChannelTrigger},
+                     origin::OriginOperation},
+            protocol::jobsrv,
+            schema::audit::{audit_origin,
+                            audit_package,
+                            audit_package_group}};
+
+use crate::{bldr_core::metrics::CounterMetric,
+            metrics::Counter};
+
+// Where an audit event was recorded. Events with the same timestamp are ordered by
+// their source, so the order of the variants is part of the cursor format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditSource {
+    Origin,
+    Package,
+    PackageGroup,
+    JobGroup,
+}
+
+impl AuditSource {
+    const ALL: [AuditSource; 4] = [AuditSource::Origin,
+                                   AuditSource::Package,
+                                   AuditSource::PackageGroup,
+                                   AuditSource::JobGroup];
+}
+
+impl fmt::Display for AuditSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match *self {
+            AuditSource::Origin => "origin",
+            AuditSource::Package => "package",
+            AuditSource::PackageGroup => "package_group",
+            AuditSource::JobGroup => "job_group",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+// A single entry of the audit timeline of an origin. The target object is what the
+// operation applied to: the origin, account or key revision for origin events, the
+// package ident for package events, the job group id for package group events and
+// the project for job group events.
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditEvent {
+    pub source:         AuditSource,
+    #[serde(with = "db_id_format")]
+    pub id:             i64,
+    pub operation:      String,
+    pub trigger:        Option<String>,
+    #[serde(with = "db_id_format")]
+    pub requester_id:   i64,
+    pub requester_name: String,
+    pub target_object:  String,
+    pub channel:        Option<String>,
+    pub created_at:     DateTime<Utc>,
+}
+
+// The position of an event in the timeline, which runs from the newest event to the
+// oldest. Job group events have no id of their own and are positioned by group id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AuditCursor {
+    pub created_at: DateTime<Utc>,
+    pub source:     AuditSource,
+    pub id:         i64,
+}
+
+// How a source has to be filtered to only return the events that come after a cursor
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuditBound {
+    Before(DateTime<Utc>),
+    AtOrBefore(DateTime<Utc>),
+    BeforeId(DateTime<Utc>, i64),
+}
+
+impl AuditCursor {
+    pub fn bound(&self, source: AuditSource) -> AuditBound {
+        if source < self.source {
+            AuditBound::AtOrBefore(self.created_at)
+        } else if source > self.source {
+            AuditBound::Before(self.created_at)
+        } else {
+            AuditBound::BeforeId(self.created_at, self.id)
+        }
+    }
+}
+
+impl fmt::Display for AuditCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{}_{}_{}",
+               self.created_at.timestamp() * 1_000_000
+               + i64::from(self.created_at.timestamp_subsec_micros()),
+               self.source as u8,
+               self.id)
+    }
+}
+
+impl FromStr for AuditCursor {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Invalid audit cursor: {}", value);
+        let parts: Vec<&str> = value.split('_').collect();
+        if parts.len() != 3 {
+            return Err(err());
+        }
+        let micros = parts[0].parse::<i64>().map_err(|_| err())?;
+        let source = parts[1].parse::<usize>()
+                             .ok()
+                             .and_then(|i| AuditSource::ALL.get(i).copied())
+                             .ok_or_else(err)?;
+        let id = parts[2].parse::<i64>().map_err(|_| err())?;
+        let created_at = Utc.timestamp_opt(micros.div_euclid(1_000_000),
+                                           (micros.rem_euclid(1_000_000) * 1000) as u32)
+                            .single()
+                            .ok_or_else(err)?;
+        Ok(AuditCursor { created_at,
+                         source,
+                         id })
+    }
+}
+
+pub struct AuditFilter<'a> {
+    pub origin:         &'a str,
+    pub operation:      Option<&'a str>,
+    pub requester_name: Option<&'a str>,
+    pub target_object:  Option<&'a str>,
+    pub from:           Option<DateTime<Utc>>,
+    pub to:             Option<DateTime<Utc>>,
+    pub cursor:         Option<AuditCursor>,
+    pub limit:          i64,
+}
+
+const ORIGIN_OPERATIONS: [OriginOperation; 5] = [OriginOperation::OriginCreate,
+                                                 OriginOperation::OriginDelete,
+                                                 OriginOperation::OwnerTransfer,
+                                                 OriginOperation::KeyRotate,
+                                                 OriginOperation::KeyRevoke];
+
+const PACKAGE_OPERATIONS: [PackageChannelOperation; 2] = [PackageChannelOperation::Promote,
+                                                          PackageChannelOperation::Demote];
+
+const JOB_GROUP_OPERATIONS: [jobsrv::JobGroupOperation; 2] =
+    [jobsrv::JobGroupOperation::JobGroupOpCreate,
+     jobsrv::JobGroupOperation::JobGroupOpCancel];
+
+type OriginAuditRow = (i64, OriginOperation, i64, String, String, Option<DateTime<Utc>>);
+type PackageAuditRow = (i64,
+                        PackageChannelOperation,
+                        PackageChannelTrigger,
+                        i64,
+                        String,
+                        String,
+                        String,
+                        Option<DateTime<Utc>>);
+type PackageGroupAuditRow = (i64,
+                             PackageChannelOperation,
+                             PackageChannelTrigger,
+                             i64,
+                             String,
+                             i64,
+                             String,
+                             Option<DateTime<Utc>>);
+
+impl AuditEvent {
+    pub fn cursor(&self) -> AuditCursor {
+        AuditCursor { created_at: self.created_at,
+                      source:     self.source,
+                      id:         self.id, }
+    }
+
+    // The events recorded by builder-api itself. The job group events are kept by the
+    // job server and are merged in by the caller with `timeline`.
+    pub fn list(filter: &AuditFilter, conn: &PgConnection) -> QueryResult<Vec<AuditEvent>> {
+        let mut events = Self::origin_events(filter, conn)?;
+        events.append(&mut Self::package_events(filter, conn)?);
+        events.append(&mut Self::package_group_events(filter, conn)?);
+        Ok(Self::timeline(events, filter.limit))
+    }
+
+    // Orders events from several sources newest first and keeps the first `limit`
+    pub fn timeline(mut events: Vec<AuditEvent>, limit: i64) -> Vec<AuditEvent> {
+        events.sort_by(|a, b| b.cursor().cmp(&a.cursor()));
+        events.truncate(limit as usize);
+        events
+    }
+
+    // The job group operation named by an operation filter, if there is one
+    pub fn job_group_operation(name: &str) -> Option<jobsrv::JobGroupOperation> {
+        JOB_GROUP_OPERATIONS.iter()
+                            .copied()
+                            .find(|op| job_group_operation_name(*op).eq_ignore_ascii_case(name))
+    }
+
+    pub fn from_job_group_audit(audit: &jobsrv::JobGroupAudit) -> Option<AuditEvent> {
+        let created_at = match audit.get_created_at().parse::<DateTime<Utc>>() {
+            Ok(created_at) => created_at,
+            Err(err) => {
+                warn!("Skipping job group audit with invalid timestamp: {}", err);
+                return None;
+            }
+        };
+
+        Some(AuditEvent { source: AuditSource::JobGroup,
+                          id: audit.get_group_id() as i64,
+                          operation: job_group_operation_name(audit.get_operation()).to_string(),
+                          trigger: Some(format!("{:?}", audit.get_trigger())),
+                          requester_id: audit.get_requester_id() as i64,
+                          requester_name: audit.get_requester_name().to_string(),
+                          target_object: audit.get_project_name().to_string(),
+                          channel: None,
+                          created_at })
+    }
+
+    fn origin_events(filter: &AuditFilter, conn: &PgConnection) -> QueryResult<Vec<AuditEvent>> {
+        Counter::DBCall.increment();
+        let mut query = audit_origin::table.select((audit_origin::id,
+                                                    audit_origin::operation,
+                                                    audit_origin::requester_id,
+                                                    audit_origin::requester_name,
+                                                    audit_origin::target_object,
+                                                    audit_origin::created_at))
+                                           .filter(audit_origin::origin.eq(filter.origin))
+                                           .filter(audit_origin::created_at.is_not_null())
+                                           .into_boxed();
+        if let Some(name) = filter.operation {
+            match operation_named(&ORIGIN_OPERATIONS, name) {
+                Some(operation) => query = query.filter(audit_origin::operation.eq(operation)),
+                None => return Ok(Vec::new()),
+            }
+        }
+        if let Some(requester_name) = filter.requester_name {
+            query = query.filter(audit_origin::requester_name.eq(requester_name));
+        }
+        if let Some(target_object) = filter.target_object {
+            query = query.filter(audit_origin::target_object.eq(target_object));
+        }
+        if let Some(from) = filter.from {
+            query = query.filter(audit_origin::created_at.ge(from));
+        }
+        if let Some(to) = filter.to {
+            query = query.filter(audit_origin::created_at.lt(to));
+        }
+        if let Some(cursor) = filter.cursor {
+            query = match cursor.bound(AuditSource::Origin) {
+                AuditBound::Before(at) => query.filter(audit_origin::created_at.lt(at)),
+                AuditBound::AtOrBefore(at) => query.filter(audit_origin::created_at.le(at)),
+                AuditBound::BeforeId(at, id) => {
+                    query.filter(audit_origin::created_at.lt(at).or(audit_origin::created_at
+                                                                        .eq(at)
+                                                                        .and(audit_origin::id.lt(id))))
+                }
+            };
+        }
+
+        let rows: Vec<OriginAuditRow> = query.order((audit_origin::created_at.desc(),
+                                                     audit_origin::id.desc()))
+                                             .limit(filter.limit)
+                                             .get_results(conn)?;
+
+        Ok(rows.into_iter()
+               .filter_map(|(id, operation, requester_id, requester_name, target_object, at)| {
+                               Some(AuditEvent { source: AuditSource::Origin,
+                                                 id,
+                                                 operation: format!("{:?}", operation),
+                                                 trigger: None,
+                                                 requester_id,
+                                                 requester_name,
+                                                 target_object,
+                                                 channel: None,
+                                                 created_at: at? })
+                           })
+               .collect())
+    }
+
+    fn package_events(filter: &AuditFilter, conn: &PgConnection) -> QueryResult<Vec<AuditEvent>> {
+        Counter::DBCall.increment();
+        let mut query = audit_package::table.select((audit_package::id,
+                                                     audit_package::operation,
+                                                     audit_package::trigger,
+                                                     audit_package::requester_id,
+                                                     audit_package::requester_name,
+                                                     audit_package::package_ident,
+                                                     audit_package::channel,
+                                                     audit_package::created_at))
+                                            .filter(audit_package::origin.eq(filter.origin))
+                                            .filter(audit_package::created_at.is_not_null())
+                                            .into_boxed();
+        if let Some(name) = filter.operation {
+            match operation_named(&PACKAGE_OPERATIONS, name) {
+                Some(operation) => query = query.filter(audit_package::operation.eq(operation)),
+                None => return Ok(Vec::new()),
+            }
+        }
+        if let Some(requester_name) = filter.requester_name {
+            query = query.filter(audit_package::requester_name.eq(requester_name));
+        }
+        // A partial ident matches all the releases under it
+        if let Some(target_object) = filter.target_object {
+            query = query.filter(audit_package::package_ident.eq(target_object)
+                                                             .or(audit_package::package_ident
+                                                                     .like(like_prefix(target_object))));
+        }
+        if let Some(from) = filter.from {
+            query = query.filter(audit_package::created_at.ge(from));
+        }
+        if let Some(to) = filter.to {
+            query = query.filter(audit_package::created_at.lt(to));
+        }
+        if let Some(cursor) = filter.cursor {
+            query = match cursor.bound(AuditSource::Package) {
+                AuditBound::Before(at) => query.filter(audit_package::created_at.lt(at)),
+                AuditBound::AtOrBefore(at) => query.filter(audit_package::created_at.le(at)),
+                AuditBound::BeforeId(at, id) => {
+                    query.filter(audit_package::created_at.lt(at).or(audit_package::created_at
+                                                                         .eq(at)
+                                                                         .and(audit_package::id.lt(id))))
+                }
+            };
+        }
+
+        let rows: Vec<PackageAuditRow> = query.order((audit_package::created_at.desc(),
+                                                      audit_package::id.desc()))
+                                              .limit(filter.limit)
+                                              .get_results(conn)?;
+
+        Ok(rows.into_iter()
+               .filter_map(|(id,
+                             operation,
+                             trigger,
+                             requester_id,
+                             requester_name,
+                             package_ident,
+                             channel,
+                             at)| {
+                               Some(AuditEvent { source: AuditSource::Package,
+                                                 id,
+                                                 operation: format!("{:?}", operation),
+                                                 trigger: Some(format!("{:?}", trigger)),
+                                                 requester_id,
+                                                 requester_name,
+                                                 target_object: package_ident,
+                                                 channel: Some(channel),
+                                                 created_at: at? })
+                           })
+               .collect())
+    }
+
+    fn package_group_events(filter: &AuditFilter,
+                            conn: &PgConnection)
+                            -> QueryResult<Vec<AuditEvent>> {
+        Counter::DBCall.increment();
+        let mut query =
+            audit_package_group::table.select((audit_package_group::id,
+                                               audit_package_group::operation,
+                                               audit_package_group::trigger,
+                                               audit_package_group::requester_id,
+                                               audit_package_group::requester_name,
+                                               audit_package_group::group_id,
+                                               audit_package_group::channel,
+                                               audit_package_group::created_at))
+                                      .filter(audit_package_group::origin.eq(filter.origin))
+                                      .filter(audit_package_group::created_at.is_not_null())
+                                      .into_boxed();
+        if let Some(name) = filter.operation {
+            match operation_named(&PACKAGE_OPERATIONS, name) {
+                Some(operation) => {
+                    query = query.filter(audit_package_group::operation.eq(operation))
+                }
+                None => return Ok(Vec::new()),
+            }
+        }
+        if let Some(requester_name) = filter.requester_name {
+            query = query.filter(audit_package_group::requester_name.eq(requester_name));
+        }
+        if let Some(target_object) = filter.target_object {
+            match target_object.parse::<i64>() {
+                Ok(group_id) => query = query.filter(audit_package_group::group_id.eq(group_id)),
+                Err(_) => return Ok(Vec::new()),
+            }
+        }
+        if let Some(from) = filter.from {
+            query = query.filter(audit_package_group::created_at.ge(from));
+        }
+        if let Some(to) = filter.to {
+            query = query.filter(audit_package_group::created_at.lt(to));
+        }
+        if let Some(cursor) = filter.cursor {
+            query = match cursor.bound(AuditSource::PackageGroup) {
+                AuditBound::Before(at) => query.filter(audit_package_group::created_at.lt(at)),
+                AuditBound::AtOrBefore(at) => query.filter(audit_package_group::created_at.le(at)),
+                AuditBound::BeforeId(at, id) => {
+                    query.filter(audit_package_group::created_at.lt(at).or(
+                        audit_package_group::created_at.eq(at)
+                                                       .and(audit_package_group::id.lt(id)),
+                    ))
+                }
+            };
+        }
+
+        let rows: Vec<PackageGroupAuditRow> = query.order((audit_package_group::created_at.desc(),
+                                                           audit_package_group::id.desc()))
+                                                   .limit(filter.limit)
+                                                   .get_results(conn)?;
+
+        Ok(rows.into_iter()
+               .filter_map(|(id,
+                             operation,
+                             trigger,
+                             requester_id,
+                             requester_name,
+                             group_id,
+                             channel,
+                             at)| {
+                               Some(AuditEvent { source: AuditSource::PackageGroup,
+                                                 id,
+                                                 operation: format!("{:?}", operation),
+                                                 trigger: Some(format!("{:?}", trigger)),
+                                                 requester_id,
+                                                 requester_name,
+                                                 target_object: group_id.to_string(),
+                                                 channel: Some(channel),
+                                                 created_at: at? })
+                           })
+               .collect())
+    }
+}
+
+fn operation_named<T: Copy + fmt::Debug>(operations: &[T], name: &str) -> Option<T> {
+    operations.iter()
+              .copied()
+              .find(|op| format!("{:?}", op).eq_ignore_ascii_case(name))
+}
+
+fn job_group_operation_name(operation: jobsrv::JobGroupOperation) -> &'static str {
+    match operation {
+        jobsrv::JobGroupOperation::JobGroupOpCreate => "JobGroupCreate",
+        jobsrv::JobGroupOperation::JobGroupOpCancel => "JobGroupCancel",
+    }
+}
+
+// A LIKE pattern for everything below an ident, with the wildcards in it escaped
+fn like_prefix(ident: &str) -> String {
+    let escaped = ident.replace('\\', "\\\\")
+                       .replace('%', "\\%")
+                       .replace('_', "\\_");
+    format!("{}/%", escaped)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor = AuditCursor { created_at: Utc.ymd(2020, 10, 8)
+                                                  .and_hms_micro(10, 0, 0, 123_456),
+                                   source:     AuditSource::PackageGroup,
+                                   id:         1_217_629_530_451_320_832, };
+        let encoded = cursor.to_string();
+        assert_eq!(encoded.parse::<AuditCursor>().unwrap(), cursor);
+        assert!("1601_9_1".parse::<AuditCursor>().is_err());
+        assert!("garbage".parse::<AuditCursor>().is_err());
+    }
+
+    #[test]
+    fn cursor_out_of_range_is_an_error() {
+        assert!("9223372036854775807_0_0".parse::<AuditCursor>().is_err());
+        assert!("-9223372036854775808_0_0".parse::<AuditCursor>().is_err());
+    }
+
+    #[test]
+    fn cursor_bounds_follow_source_order() {
+        let at = Utc.ymd(2020, 10, 8).and_hms(10, 0, 0);
+        let cursor = AuditCursor { created_at: at,
+                                   source:     AuditSource::Package,
+                                   id:         42, };
+        assert_eq!(cursor.bound(AuditSource::Origin),
+                   AuditBound::AtOrBefore(at));
+        assert_eq!(cursor.bound(AuditSource::Package),
+                   AuditBound::BeforeId(at, 42));
+        assert_eq!(cursor.bound(AuditSource::JobGroup), AuditBound::Before(at));
+    }
+}
@@ -1,4 +1,5 @@
-use super::db_id_format;
+use super::{db_id_format,
+            db_id_vec_format};
 use chrono::NaiveDateTime;
 use std::time::Instant;
 
@@ -8,6 +9,11 @@ use diesel::{self,
              pg::{expression::dsl::any,
                   PgConnection},
              result::QueryResult,
+             sql_query,
+             sql_types::{Array,
+                         BigInt,
+                         Nullable,
+                         Text},
              ExpressionMethods,
              NullableExpressionMethods,
              PgArrayExpressionMethods,
@@ -16,8 +22,11 @@ use diesel::{self,
              Table,
              TextExpressionMethods};
 
-use crate::{models::{package::{BuilderPackageIdent,
+use crate::{models::{origin::OriginMemberRole,
+                     package::{BuilderPackageIdent,
+                               BuilderPackageTarget,
                                PackageVisibility,
+                               PackageVisibilityMapping,
                                PackageWithVersionArray},
                      pagination::Paginate},
             protocol::jobsrv::JobGroupTrigger,
@@ -39,13 +48,29 @@ use crate::{bldr_core::metrics::{CounterMetric,
 #[derive(AsExpression, Debug, Serialize, Deserialize, Queryable)]
 pub struct Channel {
     #[serde(with = "db_id_format")]
-    pub id:         i64,
+    pub id:               i64,
     #[serde(with = "db_id_format")]
-    pub owner_id:   i64,
-    pub name:       String,
-    pub created_at: Option<NaiveDateTime>,
-    pub updated_at: Option<NaiveDateTime>,
-    pub origin:     String,
+    pub owner_id:         i64,
+    pub name:             String,
+    pub created_at:       Option<NaiveDateTime>,
+    pub updated_at:       Option<NaiveDateTime>,
+    pub origin:           String,
+    pub promote_min_role: Option<OriginMemberRole>,
+    pub demote_min_role:  Option<OriginMemberRole>,
+    pub no_delete:        bool,
+    // Accounts allowed to write to the channel, anyone with the role when empty
+    #[serde(with = "db_id_vec_format")]
+    pub allowed_accounts: Vec<i64>,
+}
+
+#[derive(AsChangeset)]
+#[table_name = "origin_channels"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct ChannelProtection {
+    pub promote_min_role: Option<OriginMemberRole>,
+    pub demote_min_role:  Option<OriginMemberRole>,
+    pub no_delete:        bool,
+    pub allowed_accounts: Vec<i64>,
 }
 
 #[derive(Insertable)]
@@ -87,6 +112,62 @@ pub struct ListAllChannelPackagesForTarget<'a> {
     pub target:     &'a str,
 }
 
+pub struct DiffChannels<'a> {
+    pub visibility: &'a Vec<PackageVisibility>,
+    pub origin:     &'a str,
+    pub a:          &'a ChannelIdent,
+    pub b:          &'a ChannelIdent,
+    pub targets:    &'a [String],
+    pub start:      i64,
+    pub limit:      i64,
+}
+
+// A name whose latest release on a target differs between two channels
+#[derive(Debug, QueryableByName)]
+pub struct ChannelDiffRow {
+    #[sql_type = "Text"]
+    pub name:   String,
+    #[sql_type = "Text"]
+    pub target: String,
+    #[sql_type = "Nullable<Text>"]
+    pub a:      Option<BuilderPackageIdent>,
+    #[sql_type = "Nullable<Text>"]
+    pub b:      Option<BuilderPackageIdent>,
+}
+
+#[derive(QueryableByName)]
+struct ChannelDiffCount {
+    #[sql_type = "BigInt"]
+    total_count: i64,
+}
+
+// The latest release of each name and target in either channel, ordered the same way as
+// list_latest_packages, paired up across the channels. Both the page and the count of all
+// the differences are selected from it.
+const DIFF_CHANNELS_CTE: &str = "
+    WITH latest AS (
+        SELECT DISTINCT ON (c.name, p.name, p.target) c.name AS channel, p.name, p.target, p.ident
+        FROM origin_packages_with_version_array p
+        INNER JOIN origin_channel_packages cp ON cp.package_id = p.id
+        INNER JOIN origin_channels c ON c.id = cp.channel_id
+        WHERE p.origin = $1 AND c.origin = $1 AND c.name IN ($2, $3)
+            AND p.target = ANY($4) AND p.visibility = ANY($5)
+        ORDER BY c.name, p.name, p.target,
+            string_to_array(p.version_array[1],'.')::numeric[] DESC,
+            p.version_array[2] DESC,
+            p.ident_array[4] DESC
+    ),
+    a AS (SELECT * FROM latest WHERE channel = $2),
+    b AS (SELECT * FROM latest WHERE channel = $3),
+    diff AS (
+        SELECT COALESCE(a.name, b.name) AS name,
+            COALESCE(a.target, b.target) AS target,
+            a.ident AS a,
+            b.ident AS b
+        FROM a FULL OUTER JOIN b ON a.name = b.name AND a.target = b.target
+        WHERE a.ident IS DISTINCT FROM b.ident
+    )";
+
 impl Channel {
     pub fn list(origin: &str,
                 include_sandbox_channels: bool,
@@ -115,6 +196,21 @@ impl Channel {
                                                    .get_result(conn)
     }
 
+    pub fn set_protection(origin: &str,
+                          channel: &ChannelIdent,
+                          protection: &ChannelProtection,
+                          conn: &PgConnection)
+                          -> QueryResult<Channel> {
+        Counter::DBCall.increment();
+        diesel::update(
+            origin_channels::table
+                .filter(origin_channels::origin.eq(origin))
+                .filter(origin_channels::name.eq(channel.as_str())),
+        )
+        .set(protection)
+        .get_result(conn)
+    }
+
     pub fn delete(origin: &str, channel: &ChannelIdent, conn: &PgConnection) -> QueryResult<usize> {
         Counter::DBCall.increment();
         diesel::delete(
@@ -208,6 +304,38 @@ impl Channel {
         result.map(|x| (channel, target, x))
     }
 
+    // One page of the names whose latest release differs between the channels, along with
+    // the total number of them
+    pub fn diff_latest(req: &DiffChannels,
+                       conn: &PgConnection)
+                       -> QueryResult<(Vec<ChannelDiffRow>, i64)> {
+        Counter::DBCall.increment();
+        let page = format!("{} SELECT * FROM diff ORDER BY 1, 2 LIMIT $6 OFFSET $7",
+                           DIFF_CHANNELS_CTE);
+        let rows: Vec<ChannelDiffRow> =
+            sql_query(page).bind::<Text, _>(req.origin)
+                           .bind::<Text, _>(req.a.as_str())
+                           .bind::<Text, _>(req.b.as_str())
+                           .bind::<Array<Text>, _>(req.targets)
+                           .bind::<Array<PackageVisibilityMapping>, _>(req.visibility)
+                           .bind::<BigInt, _>(req.limit)
+                           .bind::<BigInt, _>(req.start)
+                           .get_results(conn)?;
+
+        // Counted separately, since a page past the end has no rows to carry the total
+        Counter::DBCall.increment();
+        let count = format!("{} SELECT COUNT(*) AS total_count FROM diff",
+                            DIFF_CHANNELS_CTE);
+        let total: ChannelDiffCount =
+            sql_query(count).bind::<Text, _>(req.origin)
+                            .bind::<Text, _>(req.a.as_str())
+                            .bind::<Text, _>(req.b.as_str())
+                            .bind::<Array<Text>, _>(req.targets)
+                            .bind::<Array<PackageVisibilityMapping>, _>(req.visibility)
+                            .get_result(conn)?;
+        Ok((rows, total.total_count))
+    }
+
     pub fn list_packages(lcp: &ListChannelPackages,
                          conn: &PgConnection)
                          -> QueryResult<(Vec<BuilderPackageIdent>, i64)> {
@@ -289,6 +417,23 @@ impl Channel {
                               .first(conn)
     }
 
+    // The idents, built for the given target, which are in the named channel of their origin
+    pub fn filter_members(channel: &ChannelIdent,
+                          idents: &[String],
+                          target: &str,
+                          conn: &PgConnection)
+                          -> QueryResult<Vec<BuilderPackageIdent>> {
+        Counter::DBCall.increment();
+        origin_packages::table
+            .inner_join(origin_channel_packages::table.inner_join(origin_channels::table))
+            .filter(origin_channels::origin.eq(origin_packages::origin))
+            .filter(origin_channels::name.eq(channel.as_str()))
+            .filter(origin_packages::ident.eq(any(idents)))
+            .filter(origin_packages::target.eq(target))
+            .select(origin_packages::ident)
+            .get_results(conn)
+    }
+
     pub fn promote_packages(channel_id: i64,
                             package_ids: &[i64],
                             conn: &PgConnection)
@@ -324,6 +469,8 @@ pub enum PackageChannelTrigger {
     Unknown,
     BuilderUi,
     HabClient,
+    Import,
+    Scheduled,
 }
 
 impl From<JobGroupTrigger> for PackageChannelTrigger {
@@ -336,7 +483,7 @@ impl From<JobGroupTrigger> for PackageChannelTrigger {
     }
 }
 
-#[derive(DbEnum, Debug, Serialize, Deserialize)]
+#[derive(DbEnum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum PackageChannelOperation {
     Promote,
     Demote,
@@ -353,6 +500,7 @@ pub struct PackageChannelAudit<'a> {
     pub requester_id:   i64,
     pub requester_name: &'a str,
     pub origin:         &'a str,
+    pub target:         Option<BuilderPackageTarget>,
 }
 
 impl<'a> PackageChannelAudit<'a> {
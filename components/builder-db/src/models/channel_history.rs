@@ -0,0 +1,506 @@
+use super::db_id_format;
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+
+use diesel::{self,
+             pg::{expression::dsl::any,
+                  PgConnection},
+             result::{Error,
+                      QueryResult},
+             sql_query,
+             sql_types::{Array,
+                         BigInt,
+                         Bool,
+                         Nullable,
+                         Text,
+                         Timestamptz},
+             Connection,
+             ExpressionMethods,
+             QueryDsl,
+             RunQueryDsl};
+
+use crate::{models::{channel::{Channel,
+                               PackageChannelAudit,
+                               PackageChannelOperation,
+                               PackageChannelOperationMapping,
+                               PackageChannelTrigger,
+                               PackageChannelTriggerMapping,
+                               PackageGroupChannelAudit},
+                     package::{BuilderPackageIdent,
+                               BuilderPackageTarget}},
+            schema::{audit::{audit_package,
+                             audit_package_group},
+                     package::origin_packages}};
+
+use crate::{bldr_core::metrics::CounterMetric,
+            hab_core::ChannelIdent,
+            metrics::Counter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelHistoryKind {
+    // A single package moved through the promote and demote endpoints
+    Package,
+    // Packages moved together, by a job group or a bulk promotion
+    Group,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelHistoryPackage {
+    pub ident:  BuilderPackageIdent,
+    // Not recorded for single package entries written before targets were audited
+    pub target: Option<BuilderPackageTarget>,
+}
+
+// An entry of either of the channel audit tables
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelHistoryEntry {
+    #[serde(with = "db_id_format")]
+    pub id:             i64,
+    pub kind:           ChannelHistoryKind,
+    pub origin:         String,
+    pub channel:        String,
+    pub operation:      PackageChannelOperation,
+    pub trigger:        PackageChannelTrigger,
+    #[serde(with = "db_id_format")]
+    pub requester_id:   i64,
+    pub requester_name: String,
+    // The job group, or the snapshot restored, of a group entry; 0 when it came from neither
+    #[serde(with = "db_id_format")]
+    pub group_id:       i64,
+    pub created_at:     Option<NaiveDateTime>,
+    // The packages of a group entry which still exist
+    pub packages:       Vec<ChannelHistoryPackage>,
+    #[serde(skip)]
+    pub package_ids:    Vec<i64>,
+}
+
+#[derive(Queryable)]
+struct PackageAuditRow {
+    id:             i64,
+    package_ident:  BuilderPackageIdent,
+    channel:        String,
+    operation:      PackageChannelOperation,
+    trigger:        PackageChannelTrigger,
+    requester_id:   i64,
+    requester_name: String,
+    created_at:     Option<NaiveDateTime>,
+    origin:         String,
+    target:         Option<BuilderPackageTarget>,
+}
+
+#[derive(Queryable)]
+struct FoundPackage {
+    id:     i64,
+    ident:  BuilderPackageIdent,
+    target: BuilderPackageTarget,
+}
+
+impl From<FoundPackage> for ChannelHistoryPackage {
+    fn from(package: FoundPackage) -> ChannelHistoryPackage {
+        ChannelHistoryPackage { ident:  package.ident,
+                                target: Some(package.target), }
+    }
+}
+
+#[derive(Queryable)]
+struct GroupAuditRow {
+    id:             i64,
+    channel:        String,
+    package_ids:    Vec<i64>,
+    operation:      PackageChannelOperation,
+    trigger:        PackageChannelTrigger,
+    requester_id:   i64,
+    requester_name: String,
+    group_id:       i64,
+    created_at:     Option<NaiveDateTime>,
+    origin:         String,
+}
+
+impl From<PackageAuditRow> for ChannelHistoryEntry {
+    fn from(row: PackageAuditRow) -> ChannelHistoryEntry {
+        let package = ChannelHistoryPackage { ident:  row.package_ident,
+                                              target: row.target, };
+        ChannelHistoryEntry { id:             row.id,
+                              kind:           ChannelHistoryKind::Package,
+                              origin:         row.origin,
+                              channel:        row.channel,
+                              operation:      row.operation,
+                              trigger:        row.trigger,
+                              requester_id:   row.requester_id,
+                              requester_name: row.requester_name,
+                              group_id:       0,
+                              created_at:     row.created_at,
+                              packages:       vec![package],
+                              package_ids:    Vec::new(), }
+    }
+}
+
+impl From<GroupAuditRow> for ChannelHistoryEntry {
+    fn from(row: GroupAuditRow) -> ChannelHistoryEntry {
+        ChannelHistoryEntry { id:             row.id,
+                              kind:           ChannelHistoryKind::Group,
+                              origin:         row.origin,
+                              channel:        row.channel,
+                              operation:      row.operation,
+                              trigger:        row.trigger,
+                              requester_id:   row.requester_id,
+                              requester_name: row.requester_name,
+                              group_id:       row.group_id,
+                              created_at:     row.created_at,
+                              packages:       Vec::new(),
+                              package_ids:    row.package_ids, }
+    }
+}
+
+// An entry of either audit table, as merged by CHANNEL_HISTORY_SQL
+#[derive(QueryableByName)]
+struct HistoryRow {
+    #[sql_type = "BigInt"]
+    id:             i64,
+    #[sql_type = "Bool"]
+    grouped:        bool,
+    #[sql_type = "Text"]
+    origin:         String,
+    #[sql_type = "Text"]
+    channel:        String,
+    #[sql_type = "PackageChannelOperationMapping"]
+    operation:      PackageChannelOperation,
+    #[sql_type = "PackageChannelTriggerMapping"]
+    trigger:        PackageChannelTrigger,
+    #[sql_type = "BigInt"]
+    requester_id:   i64,
+    #[sql_type = "Text"]
+    requester_name: String,
+    #[sql_type = "BigInt"]
+    group_id:       i64,
+    #[sql_type = "Nullable<Timestamptz>"]
+    created_at:     Option<NaiveDateTime>,
+    #[sql_type = "Nullable<Text>"]
+    package_ident:  Option<BuilderPackageIdent>,
+    #[sql_type = "Nullable<Text>"]
+    target:         Option<BuilderPackageTarget>,
+    #[sql_type = "Array<BigInt>"]
+    package_ids:    Vec<i64>,
+}
+
+impl From<HistoryRow> for ChannelHistoryEntry {
+    fn from(row: HistoryRow) -> ChannelHistoryEntry {
+        let kind = if row.grouped {
+            ChannelHistoryKind::Group
+        } else {
+            ChannelHistoryKind::Package
+        };
+        let target = row.target;
+        let packages = row.package_ident
+                          .into_iter()
+                          .map(|ident| {
+                              ChannelHistoryPackage { ident,
+                                                      target: target.clone() }
+                          })
+                          .collect();
+
+        ChannelHistoryEntry { id: row.id,
+                              kind,
+                              origin: row.origin,
+                              channel: row.channel,
+                              operation: row.operation,
+                              trigger: row.trigger,
+                              requester_id: row.requester_id,
+                              requester_name: row.requester_name,
+                              group_id: row.group_id,
+                              created_at: row.created_at,
+                              packages,
+                              package_ids: row.package_ids }
+    }
+}
+
+// One page of the entries of both audit tables for a channel, newest first
+const CHANNEL_HISTORY_SQL: &str = "
+    SELECT id, FALSE AS grouped, origin, channel, operation, trigger, requester_id,
+        requester_name, 0::bigint AS group_id, created_at, package_ident, target,
+        '{}'::bigint[] AS package_ids
+    FROM audit_package
+    WHERE origin = $1 AND channel = $2
+    UNION ALL
+    SELECT id, TRUE AS grouped, origin, channel, operation, trigger, requester_id,
+        requester_name, group_id, created_at, NULL AS package_ident, NULL AS target,
+        package_ids
+    FROM audit_package_group
+    WHERE origin = $1 AND channel = $2
+    ORDER BY created_at DESC NULLS LAST, id DESC
+    LIMIT $3 OFFSET $4";
+
+pub struct RevertChannelHistory<'a> {
+    pub trigger:        PackageChannelTrigger,
+    pub requester_id:   i64,
+    pub requester_name: &'a str,
+}
+
+pub struct ChannelHistory;
+
+impl ChannelHistory {
+    // The entries of both audit tables for the channel, newest first, along with the total
+    // number of entries
+    pub fn list(origin: &str,
+                channel: &str,
+                start: i64,
+                limit: i64,
+                conn: &PgConnection)
+                -> QueryResult<(Vec<ChannelHistoryEntry>, i64)> {
+        Counter::DBCall.increment();
+        let rows: Vec<HistoryRow> = sql_query(CHANNEL_HISTORY_SQL).bind::<Text, _>(origin)
+                                                                  .bind::<Text, _>(channel)
+                                                                  .bind::<BigInt, _>(limit)
+                                                                  .bind::<BigInt, _>(start)
+                                                                  .get_results(conn)?;
+
+        Counter::DBCall.increment();
+        let package_count: i64 = audit_package::table.filter(audit_package::origin.eq(origin))
+                                                     .filter(audit_package::channel.eq(channel))
+                                                     .count()
+                                                     .get_result(conn)?;
+
+        Counter::DBCall.increment();
+        let group_count: i64 =
+            audit_package_group::table.filter(audit_package_group::origin.eq(origin))
+                                      .filter(audit_package_group::channel.eq(channel))
+                                      .count()
+                                      .get_result(conn)?;
+
+        let mut page: Vec<ChannelHistoryEntry> =
+            rows.into_iter().map(ChannelHistoryEntry::from).collect();
+        Self::resolve(&mut page, conn)?;
+
+        Ok((page, package_count + group_count))
+    }
+
+    pub fn get(id: i64, conn: &PgConnection) -> QueryResult<ChannelHistoryEntry> {
+        Counter::DBCall.increment();
+        let mut entry = match audit_package::table.find(id)
+                                                  .get_result::<PackageAuditRow>(conn)
+        {
+            Ok(row) => ChannelHistoryEntry::from(row),
+            Err(Error::NotFound) => {
+                Counter::DBCall.increment();
+                audit_package_group::table.find(id)
+                                          .get_result::<GroupAuditRow>(conn)?
+                                          .into()
+            }
+            Err(err) => return Err(err),
+        };
+
+        Self::resolve(std::slice::from_mut(&mut entry), conn)?;
+        Ok(entry)
+    }
+
+    // The entries a job group wrote for the channel, across every origin of the group
+    pub fn for_group(group_id: i64,
+                     channel: &str,
+                     conn: &PgConnection)
+                     -> QueryResult<Vec<ChannelHistoryEntry>> {
+        Counter::DBCall.increment();
+        let rows: Vec<GroupAuditRow> =
+            audit_package_group::table.filter(audit_package_group::group_id.eq(group_id))
+                                      .filter(audit_package_group::channel.eq(channel))
+                                      .order(audit_package_group::id.asc())
+                                      .get_results(conn)?;
+
+        let mut entries: Vec<ChannelHistoryEntry> =
+            rows.into_iter().map(ChannelHistoryEntry::from).collect();
+        Self::resolve(&mut entries, conn)?;
+        Ok(entries)
+    }
+
+    // Undoes the entries by applying the opposite operation to their packages, recording each
+    // as a new entry under the job group of the entry. Single package entries that predate targets
+    // being audited apply to the ident on every target. Packages deleted since are skipped.
+    // Returns the packages moved.
+    pub fn revert(entries: &[ChannelHistoryEntry],
+                  req: &RevertChannelHistory,
+                  conn: &PgConnection)
+                  -> QueryResult<Vec<ChannelHistoryPackage>> {
+        conn.transaction::<_, Error, _>(|| {
+                let mut reverted = Vec::new();
+
+                for entry in entries {
+                    let channel = Channel::get(&entry.origin,
+                                               &ChannelIdent::from(entry.channel.as_str()),
+                                               conn)?;
+                    let packages = match entry.kind {
+                        ChannelHistoryKind::Package => Self::lookup_idents(&entry.packages, conn)?,
+                        ChannelHistoryKind::Group => Self::lookup(&entry.package_ids, conn)?,
+                    };
+                    if packages.is_empty() {
+                        continue;
+                    }
+
+                    let ids: Vec<i64> = packages.iter().map(|p| p.id).collect();
+                    let operation = match entry.operation {
+                        PackageChannelOperation::Promote => {
+                            Channel::demote_packages(channel.id, &ids, conn)?;
+                            PackageChannelOperation::Demote
+                        }
+                        PackageChannelOperation::Demote => {
+                            Channel::promote_packages(channel.id, &ids, conn)?;
+                            PackageChannelOperation::Promote
+                        }
+                    };
+
+                    match entry.kind {
+                        ChannelHistoryKind::Package => {
+                            for package in packages.iter() {
+                                let audit =
+                                    PackageChannelAudit { package_ident: package.ident.clone(),
+                                                          channel: &entry.channel,
+                                                          operation,
+                                                          trigger: req.trigger.clone(),
+                                                          requester_id: req.requester_id,
+                                                          requester_name: req.requester_name,
+                                                          origin: &entry.origin,
+                                                          target: Some(package.target.clone()) };
+                                PackageChannelAudit::audit(&audit, conn)?;
+                            }
+                        }
+                        ChannelHistoryKind::Group => {
+                            let audit =
+                                PackageGroupChannelAudit { origin: &entry.origin,
+                                                           channel: &entry.channel,
+                                                           package_ids: ids,
+                                                           operation,
+                                                           trigger: req.trigger.clone(),
+                                                           requester_id: req.requester_id,
+                                                           requester_name: req.requester_name,
+                                                           group_id: entry.group_id };
+                            PackageGroupChannelAudit::audit(audit, conn)?;
+                        }
+                    }
+
+                    reverted.extend(packages.into_iter().map(ChannelHistoryPackage::from));
+                }
+
+                Ok(reverted)
+            })
+    }
+
+    // The packages of the entry which still exist. Single package entries that predate
+    // targets being audited give the ident on every target.
+    pub fn existing_packages(entry: &ChannelHistoryEntry,
+                             conn: &PgConnection)
+                             -> QueryResult<Vec<ChannelHistoryPackage>> {
+        let packages = match entry.kind {
+            ChannelHistoryKind::Package => Self::lookup_idents(&entry.packages, conn)?,
+            ChannelHistoryKind::Group => Self::lookup(&entry.package_ids, conn)?,
+        };
+        Ok(packages.into_iter()
+                   .map(ChannelHistoryPackage::from)
+                   .collect())
+    }
+
+    // Fills in the packages of group entries
+    fn resolve(entries: &mut [ChannelHistoryEntry], conn: &PgConnection) -> QueryResult<()> {
+        let ids: Vec<i64> = entries.iter()
+                                   .flat_map(|e| e.package_ids.iter().cloned())
+                                   .collect();
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let found: HashMap<i64, ChannelHistoryPackage> =
+            Self::lookup(&ids, conn)?.into_iter()
+                                     .map(|p| (p.id, ChannelHistoryPackage::from(p)))
+                                     .collect();
+
+        for entry in entries.iter_mut() {
+            entry.packages = entry.package_ids
+                                  .iter()
+                                  .filter_map(|id| found.get(id).cloned())
+                                  .collect();
+        }
+        Ok(())
+    }
+
+    fn lookup(package_ids: &[i64], conn: &PgConnection) -> QueryResult<Vec<FoundPackage>> {
+        Counter::DBCall.increment();
+        origin_packages::table.filter(origin_packages::id.eq(any(package_ids)))
+                              .select((origin_packages::id,
+                                       origin_packages::ident,
+                                       origin_packages::target))
+                              .get_results(conn)
+    }
+
+    fn lookup_idents(packages: &[ChannelHistoryPackage],
+                     conn: &PgConnection)
+                     -> QueryResult<Vec<FoundPackage>> {
+        let mut found = Vec::new();
+        for package in packages {
+            let mut query =
+                origin_packages::table.filter(origin_packages::ident.eq(package.ident.to_string()))
+                                      .select((origin_packages::id,
+                                               origin_packages::ident,
+                                               origin_packages::target))
+                                      .into_boxed();
+            if let Some(ref target) = package.target {
+                query = query.filter(origin_packages::target.eq(target.to_string()));
+            }
+
+            Counter::DBCall.increment();
+            found.extend(query.get_results::<FoundPackage>(conn)?);
+        }
+        Ok(found)
+    }
+}
+
+// Orders the entries newest first and returns the requested page of them
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hab_core::package::{PackageIdent,
+                                   PackageTarget};
+    use chrono::NaiveDate;
+    use std::str::FromStr;
+
+    fn row(id: i64, grouped: bool) -> HistoryRow {
+        HistoryRow { id,
+                     grouped,
+                     origin: "core".to_string(),
+                     channel: "stable".to_string(),
+                     operation: PackageChannelOperation::Promote,
+                     trigger: PackageChannelTrigger::BuilderUi,
+                     requester_id: 1,
+                     requester_name: "bobo".to_string(),
+                     group_id: if grouped { 7 } else { 0 },
+                     created_at: Some(NaiveDate::from_ymd(2020, 9, 10).and_hms(10, 30, 0)),
+                     package_ident: None,
+                     target: None,
+                     package_ids: Vec::new() }
+    }
+
+    #[test]
+    fn package_rows_become_single_package_entries() {
+        let ident =
+            BuilderPackageIdent(PackageIdent::from_str("core/redis/5.0.7/20200910103000").unwrap());
+        let target = BuilderPackageTarget(PackageTarget::from_str("x86_64-linux").unwrap());
+        let entry = ChannelHistoryEntry::from(HistoryRow { package_ident: Some(ident.clone()),
+                                                           target: Some(target.clone()),
+                                                           ..row(1, false) });
+
+        assert_eq!(entry.kind, ChannelHistoryKind::Package);
+        assert_eq!(entry.group_id, 0);
+        assert_eq!(entry.packages.len(), 1);
+        assert_eq!(entry.packages[0].ident, ident);
+        assert_eq!(entry.packages[0].target, Some(target));
+        assert!(entry.package_ids.is_empty());
+    }
+
+    #[test]
+    fn group_rows_keep_their_package_ids() {
+        let entry = ChannelHistoryEntry::from(HistoryRow { package_ids: vec![3, 4],
+                                                           ..row(2, true) });
+
+        assert_eq!(entry.kind, ChannelHistoryKind::Group);
+        assert_eq!(entry.group_id, 7);
+        assert!(entry.packages.is_empty());
+        assert_eq!(entry.package_ids, vec![3, 4]);
+    }
+}
@@ -0,0 +1,93 @@
+use super::db_id_format;
+use chrono::NaiveDateTime;
+
+use diesel::{self,
+             pg::PgConnection,
+             result::QueryResult,
+             ExpressionMethods,
+             QueryDsl,
+             RunQueryDsl};
+
+use crate::{models::origin::OriginMemberRole,
+            schema::channel_policy::origin_channel_policies};
+
+use crate::{bldr_core::metrics::CounterMetric,
+            metrics::Counter};
+
+#[derive(Debug,
+         Serialize,
+         Deserialize,
+         QueryableByName,
+         Queryable,
+         Clone,
+         Identifiable)]
+#[table_name = "origin_channel_policies"]
+pub struct OriginChannelPolicy {
+    #[serde(with = "db_id_format")]
+    pub id: i64,
+    pub origin: String,
+    pub channel: String,
+    pub require_channel: Option<String>,
+    pub require_tdeps_in_channel: bool,
+    pub require_worker_build: bool,
+    pub min_member_role: Option<OriginMemberRole>,
+    #[serde(with = "db_id_format")]
+    pub owner_id: i64,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[table_name = "origin_channel_policies"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct NewOriginChannelPolicy<'a> {
+    pub origin:                   &'a str,
+    pub channel:                  &'a str,
+    pub require_channel:          Option<&'a str>,
+    pub require_tdeps_in_channel: bool,
+    pub require_worker_build:     bool,
+    pub min_member_role:          Option<OriginMemberRole>,
+    pub owner_id:                 i64,
+}
+
+impl OriginChannelPolicy {
+    pub fn list(origin: &str, conn: &PgConnection) -> QueryResult<Vec<OriginChannelPolicy>> {
+        Counter::DBCall.increment();
+        origin_channel_policies::table.filter(origin_channel_policies::origin.eq(origin))
+                                      .order(origin_channel_policies::channel.asc())
+                                      .get_results(conn)
+    }
+
+    pub fn get(origin: &str,
+               channel: &str,
+               conn: &PgConnection)
+               -> QueryResult<OriginChannelPolicy> {
+        Counter::DBCall.increment();
+        origin_channel_policies::table.filter(origin_channel_policies::origin.eq(origin))
+                                      .filter(origin_channel_policies::channel.eq(channel))
+                                      .get_result(conn)
+    }
+
+    // Replaces any existing policy for the same origin and channel
+    pub fn set(req: &NewOriginChannelPolicy,
+               conn: &PgConnection)
+               -> QueryResult<OriginChannelPolicy> {
+        Counter::DBCall.increment();
+        diesel::insert_into(origin_channel_policies::table)
+            .values(req)
+            .on_conflict((origin_channel_policies::origin, origin_channel_policies::channel))
+            .do_update()
+            .set(req)
+            .get_result(conn)
+    }
+
+    pub fn delete(origin: &str, channel: &str, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::delete(
+            origin_channel_policies::table
+                .filter(origin_channel_policies::origin.eq(origin))
+                .filter(origin_channel_policies::channel.eq(channel)),
+        )
+        .execute(conn)
+    }
+}
@@ -0,0 +1,258 @@
+use super::db_id_format;
+use chrono::NaiveDateTime;
+use std::collections::HashSet;
+
+use diesel::{self,
+             pg::{expression::dsl::any,
+                  PgConnection},
+             result::{Error,
+                      QueryResult},
+             Connection,
+             ExpressionMethods,
+             QueryDsl,
+             RunQueryDsl};
+
+use crate::{models::{channel::{Channel,
+                               PackageChannelOperation,
+                               PackageChannelTrigger,
+                               PackageGroupChannelAudit},
+                     package::{BuilderPackageIdent,
+                               BuilderPackageTarget}},
+            schema::{channel::origin_channel_packages,
+                     channel_snapshot::origin_channel_snapshots,
+                     package::origin_packages}};
+
+use crate::{bldr_core::metrics::CounterMetric,
+            metrics::Counter};
+
+#[derive(Debug,
+         Serialize,
+         Deserialize,
+         QueryableByName,
+         Queryable,
+         Clone,
+         Identifiable)]
+#[table_name = "origin_channel_snapshots"]
+pub struct ChannelSnapshot {
+    #[serde(with = "db_id_format")]
+    pub id:          i64,
+    pub origin:      String,
+    pub channel:     String,
+    pub name:        String,
+    #[serde(skip)]
+    pub package_ids: Vec<i64>,
+    #[serde(with = "db_id_format")]
+    pub owner_id:    i64,
+    pub created_at:  Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "origin_channel_snapshots"]
+pub struct NewChannelSnapshot<'a> {
+    pub origin:      &'a str,
+    pub channel:     &'a str,
+    pub name:        &'a str,
+    pub package_ids: Vec<i64>,
+    pub owner_id:    i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Clone)]
+pub struct SnapshotPackage {
+    #[serde(with = "db_id_format")]
+    pub id:     i64,
+    pub ident:  BuilderPackageIdent,
+    pub target: BuilderPackageTarget,
+}
+
+// Differences of the live channel from a snapshot of it
+#[derive(Debug, Serialize)]
+pub struct ChannelSnapshotDiff {
+    // In the channel now but not in the snapshot
+    pub added:   Vec<SnapshotPackage>,
+    // In the snapshot but no longer in the channel
+    pub removed: Vec<SnapshotPackage>,
+}
+
+pub struct RestoreChannelSnapshot<'a> {
+    pub channel_id:     i64,
+    pub trigger:        PackageChannelTrigger,
+    pub requester_id:   i64,
+    pub requester_name: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelSnapshotRestore {
+    pub promoted: Vec<SnapshotPackage>,
+    pub demoted:  Vec<SnapshotPackage>,
+}
+
+impl ChannelSnapshot {
+    // Records the current members of the channel under the given name
+    pub fn create(channel: &Channel,
+                  name: &str,
+                  owner_id: i64,
+                  conn: &PgConnection)
+                  -> QueryResult<ChannelSnapshot> {
+        let package_ids = Self::channel_package_ids(channel.id, conn)?;
+
+        Counter::DBCall.increment();
+        diesel::insert_into(origin_channel_snapshots::table)
+            .values(&NewChannelSnapshot { origin: &channel.origin,
+                                          channel: &channel.name,
+                                          name,
+                                          package_ids,
+                                          owner_id })
+            .get_result(conn)
+    }
+
+    pub fn list(origin: &str,
+                channel: &str,
+                conn: &PgConnection)
+                -> QueryResult<Vec<ChannelSnapshot>> {
+        Counter::DBCall.increment();
+        origin_channel_snapshots::table.filter(origin_channel_snapshots::origin.eq(origin))
+                                       .filter(origin_channel_snapshots::channel.eq(channel))
+                                       .order(origin_channel_snapshots::created_at.desc())
+                                       .get_results(conn)
+    }
+
+    pub fn get(origin: &str,
+               channel: &str,
+               name: &str,
+               conn: &PgConnection)
+               -> QueryResult<ChannelSnapshot> {
+        Counter::DBCall.increment();
+        origin_channel_snapshots::table.filter(origin_channel_snapshots::origin.eq(origin))
+                                       .filter(origin_channel_snapshots::channel.eq(channel))
+                                       .filter(origin_channel_snapshots::name.eq(name))
+                                       .get_result(conn)
+    }
+
+    // Removes every snapshot of the channel
+    pub fn delete_all(origin: &str, channel: &str, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::delete(
+            origin_channel_snapshots::table
+                .filter(origin_channel_snapshots::origin.eq(origin))
+                .filter(origin_channel_snapshots::channel.eq(channel)),
+        )
+        .execute(conn)
+    }
+
+    // The packages in the snapshot which still exist
+    pub fn packages(&self, conn: &PgConnection) -> QueryResult<Vec<SnapshotPackage>> {
+        Self::lookup(&self.package_ids, conn)
+    }
+
+    pub fn diff(&self, channel_id: i64, conn: &PgConnection) -> QueryResult<ChannelSnapshotDiff> {
+        let live = Self::channel_package_ids(channel_id, conn)?;
+        let (added, removed) = self.changes(&live);
+
+        Ok(ChannelSnapshotDiff { added:   Self::lookup(&added, conn)?,
+                                 removed: Self::lookup(&removed, conn)?, })
+    }
+
+    // Resets the members of the channel to the snapshot, recording the promotions and
+    // demotions that take in the group audit log under the id of the snapshot. Packages
+    // deleted since the snapshot was taken can not be restored and are skipped. `check` is given
+    // the packages to be promoted before anything changes, and nothing changes if it fails.
+    pub fn restore<F, E>(&self,
+                         req: &RestoreChannelSnapshot,
+                         check: F,
+                         conn: &PgConnection)
+                         -> Result<ChannelSnapshotRestore, E>
+        where F: FnOnce(&[SnapshotPackage]) -> Result<(), E>,
+              E: From<Error>
+    {
+        conn.transaction::<_, E, _>(|| {
+                let live = Self::channel_package_ids(req.channel_id, conn)?;
+                let (demote, promote) = self.changes(&live);
+                let promoted = Self::lookup(&promote, conn)?;
+                let demoted = Self::lookup(&demote, conn)?;
+
+                // Checked against the same packages that are about to be promoted, so nothing
+                // promoted since the caller looked can get past the check
+                check(&promoted)?;
+
+                let audit = |package_ids, operation| {
+                    let entry = PackageGroupChannelAudit { origin: &self.origin,
+                                                           channel: &self.channel,
+                                                           package_ids,
+                                                           operation,
+                                                           trigger: req.trigger.clone(),
+                                                           requester_id: req.requester_id,
+                                                           requester_name: req.requester_name,
+                                                           group_id: self.id };
+                    PackageGroupChannelAudit::audit(entry, conn)
+                };
+
+                if !demoted.is_empty() {
+                    let ids: Vec<i64> = demoted.iter().map(|p| p.id).collect();
+                    Channel::demote_packages(req.channel_id, &ids, conn)?;
+                    audit(ids, PackageChannelOperation::Demote)?;
+                }
+                if !promoted.is_empty() {
+                    let ids: Vec<i64> = promoted.iter().map(|p| p.id).collect();
+                    Channel::promote_packages(req.channel_id, &ids, conn)?;
+                    audit(ids, PackageChannelOperation::Promote)?;
+                }
+
+                Ok(ChannelSnapshotRestore { promoted, demoted })
+            })
+    }
+
+    // The ids in `live` but not in the snapshot, and those in the snapshot but not in `live`
+    fn changes(&self, live: &[i64]) -> (Vec<i64>, Vec<i64>) {
+        let snapshot: HashSet<i64> = self.package_ids.iter().cloned().collect();
+        let live_set: HashSet<i64> = live.iter().cloned().collect();
+
+        let added = live.iter()
+                        .filter(|id| !snapshot.contains(*id))
+                        .cloned()
+                        .collect();
+        let removed = self.package_ids
+                          .iter()
+                          .filter(|id| !live_set.contains(*id))
+                          .cloned()
+                          .collect();
+        (added, removed)
+    }
+
+    fn channel_package_ids(channel_id: i64, conn: &PgConnection) -> QueryResult<Vec<i64>> {
+        Counter::DBCall.increment();
+        origin_channel_packages::table.filter(origin_channel_packages::channel_id.eq(channel_id))
+                                      .select(origin_channel_packages::package_id)
+                                      .order(origin_channel_packages::package_id.asc())
+                                      .get_results(conn)
+    }
+
+    fn lookup(package_ids: &[i64], conn: &PgConnection) -> QueryResult<Vec<SnapshotPackage>> {
+        Counter::DBCall.increment();
+        origin_packages::table.filter(origin_packages::id.eq(any(package_ids)))
+                              .select((origin_packages::id,
+                                       origin_packages::ident,
+                                       origin_packages::target))
+                              .order(origin_packages::ident.asc())
+                              .get_results(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_changes() {
+        let snapshot = ChannelSnapshot { id:          1,
+                                         origin:      "core".to_string(),
+                                         channel:     "stable".to_string(),
+                                         name:        "before-upgrade".to_string(),
+                                         package_ids: vec![1, 2, 3],
+                                         owner_id:    1,
+                                         created_at:  None, };
+
+        assert_eq!(snapshot.changes(&[1, 2, 3]), (vec![], vec![]));
+        assert_eq!(snapshot.changes(&[2, 3, 4, 5]), (vec![4, 5], vec![1]));
+        assert_eq!(snapshot.changes(&[]), (vec![], vec![1, 2, 3]));
+    }
+}
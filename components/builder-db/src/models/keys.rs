@@ -1,15 +1,46 @@
 use super::db_id_format;
 use chrono::NaiveDateTime;
 use diesel::{self,
+             dsl::now,
              pg::PgConnection,
              result::QueryResult,
              ExpressionMethods,
+             NullableExpressionMethods,
              QueryDsl,
              RunQueryDsl};
+use std::fmt;
 
 use crate::{bldr_core::metrics::CounterMetric,
             metrics::Counter,
-            schema::key::*};
+            models::package::{BuilderPackageIdent,
+                              BuilderPackageTarget},
+            schema::{channel::{origin_channel_packages,
+                               origin_channels},
+                     key::*,
+                     package::origin_packages}};
+
+// Rotation retires the previous signing key revisions: they are still served so that
+// packages signed with them can be verified, but their secret keys are no longer handed
+// out. Uploads signed with a revoked revision are refused.
+#[derive(DbEnum, Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[PgType = "origin_key_state"]
+#[serde(rename_all = "snake_case")]
+pub enum OriginKeyState {
+    Active,
+    Retired,
+    Revoked,
+}
+
+impl fmt::Display for OriginKeyState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match *self {
+            OriginKeyState::Active => "active",
+            OriginKeyState::Retired => "retired",
+            OriginKeyState::Revoked => "revoked",
+        };
+        write!(f, "{}", value)
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, QueryableByName, Queryable)]
 #[table_name = "origin_public_encryption_keys"]
@@ -64,16 +95,18 @@ pub struct OriginPrivateSigningKey {
 #[table_name = "origin_public_keys"]
 pub struct OriginPublicSigningKey {
     #[serde(with = "db_id_format")]
-    pub id:         i64,
+    pub id:               i64,
     #[serde(with = "db_id_format")]
-    pub owner_id:   i64,
-    pub name:       String,
-    pub revision:   String,
-    pub full_name:  String,
-    pub body:       Vec<u8>,
-    pub created_at: Option<NaiveDateTime>,
-    pub updated_at: Option<NaiveDateTime>,
-    pub origin:     String,
+    pub owner_id:         i64,
+    pub name:             String,
+    pub revision:         String,
+    pub full_name:        String,
+    pub body:             Vec<u8>,
+    pub created_at:       Option<NaiveDateTime>,
+    pub updated_at:       Option<NaiveDateTime>,
+    pub origin:           String,
+    pub state:            OriginKeyState,
+    pub state_changed_at: Option<NaiveDateTime>,
 }
 
 #[derive(Insertable)]
@@ -181,6 +214,110 @@ impl OriginPrivateEncryptionKey {
     }
 }
 
+// An upload which was accepted despite being signed with a key revision the origin has not
+// registered
+#[derive(Debug, Serialize, Deserialize, QueryableByName, Queryable)]
+#[table_name = "origin_package_signer_flags"]
+pub struct PackageSignerFlag {
+    #[serde(with = "db_id_format")]
+    pub id:         i64,
+    pub origin:     String,
+    #[serde(with = "db_id_format")]
+    pub package_id: i64,
+    pub ident:      BuilderPackageIdent,
+    pub target:     BuilderPackageTarget,
+    pub signer:     String,
+    #[serde(with = "db_id_format")]
+    pub owner_id:   i64,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[table_name = "origin_package_signer_flags"]
+pub struct NewPackageSignerFlag<'a> {
+    pub origin:     &'a str,
+    pub package_id: i64,
+    pub ident:      BuilderPackageIdent,
+    pub target:     BuilderPackageTarget,
+    pub signer:     &'a str,
+    pub owner_id:   i64,
+}
+
+impl PackageSignerFlag {
+    pub fn create(req: &NewPackageSignerFlag,
+                  conn: &PgConnection)
+                  -> QueryResult<PackageSignerFlag> {
+        Counter::DBCall.increment();
+        diesel::insert_into(origin_package_signer_flags::table).values(req)
+                                                               .get_result(conn)
+    }
+
+    pub fn list(origin: &str, conn: &PgConnection) -> QueryResult<Vec<PackageSignerFlag>> {
+        Counter::DBCall.increment();
+        origin_package_signer_flags::table.filter(origin_package_signer_flags::origin.eq(origin))
+                                          .order(origin_package_signer_flags::created_at.desc())
+                                          .get_results(conn)
+    }
+}
+
+// The key revision an uploaded package was signed with
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct PackageSigner {
+    #[serde(with = "db_id_format")]
+    pub package_id: i64,
+    pub origin:     String,
+    pub signer:     String,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+// A package in one of the channels of a signer report, with the revision it was signed
+// with if the upload recorded one
+#[derive(Debug, Serialize, Queryable)]
+pub struct ChannelPackageSigner {
+    pub channel: String,
+    pub ident:   BuilderPackageIdent,
+    pub target:  BuilderPackageTarget,
+    pub signer:  Option<String>,
+}
+
+impl PackageSigner {
+    pub fn record(package_id: i64,
+                  origin: &str,
+                  signer: &str,
+                  conn: &PgConnection)
+                  -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::insert_into(origin_package_signers::table)
+            .values((origin_package_signers::package_id.eq(package_id),
+                     origin_package_signers::origin.eq(origin),
+                     origin_package_signers::signer.eq(signer)))
+            .on_conflict(origin_package_signers::package_id)
+            .do_update()
+            .set((origin_package_signers::signer.eq(signer),
+                  origin_package_signers::created_at.eq(now.nullable())))
+            .execute(conn)
+    }
+
+    pub fn list_in_channels(origin: &str,
+                            channels: &[String],
+                            conn: &PgConnection)
+                            -> QueryResult<Vec<ChannelPackageSigner>> {
+        Counter::DBCall.increment();
+        origin_packages::table
+            .inner_join(origin_channel_packages::table.inner_join(origin_channels::table))
+            .left_join(origin_package_signers::table)
+            .select((origin_channels::name,
+                     origin_packages::ident,
+                     origin_packages::target,
+                     origin_package_signers::signer.nullable()))
+            .filter(origin_packages::origin.eq(origin))
+            .filter(origin_channels::origin.eq(origin))
+            .filter(origin_channels::name.eq_any(channels))
+            .order((origin_channels::name.asc(), origin_packages::ident.asc()))
+            .get_results(conn)
+    }
+}
+
 impl OriginPublicSigningKey {
     pub fn get(origin: &str,
                revision: &str,
@@ -202,11 +339,15 @@ impl OriginPublicSigningKey {
                                                       .get_result(conn)
     }
 
+    // The newest active revision, or the newest retired one once none is active so that
+    // packages can still be verified. A revoked revision is never the latest.
     pub fn latest(origin: &str, conn: &PgConnection) -> QueryResult<OriginPublicSigningKey> {
         Counter::DBCall.increment();
         origin_public_keys::table.filter(origin_public_keys::origin.eq(origin))
+                                 .filter(origin_public_keys::state.ne(OriginKeyState::Revoked))
                                  .limit(1)
-                                 .order(origin_public_keys::revision.desc())
+                                 .order((origin_public_keys::state.asc(),
+                                         origin_public_keys::revision.desc()))
                                  .get_result(conn)
     }
 
@@ -216,13 +357,51 @@ impl OriginPublicSigningKey {
                                  .order(origin_public_keys::revision.desc())
                                  .get_results(conn)
     }
+
+    // Retires every active revision of the origin but the given one, returning the
+    // revisions that were retired
+    pub fn retire_others(origin: &str,
+                         revision: &str,
+                         conn: &PgConnection)
+                         -> QueryResult<Vec<String>> {
+        Counter::DBCall.increment();
+        diesel::update(
+            origin_public_keys::table
+                .filter(origin_public_keys::origin.eq(origin))
+                .filter(origin_public_keys::revision.ne(revision))
+                .filter(origin_public_keys::state.eq(OriginKeyState::Active)),
+        )
+        .set((origin_public_keys::state.eq(OriginKeyState::Retired),
+              origin_public_keys::state_changed_at.eq(now.nullable())))
+        .returning(origin_public_keys::revision)
+        .get_results(conn)
+    }
+
+    pub fn revoke(origin: &str, revision: &str, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::update(
+            origin_public_keys::table
+                .filter(origin_public_keys::origin.eq(origin))
+                .filter(origin_public_keys::revision.eq(revision))
+                .filter(origin_public_keys::state.ne(OriginKeyState::Revoked)),
+        )
+        .set((origin_public_keys::state.eq(OriginKeyState::Revoked),
+              origin_public_keys::state_changed_at.eq(now.nullable())))
+        .execute(conn)
+    }
 }
 
 impl OriginPrivateSigningKey {
     pub fn get(origin: &str, conn: &PgConnection) -> QueryResult<OriginPrivateSigningKey> {
         Counter::DBCall.increment();
-        // This is really latest because you're not allowed to get old keys
+        // This is really latest because you're not allowed to get old keys, and neither
+        // are the keys of retired or revoked revisions handed out
+        let inactive =
+            origin_public_keys::table.select(origin_public_keys::revision)
+                                     .filter(origin_public_keys::origin.eq(origin))
+                                     .filter(origin_public_keys::state.ne(OriginKeyState::Active));
         origin_secret_keys::table.filter(origin_secret_keys::origin.eq(origin))
+                                 .filter(origin_secret_keys::revision.ne_all(inactive))
                                  .limit(1)
                                  .order(origin_secret_keys::full_name.desc())
                                  .get_result(conn)
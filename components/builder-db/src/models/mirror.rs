@@ -0,0 +1,257 @@
+use super::db_id_format;
+use chrono::{Duration,
+             NaiveDateTime,
+             Utc};
+
+use diesel::{self,
+             dsl::{exists,
+                   not,
+                   now},
+             pg::PgConnection,
+             result::QueryResult,
+             BoolExpressionMethods,
+             ExpressionMethods,
+             NullableExpressionMethods,
+             QueryDsl,
+             RunQueryDsl};
+
+use crate::schema::mirror::{origin_channel_mirror_runs,
+                            origin_channel_mirrors};
+
+use crate::{bldr_core::metrics::CounterMetric,
+            metrics::Counter};
+
+#[derive(Debug,
+         Serialize,
+         Deserialize,
+         QueryableByName,
+         Queryable,
+         Clone,
+         Identifiable)]
+#[table_name = "origin_channel_mirrors"]
+pub struct OriginChannelMirror {
+    #[serde(with = "db_id_format")]
+    pub id:           i64,
+    pub origin:       String,
+    pub channel:      String,
+    pub upstream_url: String,
+    // Package names to mirror; every package when empty
+    pub include:      Vec<String>,
+    // Package names never to mirror, applied after `include`
+    pub exclude:      Vec<String>,
+    #[serde(with = "db_id_format")]
+    pub owner_id:     i64,
+    pub created_at:   Option<NaiveDateTime>,
+    pub updated_at:   Option<NaiveDateTime>,
+    #[serde(skip)]
+    pub claimed_at:   Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[table_name = "origin_channel_mirrors"]
+pub struct NewOriginChannelMirror<'a> {
+    pub origin:       &'a str,
+    pub channel:      &'a str,
+    pub upstream_url: &'a str,
+    pub include:      Vec<String>,
+    pub exclude:      Vec<String>,
+    pub owner_id:     i64,
+}
+
+#[derive(DbEnum, Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[PgType = "origin_channel_mirror_status"]
+#[serde(rename_all = "snake_case")]
+pub enum MirrorRunStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug,
+         Serialize,
+         Deserialize,
+         QueryableByName,
+         Queryable,
+         Clone,
+         Identifiable)]
+#[table_name = "origin_channel_mirror_runs"]
+pub struct OriginChannelMirrorRun {
+    #[serde(with = "db_id_format")]
+    pub id:          i64,
+    #[serde(with = "db_id_format")]
+    pub mirror_id:   i64,
+    pub status:      MirrorRunStatus,
+    // Packages fetched from upstream and uploaded
+    pub uploaded:    i32,
+    // Packages added to the channel
+    pub promoted:    i32,
+    pub errors:      Vec<String>,
+    pub started_at:  Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+impl OriginChannelMirror {
+    pub fn list(origin: &str, conn: &PgConnection) -> QueryResult<Vec<OriginChannelMirror>> {
+        Counter::DBCall.increment();
+        origin_channel_mirrors::table.filter(origin_channel_mirrors::origin.eq(origin))
+                                     .order(origin_channel_mirrors::channel.asc())
+                                     .get_results(conn)
+    }
+
+    pub fn list_all(conn: &PgConnection) -> QueryResult<Vec<OriginChannelMirror>> {
+        Counter::DBCall.increment();
+        origin_channel_mirrors::table.order((origin_channel_mirrors::origin.asc(),
+                                             origin_channel_mirrors::channel.asc()))
+                                     .get_results(conn)
+    }
+
+    pub fn get(origin: &str,
+               channel: &str,
+               conn: &PgConnection)
+               -> QueryResult<OriginChannelMirror> {
+        Counter::DBCall.increment();
+        origin_channel_mirrors::table.filter(origin_channel_mirrors::origin.eq(origin))
+                                     .filter(origin_channel_mirrors::channel.eq(channel))
+                                     .get_result(conn)
+    }
+
+    // Replaces any existing mirror for the same origin and channel
+    pub fn set(req: &NewOriginChannelMirror,
+               conn: &PgConnection)
+               -> QueryResult<OriginChannelMirror> {
+        Counter::DBCall.increment();
+        diesel::insert_into(origin_channel_mirrors::table)
+            .values(req)
+            .on_conflict((origin_channel_mirrors::origin, origin_channel_mirrors::channel))
+            .do_update()
+            .set((req, origin_channel_mirrors::updated_at.eq(now)))
+            .get_result(conn)
+    }
+
+    pub fn delete(origin: &str, channel: &str, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::delete(
+            origin_channel_mirrors::table
+                .filter(origin_channel_mirrors::origin.eq(origin))
+                .filter(origin_channel_mirrors::channel.eq(channel)),
+        )
+        .execute(conn)
+    }
+
+    // Marks the mirror as claimed for a sync, unless it was already claimed less than
+    // interval_sec ago or a run of it is still going. Returns whether the caller claimed it,
+    // so only one API node syncs a mirror at a time.
+    pub fn claim(id: i64, interval_sec: u64, conn: &PgConnection) -> QueryResult<bool> {
+        Counter::DBCall.increment();
+        let cutoff = Utc::now() - Duration::seconds(interval_sec as i64);
+        let running = origin_channel_mirror_runs::table
+            .filter(origin_channel_mirror_runs::mirror_id.eq(id))
+            .filter(origin_channel_mirror_runs::status.eq(MirrorRunStatus::Running));
+        diesel::update(
+            origin_channel_mirrors::table
+                .find(id)
+                .filter(origin_channel_mirrors::claimed_at.is_null()
+                            .or(origin_channel_mirrors::claimed_at.lt(cutoff)))
+                .filter(not(exists(running))),
+        )
+        .set(origin_channel_mirrors::claimed_at.eq(now.nullable()))
+        .execute(conn)
+        .map(|count| count == 1)
+    }
+
+    // Whether the package of the given name is mirrored
+    pub fn wants(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|n| n == name))
+        && !self.exclude.iter().any(|n| n == name)
+    }
+}
+
+impl OriginChannelMirrorRun {
+    pub fn start(mirror_id: i64, conn: &PgConnection) -> QueryResult<OriginChannelMirrorRun> {
+        Counter::DBCall.increment();
+        diesel::insert_into(origin_channel_mirror_runs::table)
+            .values(origin_channel_mirror_runs::mirror_id.eq(mirror_id))
+            .get_result(conn)
+    }
+
+    pub fn finish(id: i64,
+                  uploaded: i32,
+                  promoted: i32,
+                  errors: &[String],
+                  conn: &PgConnection)
+                  -> QueryResult<OriginChannelMirrorRun> {
+        let status = if errors.is_empty() {
+            MirrorRunStatus::Succeeded
+        } else {
+            MirrorRunStatus::Failed
+        };
+
+        Counter::DBCall.increment();
+        diesel::update(origin_channel_mirror_runs::table.find(id))
+            .set((origin_channel_mirror_runs::status.eq(status),
+                  origin_channel_mirror_runs::uploaded.eq(uploaded),
+                  origin_channel_mirror_runs::promoted.eq(promoted),
+                  origin_channel_mirror_runs::errors.eq(errors),
+                  origin_channel_mirror_runs::finished_at.eq(now.nullable())))
+            .get_result(conn)
+    }
+
+    // Fails the runs which started more than lease_sec ago and never finished, the server
+    // which started them having gone away. Runs of other servers which are still within their
+    // lease are left alone. Returns the number of rows changed.
+    pub fn fail_expired(lease_sec: u64, conn: &PgConnection) -> QueryResult<usize> {
+        let errors = vec!["The sync was interrupted".to_string()];
+        let cutoff = Utc::now() - Duration::seconds(lease_sec as i64);
+
+        Counter::DBCall.increment();
+        diesel::update(
+            origin_channel_mirror_runs::table
+                .filter(origin_channel_mirror_runs::status.eq(MirrorRunStatus::Running))
+                .filter(origin_channel_mirror_runs::started_at.lt(cutoff)),
+        )
+        .set((origin_channel_mirror_runs::status.eq(MirrorRunStatus::Failed),
+              origin_channel_mirror_runs::errors.eq(errors),
+              origin_channel_mirror_runs::finished_at.eq(now.nullable())))
+        .execute(conn)
+    }
+
+    // The most recent runs of the mirror, newest first
+    pub fn list(mirror_id: i64,
+                limit: i64,
+                conn: &PgConnection)
+                -> QueryResult<Vec<OriginChannelMirrorRun>> {
+        Counter::DBCall.increment();
+        origin_channel_mirror_runs::table
+            .filter(origin_channel_mirror_runs::mirror_id.eq(mirror_id))
+            .order(origin_channel_mirror_runs::started_at.desc())
+            .limit(limit)
+            .get_results(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mirror(include: &[&str], exclude: &[&str]) -> OriginChannelMirror {
+        OriginChannelMirror { id:           1,
+                              origin:       "core".to_string(),
+                              channel:      "stable".to_string(),
+                              upstream_url: "https://bldr.habitat.sh".to_string(),
+                              include:      include.iter().map(|n| n.to_string()).collect(),
+                              exclude:      exclude.iter().map(|n| n.to_string()).collect(),
+                              owner_id:     1,
+                              created_at:   None,
+                              updated_at:   None,
+                              claimed_at:   None, }
+    }
+
+    #[test]
+    fn wants_applies_include_then_exclude() {
+        assert!(mirror(&[], &[]).wants("nginx"));
+        assert!(!mirror(&[], &["nginx"]).wants("nginx"));
+        assert!(mirror(&["nginx", "redis"], &[]).wants("redis"));
+        assert!(!mirror(&["nginx"], &[]).wants("redis"));
+        assert!(!mirror(&["nginx"], &["nginx"]).wants("nginx"));
+    }
+}
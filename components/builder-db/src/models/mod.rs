@@ -3,18 +3,28 @@
 #![allow(proc_macro_derive_resolution_fallback)]
 
 pub mod account;
+pub mod audit;
 pub mod channel;
+pub mod channel_history;
+pub mod channel_policy;
+pub mod channel_snapshot;
 pub mod integration;
 pub mod invitations;
 pub mod jobs;
 pub mod keys;
+pub mod mirror;
 pub mod origin;
 pub mod package;
 pub mod pagination;
 pub mod project_integration;
 pub mod projects;
+pub mod provenance;
+pub mod retention;
+pub mod scheduled_promotion;
 pub mod secrets;
 pub mod settings;
+pub mod team;
+pub mod upload;
 
 mod db_id_format {
     use serde::{self,
@@ -63,3 +73,26 @@ mod db_optional_id_format {
         }
     }
 }
+
+mod db_id_vec_format {
+    use serde::{self,
+                Deserialize,
+                Deserializer,
+                Serializer};
+
+    #[allow(clippy::ptr_arg)]
+    pub fn serialize<S>(ids: &Vec<i64>, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serializer.collect_seq(ids.iter().map(ToString::to_string))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<i64>, D::Error>
+        where D: Deserializer<'de>
+    {
+        let ids = Vec::<String>::deserialize(deserializer)?;
+        ids.iter()
+           .map(|s| s.parse::<i64>().map_err(serde::de::Error::custom))
+           .collect()
+    }
+}
@@ -11,28 +11,37 @@ use diesel::{self,
              QueryDsl,
              RunQueryDsl};
 
-use crate::{models::{channel::{Channel,
+use crate::{models::{account::ServiceAccount,
+                     channel::{Channel,
                                CreateChannel},
-                     package::PackageVisibility},
+                     package::PackageVisibility,
+                     team::Team},
             protocol::originsrv};
 
 use crate::schema::{audit::audit_origin,
                     channel::origin_channels,
+                    channel_policy::origin_channel_policies,
+                    channel_snapshot::origin_channel_snapshots,
                     integration::origin_integrations,
                     invitation::origin_invitations,
-                    key::{origin_private_encryption_keys,
+                    key::{origin_package_signer_flags,
+                          origin_private_encryption_keys,
                           origin_public_encryption_keys,
                           origin_public_keys,
                           origin_secret_keys},
                     member::origin_members,
+                    mirror::origin_channel_mirrors,
                     origin::{origins,
                              origins_with_secret_key,
                              origins_with_stats},
                     package::origin_packages,
                     project::origin_projects,
                     project_integration::origin_project_integrations,
+                    retention::origin_retention_policies,
+                    scheduled_promotion::origin_scheduled_promotions,
                     secrets::origin_secrets,
-                    settings::origin_package_settings};
+                    settings::origin_package_settings,
+                    upload::origin_package_uploads};
 
 use crate::{bldr_core::{metrics::CounterMetric,
                         Error as BuilderError},
@@ -51,6 +60,7 @@ pub struct Origin {
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
     pub default_package_visibility: PackageVisibility,
+    pub reject_unknown_key_revisions: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Queryable)]
@@ -61,6 +71,7 @@ pub struct OriginWithSecretKey {
     pub private_key_name: Option<String>,
     pub default_package_visibility: PackageVisibility,
     pub owner_account: String,
+    pub reject_unknown_key_revisions: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Queryable)]
@@ -166,6 +177,8 @@ pub enum OriginOperation {
     OriginCreate,
     OriginDelete,
     OwnerTransfer,
+    KeyRotate,
+    KeyRevoke,
 }
 
 #[derive(Debug, Serialize, Deserialize, Insertable)]
@@ -245,6 +258,16 @@ impl Origin {
                                                  .execute(conn)
     }
 
+    pub fn update_signer_policy(name: &str,
+                                reject_unknown_key_revisions: bool,
+                                conn: &PgConnection)
+                                -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::update(origins::table.find(name))
+            .set(origins::reject_unknown_key_revisions.eq(reject_unknown_key_revisions))
+            .execute(conn)
+    }
+
     pub fn delete(origin: &str, conn: &PgConnection) -> QueryResult<()> {
         // By this point, most of the associated origin data has already been manually deleted
         // by the user. We ensure this by double checking the most critical tables are already empty
@@ -260,6 +283,7 @@ impl Origin {
                 .execute(conn)?;
             diesel::delete(origin_public_keys::table.filter(origin_public_keys::origin.eq(origin)))
                 .execute(conn)?;
+            ServiceAccount::delete_all(origin, conn)?;
             diesel::delete(origin_members::table.filter(origin_members::origin.eq(origin)))
                 .execute(conn)?;
             diesel::delete(origin_package_settings::table.filter(origin_package_settings::origin.eq(origin)))
@@ -276,10 +300,24 @@ impl Origin {
                 .execute(conn)?;
             diesel::delete(origin_secrets::table.filter(origin_secrets::origin.eq(origin)))
                 .execute(conn)?;
+            diesel::delete(origin_retention_policies::table.filter(origin_retention_policies::origin.eq(origin)))
+                .execute(conn)?;
+            diesel::delete(origin_channel_policies::table.filter(origin_channel_policies::origin.eq(origin)))
+                .execute(conn)?;
+            diesel::delete(origin_channel_snapshots::table.filter(origin_channel_snapshots::origin.eq(origin)))
+                .execute(conn)?;
+            diesel::delete(origin_channel_mirrors::table.filter(origin_channel_mirrors::origin.eq(origin)))
+                .execute(conn)?;
+            diesel::delete(origin_scheduled_promotions::table.filter(origin_scheduled_promotions::origin.eq(origin)))
+                .execute(conn)?;
+            diesel::delete(origin_package_uploads::table.filter(origin_package_uploads::origin.eq(origin)))
+                .execute(conn)?;
             diesel::delete(origin_private_encryption_keys::table.filter(origin_private_encryption_keys::origin.eq(origin)))
                 .execute(conn)?;
             diesel::delete(origin_public_encryption_keys::table.filter(origin_public_encryption_keys::origin.eq(origin)))
                 .execute(conn)?;
+            diesel::delete(origin_package_signer_flags::table.filter(origin_package_signer_flags::origin.eq(origin)))
+                .execute(conn)?;
             diesel::delete(origin_packages::table.filter(origin_packages::origin.eq(origin)))
                 .execute(conn)?;
             diesel::delete(origins::table.filter(origins::name.eq(origin))).execute(conn)?;
@@ -287,9 +325,12 @@ impl Origin {
         })
     }
 
+    // The service accounts of the origin do not carry over to the new owner
     pub fn transfer(origin: &str, account_id: i64, conn: &PgConnection) -> QueryResult<usize> {
         Counter::DBCall.increment();
         conn.transaction::<_, Error, _>(|| {
+                ServiceAccount::delete_all(origin, conn)?;
+
                 let owner = OriginMemberRole::Owner;
                 let maintainer = OriginMemberRole::Maintainer;
 
@@ -310,10 +351,13 @@ impl Origin {
 
     pub fn depart(origin: &str, account_id: i64, conn: &PgConnection) -> QueryResult<usize> {
         Counter::DBCall.increment();
-        diesel::delete(origin_members::table
-                .filter(origin_members::account_id.eq(account_id))
-                .filter(origin_members::origin.eq(origin)))
-                .execute(conn)
+        conn.transaction::<_, Error, _>(|| {
+                Team::remove_from_origin(origin, account_id, conn)?;
+                diesel::delete(origin_members::table
+                        .filter(origin_members::account_id.eq(account_id))
+                        .filter(origin_members::origin.eq(origin)))
+                        .execute(conn)
+            })
     }
 
     pub fn check_membership(origin: &str,
@@ -344,17 +388,27 @@ impl OriginMember {
         use crate::schema::account::accounts;
 
         Counter::DBCall.increment();
-        diesel::delete(
-            origin_members::table
-                .filter(origin_members::origin.eq(origin))
-                .filter(
-                    origin_members::account_id.nullable().eq(accounts::table
-                        .select(accounts::id)
-                        .filter(accounts::name.eq(account_name))
-                        .single_value()),
-                ),
-        )
-        .execute(conn)
+        conn.transaction::<_, Error, _>(|| {
+                let account_id = accounts::table.select(accounts::id)
+                                                .filter(accounts::name.eq(account_name))
+                                                .get_result::<i64>(conn)
+                                                .optional()?;
+                if let Some(account_id) = account_id {
+                    Team::remove_from_origin(origin, account_id, conn)?;
+                }
+
+                diesel::delete(
+                origin_members::table
+                    .filter(origin_members::origin.eq(origin))
+                    .filter(
+                        origin_members::account_id.nullable().eq(accounts::table
+                            .select(accounts::id)
+                            .filter(accounts::name.eq(account_name))
+                            .single_value()),
+                    ),
+            )
+            .execute(conn)
+            })
     }
 
     pub fn add(origin: &str,
@@ -371,22 +425,41 @@ impl OriginMember {
             .execute(conn)
     }
 
+    // Counts the people in the origin, leaving out its service accounts
     pub fn count_origin_members(origin: &str, conn: &PgConnection) -> QueryResult<i64> {
+        use crate::schema::account::accounts;
+
         Counter::DBCall.increment();
-        origin_members::table.select(count(origin_members::account_id))
+        origin_members::table.inner_join(accounts::table)
+                             .select(count(origin_members::account_id))
                              .filter(origin_members::origin.eq(&origin))
+                             .filter(accounts::service_origin.is_null())
                              .first(conn)
     }
 
+    // The effective role of the account, the highest of its own role and the roles of the
+    // teams it is in
     pub fn member_role(origin: &str,
                        account_id: i64,
                        conn: &PgConnection)
                        -> QueryResult<OriginMemberRole> {
         Counter::DBCall.increment();
-        origin_members::table.select(origin_members::member_role)
-                             .filter(origin_members::origin.eq(&origin))
-                             .filter(origin_members::account_id.eq(account_id))
-                             .get_result(conn)
+        let role: OriginMemberRole =
+            origin_members::table.select(origin_members::member_role)
+                                 .filter(origin_members::origin.eq(&origin))
+                                 .filter(origin_members::account_id.eq(account_id))
+                                 .get_result(conn)?;
+
+        let team_roles = Team::roles_of(origin, account_id, conn)?;
+        Ok(OriginMember::effective_role(role, &team_roles))
+    }
+
+    // The highest of the role and the team roles, a team never lowers a role
+    pub fn effective_role(role: OriginMemberRole,
+                          team_roles: &[OriginMemberRole])
+                          -> OriginMemberRole {
+        team_roles.iter()
+                  .fold(role, |max, r| if *r > max { *r } else { max })
     }
 
     pub fn update_member_role(origin: &str,
@@ -419,7 +492,8 @@ impl From<originsrv::Origin> for Origin {
                  default_package_visibility:
                      PackageVisibility::from(origin.get_default_package_visibility()),
                  created_at: None,
-                 updated_at: None, }
+                 updated_at: None,
+                 reject_unknown_key_revisions: false, }
     }
 }
 
@@ -439,4 +513,19 @@ mod tests {
         assert_eq!(maintainer > member, true);
         assert_eq!(member > readonly_member, true);
     }
+
+    #[test]
+    fn origin_member_effective_role() {
+        let readonly_member = OriginMemberRole::ReadonlyMember;
+        let member = OriginMemberRole::Member;
+        let maintainer = OriginMemberRole::Maintainer;
+        let administrator = OriginMemberRole::Administrator;
+        assert_eq!(OriginMember::effective_role(member, &[]), member);
+        assert_eq!(OriginMember::effective_role(readonly_member, &[maintainer]),
+                   maintainer);
+        assert_eq!(OriginMember::effective_role(administrator, &[member, maintainer]),
+                   administrator);
+        assert_eq!(OriginMember::effective_role(member, &[maintainer, readonly_member]),
+                   maintainer);
+    }
 }
@@ -5,13 +5,16 @@ use std::{fmt,
                 FromStr},
           time::Instant};
 
-use chrono::NaiveDateTime;
+use chrono::{DateTime,
+             NaiveDateTime,
+             Utc};
 
 use diesel::{self,
              deserialize::{self,
                            FromSql},
              dsl::{count,
                    sql},
+             expression::SqlLiteral,
              pg::{expression::dsl::any,
                   upsert::{excluded,
                            on_constraint},
@@ -23,7 +26,10 @@ use diesel::{self,
                          IsNull,
                          Output,
                          ToSql},
-             sql_types::Text,
+             sql_types::{BigInt,
+                         Bool,
+                         Integer,
+                         Text},
              PgArrayExpressionMethods,
              RunQueryDsl};
 use diesel_full_text_search::{to_tsquery,
@@ -255,6 +261,13 @@ pub const ALL_COLUMNS_WITH_VERSION: AllColumnsWithVersion =
 type AllWithVersion =
     diesel::dsl::Select<origin_packages_with_version_array::table, AllColumnsWithVersion>;
 
+type SearchQuery =
+    diesel::dsl::IntoBoxed<'static,
+                           diesel::dsl::Select<diesel::dsl::InnerJoin<origin_packages::table,
+                                                                      origins::table>,
+                                               SqlLiteral<Text>>,
+                           Pg>;
+
 #[derive(Debug, Serialize, Deserialize, Clone, Insertable)]
 #[table_name = "origin_packages"]
 pub struct NewPackage {
@@ -315,11 +328,29 @@ pub struct ListPackages {
     pub limit:      i64,
 }
 
+pub struct ListPackagesForDeletion<'a> {
+    pub origin:         &'a str,
+    pub name:           Option<&'a str>,
+    pub target:         Option<BuilderPackageTarget>,
+    pub channel:        Option<&'a ChannelIdent>,
+    pub created_after:  Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+}
+
 pub struct SearchPackages {
-    pub query:      String,
-    pub account_id: Option<i64>,
-    pub page:       i64,
-    pub limit:      i64,
+    pub query:          String,
+    // The package name searched for, used to rank exact and prefix matches first
+    pub term:           String,
+    pub account_id:     Option<i64>,
+    pub page:           i64,
+    pub limit:          i64,
+    pub origin:         Option<String>,
+    pub target:         Option<BuilderPackageTarget>,
+    pub channel:        Option<ChannelIdent>,
+    pub visibility:     Option<PackageVisibility>,
+    pub created_after:  Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub depends_on:     Option<String>,
 }
 #[derive(Debug, Serialize, Deserialize, Queryable)]
 pub struct OriginPackageVersions {
@@ -749,6 +780,45 @@ impl Package {
         result
     }
 
+    pub fn list_for_deletion(req: &ListPackagesForDeletion,
+                             conn: &PgConnection)
+                             -> QueryResult<Vec<Package>> {
+        Counter::DBCall.increment();
+        let start_time = Instant::now();
+
+        let mut query = Self::all().filter(origin_packages::origin.eq(req.origin))
+                                   .into_boxed();
+
+        if let Some(name) = req.name {
+            query = query.filter(origin_packages::name.eq(name));
+        }
+        if let Some(ref target) = req.target {
+            query = query.filter(origin_packages::target.eq(target.clone()));
+        }
+        if let Some(channel) = req.channel {
+            let channel_packages =
+                origin_channel_packages::table.inner_join(origin_channels::table)
+                                              .select(origin_channel_packages::package_id)
+                                              .filter(origin_channels::origin.eq(req.origin))
+                                              .filter(origin_channels::name.eq(channel.as_str()));
+            query = query.filter(origin_packages::id.eq_any(channel_packages));
+        }
+        if let Some(created_after) = req.created_after {
+            query = query.filter(origin_packages::created_at.ge(created_after));
+        }
+        if let Some(created_before) = req.created_before {
+            query = query.filter(origin_packages::created_at.lt(created_before));
+        }
+
+        let result = query.order(origin_packages::ident.asc()).get_results(conn);
+
+        let duration_millis = start_time.elapsed().as_millis();
+        trace!("DBCall package::list_for_deletion time: {} ms",
+               duration_millis);
+        Histogram::DbCallTime.set(duration_millis as f64);
+        result
+    }
+
     pub fn count_origin_packages(origin: &str, conn: &PgConnection) -> QueryResult<i64> {
         Counter::DBCall.increment();
         let start_time = Instant::now();
@@ -770,14 +840,11 @@ impl Package {
                   -> QueryResult<(Vec<BuilderPackageIdent>, i64)> {
         Counter::DBCall.increment();
         let start_time = Instant::now();
+        let (account_id, page, limit) = (sp.account_id, sp.page, sp.limit);
 
-        let mut query = origin_packages::table
-            .select(origin_packages::ident)
-            .filter(to_tsquery(sp.query).matches(origin_packages::ident_vector))
-            .order(origin_packages::ident.asc())
-            .into_boxed();
+        let mut query = search_query(sp, "origin_packages.ident", "origin_packages.ident");
 
-        if let Some(session_id) = sp.account_id {
+        if let Some(session_id) = account_id {
             let origins = origin_members::table.select(origin_members::origin)
                                                .filter(origin_members::account_id.eq(session_id));
             query = query.filter(
@@ -790,8 +857,8 @@ impl Package {
             query = query.filter(origin_packages::visibility.eq(PackageVisibility::Public));
         }
 
-        let result = query.paginate(sp.page)
-                          .per_page(sp.limit)
+        let result = query.paginate(page)
+                          .per_page(limit)
                           .load_and_count_records(conn);
 
         let duration_millis = start_time.elapsed().as_millis();
@@ -801,21 +868,18 @@ impl Package {
         result
     }
 
-    // This is me giving up on fighting the typechecker and just duplicating a bunch of code
     pub fn search_distinct(sp: SearchPackages,
                            conn: &PgConnection)
                            -> QueryResult<(Vec<BuilderPackageIdent>, i64)> {
         Counter::DBCall.increment();
         let start_time = Instant::now();
+        let (account_id, page, limit) = (sp.account_id, sp.page, sp.limit);
 
-        let mut query = origin_packages::table
-            .inner_join(origins::table)
-            .select(sql("concat_ws('/', origins.name, origin_packages.name)"))
-            .filter(to_tsquery(sp.query).matches(origin_packages::ident_vector))
-            .order(origin_packages::name.asc())
-            .into_boxed();
+        let mut query = search_query(sp,
+                                     "concat_ws('/', origins.name, origin_packages.name)",
+                                     "origin_packages.name");
 
-        if let Some(session_id) = sp.account_id {
+        if let Some(session_id) = account_id {
             query = query.filter(
                 origin_packages::visibility
                     .eq(any(PackageVisibility::private()))
@@ -829,8 +893,8 @@ impl Package {
         // Because of the filter hack it is very important that this be the last filter
         query = query.filter(sql("TRUE GROUP BY origin_packages.name, origins.name"));
 
-        let result = query.paginate(sp.page)
-                          .per_page(sp.limit)
+        let result = query.paginate(page)
+                          .per_page(limit)
                           .load_and_count_records(conn);
 
         let duration_millis = start_time.elapsed().as_millis();
@@ -886,6 +950,84 @@ impl PackageWithChannelPlatform {
     }
 }
 
+// The packages matching a search, selecting `select` and ranked exact name matches first, then
+// names starting with the term, then the rest, ties broken by `order_by`. Every filter of the
+// search but visibility, which depends on how the results are grouped, is applied.
+fn search_query(sp: SearchPackages, select: &str, order_by: &str) -> SearchQuery {
+    let mut query = origin_packages::table
+        .inner_join(origins::table)
+        .select(sql::<Text>(select))
+        .filter(to_tsquery(sp.query).matches(origin_packages::ident_vector))
+        .order((
+            sql::<Integer>("CASE strpos(origin_packages.name, ")
+                .bind::<Text, _>(sp.term.clone())
+                .sql(") WHEN 0 THEN 3 WHEN 1 THEN (CASE WHEN origin_packages.name = ")
+                .bind::<Text, _>(sp.term)
+                .sql(" THEN 0 ELSE 1 END) ELSE 2 END"),
+            sql::<Text>(order_by).asc(),
+        ))
+        .into_boxed();
+
+    if let Some(origin) = sp.origin {
+        query = query.filter(origin_packages::origin.eq(origin));
+    }
+    if let Some(target) = sp.target {
+        query = query.filter(origin_packages::target.eq(target));
+    }
+    if let Some(channel) = sp.channel {
+        // Only the channel of that name in the package's own origin counts
+        query = query.filter(
+            sql::<Bool>("EXISTS (SELECT 1 FROM origin_channel_packages INNER JOIN \
+                         origin_channels ON origin_channels.id = \
+                         origin_channel_packages.channel_id WHERE \
+                         origin_channel_packages.package_id = origin_packages.id AND \
+                         origin_channels.origin = origin_packages.origin AND \
+                         origin_channels.name = ")
+                .bind::<Text, _>(channel.to_string())
+                .sql(")"),
+        );
+    }
+    if let Some(visibility) = sp.visibility {
+        query = query.filter(origin_packages::visibility.eq(visibility));
+    }
+    if let Some(created_after) = sp.created_after {
+        query = query.filter(origin_packages::created_at.ge(created_after));
+    }
+    if let Some(created_before) = sp.created_before {
+        query = query.filter(origin_packages::created_at.lt(created_before));
+    }
+    if let Some(ref dep) = sp.depends_on {
+        query = query.filter(origin_packages::id.eq_any(dependents_of(dep)));
+    }
+    query
+}
+
+// Ids of the packages with a runtime dependency on `dep`, which may be anything from an
+// origin/name to a fully qualified ident
+fn dependents_of(dep: &str) -> origin_packages::BoxedQuery<'static, Pg, BigInt> {
+    let parts: Vec<&str> = dep.split('/').collect();
+    let query = origin_packages::table.select(origin_packages::id)
+                                      .into_boxed();
+
+    if parts.len() >= 4 {
+        return query.filter(origin_packages::deps.contains(vec![dep.to_string()]));
+    }
+
+    let query = query.filter(
+        sql::<Bool>("package_dep_names_v1(origin_packages.deps) @> ARRAY[")
+            .bind::<Text, _>(parts.iter().take(2).cloned().collect::<Vec<_>>().join("/"))
+            .sql("]"),
+    );
+
+    if parts.len() == 3 {
+        query.filter(sql::<Bool>("EXISTS (SELECT 1 FROM unnest(origin_packages.deps) AS dep \
+                                  WHERE strpos(dep, ").bind::<Text, _>(format!("{}/", dep))
+                                                      .sql(") = 1)"))
+    } else {
+        query
+    }
+}
+
 fn searchable_ident(ident: &BuilderPackageIdent) -> Vec<String> {
     // https://github.com/rust-lang/rust-clippy/issues/3071U
     #[allow(clippy::redundant_closure)]
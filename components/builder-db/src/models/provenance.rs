@@ -0,0 +1,111 @@
+use super::{db_id_format,
+            db_optional_id_format};
+use chrono::NaiveDateTime;
+
+use diesel::{self,
+             pg::{expression::dsl::any,
+                  PgConnection},
+             result::QueryResult,
+             ExpressionMethods,
+             QueryDsl,
+             RunQueryDsl};
+
+use crate::{models::package::BuilderPackageIdent,
+            schema::provenance::origin_package_provenance};
+
+use crate::{bldr_core::metrics::CounterMetric,
+            metrics::Counter};
+
+#[derive(Debug,
+         Serialize,
+         Deserialize,
+         QueryableByName,
+         Queryable,
+         Clone,
+         Identifiable)]
+#[table_name = "origin_package_provenance"]
+pub struct PackageProvenance {
+    #[serde(with = "db_id_format")]
+    pub id:             i64,
+    #[serde(with = "db_id_format")]
+    pub package_id:     i64,
+    pub attested:       bool,
+    #[serde(with = "db_optional_id_format")]
+    pub job_id:         Option<i64>,
+    #[serde(with = "db_optional_id_format")]
+    pub group_id:       Option<i64>,
+    pub vcs_url:        Option<String>,
+    pub vcs_sha:        Option<String>,
+    pub worker:         Option<String>,
+    pub plan_path:      Option<String>,
+    pub studio_version: Option<String>,
+    pub hab_version:    Option<String>,
+    pub deps:           Vec<BuilderPackageIdent>,
+    pub created_at:     Option<NaiveDateTime>,
+    pub updated_at:     Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Default, Insertable, AsChangeset)]
+#[table_name = "origin_package_provenance"]
+pub struct NewPackageProvenance<'a> {
+    pub package_id:     i64,
+    pub attested:       bool,
+    pub job_id:         Option<i64>,
+    pub group_id:       Option<i64>,
+    pub vcs_url:        Option<&'a str>,
+    pub vcs_sha:        Option<&'a str>,
+    pub worker:         Option<&'a str>,
+    pub plan_path:      Option<&'a str>,
+    pub studio_version: Option<&'a str>,
+    pub hab_version:    Option<&'a str>,
+    pub deps:           Vec<BuilderPackageIdent>,
+}
+
+impl PackageProvenance {
+    // The provenance reported for a package that has nothing recorded
+    pub fn unattested(package_id: i64) -> PackageProvenance {
+        PackageProvenance { id: 0,
+                            package_id,
+                            attested: false,
+                            job_id: None,
+                            group_id: None,
+                            vcs_url: None,
+                            vcs_sha: None,
+                            worker: None,
+                            plan_path: None,
+                            studio_version: None,
+                            hab_version: None,
+                            deps: Vec::new(),
+                            created_at: None,
+                            updated_at: None }
+    }
+
+    // Records the provenance of a package, replacing anything recorded for it before
+    pub fn create(req: &NewPackageProvenance,
+                  conn: &PgConnection)
+                  -> QueryResult<PackageProvenance> {
+        Counter::DBCall.increment();
+        diesel::insert_into(origin_package_provenance::table)
+            .values(req)
+            .on_conflict(origin_package_provenance::package_id)
+            .do_update()
+            .set(req)
+            .get_result(conn)
+    }
+
+    pub fn get(package_id: i64, conn: &PgConnection) -> QueryResult<PackageProvenance> {
+        Counter::DBCall.increment();
+        origin_package_provenance::table.filter(origin_package_provenance::package_id.eq(package_id))
+                                        .get_result(conn)
+    }
+
+    // The packages from the given list which were built by a worker
+    pub fn attested(package_ids: &[i64], conn: &PgConnection) -> QueryResult<Vec<i64>> {
+        Counter::DBCall.increment();
+        origin_package_provenance::table
+            .filter(origin_package_provenance::package_id.eq(any(package_ids)))
+            .filter(origin_package_provenance::attested.eq(true))
+            .select(origin_package_provenance::package_id)
+            .get_results(conn)
+    }
+}
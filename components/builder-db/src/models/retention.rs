@@ -0,0 +1,329 @@
+use super::db_id_format;
+use chrono::{Duration,
+             NaiveDateTime,
+             Utc};
+use std::collections::HashMap;
+
+use diesel::{self,
+             dsl::now,
+             pg::PgConnection,
+             result::{Error,
+                      QueryResult},
+             BoolExpressionMethods,
+             Connection,
+             ExpressionMethods,
+             QueryDsl,
+             RunQueryDsl};
+
+use crate::{models::package::{BuilderPackageIdent,
+                              BuilderPackageTarget},
+            schema::{channel::{origin_channel_packages,
+                               origin_channels},
+                     package::origin_packages,
+                     retention::origin_retention_policies}};
+
+use crate::{bldr_core::metrics::CounterMetric,
+            metrics::Counter};
+
+#[derive(Debug,
+         Serialize,
+         Deserialize,
+         QueryableByName,
+         Queryable,
+         Clone,
+         Identifiable)]
+#[table_name = "origin_retention_policies"]
+pub struct OriginRetentionPolicy {
+    #[serde(with = "db_id_format")]
+    pub id:           i64,
+    pub origin:       String,
+    pub channel:      Option<String>,
+    pub keep_latest:  Option<i32>,
+    pub max_age_days: Option<i32>,
+    #[serde(with = "db_id_format")]
+    pub owner_id:     i64,
+    pub created_at:   Option<NaiveDateTime>,
+    pub updated_at:   Option<NaiveDateTime>,
+    #[serde(skip)]
+    pub swept_at:     Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "origin_retention_policies"]
+pub struct NewOriginRetentionPolicy<'a> {
+    pub origin:       &'a str,
+    pub channel:      Option<&'a str>,
+    pub keep_latest:  Option<i32>,
+    pub max_age_days: Option<i32>,
+    pub owner_id:     i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionReason {
+    KeepLatest,
+    MaxAge,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RetentionCandidate {
+    #[serde(with = "db_id_format")]
+    pub id:         i64,
+    pub ident:      BuilderPackageIdent,
+    pub target:     BuilderPackageTarget,
+    pub channels:   Vec<String>,
+    pub created_at: Option<NaiveDateTime>,
+    pub reason:     RetentionReason,
+}
+
+// A release of the origin: id, ident, target and upload time
+type PackageRow = (i64, BuilderPackageIdent, BuilderPackageTarget, Option<NaiveDateTime>);
+
+impl OriginRetentionPolicy {
+    pub fn list(origin: &str, conn: &PgConnection) -> QueryResult<Vec<OriginRetentionPolicy>> {
+        Counter::DBCall.increment();
+        origin_retention_policies::table.filter(origin_retention_policies::origin.eq(origin))
+                                        .order(origin_retention_policies::channel.asc())
+                                        .get_results(conn)
+    }
+
+    pub fn list_all(conn: &PgConnection) -> QueryResult<Vec<OriginRetentionPolicy>> {
+        Counter::DBCall.increment();
+        origin_retention_policies::table.order(origin_retention_policies::origin.asc())
+                                        .get_results(conn)
+    }
+
+    // Replaces any existing policy for the same origin and channel scope
+    pub fn set(req: &NewOriginRetentionPolicy,
+               conn: &PgConnection)
+               -> QueryResult<OriginRetentionPolicy> {
+        Counter::DBCall.increment();
+        conn.transaction::<_, Error, _>(|| {
+                Self::delete(req.origin, req.channel, conn)?;
+                diesel::insert_into(origin_retention_policies::table).values(req)
+                                                                     .get_result(conn)
+            })
+    }
+
+    // Marks the policy as swept, unless it was already swept less than interval_sec ago.
+    // Returns whether the caller claimed it, so only one API node applies a policy at a time.
+    pub fn claim(id: i64, interval_sec: u64, conn: &PgConnection) -> QueryResult<bool> {
+        Counter::DBCall.increment();
+        let cutoff = Utc::now() - Duration::seconds(interval_sec as i64);
+        diesel::update(
+            origin_retention_policies::table
+                .find(id)
+                .filter(origin_retention_policies::swept_at.is_null()
+                            .or(origin_retention_policies::swept_at.lt(cutoff))),
+        )
+        .set(origin_retention_policies::swept_at.eq(now.nullable()))
+        .execute(conn)
+        .map(|count| count == 1)
+    }
+
+    pub fn delete(origin: &str, channel: Option<&str>, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        match channel {
+            Some(channel) => {
+                diesel::delete(
+                    origin_retention_policies::table
+                        .filter(origin_retention_policies::origin.eq(origin))
+                        .filter(origin_retention_policies::channel.eq(channel)),
+                )
+                .execute(conn)
+            }
+            None => {
+                diesel::delete(
+                    origin_retention_policies::table
+                        .filter(origin_retention_policies::origin.eq(origin))
+                        .filter(origin_retention_policies::channel.is_null()),
+                )
+                .execute(conn)
+            }
+        }
+    }
+
+    // Computes the releases that fall outside of this policy. A release is a candidate when
+    // it is older than the `keep_latest` newest releases of its version and target, or when it
+    // was uploaded more than `max_age_days` ago and is not a member of any channel. A channel
+    // scoped policy only considers releases in that channel, and ignores membership of that
+    // channel for the age check. Callers are responsible for any further safety checks.
+    pub fn candidates(&self, conn: &PgConnection) -> QueryResult<Vec<RetentionCandidate>> {
+        Counter::DBCall.increment();
+        let packages: Vec<PackageRow> =
+            origin_packages::table.select((origin_packages::id,
+                                           origin_packages::ident,
+                                           origin_packages::target,
+                                           origin_packages::created_at))
+                                  .filter(origin_packages::origin.eq(&self.origin))
+                                  .get_results(conn)?;
+
+        Counter::DBCall.increment();
+        let memberships: Vec<(i64, String)> =
+            origin_channel_packages::table.inner_join(origin_channels::table)
+                                          .select((origin_channel_packages::package_id,
+                                                   origin_channels::name))
+                                          .filter(origin_channels::origin.eq(&self.origin))
+                                          .get_results(conn)?;
+
+        Ok(self.select(packages, memberships, Utc::now().naive_utc()))
+    }
+
+    fn select(&self,
+              packages: Vec<PackageRow>,
+              memberships: Vec<(i64, String)>,
+              now: NaiveDateTime)
+              -> Vec<RetentionCandidate> {
+        let mut channels: HashMap<i64, Vec<String>> = HashMap::new();
+        for (package_id, name) in memberships {
+            channels.entry(package_id)
+                    .or_insert_with(Vec::new)
+                    .push(name);
+        }
+
+        let in_scope = |id: &i64| {
+            match self.channel {
+                Some(ref scope) => channels.get(id).map_or(false, |c| c.contains(scope)),
+                None => true,
+            }
+        };
+
+        let mut reasons: HashMap<i64, RetentionReason> = HashMap::new();
+
+        if let Some(keep_latest) = self.keep_latest {
+            let mut groups: HashMap<(String, Option<String>, String), Vec<(i64, Option<String>)>> =
+                HashMap::new();
+            for (id, ident, target, _) in packages.iter().filter(|p| in_scope(&p.0)) {
+                groups.entry((ident.name.clone(), ident.version.clone(), target.to_string()))
+                      .or_insert_with(Vec::new)
+                      .push((*id, ident.release.clone()));
+            }
+            for releases in groups.values_mut() {
+                releases.sort_by(|a, b| b.1.cmp(&a.1));
+                for (id, _) in releases.iter().skip(keep_latest.max(0) as usize) {
+                    reasons.insert(*id, RetentionReason::KeepLatest);
+                }
+            }
+        }
+
+        if let Some(max_age_days) = self.max_age_days {
+            let cutoff = now - Duration::days(i64::from(max_age_days));
+            for (id, _, _, created_at) in packages.iter().filter(|p| in_scope(&p.0)) {
+                let expired = created_at.map_or(false, |c| c < cutoff);
+                let unreferenced =
+                    channels.get(id)
+                            .map_or(true, |c| c.iter().all(|n| Some(n) == self.channel.as_ref()));
+                if expired && unreferenced {
+                    reasons.entry(*id).or_insert(RetentionReason::MaxAge);
+                }
+            }
+        }
+
+        let mut result: Vec<RetentionCandidate> =
+            packages.into_iter()
+                    .filter_map(|(id, ident, target, created_at)| {
+                        reasons.remove(&id).map(|reason| {
+                                               RetentionCandidate { id,
+                                                                    ident,
+                                                                    target,
+                                                                    channels: channels.remove(&id)
+                                                                                      .unwrap_or_default(),
+                                                                    created_at,
+                                                                    reason }
+                                           })
+                    })
+                    .collect();
+        result.sort_by(|a, b| a.ident.to_string().cmp(&b.ident.to_string()));
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hab_core::package::{PackageIdent,
+                                   PackageTarget};
+    use std::str::FromStr;
+
+    fn policy(channel: Option<&str>,
+              keep_latest: Option<i32>,
+              max_age_days: Option<i32>)
+              -> OriginRetentionPolicy {
+        OriginRetentionPolicy { id: 1,
+                                origin: "core".to_string(),
+                                channel: channel.map(str::to_string),
+                                keep_latest,
+                                max_age_days,
+                                owner_id: 1,
+                                created_at: None,
+                                updated_at: None,
+                                swept_at: None }
+    }
+
+    fn now() -> NaiveDateTime { NaiveDateTime::from_timestamp(1_600_000_000, 0) }
+
+    fn package(id: i64, ident: &str, age_days: i64) -> PackageRow {
+        (id,
+         BuilderPackageIdent(PackageIdent::from_str(ident).unwrap()),
+         BuilderPackageTarget(PackageTarget::from_str("x86_64-linux").unwrap()),
+         Some(now() - Duration::days(age_days)))
+    }
+
+    fn packages() -> Vec<PackageRow> {
+        vec![package(1, "core/redis/6.0.6/20200101000000", 300),
+             package(2, "core/redis/6.0.6/20200201000000", 200),
+             package(3, "core/redis/6.0.6/20200301000000", 100),
+             package(4, "core/redis/6.0.7/20200401000000", 50),]
+    }
+
+    fn selected(candidates: &[RetentionCandidate]) -> Vec<(i64, RetentionReason)> {
+        candidates.iter()
+                  .map(|c| (c.id, c.reason.clone()))
+                  .collect()
+    }
+
+    #[test]
+    fn keep_latest_keeps_the_newest_releases_of_each_version() {
+        let candidates = policy(None, Some(1), None).select(packages(), Vec::new(), now());
+        assert_eq!(selected(&candidates),
+                   vec![(1, RetentionReason::KeepLatest),
+                        (2, RetentionReason::KeepLatest)]);
+
+        let candidates = policy(None, Some(3), None).select(packages(), Vec::new(), now());
+        assert!(candidates.is_empty());
+    }
+
+    #[test]
+    fn max_age_skips_releases_in_a_channel() {
+        let memberships = vec![(1, "stable".to_string())];
+        let candidates = policy(None, None, Some(150)).select(packages(), memberships, now());
+        assert_eq!(selected(&candidates), vec![(2, RetentionReason::MaxAge)]);
+    }
+
+    #[test]
+    fn keep_latest_takes_precedence_over_max_age() {
+        let candidates = policy(None, Some(2), Some(250)).select(packages(), Vec::new(), now());
+        assert_eq!(selected(&candidates),
+                   vec![(1, RetentionReason::KeepLatest)]);
+    }
+
+    #[test]
+    fn channel_policies_only_consider_their_channel() {
+        let memberships = vec![(1, "unstable".to_string()),
+                               (2, "unstable".to_string()),
+                               (2, "stable".to_string()),
+                               (3, "unstable".to_string())];
+        let candidates =
+            policy(Some("unstable"), Some(1), Some(150)).select(packages(), memberships, now());
+        assert_eq!(selected(&candidates),
+                   vec![(1, RetentionReason::KeepLatest),
+                        (2, RetentionReason::KeepLatest)]);
+        assert_eq!(candidates[1].channels,
+                   vec!["unstable".to_string(), "stable".to_string()]);
+
+        let memberships = vec![(1, "unstable".to_string()), (2, "stable".to_string())];
+        let candidates =
+            policy(Some("unstable"), None, Some(150)).select(packages(), memberships, now());
+        assert_eq!(selected(&candidates), vec![(1, RetentionReason::MaxAge)]);
+    }
+}
@@ -0,0 +1,198 @@
+use super::{db_id_format,
+            db_optional_id_format};
+use chrono::{DateTime,
+             Duration,
+             NaiveDateTime,
+             Utc};
+
+use diesel::{self,
+             dsl::now,
+             pg::PgConnection,
+             result::QueryResult,
+             ExpressionMethods,
+             NullableExpressionMethods,
+             QueryDsl,
+             RunQueryDsl};
+
+use crate::schema::scheduled_promotion::{origin_scheduled_promotion_items,
+                                         origin_scheduled_promotions};
+
+use crate::{bldr_core::metrics::CounterMetric,
+            metrics::Counter};
+
+#[derive(DbEnum, Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[PgType = "origin_scheduled_promotion_state"]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduledPromotionState {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Canceled,
+}
+
+#[derive(Debug,
+         Serialize,
+         Deserialize,
+         QueryableByName,
+         Queryable,
+         Clone,
+         Identifiable)]
+#[table_name = "origin_scheduled_promotions"]
+pub struct ScheduledPromotion {
+    #[serde(with = "db_id_format")]
+    pub id:             i64,
+    pub origin:         String,
+    pub channel:        String,
+    pub target:         String,
+    // Fully qualified idents to promote, empty when promoting a job group
+    pub idents:         Vec<String>,
+    #[serde(with = "db_optional_id_format")]
+    pub group_id:       Option<i64>,
+    pub execute_at:     DateTime<Utc>,
+    pub state:          ScheduledPromotionState,
+    #[serde(with = "db_id_format")]
+    pub requester_id:   i64,
+    pub requester_name: String,
+    // Why the promotion as a whole could not run, the items record their own failures
+    pub error:          Option<String>,
+    pub created_at:     Option<NaiveDateTime>,
+    pub finished_at:    Option<NaiveDateTime>,
+    pub claimed_at:     Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "origin_scheduled_promotions"]
+pub struct NewScheduledPromotion<'a> {
+    pub origin:         &'a str,
+    pub channel:        &'a str,
+    pub target:         &'a str,
+    pub idents:         Vec<String>,
+    pub group_id:       Option<i64>,
+    pub execute_at:     DateTime<Utc>,
+    pub requester_id:   i64,
+    pub requester_name: &'a str,
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Clone)]
+pub struct ScheduledPromotionItem {
+    #[serde(with = "db_id_format")]
+    pub id:           i64,
+    #[serde(with = "db_id_format")]
+    pub promotion_id: i64,
+    pub ident:        String,
+    pub target:       String,
+    pub succeeded:    bool,
+    pub error:        Option<String>,
+    pub created_at:   Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "origin_scheduled_promotion_items"]
+pub struct NewScheduledPromotionItem<'a> {
+    pub promotion_id: i64,
+    pub ident:        &'a str,
+    pub target:       &'a str,
+    pub succeeded:    bool,
+    pub error:        Option<&'a str>,
+}
+
+impl ScheduledPromotion {
+    pub fn create(req: &NewScheduledPromotion,
+                  conn: &PgConnection)
+                  -> QueryResult<ScheduledPromotion> {
+        Counter::DBCall.increment();
+        diesel::insert_into(origin_scheduled_promotions::table).values(req)
+                                                               .get_result(conn)
+    }
+
+    pub fn get(id: i64, conn: &PgConnection) -> QueryResult<ScheduledPromotion> {
+        Counter::DBCall.increment();
+        origin_scheduled_promotions::table.find(id).get_result(conn)
+    }
+
+    // The promotions of the origin, the latest to run first
+    pub fn list(origin: &str, conn: &PgConnection) -> QueryResult<Vec<ScheduledPromotion>> {
+        Counter::DBCall.increment();
+        origin_scheduled_promotions::table.filter(origin_scheduled_promotions::origin.eq(origin))
+                                          .order(origin_scheduled_promotions::execute_at.desc())
+                                          .get_results(conn)
+    }
+
+    // Cancels the promotion if it has not started yet. Returns the number of rows changed.
+    pub fn cancel(id: i64, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::update(
+            origin_scheduled_promotions::table
+                .find(id)
+                .filter(origin_scheduled_promotions::state.eq(ScheduledPromotionState::Pending)),
+        )
+        .set((origin_scheduled_promotions::state.eq(ScheduledPromotionState::Canceled),
+              origin_scheduled_promotions::finished_at.eq(now.nullable())))
+        .execute(conn)
+    }
+
+    // Marks every pending promotion which is due as running and returns them. Only one caller
+    // gets to claim a promotion, so several API nodes can run the scheduler at once.
+    pub fn claim_due(conn: &PgConnection) -> QueryResult<Vec<ScheduledPromotion>> {
+        Counter::DBCall.increment();
+        diesel::update(
+            origin_scheduled_promotions::table
+                .filter(origin_scheduled_promotions::state.eq(ScheduledPromotionState::Pending))
+                .filter(origin_scheduled_promotions::execute_at.le(now)),
+        )
+        .set((origin_scheduled_promotions::state.eq(ScheduledPromotionState::Running),
+              origin_scheduled_promotions::claimed_at.eq(now.nullable())))
+        .get_results(conn)
+    }
+
+    // Fails the promotions which were claimed more than lease_sec ago and never finished, the
+    // scheduler which claimed them having gone away. They are not run again since some of their
+    // packages may already have been promoted. Returns the number of rows changed.
+    pub fn fail_expired(lease_sec: u64, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        let cutoff = Utc::now() - Duration::seconds(lease_sec as i64);
+        diesel::update(
+            origin_scheduled_promotions::table
+                .filter(origin_scheduled_promotions::state.eq(ScheduledPromotionState::Running))
+                .filter(origin_scheduled_promotions::claimed_at.lt(cutoff)),
+        )
+        .set((origin_scheduled_promotions::state.eq(ScheduledPromotionState::Failed),
+              origin_scheduled_promotions::error.eq("The promotion was interrupted"),
+              origin_scheduled_promotions::finished_at.eq(now.nullable())))
+        .execute(conn)
+    }
+
+    pub fn finish(id: i64,
+                  state: ScheduledPromotionState,
+                  error: Option<&str>,
+                  conn: &PgConnection)
+                  -> QueryResult<ScheduledPromotion> {
+        Counter::DBCall.increment();
+        diesel::update(origin_scheduled_promotions::table.find(id))
+            .set((origin_scheduled_promotions::state.eq(state),
+                  origin_scheduled_promotions::error.eq(error),
+                  origin_scheduled_promotions::finished_at.eq(now.nullable())))
+            .get_result(conn)
+    }
+}
+
+impl ScheduledPromotionItem {
+    pub fn create(req: &NewScheduledPromotionItem,
+                  conn: &PgConnection)
+                  -> QueryResult<ScheduledPromotionItem> {
+        Counter::DBCall.increment();
+        diesel::insert_into(origin_scheduled_promotion_items::table).values(req)
+                                                                    .get_result(conn)
+    }
+
+    pub fn list(promotion_id: i64,
+                conn: &PgConnection)
+                -> QueryResult<Vec<ScheduledPromotionItem>> {
+        Counter::DBCall.increment();
+        origin_scheduled_promotion_items::table
+            .filter(origin_scheduled_promotion_items::promotion_id.eq(promotion_id))
+            .order(origin_scheduled_promotion_items::ident.asc())
+            .get_results(conn)
+    }
+}
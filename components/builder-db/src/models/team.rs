@@ -0,0 +1,141 @@
+use super::db_id_format;
+use chrono::NaiveDateTime;
+
+use diesel::{self,
+             dsl::now,
+             pg::PgConnection,
+             result::QueryResult,
+             ExpressionMethods,
+             NullableExpressionMethods,
+             QueryDsl,
+             RunQueryDsl};
+
+use crate::{models::origin::OriginMemberRole,
+            schema::{account::accounts,
+                     team::{origin_team_members,
+                            origin_teams}}};
+
+use crate::{bldr_core::metrics::CounterMetric,
+            metrics::Counter};
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Clone)]
+pub struct Team {
+    #[serde(with = "db_id_format")]
+    pub id:          i64,
+    pub origin:      String,
+    pub name:        String,
+    pub member_role: OriginMemberRole,
+    #[serde(with = "db_id_format")]
+    pub owner_id:    i64,
+    pub created_at:  Option<NaiveDateTime>,
+    pub updated_at:  Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[table_name = "origin_teams"]
+pub struct NewTeam<'a> {
+    pub origin:      &'a str,
+    pub name:        &'a str,
+    pub member_role: OriginMemberRole,
+    pub owner_id:    i64,
+}
+
+impl Team {
+    pub fn list(origin: &str, conn: &PgConnection) -> QueryResult<Vec<Team>> {
+        Counter::DBCall.increment();
+        origin_teams::table.filter(origin_teams::origin.eq(origin))
+                           .order(origin_teams::name.asc())
+                           .get_results(conn)
+    }
+
+    pub fn get(origin: &str, name: &str, conn: &PgConnection) -> QueryResult<Team> {
+        Counter::DBCall.increment();
+        origin_teams::table.filter(origin_teams::origin.eq(origin))
+                           .filter(origin_teams::name.eq(name))
+                           .get_result(conn)
+    }
+
+    pub fn create(req: &NewTeam, conn: &PgConnection) -> QueryResult<Team> {
+        Counter::DBCall.increment();
+        diesel::insert_into(origin_teams::table).values(req)
+                                                .get_result(conn)
+    }
+
+    pub fn update_role(id: i64,
+                       member_role: OriginMemberRole,
+                       conn: &PgConnection)
+                       -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::update(origin_teams::table.find(id))
+            .set((origin_teams::member_role.eq(member_role),
+                  origin_teams::updated_at.eq(now.nullable())))
+            .execute(conn)
+    }
+
+    // Deleting the team removes its members along with it
+    pub fn delete(id: i64, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::delete(origin_teams::table.find(id)).execute(conn)
+    }
+
+    // The account ids of the members, to invalidate their cached roles
+    pub fn member_ids(id: i64, conn: &PgConnection) -> QueryResult<Vec<i64>> {
+        Counter::DBCall.increment();
+        origin_team_members::table.select(origin_team_members::account_id)
+                                  .filter(origin_team_members::team_id.eq(id))
+                                  .get_results(conn)
+    }
+
+    pub fn member_names(id: i64, conn: &PgConnection) -> QueryResult<Vec<String>> {
+        Counter::DBCall.increment();
+        origin_team_members::table.inner_join(accounts::table)
+                                  .select(accounts::name)
+                                  .filter(origin_team_members::team_id.eq(id))
+                                  .order(accounts::name.asc())
+                                  .get_results(conn)
+    }
+
+    pub fn add_member(id: i64, account_id: i64, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::insert_into(origin_team_members::table)
+            .values((origin_team_members::team_id.eq(id),
+                     origin_team_members::account_id.eq(account_id)))
+            .on_conflict_do_nothing()
+            .execute(conn)
+    }
+
+    pub fn remove_member(id: i64, account_id: i64, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::delete(origin_team_members::table.find((id, account_id))).execute(conn)
+    }
+
+    // Takes the account out of every team of the origin, for when it leaves the origin
+    pub fn remove_from_origin(origin: &str,
+                              account_id: i64,
+                              conn: &PgConnection)
+                              -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::delete(
+            origin_team_members::table
+                .filter(origin_team_members::account_id.eq(account_id))
+                .filter(origin_team_members::team_id.eq_any(
+                    origin_teams::table.select(origin_teams::id)
+                                       .filter(origin_teams::origin.eq(origin)),
+                )),
+        )
+        .execute(conn)
+    }
+
+    // The roles of the teams of the origin the account is in
+    pub fn roles_of(origin: &str,
+                    account_id: i64,
+                    conn: &PgConnection)
+                    -> QueryResult<Vec<OriginMemberRole>> {
+        Counter::DBCall.increment();
+        origin_team_members::table.inner_join(origin_teams::table)
+                                  .select(origin_teams::member_role)
+                                  .filter(origin_teams::origin.eq(origin))
+                                  .filter(origin_team_members::account_id.eq(account_id))
+                                  .get_results(conn)
+    }
+}
@@ -0,0 +1,156 @@
+use super::db_id_format;
+use chrono::{DateTime,
+             NaiveDateTime,
+             Utc};
+
+use diesel::{self,
+             dsl::now,
+             pg::PgConnection,
+             result::QueryResult,
+             BoolExpressionMethods,
+             ExpressionMethods,
+             QueryDsl,
+             RunQueryDsl};
+
+use crate::{models::package::{BuilderPackageIdent,
+                              BuilderPackageTarget},
+            schema::upload::origin_package_uploads};
+
+use crate::{bldr_core::metrics::CounterMetric,
+            metrics::Counter};
+
+#[derive(Debug,
+         Serialize,
+         Deserialize,
+         QueryableByName,
+         Queryable,
+         Clone,
+         Identifiable)]
+#[table_name = "origin_package_uploads"]
+pub struct PackageUpload {
+    #[serde(with = "db_id_format")]
+    pub id:             i64,
+    pub origin:         String,
+    pub ident:          BuilderPackageIdent,
+    pub target:         BuilderPackageTarget,
+    pub checksum:       String,
+    pub forced:         bool,
+    pub builder:        Option<String>,
+    #[serde(with = "db_id_format")]
+    pub owner_id:       i64,
+    pub received_bytes: i64,
+    pub next_chunk:     i32,
+    pub created_at:     Option<NaiveDateTime>,
+    pub updated_at:     Option<NaiveDateTime>,
+    #[serde(skip)]
+    pub locked_at:      Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "origin_package_uploads"]
+pub struct NewPackageUpload<'a> {
+    pub origin:   &'a str,
+    pub ident:    BuilderPackageIdent,
+    pub target:   BuilderPackageTarget,
+    pub checksum: &'a str,
+    pub forced:   bool,
+    pub builder:  Option<&'a str>,
+    pub owner_id: i64,
+}
+
+impl PackageUpload {
+    pub fn create(req: &NewPackageUpload, conn: &PgConnection) -> QueryResult<PackageUpload> {
+        Counter::DBCall.increment();
+        diesel::insert_into(origin_package_uploads::table).values(req)
+                                                          .get_result(conn)
+    }
+
+    pub fn get(id: i64, conn: &PgConnection) -> QueryResult<PackageUpload> {
+        Counter::DBCall.increment();
+        origin_package_uploads::table.find(id).get_result(conn)
+    }
+
+    // Records that `chunk` has been written, leaving `received_bytes` in the upload file.
+    // Only succeeds if `chunk` is the next chunk expected, so concurrent writers of the same
+    // chunk cannot both advance the session. Returns NotFound otherwise.
+    pub fn record_chunk(id: i64,
+                        chunk: i32,
+                        received_bytes: i64,
+                        conn: &PgConnection)
+                        -> QueryResult<PackageUpload> {
+        Counter::DBCall.increment();
+        diesel::update(origin_package_uploads::table.find(id)
+                                                    .filter(origin_package_uploads::next_chunk.eq(chunk)))
+            .set((origin_package_uploads::next_chunk.eq(chunk + 1),
+                  origin_package_uploads::received_bytes.eq(received_bytes),
+                  origin_package_uploads::updated_at.eq(now)))
+            .get_result(conn)
+    }
+
+    // Locks the session for a single writer, unless another one locked it after `cutoff`.
+    // The lock lives in the database so it holds across API nodes, and a lock older than
+    // `cutoff` is assumed to have been left behind by a writer which went away. Returns
+    // whether the lock was taken.
+    pub fn lock(id: i64, cutoff: DateTime<Utc>, conn: &PgConnection) -> QueryResult<bool> {
+        Counter::DBCall.increment();
+        diesel::update(
+            origin_package_uploads::table
+                .find(id)
+                .filter(origin_package_uploads::locked_at.is_null()
+                            .or(origin_package_uploads::locked_at.lt(cutoff))),
+        )
+        .set(origin_package_uploads::locked_at.eq(now.nullable()))
+        .execute(conn)
+        .map(|count| count == 1)
+    }
+
+    pub fn unlock(id: i64, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::update(origin_package_uploads::table.find(id))
+            .set(origin_package_uploads::locked_at.eq(None::<DateTime<Utc>>))
+            .execute(conn)
+    }
+
+    pub fn delete(id: i64, conn: &PgConnection) -> QueryResult<usize> {
+        Counter::DBCall.increment();
+        diesel::delete(origin_package_uploads::table.find(id)).execute(conn)
+    }
+
+    pub fn list_ids_for_origin(origin: &str, conn: &PgConnection) -> QueryResult<Vec<i64>> {
+        Counter::DBCall.increment();
+        origin_package_uploads::table.select(origin_package_uploads::id)
+                                     .filter(origin_package_uploads::origin.eq(origin))
+                                     .get_results(conn)
+    }
+
+    // Removes the sessions which have not received a chunk since `cutoff` and are not locked
+    // by a writer, returning their ids. A session locked after the removal started is kept.
+    pub fn delete_expired(cutoff: DateTime<Utc>, conn: &PgConnection) -> QueryResult<Vec<i64>> {
+        Counter::DBCall.increment();
+        diesel::delete(
+            origin_package_uploads::table
+                .filter(origin_package_uploads::updated_at.lt(cutoff))
+                .filter(origin_package_uploads::locked_at.is_null()
+                            .or(origin_package_uploads::locked_at.lt(cutoff))),
+        )
+        .returning(origin_package_uploads::id)
+        .get_results(conn)
+    }
+
+    // Like delete_expired, for a single session. Returns whether it was removed.
+    pub fn delete_if_expired(id: i64,
+                             cutoff: DateTime<Utc>,
+                             conn: &PgConnection)
+                             -> QueryResult<bool> {
+        Counter::DBCall.increment();
+        diesel::delete(
+            origin_package_uploads::table
+                .find(id)
+                .filter(origin_package_uploads::updated_at.lt(cutoff))
+                .filter(origin_package_uploads::locked_at.is_null()
+                            .or(origin_package_uploads::locked_at.lt(cutoff))),
+        )
+        .execute(conn)
+        .map(|count| count == 1)
+    }
+}
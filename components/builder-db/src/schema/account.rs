@@ -5,6 +5,7 @@ table! {
         name -> Text,
         created_at -> Nullable<Timestamptz>,
         updated_at -> Nullable<Timestamptz>,
+        service_origin -> Nullable<Text>,
     }
 }
 
@@ -14,5 +15,13 @@ table! {
         account_id -> BigInt,
         token -> Text,
         created_at -> Nullable<Timestamptz>,
+        name -> Text,
+        scopes -> Array<Text>,
+        origins -> Array<Text>,
+        channels -> Array<Text>,
+        expires_at -> Nullable<Timestamptz>,
+        last_used_at -> Nullable<Timestamptz>,
     }
 }
+
+allow_tables_to_appear_in_same_query!(account_tokens, accounts);
@@ -1,7 +1,8 @@
 table! {
     use crate::models::channel::{PackageChannelOperationMapping, PackageChannelTriggerMapping};
     use diesel::sql_types::{BigInt, Text, Nullable, Timestamptz};
-    audit_package (origin, package_ident, channel) {
+    audit_package (id) {
+        id -> BigInt,
         package_ident -> Text,
         channel -> Text,
         operation -> PackageChannelOperationMapping,
@@ -10,13 +11,15 @@ table! {
         requester_name -> Text,
         created_at -> Nullable<Timestamptz>,
         origin -> Text,
+        target -> Nullable<Text>,
     }
 }
 
 table! {
     use crate::models::channel::{PackageChannelOperationMapping, PackageChannelTriggerMapping};
     use diesel::sql_types::{BigInt, Array, Text, Nullable, Timestamptz};
-    audit_package_group (origin, channel) {
+    audit_package_group (id) {
+        id -> BigInt,
         channel -> Text,
         package_ids -> Array<BigInt>,
         operation -> PackageChannelOperationMapping,
@@ -1,4 +1,7 @@
 table! {
+    use crate::models::origin::OriginMemberRoleMapping;
+    use diesel::sql_types::{Array, BigInt, Bool, Nullable, Text, Timestamptz};
+
     origin_channels (id) {
         id -> BigInt,
         owner_id -> BigInt,
@@ -6,6 +9,10 @@ table! {
         created_at -> Nullable<Timestamptz>,
         updated_at -> Nullable<Timestamptz>,
         origin -> Text,
+        promote_min_role -> Nullable<OriginMemberRoleMapping>,
+        demote_min_role -> Nullable<OriginMemberRoleMapping>,
+        no_delete -> Bool,
+        allowed_accounts -> Array<BigInt>,
     }
 }
 
@@ -0,0 +1,17 @@
+table! {
+    use crate::models::origin::OriginMemberRoleMapping;
+    use diesel::sql_types::{BigInt, Bool, Nullable, Text, Timestamptz};
+
+    origin_channel_policies {
+        id -> BigInt,
+        origin -> Text,
+        channel -> Text,
+        require_channel -> Nullable<Text>,
+        require_tdeps_in_channel -> Bool,
+        require_worker_build -> Bool,
+        min_member_role -> Nullable<OriginMemberRoleMapping>,
+        owner_id -> BigInt,
+        created_at -> Nullable<Timestamptz>,
+        updated_at -> Nullable<Timestamptz>,
+    }
+}
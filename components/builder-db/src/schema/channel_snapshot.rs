@@ -0,0 +1,13 @@
+table! {
+    use diesel::sql_types::{Array, BigInt, Nullable, Text, Timestamptz};
+
+    origin_channel_snapshots {
+        id -> BigInt,
+        origin -> Text,
+        channel -> Text,
+        name -> Text,
+        package_ids -> Array<BigInt>,
+        owner_id -> BigInt,
+        created_at -> Nullable<Timestamptz>,
+    }
+}
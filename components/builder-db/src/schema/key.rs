@@ -1,4 +1,7 @@
 table! {
+    use crate::models::keys::OriginKeyStateMapping;
+    use diesel::sql_types::{BigInt, Binary, Nullable, Text, Timestamptz};
+
     origin_public_keys(id) {
         id -> BigInt,
         owner_id -> BigInt,
@@ -9,6 +12,8 @@ table! {
         created_at -> Nullable<Timestamptz>,
         updated_at -> Nullable<Timestamptz>,
         origin -> Text,
+        state -> OriginKeyStateMapping,
+        state_changed_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -54,3 +59,36 @@ table! {
         origin -> Text,
     }
 }
+
+table! {
+    origin_package_signer_flags(id) {
+        id -> BigInt,
+        origin -> Text,
+        package_id -> BigInt,
+        ident -> Text,
+        target -> Text,
+        signer -> Text,
+        owner_id -> BigInt,
+        created_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    origin_package_signers(package_id) {
+        package_id -> BigInt,
+        origin -> Text,
+        signer -> Text,
+        created_at -> Nullable<Timestamptz>,
+    }
+}
+
+use super::{channel::{origin_channel_packages,
+                      origin_channels},
+            package::origin_packages};
+
+joinable!(origin_package_signers -> origin_packages (package_id));
+
+allow_tables_to_appear_in_same_query!(origin_package_signers, origin_packages);
+allow_tables_to_appear_in_same_query!(origin_package_signers, origin_channel_packages);
+allow_tables_to_appear_in_same_query!(origin_package_signers, origin_channels);
+allow_tables_to_appear_in_same_query!(origin_secret_keys, origin_public_keys);
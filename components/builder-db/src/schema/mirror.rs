@@ -0,0 +1,35 @@
+table! {
+    use diesel::sql_types::{Array, BigInt, Nullable, Text, Timestamptz};
+
+    origin_channel_mirrors {
+        id -> BigInt,
+        origin -> Text,
+        channel -> Text,
+        upstream_url -> Text,
+        include -> Array<Text>,
+        exclude -> Array<Text>,
+        owner_id -> BigInt,
+        created_at -> Nullable<Timestamptz>,
+        updated_at -> Nullable<Timestamptz>,
+        claimed_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    use crate::models::mirror::MirrorRunStatusMapping;
+    use diesel::sql_types::{Array, BigInt, Integer, Nullable, Text, Timestamptz};
+
+    origin_channel_mirror_runs {
+        id -> BigInt,
+        mirror_id -> BigInt,
+        status -> MirrorRunStatusMapping,
+        uploaded -> Integer,
+        promoted -> Integer,
+        errors -> Array<Text>,
+        started_at -> Nullable<Timestamptz>,
+        finished_at -> Nullable<Timestamptz>,
+    }
+}
+
+joinable!(origin_channel_mirror_runs -> origin_channel_mirrors (mirror_id));
+allow_tables_to_appear_in_same_query!(origin_channel_mirrors, origin_channel_mirror_runs);
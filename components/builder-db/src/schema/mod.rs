@@ -5,14 +5,22 @@
 pub mod account;
 pub mod audit;
 pub mod channel;
+pub mod channel_policy;
+pub mod channel_snapshot;
 pub mod integration;
 pub mod invitation;
 pub mod jobs;
 pub mod key;
 pub mod member;
+pub mod mirror;
 pub mod origin;
 pub mod package;
 pub mod project;
 pub mod project_integration;
+pub mod provenance;
+pub mod retention;
+pub mod scheduled_promotion;
 pub mod secrets;
 pub mod settings;
+pub mod team;
+pub mod upload;
@@ -1,24 +1,26 @@
 table! {
     use crate::models::package::PackageVisibilityMapping;
-    use diesel::sql_types::{BigInt, Text, Nullable, Timestamptz};
+    use diesel::sql_types::{BigInt, Bool, Text, Nullable, Timestamptz};
     origins (name) {
         owner_id -> BigInt,
         name -> Text,
         created_at -> Nullable<Timestamptz>,
         updated_at -> Nullable<Timestamptz>,
         default_package_visibility -> PackageVisibilityMapping,
+        reject_unknown_key_revisions -> Bool,
     }
 }
 
 table! {
     use crate::models::package::PackageVisibilityMapping;
-    use diesel::sql_types::{BigInt, Text, Nullable};
+    use diesel::sql_types::{BigInt, Bool, Text, Nullable};
     origins_with_secret_key (name) {
         owner_id -> BigInt,
         name -> Text,
         private_key_name -> Nullable<Text>,
         default_package_visibility -> PackageVisibilityMapping,
         owner_account -> Text,
+        reject_unknown_key_revisions -> Bool,
     }
 }
 
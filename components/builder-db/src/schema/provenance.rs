@@ -0,0 +1,20 @@
+table! {
+    use diesel::sql_types::{Array, BigInt, Bool, Nullable, Text, Timestamptz};
+
+    origin_package_provenance {
+        id -> BigInt,
+        package_id -> BigInt,
+        attested -> Bool,
+        job_id -> Nullable<BigInt>,
+        group_id -> Nullable<BigInt>,
+        vcs_url -> Nullable<Text>,
+        vcs_sha -> Nullable<Text>,
+        worker -> Nullable<Text>,
+        plan_path -> Nullable<Text>,
+        studio_version -> Nullable<Text>,
+        hab_version -> Nullable<Text>,
+        deps -> Array<Text>,
+        created_at -> Nullable<Timestamptz>,
+        updated_at -> Nullable<Timestamptz>,
+    }
+}
@@ -0,0 +1,15 @@
+table! {
+    use diesel::sql_types::{BigInt, Integer, Nullable, Text, Timestamptz};
+
+    origin_retention_policies {
+        id -> BigInt,
+        origin -> Text,
+        channel -> Nullable<Text>,
+        keep_latest -> Nullable<Integer>,
+        max_age_days -> Nullable<Integer>,
+        owner_id -> BigInt,
+        created_at -> Nullable<Timestamptz>,
+        updated_at -> Nullable<Timestamptz>,
+        swept_at -> Nullable<Timestamptz>,
+    }
+}
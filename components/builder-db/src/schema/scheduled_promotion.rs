@@ -0,0 +1,39 @@
+table! {
+    use crate::models::scheduled_promotion::ScheduledPromotionStateMapping;
+    use diesel::sql_types::{Array, BigInt, Nullable, Text, Timestamptz};
+
+    origin_scheduled_promotions {
+        id -> BigInt,
+        origin -> Text,
+        channel -> Text,
+        target -> Text,
+        idents -> Array<Text>,
+        group_id -> Nullable<BigInt>,
+        execute_at -> Timestamptz,
+        state -> ScheduledPromotionStateMapping,
+        requester_id -> BigInt,
+        requester_name -> Text,
+        error -> Nullable<Text>,
+        created_at -> Nullable<Timestamptz>,
+        finished_at -> Nullable<Timestamptz>,
+        claimed_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    use diesel::sql_types::{BigInt, Bool, Nullable, Text, Timestamptz};
+
+    origin_scheduled_promotion_items {
+        id -> BigInt,
+        promotion_id -> BigInt,
+        ident -> Text,
+        target -> Text,
+        succeeded -> Bool,
+        error -> Nullable<Text>,
+        created_at -> Nullable<Timestamptz>,
+    }
+}
+
+joinable!(origin_scheduled_promotion_items -> origin_scheduled_promotions (promotion_id));
+allow_tables_to_appear_in_same_query!(origin_scheduled_promotions,
+                                      origin_scheduled_promotion_items);
@@ -0,0 +1,33 @@
+table! {
+    use crate::models::origin::OriginMemberRoleMapping;
+    use diesel::sql_types::{BigInt, Nullable, Text, Timestamptz};
+
+    origin_teams (id) {
+        id -> BigInt,
+        origin -> Text,
+        name -> Text,
+        member_role -> OriginMemberRoleMapping,
+        owner_id -> BigInt,
+        created_at -> Nullable<Timestamptz>,
+        updated_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    use diesel::sql_types::{BigInt, Nullable, Timestamptz};
+
+    origin_team_members (team_id, account_id) {
+        team_id -> BigInt,
+        account_id -> BigInt,
+        created_at -> Nullable<Timestamptz>,
+    }
+}
+
+use super::{account::accounts,
+            member::origin_members};
+
+joinable!(origin_team_members -> origin_teams (team_id));
+joinable!(origin_team_members -> accounts (account_id));
+allow_tables_to_appear_in_same_query!(origin_teams, origin_team_members, accounts);
+allow_tables_to_appear_in_same_query!(origin_teams, origin_members);
+allow_tables_to_appear_in_same_query!(origin_team_members, origin_members);
@@ -0,0 +1,19 @@
+table! {
+    use diesel::sql_types::{BigInt, Bool, Integer, Nullable, Text, Timestamptz};
+
+    origin_package_uploads {
+        id -> BigInt,
+        origin -> Text,
+        ident -> Text,
+        target -> Text,
+        checksum -> Text,
+        forced -> Bool,
+        builder -> Nullable<Text>,
+        owner_id -> BigInt,
+        received_bytes -> BigInt,
+        next_chunk -> Integer,
+        created_at -> Nullable<Timestamptz>,
+        updated_at -> Nullable<Timestamptz>,
+        locked_at -> Nullable<Timestamptz>,
+    }
+}
@@ -452,6 +452,90 @@ impl DataStore {
         Ok(())
     }
 
+    pub fn get_job_group_audits(&self,
+                                msg: &jobsrv::JobGroupAuditGet)
+                                -> Result<jobsrv::JobGroupAuditResponse> {
+        fn optional_time(present: bool, value: &str) -> Result<Option<DateTime<Utc>>> {
+            if present {
+                Ok(Some(value.parse::<DateTime<Utc>>()?))
+            } else {
+                Ok(None)
+            }
+        }
+
+        let operation = if msg.has_operation() {
+            Some(msg.get_operation() as i16)
+        } else {
+            None
+        };
+        let requester_name = if msg.has_requester_name() {
+            Some(msg.get_requester_name())
+        } else {
+            None
+        };
+        let project_name = if msg.has_project_name() {
+            Some(msg.get_project_name())
+        } else {
+            None
+        };
+        let from = optional_time(msg.has_from(), msg.get_from())?;
+        let to = optional_time(msg.has_to(), msg.get_to())?;
+        let before = optional_time(msg.has_before(), msg.get_before())?;
+        let before_group_id = if msg.has_before_group_id() {
+            Some(msg.get_before_group_id() as i64)
+        } else {
+            None
+        };
+
+        let conn = self.pool.get()?;
+        let rows = &conn.query("SELECT * FROM get_audit_jobs_for_origin_v1($1, $2, $3, $4, $5, \
+                                $6, $7, $8, $9, $10)",
+                               &[&msg.get_origin(),
+                                 &operation,
+                                 &requester_name,
+                                 &project_name,
+                                 &from,
+                                 &to,
+                                 &before,
+                                 &before_group_id,
+                                 &msg.get_before_inclusive(),
+                                 &(msg.get_limit() as i32)])
+                        .map_err(Error::JobGroupAuditGet)?;
+
+        let mut response = jobsrv::JobGroupAuditResponse::new();
+        let mut audits = RepeatedField::new();
+
+        for row in rows {
+            let operation: i16 = row.get("operation");
+            let operation = match jobsrv::JobGroupOperation::from_i32(i32::from(operation)) {
+                Some(operation) => operation,
+                None => {
+                    warn!("Skipping audit entry with unknown operation {}", operation);
+                    continue;
+                }
+            };
+            let trigger: i16 = row.get("trigger");
+            let trigger = jobsrv::JobGroupTrigger::from_i32(i32::from(trigger))
+                .unwrap_or(jobsrv::JobGroupTrigger::Unknown);
+
+            let mut audit = jobsrv::JobGroupAudit::new();
+            let group_id: i64 = row.get("group_id");
+            audit.set_group_id(group_id as u64);
+            audit.set_operation(operation);
+            audit.set_trigger(trigger);
+            let requester_id: i64 = row.get("requester_id");
+            audit.set_requester_id(requester_id as u64);
+            audit.set_requester_name(row.get("requester_name"));
+            audit.set_project_name(row.get("project_name"));
+            let created_at = row.get::<&str, DateTime<Utc>>("created_at");
+            audit.set_created_at(created_at.to_rfc3339());
+            audits.push(audit);
+        }
+
+        response.set_audits(audits);
+        Ok(response)
+    }
+
     pub fn get_job_group_origin(&self,
                                 msg: &jobsrv::JobGroupOriginGet)
                                 -> Result<jobsrv::JobGroupOriginResponse> {
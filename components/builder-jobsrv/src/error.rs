@@ -46,6 +46,7 @@ pub enum Error {
     InvalidUrl,
     IO(io::Error),
     JobGroupAudit(postgres::error::Error),
+    JobGroupAuditGet(postgres::error::Error),
     JobGroupCreate(postgres::error::Error),
     JobGroupCancel(postgres::error::Error),
     JobGroupGet(postgres::error::Error),
@@ -121,6 +122,9 @@ impl fmt::Display for Error {
             Error::InvalidUrl => "Bad URL!".to_string(),
             Error::IO(ref e) => format!("{}", e),
             Error::JobGroupAudit(ref e) => format!("Database error creating audit entry, {}", e),
+            Error::JobGroupAuditGet(ref e) => {
+                format!("Database error getting audit entries for an origin, {}", e)
+            }
             Error::JobGroupCreate(ref e) => format!("Database error creating a new group, {}", e),
             Error::JobGroupCancel(ref e) => format!("Database error canceling a job group, {}", e),
             Error::JobGroupGet(ref e) => format!("Database error getting group data, {}", e),
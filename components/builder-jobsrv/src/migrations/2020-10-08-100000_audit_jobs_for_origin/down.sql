@@ -0,0 +1,3 @@
+DROP FUNCTION IF EXISTS get_audit_jobs_for_origin_v1(text, smallint, text, text, timestamptz, timestamptz, timestamptz, bigint, boolean, integer);
+DROP INDEX IF EXISTS audit_jobs_created_at;
+DROP INDEX IF EXISTS audit_jobs_group_id;
@@ -0,0 +1,38 @@
+CREATE INDEX IF NOT EXISTS audit_jobs_group_id ON audit_jobs(group_id);
+CREATE INDEX IF NOT EXISTS audit_jobs_created_at ON audit_jobs(created_at);
+
+CREATE OR REPLACE FUNCTION get_audit_jobs_for_origin_v1(op_origin text,
+                                                        op_operation smallint,
+                                                        op_requester_name text,
+                                                        op_project_name text,
+                                                        op_from timestamptz,
+                                                        op_to timestamptz,
+                                                        op_before timestamptz,
+                                                        op_before_group_id bigint,
+                                                        op_before_inclusive boolean,
+                                                        op_limit integer)
+    RETURNS TABLE(group_id bigint,
+                  operation smallint,
+                  trigger smallint,
+                  requester_id bigint,
+                  requester_name text,
+                  project_name text,
+                  created_at timestamptz)
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT a.group_id, a.operation, a.trigger, a.requester_id, a.requester_name, g.project_name, a.created_at
+  FROM audit_jobs a
+  INNER JOIN groups g ON g.id = a.group_id
+  -- Not a LIKE, the _ allowed in origin names would match any character
+  WHERE split_part(g.project_name, '/', 1) = op_origin
+  AND (op_operation IS NULL OR a.operation = op_operation)
+  AND (op_requester_name IS NULL OR a.requester_name = op_requester_name)
+  AND (op_project_name IS NULL OR g.project_name = op_project_name)
+  AND (op_from IS NULL OR a.created_at >= op_from)
+  AND (op_to IS NULL OR a.created_at < op_to)
+  AND (op_before IS NULL
+       OR a.created_at < op_before
+       OR (a.created_at = op_before AND (op_before_inclusive OR a.group_id < op_before_group_id)))
+  ORDER BY a.created_at DESC, a.group_id DESC
+  LIMIT op_limit
+$$;
@@ -587,6 +587,18 @@ fn compute_rdep_build_groups(state: &AppState,
     Ok(rdep_groups)
 }
 
+pub fn job_group_audit_get(req: &RpcMessage, state: &AppState) -> Result<RpcMessage> {
+    let msg = req.parse::<jobsrv::JobGroupAuditGet>()?;
+
+    match state.datastore.get_job_group_audits(&msg) {
+        Ok(ref jgar) => RpcMessage::make(jgar).map_err(Error::BuilderCore),
+        Err(e) => {
+            warn!("job_group_audit_get error: {:?}", e);
+            Err(Error::System)
+        }
+    }
+}
+
 pub fn job_group_origin_get(req: &RpcMessage, state: &AppState) -> Result<RpcMessage> {
     let msg = req.parse::<jobsrv::JobGroupOriginGet>()?;
 
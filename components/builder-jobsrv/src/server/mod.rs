@@ -106,6 +106,7 @@ fn handle_rpc(msg: Json<RpcMessage>, state: Data<AppState>) -> HttpResponse {
         "JobGroupCancel" => handlers::job_group_cancel(&msg, &state),
         "JobGroupGet" => handlers::job_group_get(&msg, &state),
         "JobGroupOriginGet" => handlers::job_group_origin_get(&msg, &state),
+        "JobGroupAuditGet" => handlers::job_group_audit_get(&msg, &state),
         "JobGraphPackageCreate" => handlers::job_graph_package_create(&msg, &state),
         "JobGraphPackagePreCreate" => handlers::job_graph_package_precreate(&msg, &state),
         "JobGraphPackageReverseDependenciesGet" => {
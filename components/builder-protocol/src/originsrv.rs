@@ -22,6 +22,7 @@ pub use crate::message::originsrv::*;
 pub enum Error {
     BadOriginPackageVisibility,
     BadOAuthProvider,
+    BadTokenScope,
 }
 
 pub trait Pageable {
@@ -34,10 +35,11 @@ impl Serialize for OriginKeyIdent {
     fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
         where S: Serializer
     {
-        let mut strukt = serializer.serialize_struct("origin_key", 3)?;
+        let mut strukt = serializer.serialize_struct("origin_key", 4)?;
         strukt.serialize_field("origin", self.get_origin())?;
         strukt.serialize_field("revision", self.get_revision())?;
         strukt.serialize_field("location", self.get_location())?;
+        strukt.serialize_field("state", self.get_state())?;
         strukt.end()
     }
 }
@@ -184,11 +186,47 @@ impl FromStr for OAuthProvider {
     }
 }
 
+impl FromStr for TokenScope {
+    type Err = Error;
+
+    fn from_str(value: &str) -> result::Result<Self, Self::Err> {
+        match value.to_lowercase().as_ref() {
+            "read" => Ok(TokenScope::Read),
+            "upload" => Ok(TokenScope::Upload),
+            "promote" => Ok(TokenScope::Promote),
+            "jobs" => Ok(TokenScope::Jobs),
+            _ => Err(Error::BadTokenScope),
+        }
+    }
+}
+
+impl fmt::Display for TokenScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match *self {
+            TokenScope::Read => "read",
+            TokenScope::Upload => "upload",
+            TokenScope::Promote => "promote",
+            TokenScope::Jobs => "jobs",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+impl AccessToken {
+    // Whether the token is limited to less than what the account can do
+    pub fn is_restricted(&self) -> bool {
+        !self.get_scopes().is_empty()
+        || !self.get_origins().is_empty()
+        || !self.get_channels().is_empty()
+    }
+}
+
 impl Into<Session> for AccessToken {
     fn into(self) -> Session {
         let mut session = Session::new();
         session.set_id(self.get_account_id());
         session.set_flags(self.get_flags());
+        session.set_access_token(self);
         session
     }
 }
@@ -12,13 +12,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::{bldr_core::logger::Logger,
+use crate::{bldr_core::{api_client::PackageProvenance,
+                        logger::Logger},
             config::Config,
             error::Result,
-            hab_core::{package::archive::PackageArchive,
-                       ChannelIdent}};
+            hab_core::{package::{archive::PackageArchive,
+                                 PackageIdent},
+                       ChannelIdent},
+            vcs};
 
 use super::{publisher::Publisher,
+            studio::{HAB_PKG_IDENT,
+                     STUDIO_PKG_IDENT},
             workspace::Workspace};
 
 pub async fn post_process(archive: &mut PackageArchive,
@@ -39,6 +44,84 @@ pub async fn post_process(archive: &mut PackageArchive,
                                     url,
                                     channel_opt };
 
+    // A package whose provenance can not be worked out is still published, just unattested
+    let provenance = match provenance(archive, workspace) {
+        Ok(provenance) => Some(provenance),
+        Err(err) => {
+            let msg = format!("Unable to determine provenance, publishing without it, err={:?}",
+                              err);
+            warn!("{}", msg);
+            logger.log(&msg);
+            None
+        }
+    };
+
     debug!("Starting post processing");
-    publisher.run(archive, auth_token, logger).await
+    publisher.run(archive, provenance.as_ref(), auth_token, logger)
+             .await
+}
+
+fn provenance(archive: &mut PackageArchive, workspace: &Workspace) -> Result<PackageProvenance> {
+    let project = workspace.job.get_project();
+
+    let vcs_sha = match vcs::head_sha(workspace.src()) {
+        Ok(sha) => Some(sha),
+        Err(err) => {
+            warn!("Unable to determine the commit built, err={:?}", err);
+            None
+        }
+    };
+
+    let deps = inputs(&archive.tdeps()?, &archive.build_tdeps()?);
+
+    Ok(PackageProvenance { job_id: workspace.job.get_id(),
+                           group_id: workspace.job.get_owner_id(),
+                           vcs_url: project.get_vcs_data().to_string(),
+                           vcs_sha,
+                           worker: workspace.job.get_worker().to_string(),
+                           plan_path: project.get_plan_path().to_string(),
+                           studio_version: STUDIO_PKG_IDENT.trim().to_string(),
+                           hab_version: HAB_PKG_IDENT.trim().to_string(),
+                           deps })
+}
+
+// The runtime and build dependencies the package was built with, sorted and without duplicates
+fn inputs(tdeps: &[PackageIdent], build_tdeps: &[PackageIdent]) -> Vec<String> {
+    let mut deps: Vec<String> = tdeps.iter()
+                                     .chain(build_tdeps.iter())
+                                     .map(ToString::to_string)
+                                     .collect();
+    deps.sort();
+    deps.dedup();
+    deps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn idents(values: &[&str]) -> Vec<PackageIdent> {
+        values.iter()
+              .map(|v| PackageIdent::from_str(v).unwrap())
+              .collect()
+    }
+
+    #[test]
+    fn inputs_are_sorted_and_deduplicated() {
+        let tdeps = idents(&["core/openssl/1.0.2t/20200306005450",
+                             "core/glibc/2.29/20200305172459"]);
+        let build_tdeps = idents(&["core/gcc/9.1.0/20200305225533",
+                                   "core/glibc/2.29/20200305172459"]);
+
+        assert_eq!(inputs(&tdeps, &build_tdeps),
+                   vec!["core/gcc/9.1.0/20200305225533",
+                        "core/glibc/2.29/20200305172459",
+                        "core/openssl/1.0.2t/20200306005450"]);
+    }
+
+    #[test]
+    fn inputs_of_a_package_without_deps_are_empty() {
+        assert!(inputs(&[], &[]).is_empty());
+    }
 }
@@ -14,7 +14,8 @@
 
 use super::{RETRIES,
             RETRY_WAIT};
-use crate::{bldr_core::{api_client::ApiClient,
+use crate::{bldr_core::{api_client::{ApiClient,
+                                     PackageProvenance},
                         logger::Logger},
             error::{Error,
                     Result},
@@ -34,6 +35,7 @@ pub struct Publisher {
 impl Publisher {
     pub async fn run(&mut self,
                      archive: &mut PackageArchive,
+                     provenance: Option<&PackageProvenance>,
                      auth_token: &str,
                      logger: &mut Logger)
                      -> Result<()> {
@@ -60,6 +62,21 @@ impl Publisher {
             }
         }
 
+        // The package is already published, so a missing provenance record
+        // leaves it unattested rather than failing the job
+        if let Some(provenance) = provenance {
+            if let Err(err) = retry::retry_future!(delay::Fixed::from(RETRY_WAIT).take(RETRIES),
+                                                   self.put_provenance(&client, archive,
+                                                                       provenance, auth_token,
+                                                                       logger)).await
+            {
+                let msg = format!("Failed to record provenance for {} after {} retries, err={:?}",
+                                  ident, RETRIES, err);
+                warn!("{}", msg);
+                logger.log(&msg);
+            }
+        }
+
         if let Some(channel) = &self.channel_opt {
             if channel != &ChannelIdent::stable() && channel != &ChannelIdent::unstable() {
                 match retry::retry_future!(delay::Fixed::from(RETRY_WAIT).take(RETRIES),
@@ -115,6 +132,24 @@ impl Publisher {
         res
     }
 
+    async fn put_provenance(&self,
+                            client: &ApiClient,
+                            archive: &mut PackageArchive,
+                            provenance: &PackageProvenance,
+                            auth_token: &str,
+                            logger: &mut Logger)
+                            -> std::result::Result<(), builder_core::Error> {
+        let res = client.put_package_provenance(archive, provenance, auth_token)
+                        .await;
+        if let Err(ref err) = res {
+            let msg = format!("Provenance {}: {:?}", archive.ident().unwrap(), err);
+            debug!("{}", msg);
+            logger.log(&msg);
+        }
+
+        res
+    }
+
     async fn create_channel(&self,
                             client: &ApiClient,
                             ident: &PackageIdent,
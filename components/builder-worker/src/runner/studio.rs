@@ -25,17 +25,20 @@ pub static STUDIO_GID: AtomicUsize = AtomicUsize::new(0);
 pub const DEBUG_ENVVARS: &[&str] = &["RUST_LOG", "DEBUG", "RUST_BACKTRACE"];
 pub const WINDOWS_ENVVARS: &[&str] = &["SYSTEMDRIVE", "USERNAME", "COMPUTERNAME", "TEMP"];
 
+pub const STUDIO_PKG_IDENT: &str = include_str!(concat!(env!("OUT_DIR"), "/STUDIO_PKG_IDENT"));
+pub const HAB_PKG_IDENT: &str = include_str!(concat!(env!("OUT_DIR"), "/HAB_PKG_IDENT"));
+
 lazy_static! {
     /// Absolute path to the Studio program
     static ref STUDIO_PROGRAM: PathBuf = fs::resolve_cmd_in_pkg(
         "hab-studio",
-        include_str!(concat!(env!("OUT_DIR"), "/STUDIO_PKG_IDENT")),
+        STUDIO_PKG_IDENT,
     );
 
     /// Absolute path to the hab cli
     static ref HAB_CLI: PathBuf = fs::resolve_cmd_in_pkg(
         "hab",
-        include_str!(concat!(env!("OUT_DIR"), "/HAB_PKG_IDENT")),
+        HAB_PKG_IDENT,
     );
 
     pub static ref STUDIO_HOME: Mutex<PathBuf> = {
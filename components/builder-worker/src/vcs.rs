@@ -114,3 +114,12 @@ impl VCS {
         Ok(url)
     }
 }
+
+// The commit a cloned repository at `path` has checked out
+pub fn head_sha(path: &Path) -> Result<String> {
+    let repo = git2::Repository::open(path).map_err(Error::Git)?;
+    let commit = repo.head()
+                     .and_then(|head| head.peel_to_commit())
+                     .map_err(Error::Git)?;
+    Ok(commit.id().to_string())
+}
@@ -30,6 +30,14 @@ mkdir -p /hab/svc/builder-api
 cat <<EOT > /hab/svc/builder-api/user.toml
 log_level = "debug,tokio_core=error,tokio_reactor=error,zmq=error,hyper=error"
 
+[api]
+# Short enough for the API tests to see a resumable upload expire
+upload_session_ttl = 5
+
+[scheduler]
+# Scheduling promotions is refused while the scheduler is disabled
+enabled = true
+
 [http]
 handler_count = 15
 
@@ -18,3 +18,10 @@ require('./jobs.js');
 require('./ext.js');
 require('./misc.js');
 require('./roles.js');
+require('./service_accounts.js');
+require('./teams.js');
+require('./retention.js');
+require('./sbom.js');
+require('./uploads.js');
+require('./bundles.js');
+require('./signers.js');
@@ -0,0 +1,100 @@
+const expect = require('chai').expect;
+const supertest = require('supertest');
+const request = supertest('http://localhost:9636/v1');
+const fs = require('fs');
+
+const bundle = fs.readFileSync(__dirname + '/../fixtures/sbomapp-unstable-bundle.tar.gz');
+const bundle2 = fs.readFileSync(__dirname + '/../fixtures/sbomapp-unstable-bundle-2.tar.gz');
+const tampered = fs.readFileSync(__dirname + '/../fixtures/sbomapp-unstable-bundle-tampered.tar.gz');
+const ident = 'sbomapp/bundled/1.0.0/20201101000400';
+
+describe('Bundle import API', function () {
+  describe('Importing without permission to promote', function () {
+    it('is refused', function (done) {
+      request.post('/depot/bundles/import')
+        .set('Authorization', global.mystiqueBearer)
+        .set('Content-Type', 'application/gzip')
+        .send(bundle2)
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('uploads nothing', function (done) {
+      request.get('/depot/pkgs/sbomapp/bundled/1.0.0/20201101000500')
+        .set('Authorization', global.boboBearer)
+        .expect(404)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+  });
+
+  describe('Importing a tampered bundle', function () {
+    it('fails verification', function (done) {
+      request.post('/depot/bundles/import')
+        .set('Authorization', global.boboBearer)
+        .set('Content-Type', 'application/gzip')
+        .send(tampered)
+        .expect(422)
+        .end(function (err, res) {
+          expect(res.text).to.contain('does not match the index');
+          done(err);
+        });
+    });
+
+    it('uploads nothing', function (done) {
+      request.get('/depot/pkgs/sbomapp/bundled/1.0.0/20201101000600')
+        .set('Authorization', global.boboBearer)
+        .expect(404)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+  });
+
+  describe('Importing a bundle', function () {
+    it('uploads and promotes the channel members', function (done) {
+      request.post('/depot/bundles/import')
+        .set('Authorization', global.boboBearer)
+        .set('Content-Type', 'application/gzip')
+        .send(bundle)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.origin).to.equal('sbomapp');
+          expect(res.body.channel).to.equal('unstable');
+          expect(res.body.created_origins).to.deep.equal([]);
+          expect(res.body.created_keys).to.deep.equal([]);
+          expect(res.body.uploaded).to.deep.equal([ident]);
+          expect(res.body.existing).to.deep.equal([]);
+          expect(res.body.promoted).to.deep.equal([ident]);
+          done(err);
+        });
+    });
+
+    it('puts the package in the channel', function (done) {
+      request.get('/depot/channels/sbomapp/unstable/pkgs/bundled/1.0.0/20201101000400')
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.ident.release).to.equal('20201101000400');
+          done(err);
+        });
+    });
+
+    it('changes nothing when imported again', function (done) {
+      request.post('/depot/bundles/import')
+        .set('Authorization', global.boboBearer)
+        .set('Content-Type', 'application/gzip')
+        .send(bundle)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.uploaded).to.deep.equal([]);
+          expect(res.body.existing).to.deep.equal([ident]);
+          expect(res.body.promoted).to.deep.equal([]);
+          done(err);
+        });
+    });
+  });
+});
@@ -723,4 +723,209 @@ describe('Channels API', function () {
         });
     });
   });
+
+  describe('Channel diff', function () {
+    it('returns the names whose latest release differs', function (done) {
+      request.get('/depot/channels/neurosis/unstable/diff/stable')
+        .type('application/json')
+        .accept('application/json')
+        .end(function (err, res) {
+          expect(res.body.range_start).to.equal(0);
+          expect(res.body.total_count).to.be.above(0);
+          expect(res.body.data.length).to.be.above(0);
+          global.channelDiffCount = res.body.total_count;
+          done(err);
+        });
+    });
+
+    it('keeps the total on a page past the end', function (done) {
+      request.get('/depot/channels/neurosis/unstable/diff/stable')
+        .query({ range: 1000 })
+        .type('application/json')
+        .accept('application/json')
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.total_count).to.equal(global.channelDiffCount);
+          expect(res.body.data.length).to.equal(0);
+          done(err);
+        });
+    });
+  });
+
+  describe('Scheduled promotions', function () {
+    it('requires authentication to schedule a promotion', function (done) {
+      request.post('/depot/channels/neurosis/stable/scheduled-promotions')
+        .type('application/json')
+        .send({
+          idents: ['neurosis/testapp/0.1.3/20171205003213'],
+          execute_at: '2099-01-01T00:00:00Z'
+        })
+        .expect(401)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('requires origin membership to schedule a promotion', function (done) {
+      request.post('/depot/channels/neurosis/stable/scheduled-promotions')
+        .set('Authorization', global.mystiqueBearer)
+        .type('application/json')
+        .send({
+          idents: ['neurosis/testapp/0.1.3/20171205003213'],
+          execute_at: '2099-01-01T00:00:00Z'
+        })
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rejects promotions into the unstable channel', function (done) {
+      request.post('/depot/channels/neurosis/unstable/scheduled-promotions')
+        .set('Authorization', global.boboBearer)
+        .type('application/json')
+        .send({
+          idents: ['neurosis/testapp/0.1.3/20171205003213'],
+          execute_at: '2099-01-01T00:00:00Z'
+        })
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rejects promotions in the past', function (done) {
+      request.post('/depot/channels/neurosis/stable/scheduled-promotions')
+        .set('Authorization', global.boboBearer)
+        .type('application/json')
+        .send({
+          idents: ['neurosis/testapp/0.1.3/20171205003213'],
+          execute_at: '2000-01-01T00:00:00Z'
+        })
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rejects idents which are not fully qualified', function (done) {
+      request.post('/depot/channels/neurosis/stable/scheduled-promotions')
+        .set('Authorization', global.boboBearer)
+        .type('application/json')
+        .send({
+          idents: ['neurosis/testapp/0.1.3'],
+          execute_at: '2099-01-01T00:00:00Z'
+        })
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rejects idents of another origin', function (done) {
+      request.post('/depot/channels/neurosis/stable/scheduled-promotions')
+        .set('Authorization', global.boboBearer)
+        .type('application/json')
+        .send({
+          idents: ['core/testapp/0.1.3/20171205003213'],
+          execute_at: '2099-01-01T00:00:00Z'
+        })
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('schedules the promotion', function (done) {
+      request.post('/depot/channels/neurosis/stable/scheduled-promotions')
+        .set('Authorization', global.boboBearer)
+        .type('application/json')
+        .send({
+          idents: ['neurosis/testapp/0.1.3/20171205003213'],
+          execute_at: '2099-01-01T00:00:00Z'
+        })
+        .expect(201)
+        .end(function (err, res) {
+          expect(res.body.origin).to.equal('neurosis');
+          expect(res.body.channel).to.equal('stable');
+          expect(res.body.target).to.equal('x86_64-linux');
+          expect(res.body.state).to.equal('pending');
+          expect(res.body.requester_id).to.equal(global.sessionBobo.id);
+          expect(res.body.claimed_at).to.equal(null);
+          global.scheduledPromotion = res.body;
+          done(err);
+        });
+    });
+
+    it('lists the promotions of the origin', function (done) {
+      request.get('/depot/origins/neurosis/scheduled-promotions')
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.length).to.equal(1);
+          expect(res.body[0].id).to.equal(global.scheduledPromotion.id);
+          done(err);
+        });
+    });
+
+    it('requires origin membership to list the promotions', function (done) {
+      request.get('/depot/origins/neurosis/scheduled-promotions')
+        .set('Authorization', global.mystiqueBearer)
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('returns the promotion without items before it runs', function (done) {
+      request.get(`/depot/origins/neurosis/scheduled-promotions/${global.scheduledPromotion.id}`)
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.state).to.equal('pending');
+          expect(res.body.items.length).to.equal(0);
+          done(err);
+        });
+    });
+
+    it('does not find the promotion in another origin', function (done) {
+      request.get(`/depot/origins/xmen/scheduled-promotions/${global.scheduledPromotion.id}`)
+        .set('Authorization', global.mystiqueBearer)
+        .expect(404)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('cancels the promotion', function (done) {
+      request.delete(`/depot/origins/neurosis/scheduled-promotions/${global.scheduledPromotion.id}`)
+        .set('Authorization', global.boboBearer)
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rejects canceling the promotion twice', function (done) {
+      request.delete(`/depot/origins/neurosis/scheduled-promotions/${global.scheduledPromotion.id}`)
+        .set('Authorization', global.boboBearer)
+        .expect(409)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('never runs a canceled promotion', function (done) {
+      request.get(`/depot/origins/neurosis/scheduled-promotions/${global.scheduledPromotion.id}`)
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.state).to.equal('canceled');
+          expect(res.body.finished_at).to.not.equal(null);
+          expect(res.body.claimed_at).to.equal(null);
+          expect(res.body.items.length).to.equal(0);
+          done(err);
+        });
+    });
+  });
 });
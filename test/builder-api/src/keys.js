@@ -240,4 +240,145 @@ describe('Keys API', function () {
         });
     });
   });
+
+  describe('Rotating and revoking keys', function () {
+    it('creates the keyrot origin', function (done) {
+      request.post('/depot/origins')
+        .set('Authorization', global.boboBearer)
+        .send({ 'name': 'keyrot' })
+        .expect(201)
+        .end(function (err, res) {
+          expect(res.body.name).to.equal('keyrot');
+          done(err);
+        });
+    });
+
+    it('requires the administrator role to rotate', function (done) {
+      request.post('/depot/origins/keyrot/keys/rotate')
+        .set('Authorization', global.mystiqueBearer)
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rotates in a first key', function (done) {
+      request.post('/depot/origins/keyrot/keys/rotate')
+        .set('Authorization', global.boboBearer)
+        .expect(201)
+        .end(function (err, res) {
+          expect(res.body.retired.length).to.equal(0);
+          global.keyrotRevision1 = res.body.revision;
+          done(err);
+        });
+    });
+
+    it('rotates in a second key and retires the first', function (done) {
+      this.timeout(5000);
+      // Revisions are timestamps down to the second
+      setTimeout(function () {
+        request.post('/depot/origins/keyrot/keys/rotate')
+          .set('Authorization', global.boboBearer)
+          .expect(201)
+          .end(function (err, res) {
+            expect(res.body.retired).to.deep.equal([global.keyrotRevision1]);
+            global.keyrotRevision2 = res.body.revision;
+            done(err);
+          });
+      }, 1100);
+    });
+
+    it('lists the state of each revision', function (done) {
+      request.get('/depot/origins/keyrot/keys')
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.length).to.equal(2);
+          expect(res.body[0].revision).to.equal(global.keyrotRevision2);
+          expect(res.body[0].state).to.equal('active');
+          expect(res.body[1].revision).to.equal(global.keyrotRevision1);
+          expect(res.body[1].state).to.equal('retired');
+          done(err);
+        });
+    });
+
+    it('returns the active revision as the latest key', function (done) {
+      request.get('/depot/origins/keyrot/keys/latest')
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.text).to.contain(`keyrot-${global.keyrotRevision2}`);
+          done(err);
+        });
+    });
+
+    it('requires the owner role to revoke', function (done) {
+      request.post(`/depot/origins/keyrot/keys/${global.keyrotRevision2}/revoke`)
+        .set('Authorization', global.mystiqueBearer)
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('revokes the active revision', function (done) {
+      request.post(`/depot/origins/keyrot/keys/${global.keyrotRevision2}/revoke`)
+        .set('Authorization', global.boboBearer)
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('returns the newest retired revision as the latest key once none is active', function (done) {
+      request.get('/depot/origins/keyrot/keys/latest')
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.text).to.contain(`keyrot-${global.keyrotRevision1}`);
+          done(err);
+        });
+    });
+
+    it('no longer hands out a secret key', function (done) {
+      request.get('/depot/origins/keyrot/secret_keys/latest')
+        .set('Authorization', global.boboBearer)
+        .expect(404)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('revokes the retired revision', function (done) {
+      request.post(`/depot/origins/keyrot/keys/${global.keyrotRevision1}/revoke`)
+        .set('Authorization', global.boboBearer)
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('has no latest key once every revision is revoked', function (done) {
+      request.get('/depot/origins/keyrot/keys/latest')
+        .expect(404)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('still serves a revoked revision by name', function (done) {
+      request.get(`/depot/origins/keyrot/keys/${global.keyrotRevision1}`)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.text).to.contain(`keyrot-${global.keyrotRevision1}`);
+          done(err);
+        });
+    });
+
+    it('returns not found when revoking an unknown revision', function (done) {
+      request.post('/depot/origins/keyrot/keys/20000101000000/revoke')
+        .set('Authorization', global.boboBearer)
+        .expect(404)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+  });
 });
@@ -334,6 +334,89 @@ describe('Working with packages', function () {
     });
   });
 
+  describe('Package provenance', function () {
+    const provenance = {
+      job_id: 1196389192484823040,
+      group_id: 1196389192426102784,
+      vcs_url: 'https://github.com/habitat-sh/testapp.git',
+      worker: 'worker-1',
+      plan_path: 'plan.sh',
+      studio_version: 'core/hab-studio/1.6.0/20200420200029',
+      hab_version: 'core/hab/1.6.0/20200420200029',
+      deps: []
+    };
+
+    it('records packages uploaded by users as unattested', function (done) {
+      request.get(`/depot/pkgs/neurosis/testapp/0.1.3/${release1}/provenance`)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.id).to.not.equal('0');
+          expect(res.body.attested).to.equal(false);
+          expect(res.body.job_id).to.equal('');
+          expect(res.body.vcs_url).to.equal(null);
+          expect(res.body.deps).to.deep.equal([]);
+          done(err);
+        });
+    });
+
+    it('returns 404 for a package which does not exist', function (done) {
+      request.get('/depot/pkgs/neurosis/testapp/0.1.3/20990101000000/provenance')
+        .expect(404)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rejects an invalid target', function (done) {
+      request.get(`/depot/pkgs/neurosis/testapp/0.1.3/${release1}/provenance?target=foo`)
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('requires authentication to record provenance', function (done) {
+      request.put(`/depot/pkgs/neurosis/testapp/0.1.3/${release1}/provenance`)
+        .type('application/json')
+        .send(provenance)
+        .expect(401)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('does not let origin members attest packages', function (done) {
+      request.put(`/depot/pkgs/neurosis/testapp/0.1.3/${release1}/provenance`)
+        .set('Authorization', global.boboBearer)
+        .type('application/json')
+        .send(provenance)
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('does not let other users attest packages', function (done) {
+      request.put(`/depot/pkgs/neurosis/testapp/0.1.3/${release1}/provenance`)
+        .set('Authorization', global.mystiqueBearer)
+        .type('application/json')
+        .send(provenance)
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('leaves the package unattested', function (done) {
+      request.get(`/depot/pkgs/neurosis/testapp/0.1.3/${release1}/provenance`)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.attested).to.equal(false);
+          done(err);
+        });
+    });
+  });
+
   describe('Deleting origin after package exists', function () {
     it('is not allowed', function (done) {
       request.delete('/depot/origins/neurosis')
@@ -397,6 +480,79 @@ describe('Working with packages', function () {
         });
     });
 
+    it('ranks exact name matches first when searching', function (done) {
+      request.get('/depot/pkgs/search/neurosis')
+        .type('application/json')
+        .accept('application/json')
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.data[0].origin).to.equal('neurosis');
+          expect(res.body.data[0].name).to.equal('neurosis');
+          expect(res.body.data[1].name).to.not.equal('neurosis');
+          done(err);
+        });
+    });
+
+    it('filters searches by origin', function (done) {
+      request.get('/depot/pkgs/search/testapp?origin=xmen')
+        .type('application/json')
+        .accept('application/json')
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.total_count).to.equal(1);
+          expect(res.body.data[0].origin).to.equal('xmen');
+          expect(res.body.data[0].release).to.equal(release4);
+          done(err);
+        });
+    });
+
+    it('filters searches by target', function (done) {
+      request.get('/depot/pkgs/search/testapp?target=x86_64-windows')
+        .type('application/json')
+        .accept('application/json')
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.total_count).to.equal(1);
+          expect(res.body.data[0].origin).to.equal('neurosis');
+          expect(res.body.data[0].release).to.equal(release7);
+          done(err);
+        });
+    });
+
+    it('filters searches by channel of the package origin', function (done) {
+      request.get('/depot/pkgs/search/testapp?channel=unstable&origin=xmen')
+        .type('application/json')
+        .accept('application/json')
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.total_count).to.equal(1);
+          expect(res.body.data[0].origin).to.equal('xmen');
+          done(err);
+        });
+    });
+
+    it('finds nothing in a channel the packages are not in', function (done) {
+      request.get('/depot/pkgs/search/testapp?channel=stable')
+        .type('application/json')
+        .accept('application/json')
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.total_count).to.equal(0);
+          expect(res.body.data.length).to.equal(0);
+          done(err);
+        });
+    });
+
+    it('rejects an unknown search target', function (done) {
+      request.get('/depot/pkgs/search/testapp?target=nope')
+        .type('application/json')
+        .accept('application/json')
+        .expect(400)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
     it('lists all packages', function (done) {
       request.get('/depot/pkgs/neurosis')
         .type('application/json')
@@ -792,6 +948,93 @@ describe('Working with packages', function () {
           done(err);
         });
     });
+
+    it('uploads the leaf node package again', function (done) {
+      request.post(`/depot/pkgs/neurosis/testapp3/0.1.0/${release9}`)
+        .set('Authorization', global.boboBearer)
+        .set('Content-Length', file9.length)
+        .query({ checksum: '02edaaf2d5fdb167e57026b17c86e8df5a7ca285e042f113bcb31ede765a67ce' })
+        .send(file9)
+        .expect(201)
+        .end(function (err, res) {
+          expect(res.text).to.equal(`/pkgs/neurosis/testapp3/0.1.0/${release9}/download`);
+          done(err);
+        });
+    });
+
+    it('requires a member of the origin to bulk delete', function (done) {
+      request.post('/depot/pkgs/neurosis/bulk_delete')
+        .set('Authorization', global.mystiqueBearer)
+        .send({ name: 'testapp3' })
+        .expect(403)
+        .end(function (err, res) {
+          expect(res.text).to.be.empty;
+          done(err);
+        });
+    });
+
+    it('only plans the bulk delete without execute', function (done) {
+      request.post('/depot/pkgs/neurosis/bulk_delete')
+        .set('Authorization', global.boboBearer)
+        .send({ name: 'testapp3' })
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.executed).to.equal(false);
+          expect(res.body.delete.length).to.equal(1);
+          expect(res.body.delete[0].ident.name).to.equal('testapp3');
+          expect(res.body.delete[0].ident.release).to.equal(release9);
+          expect(res.body.blocked.length).to.equal(0);
+          done(err);
+        });
+    });
+
+    it('still returns the package after the dry run', function (done) {
+      request.get('/depot/pkgs/neurosis/testapp3/latest')
+        .type('application/json')
+        .accept('application/json')
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.ident.release).to.equal(release9);
+          done(err);
+        });
+    });
+
+    it('blocks non-leaf packages in the bulk delete plan', function (done) {
+      request.post('/depot/pkgs/neurosis/bulk_delete')
+        .set('Authorization', global.boboBearer)
+        .send({ name: 'testapp', version: '0.1.3' })
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.executed).to.equal(false);
+          expect(res.body.blocked.map(e => e.ident.release)).to.include(release2);
+          done(err);
+        });
+    });
+
+    it('carries out the bulk delete plan with execute', function (done) {
+      request.post('/depot/pkgs/neurosis/bulk_delete')
+        .set('Authorization', global.boboBearer)
+        .query({ execute: true })
+        .send({ name: 'testapp3' })
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.executed).to.equal(true);
+          expect(res.body.delete.length).to.equal(1);
+          expect(res.body.delete[0].ident.release).to.equal(release9);
+          done(err);
+        });
+    });
+
+    it('doesnt return the package after the bulk delete', function (done) {
+      request.get('/depot/pkgs/neurosis/testapp3/latest')
+        .type('application/json')
+        .accept('application/json')
+        .expect(404)
+        .end(function (err, res) {
+          expect(res.text).to.be.empty
+          done(err);
+        });
+    });
   });
 
   describe('Other functions', function () {
@@ -0,0 +1,196 @@
+const expect = require('chai').expect;
+const supertest = require('supertest');
+const request = supertest('http://localhost:9636/v1');
+
+describe('Retention Policies API', function () {
+  describe('Setting a policy', function () {
+    it('requires the administrator role', function (done) {
+      request.put('/depot/origins/crew/retention')
+        .set('Authorization', global.lkennedyBearer)
+        .send({ 'keep_latest': 3 })
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('requires keep_latest or max_age_days', function (done) {
+      request.put('/depot/origins/crew/retention')
+        .set('Authorization', global.boboBearer)
+        .send({})
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rejects a keep_latest below one', function (done) {
+      request.put('/depot/origins/crew/retention')
+        .set('Authorization', global.boboBearer)
+        .send({ 'keep_latest': 0 })
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rejects a policy scoped to stable', function (done) {
+      request.put('/depot/origins/crew/retention')
+        .set('Authorization', global.boboBearer)
+        .send({ 'channel': 'stable', 'keep_latest': 3 })
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rejects a channel which does not exist', function (done) {
+      request.put('/depot/origins/crew/retention')
+        .set('Authorization', global.boboBearer)
+        .send({ 'channel': 'nope', 'keep_latest': 3 })
+        .expect(404)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('returns the origin wide policy', function (done) {
+      request.put('/depot/origins/crew/retention')
+        .set('Authorization', global.boboBearer)
+        .send({ 'keep_latest': 3 })
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.origin).to.equal('crew');
+          expect(res.body.channel).to.equal(null);
+          expect(res.body.keep_latest).to.equal(3);
+          expect(res.body.max_age_days).to.equal(null);
+          done(err);
+        });
+    });
+
+    it('returns the channel policy', function (done) {
+      request.put('/depot/origins/crew/retention')
+        .set('Authorization', global.boboBearer)
+        .send({ 'channel': 'unstable', 'max_age_days': 30 })
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.channel).to.equal('unstable');
+          expect(res.body.keep_latest).to.equal(null);
+          expect(res.body.max_age_days).to.equal(30);
+          done(err);
+        });
+    });
+
+    it('replaces the existing policy of the same scope', function (done) {
+      request.put('/depot/origins/crew/retention')
+        .set('Authorization', global.boboBearer)
+        .send({ 'keep_latest': 5 })
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.keep_latest).to.equal(5);
+          done(err);
+        });
+    });
+  });
+
+  describe('Listing policies', function () {
+    it('requires origin membership', function (done) {
+      request.get('/depot/origins/crew/retention')
+        .set('Authorization', global.lkennedyBearer)
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('returns every policy of the origin', function (done) {
+      request.get('/depot/origins/crew/retention')
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.length).to.equal(2);
+          let wide = res.body.find(p => p.channel === null);
+          let unstable = res.body.find(p => p.channel === 'unstable');
+          expect(wide.keep_latest).to.equal(5);
+          expect(unstable.max_age_days).to.equal(30);
+          done(err);
+        });
+    });
+
+    it('returns a dry run report for each policy', function (done) {
+      request.get('/depot/origins/crew/retention/report')
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.length).to.equal(2);
+          res.body.forEach(report => {
+            expect(report.dry_run).to.equal(true);
+            expect(report.entries).to.deep.equal([]);
+          });
+          done(err);
+        });
+    });
+
+    it('limits the report to a channel', function (done) {
+      request.get('/depot/origins/crew/retention/report')
+        .query({ channel: 'unstable' })
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.length).to.equal(1);
+          expect(res.body[0].channel).to.equal('unstable');
+          done(err);
+        });
+    });
+  });
+
+  describe('Deleting a policy', function () {
+    it('requires the administrator role', function (done) {
+      request.delete('/depot/origins/crew/retention')
+        .set('Authorization', global.lkennedyBearer)
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('deletes the channel policy', function (done) {
+      request.delete('/depot/origins/crew/retention')
+        .query({ channel: 'unstable' })
+        .set('Authorization', global.boboBearer)
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('returns not found when there is no policy', function (done) {
+      request.delete('/depot/origins/crew/retention')
+        .query({ channel: 'unstable' })
+        .set('Authorization', global.boboBearer)
+        .expect(404)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('deletes the origin wide policy', function (done) {
+      request.delete('/depot/origins/crew/retention')
+        .set('Authorization', global.boboBearer)
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('leaves no policies', function (done) {
+      request.get('/depot/origins/crew/retention')
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body).to.deep.equal([]);
+          done(err);
+        });
+    });
+  });
+});
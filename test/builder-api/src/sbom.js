@@ -0,0 +1,120 @@
+const expect = require('chai').expect;
+const supertest = require('supertest');
+const request = supertest('http://localhost:9636/v1');
+const fs = require('fs');
+
+const appRelease = '20201101000100';
+const depRelease = '20201101000000';
+const revision = '20201101000000';
+const appFile = fs.readFileSync(__dirname + `/../fixtures/sbomapp-app-1.0.0-${appRelease}-x86_64-linux.hart`);
+const depFile = fs.readFileSync(__dirname + `/../fixtures/sbomdep-libsecret-1.0.0-${depRelease}-x86_64-linux.hart`);
+const appPubFile = fs.readFileSync(__dirname + `/../fixtures/sbomapp-${revision}.pub`, 'utf8');
+const depPubFile = fs.readFileSync(__dirname + `/../fixtures/sbomdep-${revision}.pub`, 'utf8');
+
+function packageNamed(res, name) {
+  return res.body.packages.find(function (p) { return p.name === name; });
+}
+
+describe('Software bill of materials API', function () {
+  describe('A public package with a private dependency in another origin', function () {
+    it('creates the private sbomdep origin', function (done) {
+      request.post('/depot/origins')
+        .set('Authorization', global.weskerBearer)
+        .send({ 'name': 'sbomdep', 'default_package_visibility': 'private' })
+        .expect(201)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('uploads the sbomdep key', function (done) {
+      request.post(`/depot/origins/sbomdep/keys/${revision}`)
+        .set('Authorization', global.weskerBearer)
+        .send(depPubFile)
+        .expect(201)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('uploads the private dependency', function (done) {
+      request.post(`/depot/pkgs/sbomdep/libsecret/1.0.0/${depRelease}`)
+        .set('Authorization', global.weskerBearer)
+        .set('Content-Length', depFile.length)
+        .query({ checksum: '008d00099fe4340b2dfbfd247c72b1dfbe217511b6dbcb0b689cafc1f487c8e3' })
+        .send(depFile)
+        .expect(201)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('creates the public sbomapp origin', function (done) {
+      request.post('/depot/origins')
+        .set('Authorization', global.boboBearer)
+        .send({ 'name': 'sbomapp' })
+        .expect(201)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('uploads the sbomapp key', function (done) {
+      request.post(`/depot/origins/sbomapp/keys/${revision}`)
+        .set('Authorization', global.boboBearer)
+        .send(appPubFile)
+        .expect(201)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('uploads the package', function (done) {
+      request.post(`/depot/pkgs/sbomapp/app/1.0.0/${appRelease}`)
+        .set('Authorization', global.boboBearer)
+        .set('Content-Length', appFile.length)
+        .query({ checksum: 'bf60800315a5f13c267b03eedc69b29710c4bba770bef6160ba8cef26aa8c4a8' })
+        .send(appFile)
+        .expect(201)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('leaves out the metadata of the dependency for non members of its origin', function (done) {
+      request.get(`/depot/pkgs/sbomapp/app/1.0.0/${appRelease}/sbom/spdx`)
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.packages.length).to.equal(2);
+          expect(packageNamed(res, 'sbomapp/app').checksums.length).to.equal(1);
+          const dep = packageNamed(res, 'sbomdep/libsecret');
+          expect(dep.versionInfo).to.equal(`1.0.0/${depRelease}`);
+          expect(dep.checksums.length).to.equal(0);
+          expect(dep.licenseDeclared).to.equal('NOASSERTION');
+          done(err);
+        });
+    });
+
+    it('leaves out the metadata of the dependency for anonymous requests', function (done) {
+      request.get(`/depot/pkgs/sbomapp/app/1.0.0/${appRelease}/sbom/spdx`)
+        .expect(200)
+        .end(function (err, res) {
+          expect(packageNamed(res, 'sbomdep/libsecret').checksums.length).to.equal(0);
+          done(err);
+        });
+    });
+
+    it('includes the metadata of the dependency for members of its origin', function (done) {
+      request.get(`/depot/pkgs/sbomapp/app/1.0.0/${appRelease}/sbom/spdx`)
+        .set('Authorization', global.weskerBearer)
+        .expect(200)
+        .end(function (err, res) {
+          const dep = packageNamed(res, 'sbomdep/libsecret');
+          expect(dep.checksums[0].checksumValue).to.equal('008d00099fe4340b2dfbfd247c72b1dfbe217511b6dbcb0b689cafc1f487c8e3');
+          expect(dep.licenseDeclared).to.equal('Apache-2.0');
+          done(err);
+        });
+    });
+  });
+});
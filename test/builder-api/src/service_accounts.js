@@ -0,0 +1,215 @@
+const expect = require('chai').expect;
+const supertest = require('supertest');
+const request = supertest('http://localhost:9636/v1');
+
+describe('Service Accounts API', function () {
+  describe('Create svcacct origin', function () {
+    it('returns the created origin', function (done) {
+      request.post('/depot/origins')
+        .set('Authorization', global.boboBearer)
+        .send({ 'name': 'svcacct' })
+        .expect(201)
+        .end(function (err, res) {
+          expect(res.body.name).to.equal('svcacct');
+          done(err);
+        });
+    });
+  });
+
+  describe('Creating service accounts', function () {
+    it('requires the owner role', function (done) {
+      request.post('/depot/origins/svcacct/service-accounts')
+        .set('Authorization', global.mystiqueBearer)
+        .send({ 'name': 'ci', 'role': 'maintainer' })
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rejects the owner role', function (done) {
+      request.post('/depot/origins/svcacct/service-accounts')
+        .set('Authorization', global.boboBearer)
+        .send({ 'name': 'ci', 'role': 'owner' })
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rejects an invalid role', function (done) {
+      request.post('/depot/origins/svcacct/service-accounts')
+        .set('Authorization', global.boboBearer)
+        .send({ 'name': 'ci', 'role': 'janitor' })
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rejects an invalid name', function (done) {
+      request.post('/depot/origins/svcacct/service-accounts')
+        .set('Authorization', global.boboBearer)
+        .send({ 'name': 'c i', 'role': 'maintainer' })
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('returns the created service account', function (done) {
+      request.post('/depot/origins/svcacct/service-accounts')
+        .set('Authorization', global.boboBearer)
+        .send({ 'name': 'ci', 'role': 'maintainer' })
+        .expect(201)
+        .end(function (err, res) {
+          expect(res.body.name).to.equal('ci@svcacct');
+          expect(res.body.origin).to.equal('svcacct');
+          expect(res.body.member_role).to.equal('maintainer');
+          global.serviceAccountCi = res.body;
+          done(err);
+        });
+    });
+
+    it('lists the service accounts of the origin', function (done) {
+      request.get('/depot/origins/svcacct/service-accounts')
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.length).to.equal(1);
+          expect(res.body[0].id).to.equal(global.serviceAccountCi.id);
+          done(err);
+        });
+    });
+  });
+
+  describe('Updating service accounts', function () {
+    it('rejects the owner role', function (done) {
+      request.put('/depot/origins/svcacct/service-accounts/ci')
+        .set('Authorization', global.boboBearer)
+        .send({ 'role': 'owner' })
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('changes the role of the service account', function (done) {
+      request.put('/depot/origins/svcacct/service-accounts/ci')
+        .set('Authorization', global.boboBearer)
+        .send({ 'role': 'administrator' })
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('returns the new role', function (done) {
+      request.get('/depot/origins/svcacct/service-accounts')
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body[0].member_role).to.equal('administrator');
+          done(err);
+        });
+    });
+
+    it('returns not found for an unknown service account', function (done) {
+      request.put('/depot/origins/svcacct/service-accounts/nope')
+        .set('Authorization', global.boboBearer)
+        .send({ 'role': 'member' })
+        .expect(404)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+  });
+
+  describe('Service account tokens', function () {
+    it('generates a token', function (done) {
+      request.post('/depot/origins/svcacct/service-accounts/ci/tokens')
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.token).to.not.be.empty;
+          global.serviceAccountCiToken = res.body;
+          done(err);
+        });
+    });
+
+    it('lists the tokens of the service account', function (done) {
+      request.get('/depot/origins/svcacct/service-accounts/ci/tokens')
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.tokens.length).to.equal(1);
+          expect(res.body.tokens[0].id).to.equal(global.serviceAccountCiToken.id);
+          done(err);
+        });
+    });
+  });
+
+  describe('Deleting service accounts', function () {
+    it('puts the service account in a team', function (done) {
+      request.post('/depot/origins/svcacct/teams')
+        .set('Authorization', global.boboBearer)
+        .send({ 'name': 'bots', 'role': 'maintainer' })
+        .expect(201)
+        .end(function (err, res) {
+          request.put('/depot/origins/svcacct/teams/bots/members/ci@svcacct')
+            .set('Authorization', global.boboBearer)
+            .expect(204)
+            .end(function (err, res) {
+              done(err);
+            });
+        });
+    });
+
+    it('requires the owner role', function (done) {
+      request.delete('/depot/origins/svcacct/service-accounts/ci')
+        .set('Authorization', global.mystiqueBearer)
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('deletes the service account', function (done) {
+      request.delete('/depot/origins/svcacct/service-accounts/ci')
+        .set('Authorization', global.boboBearer)
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('no longer lists the service account', function (done) {
+      request.get('/depot/origins/svcacct/service-accounts')
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.length).to.equal(0);
+          done(err);
+        });
+    });
+
+    it('takes the service account out of its teams', function (done) {
+      request.get('/depot/origins/svcacct/teams/bots')
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.members.length).to.equal(0);
+          done(err);
+        });
+    });
+
+    it('returns not found once deleted', function (done) {
+      request.delete('/depot/origins/svcacct/service-accounts/ci')
+        .set('Authorization', global.boboBearer)
+        .expect(404)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+  });
+});
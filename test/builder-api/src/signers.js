@@ -0,0 +1,153 @@
+const expect = require('chai').expect;
+const supertest = require('supertest');
+const request = supertest('http://localhost:9636/v1');
+const fs = require('fs');
+
+const knownSigner = 'sbomapp-20201101000000';
+const unknownSigner = 'sbomapp-20201101000700';
+const verifiedRelease = '20201101000700';
+const flaggedRelease = '20201101000800';
+const rejectedRelease = '20201101000900';
+const tamperedRelease = '20201101001000';
+
+function hart(release) {
+  return fs.readFileSync(__dirname + `/../fixtures/sbomapp-signed-1.0.0-${release}-x86_64-linux.hart`);
+}
+
+const verifiedFile = hart(verifiedRelease);
+const flaggedFile = hart(flaggedRelease);
+const rejectedFile = hart(rejectedRelease);
+const tamperedFile = hart(tamperedRelease);
+
+function signedBy(res, signer) {
+  return res.body.find(function (entry) { return entry.signer === signer; });
+}
+
+describe('Package signer verification', function () {
+  it('accepts a package signed with a key revision of the origin', function (done) {
+    request.post(`/depot/pkgs/sbomapp/signed/1.0.0/${verifiedRelease}`)
+      .set('Authorization', global.boboBearer)
+      .set('Content-Length', verifiedFile.length)
+      .query({ checksum: 'dc1c3c2e242bc110ae70e06487f4e1eeef3b38b1b84b011a371b3a149e17953c' })
+      .send(verifiedFile)
+      .expect(201)
+      .end(function (err, res) {
+        done(err);
+      });
+  });
+
+  it('flags a package signed with an unknown key revision', function (done) {
+    request.post(`/depot/pkgs/sbomapp/signed/1.0.0/${flaggedRelease}`)
+      .set('Authorization', global.boboBearer)
+      .set('Content-Length', flaggedFile.length)
+      .query({ checksum: '7f4dc800be3fc2fc60c9a2c7c2cfc2fc24a3969bb476bd93c4fc53fba807be04' })
+      .send(flaggedFile)
+      .expect(201)
+      .end(function (err, res) {
+        done(err);
+      });
+  });
+
+  it('lists the flagged package', function (done) {
+    request.get('/depot/origins/sbomapp/signer_flags')
+      .set('Authorization', global.boboBearer)
+      .expect(200)
+      .end(function (err, res) {
+        expect(res.body.length).to.equal(1);
+        expect(res.body[0].ident).to.equal(`sbomapp/signed/1.0.0/${flaggedRelease}`);
+        expect(res.body[0].signer).to.equal(unknownSigner);
+        done(err);
+      });
+  });
+
+  it('requires origin membership to list the flags', function (done) {
+    request.get('/depot/origins/sbomapp/signer_flags')
+      .set('Authorization', global.mystiqueBearer)
+      .expect(403)
+      .end(function (err, res) {
+        done(err);
+      });
+  });
+
+  it('records the signer of each package', function (done) {
+    request.get('/depot/origins/sbomapp/keys/signers?channels=unstable')
+      .set('Authorization', global.boboBearer)
+      .expect(200)
+      .end(function (err, res) {
+        let known = signedBy(res, knownSigner);
+        expect(known.state).to.not.equal(null);
+        expect(known.packages.map(function (p) { return p.ident; }))
+          .to.include(`sbomapp/signed/1.0.0/${verifiedRelease}`);
+
+        let unknown = signedBy(res, unknownSigner);
+        expect(unknown.state).to.equal(null);
+        expect(unknown.packages.length).to.equal(1);
+        expect(unknown.packages[0].ident).to.equal(`sbomapp/signed/1.0.0/${flaggedRelease}`);
+        done(err);
+      });
+  });
+
+  it('makes the origin reject unknown key revisions', function (done) {
+    request.put('/depot/origins/sbomapp')
+      .set('Authorization', global.boboBearer)
+      .send({ 'reject_unknown_key_revisions': true })
+      .expect(204)
+      .end(function (err, res) {
+        done(err);
+      });
+  });
+
+  it('rejects a package signed with an unknown key revision', function (done) {
+    request.post(`/depot/pkgs/sbomapp/signed/1.0.0/${rejectedRelease}`)
+      .set('Authorization', global.boboBearer)
+      .set('Content-Length', rejectedFile.length)
+      .query({ checksum: '742eb43e63a95b031c30ad4bde329cc82dc145087eb3e4f2420957f4cf5aa85b' })
+      .send(rejectedFile)
+      .expect(422)
+      .end(function (err, res) {
+        expect(res.text).to.equal(`ds:up:7, unknown signing key revision ${unknownSigner}`);
+        done(err);
+      });
+  });
+
+  it('does not store the rejected package', function (done) {
+    request.get(`/depot/pkgs/sbomapp/signed/1.0.0/${rejectedRelease}`)
+      .set('Authorization', global.boboBearer)
+      .expect(404)
+      .end(function (err, res) {
+        done(err);
+      });
+  });
+
+  it('goes back to flagging unknown key revisions', function (done) {
+    request.put('/depot/origins/sbomapp')
+      .set('Authorization', global.boboBearer)
+      .send({ 'reject_unknown_key_revisions': false })
+      .expect(204)
+      .end(function (err, res) {
+        done(err);
+      });
+  });
+
+  it('rejects a package whose signature does not match its contents', function (done) {
+    request.post(`/depot/pkgs/sbomapp/signed/1.0.0/${tamperedRelease}`)
+      .set('Authorization', global.boboBearer)
+      .set('Content-Length', tamperedFile.length)
+      .query({ checksum: '87db1fc256e480b08c7e52e3785ee19abf3086cecf1db81a962128f94ddb5f43' })
+      .send(tamperedFile)
+      .expect(422)
+      .end(function (err, res) {
+        expect(res.text).to.match(/^ds:up:8, signature verification failed/);
+        done(err);
+      });
+  });
+
+  it('does not store the tampered package', function (done) {
+    request.get(`/depot/pkgs/sbomapp/signed/1.0.0/${tamperedRelease}`)
+      .set('Authorization', global.boboBearer)
+      .expect(404)
+      .end(function (err, res) {
+        done(err);
+      });
+  });
+});
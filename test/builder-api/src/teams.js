@@ -0,0 +1,231 @@
+const expect = require('chai').expect;
+const supertest = require('supertest');
+const request = supertest('http://localhost:9636/v1');
+
+describe('Origin Teams API', function () {
+  describe('Create crew origin with lkennedy as a member', function () {
+    it('returns the created origin', function (done) {
+      request.post('/depot/origins')
+        .set('Authorization', global.boboBearer)
+        .send({ 'name': 'crew' })
+        .expect(201)
+        .end(function (err, res) {
+          expect(res.body.name).to.equal('crew');
+          done(err);
+        });
+    });
+
+    it('lkennedy gets invited', function (done) {
+      request.post('/depot/origins/crew/users/lkennedy/invitations')
+        .set('Authorization', global.boboBearer)
+        .expect(201)
+        .end(function (err, res) {
+          global.inviteLkennedyToCrew = res.body;
+          done(err);
+        });
+    });
+
+    it('lkennedy joins the origin', function (done) {
+      request.put('/depot/origins/crew/invitations/' + global.inviteLkennedyToCrew.id)
+        .set('Authorization', global.lkennedyBearer)
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('lkennedy can not create a channel as a readonly member', function (done) {
+      request.post('/depot/channels/crew/foo')
+        .set('Authorization', global.lkennedyBearer)
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+  });
+
+  describe('Creating teams', function () {
+    it('requires the administrator role', function (done) {
+      request.post('/depot/origins/crew/teams')
+        .set('Authorization', global.lkennedyBearer)
+        .send({ 'name': 'release', 'role': 'maintainer' })
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rejects an invalid role', function (done) {
+      request.post('/depot/origins/crew/teams')
+        .set('Authorization', global.boboBearer)
+        .send({ 'name': 'release', 'role': 'janitor' })
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('rejects the owner role', function (done) {
+      request.post('/depot/origins/crew/teams')
+        .set('Authorization', global.boboBearer)
+        .send({ 'name': 'release', 'role': 'owner' })
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('returns the created team', function (done) {
+      request.post('/depot/origins/crew/teams')
+        .set('Authorization', global.boboBearer)
+        .send({ 'name': 'release', 'role': 'maintainer' })
+        .expect(201)
+        .end(function (err, res) {
+          expect(res.body.name).to.equal('release');
+          expect(res.body.member_role).to.equal('maintainer');
+          done(err);
+        });
+    });
+
+    it('returns conflict for a duplicate team', function (done) {
+      request.post('/depot/origins/crew/teams')
+        .set('Authorization', global.boboBearer)
+        .send({ 'name': 'release', 'role': 'member' })
+        .expect(409)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+  });
+
+  describe('Team members', function () {
+    it('only takes members of the origin', function (done) {
+      request.put('/depot/origins/crew/teams/release/members/mystique')
+        .set('Authorization', global.boboBearer)
+        .expect(422)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('adds lkennedy to the team', function (done) {
+      request.put('/depot/origins/crew/teams/release/members/lkennedy')
+        .set('Authorization', global.boboBearer)
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('lists lkennedy as a member of the team', function (done) {
+      request.get('/depot/origins/crew/teams/release')
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.members).to.deep.equal(['lkennedy']);
+          done(err);
+        });
+    });
+  });
+
+  describe('Effective roles', function () {
+    it('gives lkennedy the role of the team when it is higher', function (done) {
+      request.post('/depot/channels/crew/foo')
+        .set('Authorization', global.lkennedyBearer)
+        .expect(201)
+        .end(function (err, res) {
+          expect(res.body.name).to.equal('foo');
+          done(err);
+        });
+    });
+
+    it('does not give lkennedy more than the role of the team', function (done) {
+      request.post('/depot/origins/crew/teams')
+        .set('Authorization', global.lkennedyBearer)
+        .send({ 'name': 'ops', 'role': 'member' })
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('makes lkennedy an administrator of the origin', function (done) {
+      request.put('/depot/origins/crew/users/lkennedy/role')
+        .query({ role: 'administrator' })
+        .set('Authorization', global.boboBearer)
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('keeps the role of lkennedy when it is higher than the team', function (done) {
+      request.post('/depot/origins/crew/teams')
+        .set('Authorization', global.lkennedyBearer)
+        .send({ 'name': 'ops', 'role': 'member' })
+        .expect(201)
+        .end(function (err, res) {
+          expect(res.body.name).to.equal('ops');
+          done(err);
+        });
+    });
+
+    it('makes lkennedy a readonly member of the origin again', function (done) {
+      request.put('/depot/origins/crew/users/lkennedy/role')
+        .query({ role: 'readonly_member' })
+        .set('Authorization', global.boboBearer)
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('removes lkennedy from the team', function (done) {
+      request.delete('/depot/origins/crew/teams/release/members/lkennedy')
+        .set('Authorization', global.boboBearer)
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('drops the role of the team once lkennedy leaves it', function (done) {
+      request.post('/depot/channels/crew/bar')
+        .set('Authorization', global.lkennedyBearer)
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+  });
+
+  describe('Departing the origin', function () {
+    it('adds lkennedy to the team again', function (done) {
+      request.put('/depot/origins/crew/teams/release/members/lkennedy')
+        .set('Authorization', global.boboBearer)
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('lkennedy departs the origin', function (done) {
+      request.post('/depot/origins/crew/depart')
+        .set('Authorization', global.lkennedyBearer)
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('takes lkennedy out of the teams of the origin', function (done) {
+      request.get('/depot/origins/crew/teams/release')
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.members.length).to.equal(0);
+          done(err);
+        });
+    });
+  });
+});
@@ -0,0 +1,229 @@
+const expect = require('chai').expect;
+const supertest = require('supertest');
+const request = supertest('http://localhost:9636/v1');
+const fs = require('fs');
+
+const release1 = '20201101000200';
+const release2 = '20201101000300';
+const checksum1 = '3e1293abbe14861d12f6d4f70c69009a90aecb4cd5d3ac1cdf1a0b4edcf31e09';
+const checksum2 = '50718dfff5c9b274b18c84bab53470d74b0c10f7f917e98fe6433916ff30fe70';
+const file1 = fs.readFileSync(__dirname + `/../fixtures/sbomapp-resumable-1.0.0-${release1}-x86_64-linux.hart`);
+const file2 = fs.readFileSync(__dirname + `/../fixtures/sbomapp-resumable-1.0.0-${release2}-x86_64-linux.hart`);
+const head1 = file1.slice(0, 256);
+const tail1 = file1.slice(256);
+
+// The test environment sets upload_session_ttl to 5 seconds
+const sessionTtl = 5000;
+
+describe('Resumable uploads API', function () {
+  describe('Uploading a package in chunks', function () {
+    it('requires authentication to start a session', function (done) {
+      request.post(`/depot/pkgs/sbomapp/resumable/1.0.0/${release1}/uploads`)
+        .query({ checksum: checksum1 })
+        .expect(401)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('starts a session', function (done) {
+      request.post(`/depot/pkgs/sbomapp/resumable/1.0.0/${release1}/uploads`)
+        .query({ checksum: checksum1 })
+        .set('Authorization', global.boboBearer)
+        .expect(201)
+        .end(function (err, res) {
+          expect(res.body.ident).to.equal(`sbomapp/resumable/1.0.0/${release1}`);
+          expect(res.body.received_bytes).to.equal(0);
+          expect(res.body.next_chunk).to.equal(0);
+          global.resumableUpload = res.body;
+          done(err);
+        });
+    });
+
+    it('is not visible to other accounts', function (done) {
+      request.get(`/depot/pkgs/sbomapp/resumable/1.0.0/${release1}/uploads/${global.resumableUpload.id}`)
+        .set('Authorization', global.mystiqueBearer)
+        .expect(403)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('accepts the first chunk', function (done) {
+      request.put(`/depot/pkgs/sbomapp/resumable/1.0.0/${release1}/uploads/${global.resumableUpload.id}/0`)
+        .set('Authorization', global.boboBearer)
+        .set('Content-Length', head1.length)
+        .send(head1)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.received_bytes).to.equal(head1.length);
+          expect(res.body.next_chunk).to.equal(1);
+          done(err);
+        });
+    });
+
+    it('rejects an out of order chunk', function (done) {
+      request.put(`/depot/pkgs/sbomapp/resumable/1.0.0/${release1}/uploads/${global.resumableUpload.id}/2`)
+        .set('Authorization', global.boboBearer)
+        .set('Content-Length', tail1.length)
+        .send(tail1)
+        .expect(409)
+        .end(function (err, res) {
+          expect(res.body.next_chunk).to.equal(1);
+          done(err);
+        });
+    });
+
+    it('reports where to resume from', function (done) {
+      request.get(`/depot/pkgs/sbomapp/resumable/1.0.0/${release1}/uploads/${global.resumableUpload.id}`)
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.received_bytes).to.equal(head1.length);
+          expect(res.body.next_chunk).to.equal(1);
+          done(err);
+        });
+    });
+
+    it('ignores a chunk which was already received', function (done) {
+      request.put(`/depot/pkgs/sbomapp/resumable/1.0.0/${release1}/uploads/${global.resumableUpload.id}/0`)
+        .set('Authorization', global.boboBearer)
+        .set('Content-Length', head1.length)
+        .send(head1)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.received_bytes).to.equal(head1.length);
+          expect(res.body.next_chunk).to.equal(1);
+          done(err);
+        });
+    });
+
+    it('accepts the last chunk', function (done) {
+      request.put(`/depot/pkgs/sbomapp/resumable/1.0.0/${release1}/uploads/${global.resumableUpload.id}/1`)
+        .set('Authorization', global.boboBearer)
+        .set('Content-Length', tail1.length)
+        .send(tail1)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.received_bytes).to.equal(file1.length);
+          expect(res.body.next_chunk).to.equal(2);
+          done(err);
+        });
+    });
+
+    it('finalizes the upload', function (done) {
+      request.post(`/depot/pkgs/sbomapp/resumable/1.0.0/${release1}/uploads/${global.resumableUpload.id}/finalize`)
+        .set('Authorization', global.boboBearer)
+        .expect(201)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('removes the session once finalized', function (done) {
+      request.get(`/depot/pkgs/sbomapp/resumable/1.0.0/${release1}/uploads/${global.resumableUpload.id}`)
+        .set('Authorization', global.boboBearer)
+        .expect(404)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('stores the package', function (done) {
+      request.get(`/depot/pkgs/sbomapp/resumable/1.0.0/${release1}`)
+        .set('Authorization', global.boboBearer)
+        .expect(200)
+        .end(function (err, res) {
+          expect(res.body.checksum).to.equal(checksum1);
+          done(err);
+        });
+    });
+
+    it('will not start a session for a package which exists', function (done) {
+      request.post(`/depot/pkgs/sbomapp/resumable/1.0.0/${release1}/uploads`)
+        .query({ checksum: checksum1 })
+        .set('Authorization', global.boboBearer)
+        .expect(409)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+  });
+
+  describe('Abandoning an upload', function () {
+    it('starts a session', function (done) {
+      request.post(`/depot/pkgs/sbomapp/resumable/1.0.0/${release2}/uploads`)
+        .query({ checksum: checksum2 })
+        .set('Authorization', global.boboBearer)
+        .expect(201)
+        .end(function (err, res) {
+          global.abandonedUpload = res.body;
+          done(err);
+        });
+    });
+
+    it('discards the session', function (done) {
+      request.delete(`/depot/pkgs/sbomapp/resumable/1.0.0/${release2}/uploads/${global.abandonedUpload.id}`)
+        .set('Authorization', global.boboBearer)
+        .expect(204)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('no longer has the session', function (done) {
+      request.get(`/depot/pkgs/sbomapp/resumable/1.0.0/${release2}/uploads/${global.abandonedUpload.id}`)
+        .set('Authorization', global.boboBearer)
+        .expect(404)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+  });
+
+  describe('Expiring an upload', function () {
+    it('starts a session', function (done) {
+      request.post(`/depot/pkgs/sbomapp/resumable/1.0.0/${release2}/uploads`)
+        .query({ checksum: checksum2 })
+        .set('Authorization', global.boboBearer)
+        .expect(201)
+        .end(function (err, res) {
+          global.expiredUpload = res.body;
+          done(err);
+        });
+    });
+
+    it('accepts the first chunk', function (done) {
+      request.put(`/depot/pkgs/sbomapp/resumable/1.0.0/${release2}/uploads/${global.expiredUpload.id}/0`)
+        .set('Authorization', global.boboBearer)
+        .set('Content-Length', file2.length)
+        .send(file2)
+        .expect(200)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+
+    it('expires a session which stops receiving chunks', function (done) {
+      this.timeout(sessionTtl + 5000);
+
+      setTimeout(function () {
+        request.get(`/depot/pkgs/sbomapp/resumable/1.0.0/${release2}/uploads/${global.expiredUpload.id}`)
+          .set('Authorization', global.boboBearer)
+          .expect(404)
+          .end(function (err, res) {
+            done(err);
+          });
+      }, sessionTtl + 1000);
+    });
+
+    it('can not be finalized once expired', function (done) {
+      request.post(`/depot/pkgs/sbomapp/resumable/1.0.0/${release2}/uploads/${global.expiredUpload.id}/finalize`)
+        .set('Authorization', global.boboBearer)
+        .expect(404)
+        .end(function (err, res) {
+          done(err);
+        });
+    });
+  });
+});